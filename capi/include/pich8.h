@@ -0,0 +1,55 @@
+/*
+ * The C API of the pich8 emulation core, a CHIP-8, SUPER-CHIP and XO-CHIP interpreter.
+ *
+ * Link against the pich8_c library built with `cargo build -p pich8-capi --release`.
+ * A handle must not be used from several threads at once. Functions returning int
+ * return a negative value on error, and pich8_last_error() describes the error.
+ */
+#ifndef PICH8_H
+#define PICH8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Pich8 Pich8;
+
+/* Creates an emulator executing the given number of instructions per second, 0 for the default. */
+Pich8 *pich8_new(uint32_t speed);
+
+/* Frees the emulator, null is ignored. */
+void pich8_free(Pich8 *pich8);
+
+/* Resets the CPU and loads a ROM, returns 0 on success. */
+int pich8_load_rom(Pich8 *pich8, const uint8_t *data, size_t size);
+
+/* Executes a single instruction, returns 1 if it completed a frame, which updates the timers,
+ * and 0 otherwise. A frame is 1/60 s worth of instructions. */
+int pich8_tick(Pich8 *pich8);
+
+/* Runs the remaining instructions of the running frame and updates the timers, returns 0 on success. */
+int pich8_run_frame(Pich8 *pich8);
+
+/* Sets the pressed keys as bitmask, bit 0 is key 0 and bit 15 key F. */
+void pich8_set_keys(Pich8 *pich8, uint16_t keys);
+
+/* Returns the screen as one byte per pixel, row by row, where bit 0 is set if the pixel is set
+ * in the first plane and bit 1 if it's set in the second plane. The pointer is valid until the
+ * next call with this handle. width and height may be null. */
+const uint8_t *pich8_framebuffer(Pich8 *pich8, size_t *width, size_t *height);
+
+/* Returns whether the sound timer is running, so a tone should be played. */
+bool pich8_sound_active(const Pich8 *pich8);
+
+/* Returns the message of the last error, valid until the next call with this handle. */
+const char *pich8_last_error(const Pich8 *pich8);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,198 @@
+//! The C API of the pich8 emulation core, declared in `include/pich8.h`.
+//!
+//! All functions take the handle returned by `pich8_new`, which must not be used from several
+//! threads at once. Functions returning `int` return a negative value on error, and
+//! `pich8_last_error` describes the error.
+use pich8::cpu::CPU;
+use pich8::rom;
+use pich8::runner::Runner;
+use pich8::video_memory::Plane;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::{ptr, slice};
+
+pub struct Pich8 {
+    cpu: CPU,
+    runner: Runner,
+    keys: [bool; 16],
+    /// The pixels of both planes, bit 0 is the first plane and bit 1 the second
+    framebuffer: Vec<u8>,
+    last_error: CString,
+}
+
+impl Pich8 {
+    fn fail(&mut self, msg: impl ToString) -> c_int {
+        // Messages never contain null bytes, but an empty message is better than a panic
+        self.last_error = CString::new(msg.to_string()).unwrap_or_default();
+        -1
+    }
+}
+
+/// Creates an emulator executing the given number of instructions per second, 0 for the default.
+#[no_mangle]
+pub extern "C" fn pich8_new(speed: u32) -> *mut Pich8 {
+    let speed = if speed == 0 { 720 } else { speed };
+    Box::into_raw(Box::new(Pich8 {
+        cpu: CPU::new(),
+        runner: Runner::new(speed),
+        keys: [false; 16],
+        framebuffer: Vec::new(),
+        last_error: CString::default(),
+    }))
+}
+
+/// # Safety
+/// `pich8` must be null or a handle from `pich8_new` which isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_free(pich8: *mut Pich8) {
+    if !pich8.is_null() {
+        drop(Box::from_raw(pich8));
+    }
+}
+
+/// Resets the CPU and loads a ROM, returns 0 on success.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new` and `data` must point to `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_load_rom(pich8: *mut Pich8, data: *const u8, size: usize) -> c_int {
+    let pich8 = &mut *pich8;
+    if data.is_null() {
+        return pich8.fail("No ROM given!");
+    }
+    let rom = slice::from_raw_parts(data, size);
+    if let Err(msg) = rom::validate(rom) {
+        return pich8.fail(msg);
+    }
+    let mut cpu = CPU::new();
+    match cpu.load_rom(rom) {
+        Ok(()) => {
+            pich8.cpu = cpu;
+            0
+        }
+        Err(msg) => pich8.fail(msg),
+    }
+}
+
+/// Like `Runner::step`, returns 1 if it completed a frame and 0 otherwise.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_tick(pich8: *mut Pich8) -> c_int {
+    let pich8 = &mut *pich8;
+    match pich8.runner.step(&mut pich8.cpu, &pich8.keys) {
+        Ok(frame_done) => frame_done as c_int,
+        Err(e) => pich8.fail(e),
+    }
+}
+
+/// Like `Runner::run_frame`, returns 0 on success.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_run_frame(pich8: *mut Pich8) -> c_int {
+    let pich8 = &mut *pich8;
+    match pich8.runner.run_frame(&mut pich8.cpu, &pich8.keys) {
+        Ok(()) => 0,
+        Err(e) => pich8.fail(e),
+    }
+}
+
+/// Sets the pressed keys as bitmask, bit 0 is key 0 and bit 15 key F.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_set_keys(pich8: *mut Pich8, keys: u16) {
+    let pich8 = &mut *pich8;
+    for (idx, key) in pich8.keys.iter_mut().enumerate() {
+        *key = keys >> idx & 1 == 1;
+    }
+}
+
+/// Returns the screen row by row, one byte per pixel as in `VideoMemory::pixel`.
+/// The pointer is valid until the next call with this handle.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new`, `width` and `height` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_framebuffer(
+    pich8: *mut Pich8,
+    width: *mut usize,
+    height: *mut usize,
+) -> *const u8 {
+    let pich8 = &mut *pich8;
+    let vmem = pich8.cpu.vmem();
+    let (w, h) = (vmem.render_width(), vmem.render_height());
+    pich8.framebuffer.clear();
+    pich8.framebuffer.extend((0..w * h).map(|idx| {
+        vmem.get_index_plane(Plane::First, idx) as u8
+            | (vmem.get_index_plane(Plane::Second, idx) as u8) << 1
+    }));
+    if !width.is_null() {
+        *width = w;
+    }
+    if !height.is_null() {
+        *height = h;
+    }
+    pich8.framebuffer.as_ptr()
+}
+
+/// Returns whether the sound timer is running, so a tone should be played.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_sound_active(pich8: *const Pich8) -> bool {
+    (*pich8).cpu.ST() > 0
+}
+
+/// Returns the message of the last error, valid until the next call with this handle.
+///
+/// # Safety
+/// `pich8` must be a handle from `pich8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pich8_last_error(pich8: *const Pich8) -> *const c_char {
+    match pich8.as_ref() {
+        Some(pich8) => pich8.last_error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod capi_test {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_api() {
+        unsafe {
+            let pich8 = pich8_new(600);
+            assert_eq!(pich8_load_rom(pich8, ptr::null(), 0), -1);
+            assert_eq!(
+                CStr::from_ptr(pich8_last_error(pich8)).to_str().unwrap(),
+                "No ROM given!"
+            );
+
+            // Draws the font sprite of key 0 while it's pressed, then jumps back
+            let rom = [0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x00];
+            assert_eq!(pich8_load_rom(pich8, rom.as_ptr(), rom.len()), 0);
+            pich8_set_keys(pich8, 0b1);
+            for _ in 0..9 {
+                assert_eq!(pich8_tick(pich8), 0);
+            }
+            assert_eq!(pich8_tick(pich8), 1);
+            assert_eq!(pich8_run_frame(pich8), 0);
+
+            let (mut width, mut height) = (0, 0);
+            let pixels = pich8_framebuffer(pich8, &mut width, &mut height);
+            let pixels = slice::from_raw_parts(pixels, width * height);
+            assert_eq!((width, height), (128, 64));
+            assert!(pixels.contains(&1));
+            assert!(!pich8_sound_active(pich8));
+            pich8_free(pich8);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Runs arbitrary programs to find inputs making the CPU panic instead of returning an error.
+//!
+//! The first byte selects the quirks and the second one the pressed key, the rest is loaded
+//! as ROM. Run with `cargo +nightly fuzz run cpu_tick` in `core`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pich8::cpu::CPU;
+
+/// Enough to reach most of a ROM, while keeping the runs short
+const TICKS: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    let (quirks, key, rom) = match data {
+        [quirks, key, rom @ ..] => (*quirks, *key as usize, rom),
+        _ => return,
+    };
+    let mut cpu = CPU::new();
+    if cpu.load_rom(rom).is_err() {
+        return;
+    }
+    cpu.seed_rng(0);
+    cpu.quirk_load_store = quirks & 1 != 0;
+    cpu.quirk_shift = quirks & 2 != 0;
+    cpu.quirk_draw = quirks & 4 != 0;
+    cpu.quirk_jump = quirks & 8 != 0;
+    cpu.quirk_vf_order = quirks & 16 != 0;
+    cpu.quirk_partialwrap_h = quirks & 32 != 0;
+    cpu.quirk_partialwrap_v = quirks & 64 != 0;
+    let mut keys = [false; 16];
+    if let Some(pressed) = keys.get_mut(key) {
+        *pressed = true;
+    }
+    for tick in 0..TICKS {
+        if cpu.tick(&keys).is_err() {
+            break;
+        }
+        if tick % 12 == 11 {
+            cpu.update_timers();
+        }
+    }
+});
@@ -0,0 +1,184 @@
+use crate::disassembler::{disassemble, OctoProgram, START};
+use crate::rom::Platform;
+use crate::symbols::Symbols;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// How often an opcode is used, e.g. `8XY4` (`ADD`).
+#[derive(Debug, PartialEq)]
+pub struct OpcodeUsage {
+    pub pattern: String,
+    pub mnemonic: String,
+    pub count: usize,
+}
+
+/// What a ROM needs to run, found without running it, so it can be configured beforehand.
+///
+/// Only the instructions reachable from the start are considered, which avoids mistaking
+/// sprites for S-CHIP or XO-CHIP opcodes, but misses code only reached through computed jumps.
+#[derive(Debug, PartialEq)]
+pub struct Analysis {
+    /// The platform of the instructions below, or CHIP-8 if there are none
+    pub platform: Platform,
+    /// The address and opcode of the instructions requiring S-CHIP or XO-CHIP
+    pub extensions: Vec<(u16, u16)>,
+    pub instructions: usize,
+    /// Most used first
+    pub opcodes: Vec<OpcodeUsage>,
+    /// The bytes no instruction reaches, usually sprites, tables and text
+    pub data: Vec<Range<u16>>,
+    /// The keys checked with EX9E and EXA1 from registers holding a constant
+    pub keys: BTreeSet<u8>,
+    /// Whether keys are also checked from registers computed at runtime
+    pub unknown_keys: bool,
+    /// Whether the program waits for any key with FX0A
+    pub waits_for_key: bool,
+}
+
+impl Analysis {
+    pub fn new(rom: &[u8]) -> Self {
+        let program = OctoProgram::analyze(rom, &Symbols::default(), START);
+        let mut analysis = Self {
+            platform: Platform::Chip8,
+            extensions: Vec::new(),
+            instructions: program.code.len(),
+            opcodes: Vec::new(),
+            data: Vec::new(),
+            keys: BTreeSet::new(),
+            unknown_keys: false,
+            waits_for_key: false,
+        };
+        let mut usage = BTreeMap::<String, OpcodeUsage>::new();
+        // The constants in the registers, only tracked within straight-line code
+        let mut values = [None; 16];
+        let mut data_start = START;
+        for &address in &program.code {
+            let opcode = program.word(address).unwrap_or_default();
+            let ext = program.word(address + 2).unwrap_or_default();
+            let platform = Platform::of(opcode);
+            if platform != Platform::Chip8 {
+                analysis.platform = analysis.platform.max(platform);
+                analysis.extensions.push((address as u16, opcode));
+            }
+            let pattern = pattern(opcode);
+            usage
+                .entry(pattern.clone())
+                .or_insert_with(|| OpcodeUsage {
+                    pattern,
+                    mnemonic: mnemonic(opcode, ext),
+                    count: 0,
+                })
+                .count += 1;
+
+            if address != data_start || program.labels.contains_key(&address) {
+                values = [None; 16];
+            }
+            if address > data_start {
+                analysis.data.push(data_start as u16..address as u16);
+            }
+            data_start = data_start.max(address + program.len(address));
+            analysis.track_keys(opcode, &mut values);
+        }
+        let end = START + rom.len();
+        if end > data_start {
+            analysis.data.push(data_start as u16..end as u16);
+        }
+        analysis.opcodes = usage.into_values().collect();
+        analysis.opcodes.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.pattern.cmp(&b.pattern))
+        });
+        analysis
+    }
+
+    /// Records the keys an instruction checks and updates the known register values.
+    fn track_keys(&mut self, opcode: u16, values: &mut [Option<u8>; 16]) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let nn = (opcode & 0x00FF) as u8;
+        match (opcode >> 12, opcode & 0x00FF) {
+            (0xE, 0x9E | 0xA1) => match values[x] {
+                Some(key) => {
+                    self.keys.insert(key & 0xF);
+                }
+                None => self.unknown_keys = true,
+            },
+            (0xF, 0x0A) => {
+                self.waits_for_key = true;
+                values[x] = None;
+            }
+            (6, _) => values[x] = Some(nn),
+            (7, _) => values[x] = values[x].map(|value| value.wrapping_add(nn)),
+            (8, _) if opcode & 0xF == 0 => values[x] = values[y],
+            (8, _) => {
+                values[x] = None;
+                values[0xF] = None;
+            }
+            (0xC, _) | (0xF, 0x07) => values[x] = None,
+            (0xD, _) => values[0xF] = None,
+            (5, _) if opcode & 0xF == 3 => values[x.min(y)..=x.max(y)].fill(None),
+            (0xF, 0x65 | 0x85) => values[..=x].fill(None),
+            // Subroutines may change any register
+            (2, _) => *values = [None; 16],
+            _ => (),
+        }
+    }
+}
+
+/// Returns the opcode with its operands replaced by their names, e.g. `DXYN`.
+fn pattern(opcode: u16) -> String {
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    match opcode >> 12 {
+        _ if opcode == 0xF000 => "F000 NNNN".to_string(),
+        0 if opcode & 0xFFE0 == 0x00C0 => format!("00{:X}N", opcode >> 4 & 0xF),
+        0 if matches!(opcode, 0x00E0 | 0x00EE | 0x00FB..=0x00FF) => format!("{:04X}", opcode),
+        0 => "0NNN".to_string(),
+        1 | 2 | 0xA | 0xB => format!("{:X}NNN", opcode >> 12),
+        3 | 4 | 6 | 7 | 0xC => format!("{:X}XNN", opcode >> 12),
+        5 | 8 | 9 => format!("{:X}XY{:X}", opcode >> 12, n),
+        0xD => "DXYN".to_string(),
+        _ => format!("{:X}X{:02X}", opcode >> 12, nn),
+    }
+}
+
+/// Returns the mnemonic of the opcode without operands.
+fn mnemonic(opcode: u16, ext: u16) -> String {
+    let instruction = disassemble(opcode, ext);
+    match instruction.split_once(' ') {
+        Some((name, _)) => name.to_string(),
+        None => instruction,
+    }
+}
+
+#[cfg(test)]
+mod analysis_test {
+    use super::*;
+
+    #[test]
+    fn test_analysis() {
+        let rom = [
+            0x60, 0x05, // LD V0, 5
+            0xE0, 0x9E, // SKP V0
+            0x22, 0x08, // CALL 208
+            0xF1, 0x0A, // LD V1, K
+            0xE1, 0xA1, // SKNP V1
+            0x00, 0xFF, // HIGH
+            0x00, 0xEE, // RET
+            0xF0, 0x90, 0xF0,
+        ];
+        let analysis = Analysis::new(&rom);
+        assert_eq!(analysis.platform, Platform::SChip);
+        assert_eq!(analysis.extensions, [(0x20A, 0x00FF)]);
+        assert_eq!(analysis.instructions, 7);
+        assert_eq!(analysis.opcodes.len(), 7);
+        assert_eq!(analysis.opcodes[0].pattern, "00EE");
+        assert_eq!(analysis.opcodes[6].pattern, "FX0A");
+        assert_eq!(analysis.opcodes[6].mnemonic, "LD");
+        assert_eq!(analysis.data, vec![0x20E..0x211]);
+        assert_eq!(analysis.keys, BTreeSet::from([5]));
+        assert!(analysis.unknown_keys);
+        assert!(analysis.waits_for_key);
+    }
+}
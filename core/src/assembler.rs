@@ -0,0 +1,797 @@
+use crate::symbols::Symbols;
+use std::collections::HashMap;
+
+/// A program assembled from Octo source, with its labels for the debug views.
+pub struct Program {
+    pub rom: Vec<u8>,
+    pub symbols: Symbols,
+}
+
+/// Assembles Octo source (`.8o`) into a ROM.
+///
+/// The instructions, labels, `:const`, `:alias`, `:org`, `:next`, `:unpack`, `:byte` and `:call`
+/// directives and the `if`, `loop` and `while` control structures are supported, while macros,
+/// `:calc` and `:stringmode` are reported as errors. Errors include the line number.
+pub fn assemble(source: &str) -> Result<Program, String> {
+    let mut assembler = Assembler::new(source);
+    assembler
+        .run()
+        .map_err(|(line, msg)| format!("Line {}: {}", line, msg))?;
+    Ok(Program {
+        rom: assembler.rom,
+        symbols: assembler.symbols,
+    })
+}
+
+const START: usize = 0x200;
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// How a label's address is written into the ROM once it's known.
+#[derive(Clone, Copy)]
+enum Fixup {
+    /// The low 12 bits of an instruction
+    Address,
+    /// A 16-bit address following `i := long`
+    Long,
+    /// The high byte of an address loaded by `:unpack`, or with the nibble in the
+    /// high bits and the address' top 4 bits in the low bits
+    High(Option<u8>),
+    /// The low byte of an address loaded by `:unpack`
+    Low,
+}
+
+enum Block {
+    /// `if ... begin`, with the address of the jump to `else` or `end`
+    If(usize),
+    /// `else`, with the address of the jump to `end`
+    Else(usize),
+    /// `loop`, with its start and the jumps of its `while`s to the end
+    Loop(usize, Vec<usize>),
+}
+
+type Error = (usize, String);
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    /// The assembled memory from address 0x200 on
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<(usize, Fixup, Token<'a>)>,
+    blocks: Vec<(Block, usize)>,
+    symbols: Symbols,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(source: &'a str) -> Self {
+        let tokens = source
+            .lines()
+            .enumerate()
+            .flat_map(|(idx, line)| {
+                let code = line.split('#').next().unwrap_or_default();
+                code.split_whitespace().map(move |text| Token {
+                    text,
+                    line: idx + 1,
+                })
+            })
+            .collect();
+        Self {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            here: START,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            symbols: Symbols::default(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        // The program starts with a jump to main, wherever it is
+        let entry = Token {
+            text: "main",
+            line: 1,
+        };
+        self.fixups.push((self.here, Fixup::Address, entry));
+        self.instruction(0x1000)?;
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+        if let Some((block, line)) = self.blocks.last() {
+            let msg = match block {
+                Block::If(_) | Block::Else(_) => "'begin' without 'end'",
+                Block::Loop(..) => "'loop' without 'again'",
+            };
+            return Err((*line, msg.to_string()));
+        }
+        if !self.labels.contains_key("main") {
+            return Err((1, "The program has no 'main' label!".to_string()));
+        }
+        for (address, fixup, token) in std::mem::take(&mut self.fixups) {
+            let target = *self
+                .labels
+                .get(token.text)
+                .ok_or_else(|| (token.line, format!("Undefined name '{}'", token.text)))?;
+            self.patch(address, fixup, target, token.line)?;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, Error> {
+        let line = self.tokens.last().map_or(1, |token| token.line);
+        let token = *self
+            .tokens
+            .get(self.pos)
+            .ok_or((line, "Unexpected end of file".to_string()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|token| token.text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), Error> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err((
+                token.line,
+                format!("Expected '{}', got '{}'", text, token.text),
+            ))
+        }
+    }
+
+    fn byte(&mut self, value: u8) -> Result<(), Error> {
+        let line = self
+            .tokens
+            .get(self.pos.saturating_sub(1))
+            .map_or(1, |t| t.line);
+        if self.here > 0xFFFF {
+            return Err((line, "The program doesn't fit into memory".to_string()));
+        }
+        let offset = self.here - START;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = value;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn instruction(&mut self, opcode: u16) -> Result<(), Error> {
+        self.byte((opcode >> 8) as u8)?;
+        self.byte(opcode as u8)
+    }
+
+    fn patch(
+        &mut self,
+        address: usize,
+        fixup: Fixup,
+        target: usize,
+        line: usize,
+    ) -> Result<(), Error> {
+        let offset = address - START;
+        match fixup {
+            Fixup::Address => {
+                if target > 0xFFF {
+                    return Err((line, format!("Address {:#X} is out of range", target)));
+                }
+                self.rom[offset] = self.rom[offset] & 0xF0 | (target >> 8) as u8;
+                self.rom[offset + 1] = target as u8;
+            }
+            Fixup::Long => {
+                self.rom[offset] = (target >> 8) as u8;
+                self.rom[offset + 1] = target as u8;
+            }
+            Fixup::High(Some(nibble)) => {
+                self.rom[offset] = nibble << 4 | (target >> 8 & 0xF) as u8;
+            }
+            Fixup::High(None) => self.rom[offset] = (target >> 8) as u8,
+            Fixup::Low => self.rom[offset] = target as u8,
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, token: Token<'a>, address: usize) -> Result<(), Error> {
+        self.check_name(token)?;
+        self.labels.insert(token.text, address);
+        self.symbols.insert(token.text, address as u16);
+        Ok(())
+    }
+
+    fn check_name(&self, token: Token<'a>) -> Result<(), Error> {
+        if self.labels.contains_key(token.text)
+            || self.constants.contains_key(token.text)
+            || self.aliases.contains_key(token.text)
+        {
+            return Err((token.line, format!("'{}' is already defined", token.text)));
+        }
+        if parse_number(token.text).is_some() || self.register(token.text).is_some() {
+            return Err((
+                token.line,
+                format!("'{}' cannot be used as name", token.text),
+            ));
+        }
+        Ok(())
+    }
+
+    fn register(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        if digit.len() == 1 {
+            u8::from_str_radix(digit, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    fn next_register(&mut self) -> Result<u16, Error> {
+        let token = self.next()?;
+        self.register(token.text).map(u16::from).ok_or_else(|| {
+            (
+                token.line,
+                format!("Expected a register, got '{}'", token.text),
+            )
+        })
+    }
+
+    /// Returns the value of a number or constant.
+    fn value(&self, token: Token<'a>) -> Option<i64> {
+        parse_number(token.text).or_else(|| self.constants.get(token.text).copied())
+    }
+
+    fn next_value(&mut self, min: i64, max: i64) -> Result<i64, Error> {
+        let token = self.next()?;
+        let value = self.value(token).ok_or_else(|| {
+            (
+                token.line,
+                format!("Expected a number, got '{}'", token.text),
+            )
+        })?;
+        if value < min || value > max {
+            return Err((token.line, format!("{} is out of range", token.text)));
+        }
+        Ok(value)
+    }
+
+    fn next_byte(&mut self) -> Result<u16, Error> {
+        Ok(self.next_value(-128, 255)? as u8 as u16)
+    }
+
+    fn next_nibble(&mut self) -> Result<u16, Error> {
+        Ok(self.next_value(0, 15)? as u16)
+    }
+
+    /// Returns the address of a number, constant or label, or `None` for labels which aren't
+    /// defined yet.
+    fn next_target(&mut self) -> Result<(Option<usize>, Token<'a>), Error> {
+        let token = self.next()?;
+        match self.value(token) {
+            Some(value) if (0..=0xFFFF).contains(&value) => Ok((Some(value as usize), token)),
+            Some(_) => Err((token.line, format!("{} is out of range", token.text))),
+            None => Ok((self.labels.get(token.text).copied(), token)),
+        }
+    }
+
+    /// Returns the address of a number, constant or label, labels which aren't defined yet are
+    /// filled in at the end.
+    fn next_address(&mut self, fixup: Fixup, at: usize) -> Result<usize, Error> {
+        match self.next_target()? {
+            (Some(target), token) if matches!(fixup, Fixup::Address) && target > 0xFFF => {
+                Err((token.line, format!("Address {:#X} is out of range", target)))
+            }
+            (Some(target), _) => Ok(target),
+            (None, token) => {
+                self.fixups.push((at, fixup, token));
+                Ok(0)
+            }
+        }
+    }
+
+    /// Emits an instruction with a 12-bit address, e.g. `jump`.
+    fn address_instruction(&mut self, opcode: u16) -> Result<(), Error> {
+        let at = self.here;
+        let address = self.next_address(Fixup::Address, at)?;
+        self.instruction(opcode | address as u16)
+    }
+
+    fn statement(&mut self) -> Result<(), Error> {
+        let token = self.next()?;
+        let line = token.line;
+        match token.text {
+            ":" => {
+                let name = self.next()?;
+                self.define(name, self.here)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                self.check_name(name)?;
+                let value_token = self.next()?;
+                let value = self
+                    .value(value_token)
+                    .or_else(|| self.labels.get(value_token.text).map(|&a| a as i64))
+                    .ok_or_else(|| {
+                        (
+                            value_token.line,
+                            format!("Unknown value '{}'", value_token.text),
+                        )
+                    })?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                if self.labels.contains_key(name.text) || self.constants.contains_key(name.text) {
+                    return Err((name.line, format!("'{}' is already defined", name.text)));
+                }
+                let register = self.next_register()? as u8;
+                self.aliases.insert(name.text, register);
+            }
+            ":org" => self.here = self.next_value(START as i64, 0xFFFF)? as usize,
+            ":next" => {
+                let name = self.next()?;
+                self.define(name, self.here + 1)?;
+            }
+            ":unpack" => {
+                let nibble = if self.peek() == Some("long") {
+                    self.pos += 1;
+                    None
+                } else {
+                    Some(self.next_nibble()? as u8)
+                };
+                // v0 := high byte, v1 := low byte
+                let (target, token) = self.next_target()?;
+                let address = target.unwrap_or_default() as u16;
+                if target.is_none() {
+                    self.fixups
+                        .push((self.here + 1, Fixup::High(nibble), token));
+                    self.fixups.push((self.here + 3, Fixup::Low, token));
+                }
+                let high = match nibble {
+                    Some(nibble) => (nibble as u16) << 4 | (address >> 8 & 0xF),
+                    None => address >> 8,
+                };
+                self.instruction(0x6000 | high)?;
+                self.instruction(0x6100 | (address & 0xFF))?;
+            }
+            ":byte" => {
+                let value = self.next_byte()?;
+                self.byte(value as u8)?;
+            }
+            ":call" => self.address_instruction(0x2000)?,
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ":macro" | ":calc" | ":stringmode" | ":assert" | ":pointer" => {
+                return Err((line, format!("'{}' is not supported", token.text)));
+            }
+            "return" | ";" => self.instruction(0x00EE)?,
+            "clear" => self.instruction(0x00E0)?,
+            "hires" => self.instruction(0x00FF)?,
+            "lores" => self.instruction(0x00FE)?,
+            "exit" => self.instruction(0x00FD)?,
+            "scroll-right" => self.instruction(0x00FB)?,
+            "scroll-left" => self.instruction(0x00FC)?,
+            "scroll-down" => {
+                let rows = self.next_nibble()?;
+                self.instruction(0x00C0 | rows)?;
+            }
+            "scroll-up" => {
+                let rows = self.next_nibble()?;
+                self.instruction(0x00D0 | rows)?;
+            }
+            "audio" => self.instruction(0xF002)?,
+            "plane" => {
+                let planes = self.next_value(0, 3)? as u16;
+                self.instruction(0xF001 | planes << 8)?;
+            }
+            "bcd" => {
+                let x = self.next_register()?;
+                self.instruction(0xF033 | x << 8)?;
+            }
+            "save" | "load" => {
+                let x = self.next_register()?;
+                if self.peek() == Some("-") {
+                    self.pos += 1;
+                    let y = self.next_register()?;
+                    let op = if token.text == "save" { 2 } else { 3 };
+                    self.instruction(0x5000 | x << 8 | y << 4 | op)?;
+                } else {
+                    let op = if token.text == "save" { 0x55 } else { 0x65 };
+                    self.instruction(0xF000 | x << 8 | op)?;
+                }
+            }
+            "saveflags" => {
+                let x = self.next_register()?;
+                self.instruction(0xF075 | x << 8)?;
+            }
+            "loadflags" => {
+                let x = self.next_register()?;
+                self.instruction(0xF085 | x << 8)?;
+            }
+            "sprite" => {
+                let x = self.next_register()?;
+                let y = self.next_register()?;
+                let n = self.next_nibble()?;
+                self.instruction(0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "jump" => self.address_instruction(0x1000)?,
+            "jump0" => self.address_instruction(0xB000)?,
+            "native" => self.address_instruction(0x0000)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.next_register()?;
+                let op = match token.text {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.instruction(0xF000 | x << 8 | op)?;
+            }
+            "i" | "I" => self.index()?,
+            "if" => {
+                let cond_line = line;
+                // Without `begin`, the condition skips the single following statement
+                let is_block = self.peek_block_keyword()?;
+                self.condition(is_block)?;
+                let keyword = self.next()?;
+                if is_block {
+                    self.blocks.push((Block::If(self.here), cond_line));
+                    self.instruction(0x1000)?;
+                } else if keyword.text != "then" {
+                    return Err((
+                        keyword.line,
+                        format!("Expected 'then' or 'begin', got '{}'", keyword.text),
+                    ));
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some((Block::If(jump), if_line)) => {
+                    let end_jump = self.here;
+                    self.instruction(0x1000)?;
+                    self.patch(jump, Fixup::Address, self.here, line)?;
+                    self.blocks.push((Block::Else(end_jump), if_line));
+                }
+                _ => return Err((line, "'else' without 'if ... begin'".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some((Block::If(jump), _)) | Some((Block::Else(jump), _)) => {
+                    self.patch(jump, Fixup::Address, self.here, line)?;
+                }
+                _ => return Err((line, "'end' without 'if ... begin'".to_string())),
+            },
+            "loop" => self.blocks.push((Block::Loop(self.here, Vec::new()), line)),
+            "while" => {
+                if !self
+                    .blocks
+                    .iter()
+                    .any(|(block, _)| matches!(block, Block::Loop(..)))
+                {
+                    return Err((line, "'while' outside of a loop".to_string()));
+                }
+                self.condition(true)?;
+                let jump = self.here;
+                self.instruction(0x1000)?;
+                if let Some((Block::Loop(_, exits), _)) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|(block, _)| matches!(block, Block::Loop(..)))
+                {
+                    exits.push(jump);
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some((Block::Loop(start, exits), _)) => {
+                    if start > 0xFFF {
+                        return Err((line, format!("Address {:#X} is out of range", start)));
+                    }
+                    self.instruction(0x1000 | start as u16)?;
+                    for exit in exits {
+                        self.patch(exit, Fixup::Address, self.here, line)?;
+                    }
+                }
+                _ => return Err((line, "'again' without 'loop'".to_string())),
+            },
+            text => {
+                if let Some(x) = self.register(text) {
+                    self.assignment(x as u16)?;
+                } else if let Some(value) = self.value(token) {
+                    // Data, e.g. sprites
+                    if !(-128..=255).contains(&value) {
+                        return Err((line, format!("{} is out of range", text)));
+                    }
+                    self.byte(value as u8)?;
+                } else if text.starts_with(':') {
+                    return Err((line, format!("Unknown directive '{}'", text)));
+                } else {
+                    // A label on its own calls it
+                    self.pos -= 1;
+                    self.address_instruction(0x2000)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the `if` condition is followed by `begin` rather than `then`.
+    fn peek_block_keyword(&self) -> Result<bool, Error> {
+        let rest = &self.tokens[self.pos..];
+        match rest
+            .iter()
+            .take(4)
+            .find(|t| t.text == "then" || t.text == "begin")
+        {
+            Some(token) => Ok(token.text == "begin"),
+            None => {
+                let line = rest.first().map_or(1, |t| t.line);
+                Err((
+                    line,
+                    "Expected 'then' or 'begin' after the condition".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn index(&mut self) -> Result<(), Error> {
+        let op = self.next()?;
+        match op.text {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.pos += 1;
+                    let x = self.next_register()?;
+                    self.instruction(0xF029 | x << 8)
+                }
+                Some("bighex") => {
+                    self.pos += 1;
+                    let x = self.next_register()?;
+                    self.instruction(0xF030 | x << 8)
+                }
+                Some("long") => {
+                    self.pos += 1;
+                    self.instruction(0xF000)?;
+                    let at = self.here;
+                    let address = self.next_address(Fixup::Long, at)?;
+                    self.instruction(address as u16)
+                }
+                _ => self.address_instruction(0xA000),
+            },
+            "+=" => {
+                let x = self.next_register()?;
+                self.instruction(0xF01E | x << 8)
+            }
+            _ => Err((op.line, format!("Unknown operator '{}' for i", op.text))),
+        }
+    }
+
+    fn assignment(&mut self, x: u16) -> Result<(), Error> {
+        let op = self.next()?;
+        let source = self.next()?;
+        let y = self.register(source.text).map(u16::from);
+        match (op.text, y) {
+            (":=", _) if source.text == "random" => {
+                let mask = self.next_byte()?;
+                self.instruction(0xC000 | x << 8 | mask)
+            }
+            (":=", _) if source.text == "key" => self.instruction(0xF00A | x << 8),
+            (":=", _) if source.text == "delay" => self.instruction(0xF007 | x << 8),
+            (":=", Some(y)) => self.instruction(0x8000 | x << 8 | y << 4),
+            ("|=", Some(y)) => self.instruction(0x8001 | x << 8 | y << 4),
+            ("&=", Some(y)) => self.instruction(0x8002 | x << 8 | y << 4),
+            ("^=", Some(y)) => self.instruction(0x8003 | x << 8 | y << 4),
+            ("+=", Some(y)) => self.instruction(0x8004 | x << 8 | y << 4),
+            ("-=", Some(y)) => self.instruction(0x8005 | x << 8 | y << 4),
+            (">>=", Some(y)) => self.instruction(0x8006 | x << 8 | y << 4),
+            ("=-", Some(y)) => self.instruction(0x8007 | x << 8 | y << 4),
+            ("<<=", Some(y)) => self.instruction(0x800E | x << 8 | y << 4),
+            (":=" | "+=" | "-=", None) => {
+                self.pos -= 1;
+                let value = self.next_byte()?;
+                match op.text {
+                    ":=" => self.instruction(0x6000 | x << 8 | value),
+                    "+=" => self.instruction(0x7000 | x << 8 | value),
+                    _ => self.instruction(0x7000 | x << 8 | (value as u8).wrapping_neg() as u16),
+                }
+            }
+            _ => Err((
+                op.line,
+                format!("Cannot use '{}' with '{}'", op.text, source.text),
+            )),
+        }
+    }
+
+    /// Emits a condition skipping the next instruction if it's false, or if it's true when `inverted`.
+    fn condition(&mut self, inverted: bool) -> Result<(), Error> {
+        let x = self.next_register()?;
+        let op = self.next()?;
+        let skip_if = |skip_if_false: u16, skip_if_true: u16| {
+            if inverted {
+                skip_if_true
+            } else {
+                skip_if_false
+            }
+        };
+        match op.text {
+            "key" => return self.instruction(skip_if(0xE0A1, 0xE09E) | x << 8),
+            "-key" => return self.instruction(skip_if(0xE09E, 0xE0A1) | x << 8),
+            _ => (),
+        }
+        let source = self.next()?;
+        let y = self.register(source.text).map(u16::from);
+        if y.is_none() {
+            self.pos -= 1;
+        }
+        match (op.text, y) {
+            ("==", Some(y)) => self.instruction(skip_if(0x9000, 0x5000) | x << 8 | y << 4),
+            ("!=", Some(y)) => self.instruction(skip_if(0x5000, 0x9000) | x << 8 | y << 4),
+            ("==", None) => {
+                let n = self.next_byte()?;
+                self.instruction(skip_if(0x4000, 0x3000) | x << 8 | n)
+            }
+            ("!=", None) => {
+                let n = self.next_byte()?;
+                self.instruction(skip_if(0x3000, 0x4000) | x << 8 | n)
+            }
+            ("<" | ">=" | ">" | "<=", _) => {
+                // Compare with a subtraction in vf, whose flag is set if there's no borrow
+                match y {
+                    Some(y) => self.instruction(0x8F00 | y << 4)?,
+                    None => {
+                        let n = self.next_byte()?;
+                        self.instruction(0x6F00 | n)?;
+                    }
+                }
+                let (subtraction, flag_if_false) = match op.text {
+                    // vf := x - vf, the flag is set if x >= y
+                    "<" => (0x8F07, 1),
+                    ">=" => (0x8F07, 0),
+                    // vf := vf - x, the flag is set if y >= x
+                    ">" => (0x8F05, 1),
+                    _ => (0x8F05, 0),
+                };
+                self.instruction(subtraction | x << 4)?;
+                let skip_value = if inverted {
+                    1 - flag_if_false
+                } else {
+                    flag_if_false
+                };
+                self.instruction(0x3F00 | skip_value)
+            }
+            _ => Err((op.line, format!("Unknown comparison '{}'", op.text))),
+        }
+    }
+}
+
+/// Parses a decimal, hex (`0x`) or binary (`0b`) number, optionally negative.
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod assembler_test {
+    use super::*;
+
+    fn words(source: &str) -> Vec<u16> {
+        let program = assemble(source).unwrap();
+        program
+            .rom
+            .chunks(2)
+            .map(|word| (word[0] as u16) << 8 | *word.get(1).unwrap_or(&0) as u16)
+            .collect()
+    }
+
+    #[test]
+    fn test_instructions() {
+        let source = "
+            :alias x v1
+            :const speed 3
+            : main
+                clear
+                x := speed
+                x += -1
+                v2 := random 0xFF
+                i := sprite-data
+                sprite x v2 5
+                v2 <<= x
+                if x == 0 then return
+                draw
+                jump main
+            : draw
+                i := hex x
+                ;
+            : sprite-data
+                0xF0 0b10010000 144
+        ";
+        assert_eq!(
+            words(source),
+            [
+                0x1202, 0x00E0, 0x6103, 0x71FF, 0xC2FF, 0xA21C, 0xD125, 0x821E, 0x4100, 0x00EE,
+                0x2218, 0x1202, 0xF129, 0x00EE, 0xF090, 0x9000
+            ]
+        );
+        let program = assemble(source).unwrap();
+        assert_eq!(program.symbols.label(0x202), Some("main"));
+        assert_eq!(program.symbols.address("sprite-data"), Some(0x21C));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            : main
+                loop
+                    v0 += 1
+                    while v0 != 10
+                    if v0 > v1 begin
+                        v1 := v0
+                    else
+                        v1 += 1
+                    end
+                again
+        ";
+        assert_eq!(
+            words(source),
+            [
+                0x1202, 0x7001, 0x400A, 0x1218, 0x8F10, 0x8F05, 0x3F00, 0x1214, 0x8100, 0x1216,
+                0x7101, 0x1202
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble(": main\n  v0 := 256\n").err().unwrap(),
+            "Line 2: 256 is out of range"
+        );
+        assert_eq!(
+            assemble(": main\n\n  jump nowhere\n").err().unwrap(),
+            "Line 3: Undefined name 'nowhere'"
+        );
+        assert_eq!(
+            assemble(": main\n  loop\n  v0 += 1\n").err().unwrap(),
+            "Line 2: 'loop' without 'again'"
+        );
+        assert_eq!(
+            assemble(": start\n  clear\n").err().unwrap(),
+            "Line 1: The program has no 'main' label!"
+        );
+        assert!(assemble(":macro twice x { x x }\n: main\n").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::cpu::CPU;
+use crate::expression::Condition;
+
+/// A breakpoint at an address, on a condition, or at an address only when a condition holds.
+pub struct ConditionalBreakpoint {
+    pub address: Option<u16>,
+    pub condition: Option<Condition>,
+    /// The condition as entered by the user
+    pub description: String,
+}
+
+/// Breakpoints on events independent of the address, pausing before the next sprite is drawn
+/// or the program waits for a key, and after the sound timer was started.
+#[derive(Default)]
+pub struct EventBreakpoints {
+    pub draw: bool,
+    pub sound: bool,
+    pub key_wait: bool,
+    last_st: u8,
+}
+
+impl EventBreakpoints {
+    fn check(&mut self, cpu: &CPU) -> bool {
+        let opcode = cpu.next_opcode();
+        let sound_started = self.last_st == 0 && cpu.ST() > 0;
+        self.last_st = cpu.ST();
+        (self.draw && opcode & 0xF000 == 0xD000)
+            || (self.sound && sound_started)
+            || (self.key_wait && opcode & 0xF0FF == 0xF00A)
+    }
+}
+
+/// The breakpoints set in the disassembly view and the breakpoints window, checked after every instruction.
+#[derive(Default)]
+pub struct BreakpointList {
+    breakpoints: Vec<ConditionalBreakpoint>,
+    pub events: EventBreakpoints,
+}
+
+impl BreakpointList {
+    pub fn add(&mut self, address: Option<u16>, condition: &str) -> Result<(), String> {
+        let description = condition.trim().to_string();
+        let condition = match description.as_str() {
+            "" => None,
+            condition => Some(condition.parse()?),
+        };
+        if address.is_none() && condition.is_none() {
+            return Err("A breakpoint needs an address or a condition".to_string());
+        }
+        self.breakpoints.push(ConditionalBreakpoint {
+            address,
+            condition,
+            description,
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.breakpoints.len() {
+            self.breakpoints.remove(idx);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Removes the unconditional breakpoint at the address, or adds one if there is none.
+    pub fn toggle(&mut self, address: u16) {
+        match self
+            .breakpoints
+            .iter()
+            .position(|bp| bp.address == Some(address) && bp.condition.is_none())
+        {
+            Some(idx) => {
+                self.breakpoints.remove(idx);
+            }
+            None => self.breakpoints.push(ConditionalBreakpoint {
+                address: Some(address),
+                condition: None,
+                description: String::new(),
+            }),
+        }
+    }
+
+    /// Returns true if there's any breakpoint at the address, conditional or not.
+    pub fn has_address(&self, address: u16) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|bp| bp.address == Some(address))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ConditionalBreakpoint> {
+        self.breakpoints.iter()
+    }
+
+    /// Checks all breakpoints against the CPU state, returns true if any of them is hit.
+    /// Every condition is checked, so `changed` conditions keep track of their value.
+    pub fn check(&mut self, cpu: &CPU) -> bool {
+        let mut hit = self.events.check(cpu);
+        for bp in &mut self.breakpoints {
+            if bp.address.is_some_and(|address| address != cpu.PC()) {
+                continue;
+            }
+            hit |= bp.condition.as_mut().is_none_or(|c| c.check(cpu));
+        }
+        hit
+    }
+}
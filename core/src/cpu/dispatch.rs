@@ -0,0 +1,344 @@
+use super::*;
+
+/// Executes an opcode, which is passed to extract the operands.
+type Handler = fn(&mut CPU, u16) -> Result<(), Error>;
+
+#[inline(always)]
+fn x(opcode: u16) -> usize {
+    (opcode & 0x0F00) as usize >> 8
+}
+
+#[inline(always)]
+fn y(opcode: u16) -> usize {
+    (opcode & 0x00F0) as usize >> 4
+}
+
+#[inline(always)]
+fn n(opcode: u16) -> u8 {
+    (opcode & 0x000F) as u8
+}
+
+#[inline(always)]
+fn nn(opcode: u16) -> u8 {
+    (opcode & 0x00FF) as u8
+}
+
+#[inline(always)]
+fn nnn(opcode: u16) -> u16 {
+    opcode & 0x0FFF
+}
+
+fn invalid(cpu: &mut CPU, _: u16) -> Result<(), Error> {
+    cpu.opcode_invalid();
+    Ok(())
+}
+
+/// The handlers by the highest nibble, with the groups sharing it indexed by the lowest nibble or
+/// byte, see `decode`.
+const OPCODES: [&[Handler]; 16] = [
+    &OPCODES_00NN,
+    &[|cpu, op| {
+        match op {
+            0x1260 => cpu.opcode_0x1260(nnn(op)),
+            _ => cpu.opcode_0x1NNN(nnn(op)),
+        }
+        Ok(())
+    }],
+    &[|cpu, op| cpu.opcode_0x2NNN(nnn(op))],
+    &[|cpu, op| {
+        cpu.opcode_0x3XNN(x(op), nn(op));
+        Ok(())
+    }],
+    &[|cpu, op| {
+        cpu.opcode_0x4XNN(x(op), nn(op));
+        Ok(())
+    }],
+    &OPCODES_5XYN,
+    &[|cpu, op| {
+        cpu.opcode_0x6XNN(x(op), nn(op));
+        Ok(())
+    }],
+    &[|cpu, op| {
+        cpu.opcode_0x7XNN(x(op), nn(op));
+        Ok(())
+    }],
+    &OPCODES_8XYN,
+    &OPCODES_9XYN,
+    &[|cpu, op| {
+        cpu.opcode_0xANNN(nnn(op));
+        Ok(())
+    }],
+    &[|cpu, op| {
+        cpu.opcode_0xBNNN(nnn(op));
+        Ok(())
+    }],
+    &[|cpu, op| {
+        cpu.opcode_0xCXNN(x(op), nn(op));
+        Ok(())
+    }],
+    &[|cpu, op| cpu.opcode_0xDXYN(x(op), y(op), n(op) as usize)],
+    &OPCODES_EXNN,
+    &OPCODES_FXNN,
+];
+
+/// 0x00NN by NN
+const OPCODES_00NN: [Handler; 256] = {
+    let mut table = [invalid as Handler; 256];
+    let mut idx = 0;
+    while idx < 16 {
+        table[0xC0 + idx] = |cpu, op| {
+            cpu.opcode_schip_0x00CN(n(op));
+            Ok(())
+        };
+        table[0xD0 + idx] = |cpu, op| {
+            cpu.opcode_xochip_0x00DN(n(op));
+            Ok(())
+        };
+        idx += 1;
+    }
+    table[0xE0] = |cpu, _| {
+        cpu.opcode_0x00E0();
+        Ok(())
+    };
+    table[0xEE] = |cpu, _| cpu.opcode_0x00EE();
+    table[0xFB] = |cpu, _| {
+        cpu.opcode_schip_0x00FB();
+        Ok(())
+    };
+    table[0xFC] = |cpu, _| {
+        cpu.opcode_schip_0x00FC();
+        Ok(())
+    };
+    table[0xFD] = |cpu, _| {
+        cpu.opcode_schip_0x00FD();
+        Ok(())
+    };
+    table[0xFE] = |cpu, _| {
+        cpu.opcode_schip_0x00FE();
+        Ok(())
+    };
+    table[0xFF] = |cpu, _| {
+        cpu.opcode_schip_0x00FF();
+        Ok(())
+    };
+    table
+};
+
+/// 0x5XYN by N
+const OPCODES_5XYN: [Handler; 16] = {
+    let mut table = [invalid as Handler; 16];
+    table[0] = |cpu, op| {
+        cpu.opcode_0x5XY0(x(op), y(op));
+        Ok(())
+    };
+    table[2] = |cpu, op| cpu.opcode_xochip_0x5XY2(x(op), y(op));
+    table[3] = |cpu, op| cpu.opcode_xochip_0x5XY3(x(op), y(op));
+    table
+};
+
+/// 0x8XYN by N
+const OPCODES_8XYN: [Handler; 16] = {
+    let mut table = [invalid as Handler; 16];
+    table[0] = |cpu, op| {
+        cpu.opcode_0x8XY0(x(op), y(op));
+        Ok(())
+    };
+    table[1] = |cpu, op| {
+        cpu.opcode_0x8XY1(x(op), y(op));
+        Ok(())
+    };
+    table[2] = |cpu, op| {
+        cpu.opcode_0x8XY2(x(op), y(op));
+        Ok(())
+    };
+    table[3] = |cpu, op| {
+        cpu.opcode_0x8XY3(x(op), y(op));
+        Ok(())
+    };
+    table[4] = |cpu, op| {
+        cpu.opcode_0x8XY4(x(op), y(op));
+        Ok(())
+    };
+    table[5] = |cpu, op| {
+        cpu.opcode_0x8XY5(x(op), y(op));
+        Ok(())
+    };
+    table[6] = |cpu, op| {
+        cpu.opcode_0x8XY6(x(op), y(op));
+        Ok(())
+    };
+    table[7] = |cpu, op| {
+        cpu.opcode_0x8XY7(x(op), y(op));
+        Ok(())
+    };
+    table[0xE] = |cpu, op| {
+        cpu.opcode_0x8XYE(x(op), y(op));
+        Ok(())
+    };
+    table
+};
+
+/// 0x9XYN by N
+const OPCODES_9XYN: [Handler; 16] = {
+    let mut table = [invalid as Handler; 16];
+    table[0] = |cpu, op| {
+        cpu.opcode_0x9XY0(x(op), y(op));
+        Ok(())
+    };
+    table
+};
+
+/// 0xEXNN by NN
+const OPCODES_EXNN: [Handler; 256] = {
+    let mut table = [invalid as Handler; 256];
+    table[0x9E] = |cpu, op| {
+        cpu.opcode_0xEX9E(x(op));
+        Ok(())
+    };
+    table[0xA1] = |cpu, op| {
+        cpu.opcode_0xEXA1(x(op));
+        Ok(())
+    };
+    table
+};
+
+/// 0xFXNN by NN
+const OPCODES_FXNN: [Handler; 256] = {
+    let mut table = [invalid as Handler; 256];
+    table[0x00] = |cpu, op| match x(op) {
+        0 => {
+            cpu.opcode_xochip_0xF000();
+            Ok(())
+        }
+        _ => invalid(cpu, op),
+    };
+    table[0x01] = |cpu, op| {
+        cpu.opcode_xochip_0xFN01(x(op));
+        Ok(())
+    };
+    table[0x02] = |cpu, op| match x(op) {
+        0 => cpu.opcode_xochip_0xF002(),
+        _ => invalid(cpu, op),
+    };
+    table[0x07] = |cpu, op| {
+        cpu.opcode_0xFX07(x(op));
+        Ok(())
+    };
+    table[0x0A] = |cpu, op| {
+        cpu.opcode_0xFX0A(x(op));
+        Ok(())
+    };
+    table[0x15] = |cpu, op| {
+        cpu.opcode_0xFX15(x(op));
+        Ok(())
+    };
+    table[0x18] = |cpu, op| {
+        cpu.opcode_0xFX18(x(op));
+        Ok(())
+    };
+    table[0x1E] = |cpu, op| {
+        cpu.opcode_0xFX1E(x(op));
+        Ok(())
+    };
+    table[0x29] = |cpu, op| {
+        cpu.opcode_0xFX29(x(op));
+        Ok(())
+    };
+    table[0x30] = |cpu, op| {
+        cpu.opcode_schip_0xFX30(x(op));
+        Ok(())
+    };
+    table[0x33] = |cpu, op| cpu.opcode_0xFX33(x(op));
+    table[0x55] = |cpu, op| cpu.opcode_0xFX55(x(op));
+    table[0x65] = |cpu, op| cpu.opcode_0xFX65(x(op));
+    table[0x75] = |cpu, op| {
+        cpu.opcode_schip_0xFX75(x(op));
+        Ok(())
+    };
+    table[0x85] = |cpu, op| {
+        cpu.opcode_schip_0xFX85(x(op));
+        Ok(())
+    };
+    table
+};
+
+/// Returns the handler of the opcode.
+fn decode(opcode: u16) -> Handler {
+    let idx = match opcode >> 12 {
+        0 if opcode == 0x0230 => {
+            return |cpu, _| {
+                cpu.opcode_hires_0x0230();
+                Ok(())
+            }
+        }
+        0 if opcode > 0x00FF => return invalid,
+        0 | 0xE | 0xF => nn(opcode) as usize,
+        5 | 8 | 9 => n(opcode) as usize,
+        _ => 0,
+    };
+    OPCODES[(opcode >> 12) as usize][idx]
+}
+
+/// An instruction decoded once and reused until the memory it was read from changes.
+#[derive(Clone, Copy)]
+pub(super) struct Decoded {
+    pub opcode: u16,
+    /// The second word of F000 NNNN
+    pub ext: u16,
+    handler: Handler,
+}
+
+/// The decoded instructions by address, so loops don't decode the same opcodes over and over.
+#[derive(Default)]
+pub(super) struct DecodeCache {
+    // Allocated on first use, since the cache isn't part of states
+    entries: Vec<Option<Decoded>>,
+}
+
+impl DecodeCache {
+    /// The length of the longest instruction, F000 NNNN
+    const MAX_LEN: usize = 4;
+
+    /// Returns the instruction at the address, which must leave room for the longest instruction.
+    pub fn get(&mut self, mem: &[u8], address: usize) -> Decoded {
+        if self.entries.is_empty() {
+            self.entries = vec![None; mem.len()];
+        }
+        if let Some(decoded) = self.entries[address] {
+            return decoded;
+        }
+        let opcode = u16::from_be_bytes([mem[address], mem[address + 1]]);
+        let ext = match opcode {
+            0xF000 => u16::from_be_bytes([mem[address + 2], mem[address + 3]]),
+            _ => 0,
+        };
+        let decoded = Decoded {
+            opcode,
+            ext,
+            handler: decode(opcode),
+        };
+        self.entries[address] = Some(decoded);
+        decoded
+    }
+
+    /// Drops the instructions overlapping the written bytes.
+    pub fn invalidate(&mut self, written: Range<usize>) {
+        let start = written.start.saturating_sub(Self::MAX_LEN - 1);
+        let end = written.end.min(self.entries.len());
+        if start < end {
+            self.entries[start..end].fill(None);
+        }
+    }
+}
+
+impl CPU {
+    /// Executes the current opcode with the handler decoded when it was fetched.
+    #[inline]
+    pub(super) fn execute(&mut self) -> Result<(), Error> {
+        let handler = match self.next_decoded.take() {
+            Some(decoded) if decoded.opcode == self.opcode => decoded.handler,
+            _ => decode(self.opcode),
+        };
+        handler(self, self.opcode)
+    }
+}
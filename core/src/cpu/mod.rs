@@ -1,16 +1,23 @@
 use crate::video_memory::{Plane, VideoMemory, VideoMode};
+use log::{debug, info, trace};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::ops::Range;
 
+mod dispatch;
 mod opcodes;
 
+use dispatch::{DecodeCache, Decoded};
+
 #[derive(Debug)]
 pub enum Error {
     SaveStateFailed(rmp_serde::encode::Error),
     LoadStateFailed(rmp_serde::decode::Error),
     ProgramCounterOverflow,
     StackOverflow,
+    StackUnderflow,
+    MemoryOverflow,
 }
 
 impl fmt::Display for Error {
@@ -20,6 +27,8 @@ impl fmt::Display for Error {
             Error::LoadStateFailed(e) => write!(f, "Load state error: {}", e),
             Error::ProgramCounterOverflow => write!(f, "Program counter overflow!"),
             Error::StackOverflow => write!(f, "Stack overflow occurred! The ROM might be invalid or different quirk settings required."),
+            Error::StackUnderflow => write!(f, "Stack underflow occurred! The ROM returned without calling a subroutine."),
+            Error::MemoryOverflow => write!(f, "Memory overflow! The ROM accessed memory beyond 0xFFFF."),
         }
     }
 }
@@ -35,6 +44,16 @@ impl From<rmp_serde::decode::Error> for Error {
     }
 }
 
+/// Registers which can be changed in the debugger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Register {
+    PC,
+    I,
+    DT,
+    ST,
+    V(usize),
+}
+
 pub enum Breakpoint {
     PC(u16),
     I(u16),
@@ -57,12 +76,12 @@ pub struct CPU {
     ST: u8,       // Sound timer
     RPL: [u8; 8], // HP48 RPL flags (used for S-CHIP)
 
-    opcode: u16,                     // Current opcode
-    opcode_description: String,      // Current opcode description
-    next_opcode: u16,                // Next opcode
-    next_opcode_description: String, // Next opcode description
-    next_opcode_ext: u16,            // Next opcode extension in case of 32bit opcode (XO-CHIP)
-    sp: usize,                       // Current stack position
+    opcode: u16,                      // Current opcode
+    _opcode_description: String,      // Unused, kept for the layout of older states
+    next_opcode: u16,                 // Next opcode
+    _next_opcode_description: String, // Unused, kept for the layout of older states
+    next_opcode_ext: u16,             // Next opcode extension in case of 32bit opcode (XO-CHIP)
+    sp: usize,                        // Current stack position
 
     pub draw: bool,             // Drawing flag
     key_wait: bool,             // Key wait flag
@@ -77,12 +96,48 @@ pub struct CPU {
     pub quirk_draw: bool,          // Flag for draw quirk
     pub quirk_partialwrap_h: bool, // Flag for partial horizontal wrapping quirk
     pub quirk_partialwrap_v: bool, // Flag for partial vertical wrapping quirk
+
+    #[serde(skip)]
+    rng: Option<StdRng>, // Seeded random number generator for reproducible runs
+    #[serde(skip)]
+    history: [(u16, u16); CPU::HISTORY_LEN], // Ring buffer of the last executed opcodes and their addresses
+    #[serde(skip)]
+    history_pos: usize, // Next position in the history ring buffer
+    #[serde(skip)]
+    opcode_context: OpcodeContext, // What the current opcode's description is based on
+    #[serde(skip)]
+    decoded: DecodeCache, // Decoded instructions by address
+    #[serde(skip)]
+    next_decoded: Option<Decoded>, // Next instruction as decoded when it was fetched
 }
 
+/// The state an opcode description shows, saved when the opcode is executed so the description
+/// is only formatted when needed.
 #[allow(non_snake_case)]
+#[derive(Clone, Copy, Default)]
+struct OpcodeContext {
+    PC: u16,
+    opcode: u16,
+    ext: u16,
+    V: [u8; 16],
+    DT: u8,
+}
+
+#[allow(non_snake_case)]
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CPU {
     const BOOTROM: &'static [u8] = include_bytes!("../../data/bootrom/pich8-logo.ch8");
     const PC_INITIAL: u16 = 0x200;
+    /// The highest program counter leaving room for a 4 byte instruction skipping another one,
+    /// so the program counter can't overflow
+    const PC_MAX: u16 = 0xFFFF - 6;
+    const HISTORY_LEN: usize = 16;
+    pub const MAX_ROM_SIZE: usize = u16::MAX as usize + 1 - CPU::PC_INITIAL as usize;
     const FONTSET: &'static [u8] = &[
         0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
         0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -130,9 +185,9 @@ impl CPU {
             RPL: [0; 8],
 
             opcode: 0,
-            opcode_description: String::new(),
+            _opcode_description: String::new(),
             next_opcode: 0,
-            next_opcode_description: String::new(),
+            _next_opcode_description: String::new(),
             next_opcode_ext: 0,
             sp: 0,
 
@@ -146,6 +201,13 @@ impl CPU {
             quirk_vf_order: true,
             quirk_partialwrap_h: false,
             quirk_partialwrap_v: false,
+
+            rng: None,
+            history: [(0, 0); CPU::HISTORY_LEN],
+            history_pos: 0,
+            opcode_context: OpcodeContext::default(),
+            decoded: DecodeCache::default(),
+            next_decoded: None,
         };
 
         // Load fontsets
@@ -156,21 +218,52 @@ impl CPU {
     }
 
     pub fn from_state(state: &[u8]) -> Result<Self, String> {
-        Ok(rmp_serde::decode::from_slice(state).map_err(|_| "Failed to deserialize state!")?)
+        let mut cpu: Self =
+            rmp_serde::decode::from_slice(state).map_err(|_| "Failed to deserialize state!")?;
+        cpu.opcode_context = OpcodeContext {
+            opcode: cpu.opcode,
+            ..cpu.next_opcode_context()
+        };
+        Ok(cpu)
     }
 
     pub fn save_state(&self) -> Result<Vec<u8>, String> {
         Ok(rmp_serde::encode::to_vec(self).map_err(|_| "Failed to serialize state!")?)
     }
 
+    /// Saves the state without the main memory, which is cheaper when many states are kept and
+    /// the memory is tracked separately, see `from_state_with_mem`.
+    pub fn save_state_without_mem(&mut self) -> Result<Vec<u8>, String> {
+        let mem = std::mem::take(&mut self.mem);
+        let state = self.save_state();
+        self.mem = mem;
+        state
+    }
+
+    /// Restores a state saved with `save_state_without_mem` with the given memory.
+    pub fn from_state_with_mem(state: &[u8], mem: &[u8]) -> Result<Self, String> {
+        let mut cpu = Self::from_state(state)?;
+        if mem.len() != u16::MAX as usize + 1 {
+            return Err("Invalid memory size!".to_string());
+        }
+        cpu.mem = mem.into();
+        Ok(cpu)
+    }
+
+    /// Makes random numbers reproducible, otherwise they're drawn from the thread's generator.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Some(StdRng::seed_from_u64(seed));
+    }
+
     pub fn load_bootrom(&mut self) {
         self.load_rom(Self::BOOTROM).unwrap();
     }
 
     pub fn load_rom(&mut self, prog: &[u8]) -> Result<(), String> {
         if prog.len() <= self.mem.len() - 0x200 {
-            self.vmem.video_mode = VideoMode::Default;
+            self.vmem.set_video_mode(VideoMode::Default);
             self.mem[0x200..0x200 + prog.len()].copy_from_slice(prog);
+            self.decoded.invalidate(0x200..0x200 + prog.len());
             self.PC = CPU::PC_INITIAL;
             self.sp = 0;
             self.prefetch_next_opcode().map_err(|e| format!("{}", e))
@@ -183,6 +276,11 @@ impl CPU {
     pub fn vmem(&self) -> &VideoMemory {
         &self.vmem
     }
+    /// Returns whether the screen changed since the last call, which also resets the drawing flag.
+    pub fn take_vmem_dirty(&mut self) -> bool {
+        self.draw = false;
+        self.vmem.take_dirty()
+    }
     pub fn stack(&self) -> [u16; 16] {
         self.stack
     }
@@ -204,21 +302,88 @@ impl CPU {
     pub fn ST(&self) -> u8 {
         self.ST
     }
+    pub fn keys(&self) -> [bool; 16] {
+        self.keys
+    }
+    /// Returns the register the key is stored in if FX0A is waiting for a key press.
+    pub fn key_wait(&self) -> Option<usize> {
+        self.key_wait.then_some(self.key_reg)
+    }
     pub fn opcode(&self) -> u16 {
         self.opcode
     }
-    pub fn opcode_description(&self) -> &str {
-        &self.opcode_description
+    pub fn opcode_description(&self) -> String {
+        self.describe(&self.opcode_context)
     }
     pub fn next_opcode(&self) -> u16 {
         self.next_opcode
     }
-    pub fn next_opcode_description(&self) -> &str {
-        &self.next_opcode_description
+    pub fn next_opcode_description(&self) -> String {
+        self.describe(&self.next_opcode_context())
     }
     pub fn sp(&self) -> usize {
         self.sp
     }
+    pub fn RPL(&self) -> [u8; 8] {
+        self.RPL
+    }
+    pub fn set_RPL(&mut self, flags: [u8; 8]) {
+        self.RPL = flags;
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        match register {
+            Register::PC => self.PC,
+            Register::I => self.I,
+            Register::DT => self.DT as u16,
+            Register::ST => self.ST as u16,
+            Register::V(idx) => self.V[idx & 0xF] as u16,
+        }
+    }
+    /// Sets a register, e.g. while paused in the debugger. Values are truncated to the register's size.
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        match register {
+            Register::PC => {
+                self.PC = value.min(Self::PC_MAX);
+                let _ = self.prefetch_next_opcode();
+            }
+            Register::I => self.I = value,
+            Register::DT => self.DT = value as u8,
+            Register::ST => self.ST = value as u8,
+            Register::V(idx) => self.V[idx & 0xF] = value as u8,
+        }
+    }
+    pub fn mem(&self) -> &[u8] {
+        &self.mem
+    }
+    pub fn read_mem(&self, address: u16) -> u8 {
+        self.mem[address as usize]
+    }
+    pub fn write_mem(&mut self, address: u16, value: u8) {
+        self.mem[address as usize] = value;
+        self.decoded
+            .invalidate(address as usize..address as usize + 1);
+        // Keep the next opcode up to date when it's modified
+        if address.wrapping_sub(self.PC) < 4 {
+            let _ = self.prefetch_next_opcode();
+        }
+    }
+
+    /// Returns the last executed opcodes with their addresses, oldest first.
+    pub fn history(&self) -> Vec<(u16, u16)> {
+        let (newer, older) = self.history.split_at(self.history_pos);
+        older
+            .iter()
+            .chain(newer.iter())
+            .filter(|&&entry| entry != (0, 0))
+            .copied()
+            .collect()
+    }
+
+    /// Returns whether the next instruction jumps to itself, which many ROMs use to halt at the end.
+    pub fn is_stuck(&self) -> bool {
+        self.next_opcode & 0xF000 == 0x1000 && self.next_opcode & 0x0FFF == self.PC
+    }
 
     pub fn update_timers(&mut self) {
         if self.DT > 0 {
@@ -268,110 +433,50 @@ impl CPU {
     }
 
     fn prefetch_next_opcode(&mut self) -> Result<(), Error> {
-        if self.PC as usize >= self.mem.len() - 2 {
+        if self.PC > Self::PC_MAX {
             self.load_bootrom();
             return Err(Error::ProgramCounterOverflow);
         }
-        self.next_opcode =
-            (self.mem[self.PC as usize] as u16) << 8 | (self.mem[self.PC as usize + 1] as u16);
-        self.next_opcode_description = self.get_next_opcode_description();
+        let decoded = self.decoded.get(&self.mem, self.PC as usize);
+        self.next_opcode = decoded.opcode;
         if self.next_opcode == 0xF000 {
-            self.next_opcode_ext = (self.mem[self.PC as usize + 2] as u16) << 8
-                | (self.mem[self.PC as usize + 3] as u16);
+            self.next_opcode_ext = decoded.ext;
         }
+        self.next_decoded = Some(decoded);
         Ok(())
     }
 
     fn emulate_cycle(&mut self) -> Result<(), Error> {
         // Fetch opcode
         self.opcode = self.next_opcode;
-        self.opcode_description = self.next_opcode_description.clone();
-
-        // Decode opcode
-        let h = (self.opcode & 0xF000) as usize >> 12;
-        let x = (self.opcode & 0x0F00) as usize >> 8;
-        let y = (self.opcode & 0x00F0) as usize >> 4;
-        let n = (self.opcode & 0x000F) as u8;
-        let nn = (self.opcode & 0x00FF) as u8;
-        let nnn = (self.opcode & 0x0FFF) as u16;
-
-        // Execute opcode
-        match (h, x, y, n) {
-            (0, 0, 0xC, _) => self.opcode_schip_0x00CN(n),
-            (0, 0, 0xD, _) => self.opcode_xochip_0x00DN(n),
-            (0, 0, 0xE, 0) => self.opcode_0x00E0(),
-            (0, 0, 0xE, 0xE) => self.opcode_0x00EE(),
-            (0, 0, 0xF, 0xB) => self.opcode_schip_0x00FB(),
-            (0, 0, 0xF, 0xC) => self.opcode_schip_0x00FC(),
-            (0, 0, 0xF, 0xD) => self.opcode_schip_0x00FD(),
-            (0, 0, 0xF, 0xE) => self.opcode_schip_0x00FE(),
-            (0, 0, 0xF, 0xF) => self.opcode_schip_0x00FF(),
-            (0, 2, 3, 0) => self.opcode_hires_0x0230(),
-
-            (1, 2, 6, 0) => self.opcode_0x1260(nnn),
-            (1, _, _, _) => self.opcode_0x1NNN(nnn),
-
-            (2, _, _, _) => self.opcode_0x2NNN(nnn)?,
-
-            (3, _, _, _) => self.opcode_0x3XNN(x, nn),
-
-            (4, _, _, _) => self.opcode_0x4XNN(x, nn),
-
-            (5, _, _, 0) => self.opcode_0x5XY0(x, y),
-            (5, _, _, 2) => self.opcode_xochip_0x5XY2(x, y),
-            (5, _, _, 3) => self.opcode_xochip_0x5XY3(x, y),
-
-            (6, _, _, _) => self.opcode_0x6XNN(x, nn),
-
-            (7, _, _, _) => self.opcode_0x7XNN(x, nn),
-
-            (8, _, _, 0) => self.opcode_0x8XY0(x, y),
-            (8, _, _, 1) => self.opcode_0x8XY1(x, y),
-            (8, _, _, 2) => self.opcode_0x8XY2(x, y),
-            (8, _, _, 3) => self.opcode_0x8XY3(x, y),
-            (8, _, _, 4) => self.opcode_0x8XY4(x, y),
-            (8, _, _, 5) => self.opcode_0x8XY5(x, y),
-            (8, _, _, 6) => self.opcode_0x8XY6(x, y),
-            (8, _, _, 7) => self.opcode_0x8XY7(x, y),
-            (8, _, _, 0xE) => self.opcode_0x8XYE(x, y),
-
-            (9, _, _, 0) => self.opcode_0x9XY0(x, y),
-
-            (0xA, _, _, _) => self.opcode_0xANNN(nnn),
-
-            (0xB, _, _, _) => self.opcode_0xBNNN(nnn),
-
-            (0xC, _, _, _) => self.opcode_0xCXNN(x, nn),
-
-            (0xD, _, _, _) => self.opcode_0xDXYN(x, y, n as usize),
-
-            (0xE, _, 9, 0xE) => self.opcode_0xEX9E(x),
-            (0xE, _, 0xA, 1) => self.opcode_0xEXA1(x),
-
-            (0xF, 0, 0, 0) => self.opcode_xochip_0xF000(),
-            (0xF, _, 0, 1) => self.opcode_xochip_0xFN01(x),
-            (0xF, 0, 0, 2) => self.opcode_xochip_0xF002(),
-            (0xF, _, 0, 7) => self.opcode_0xFX07(x),
-            (0xF, _, 0, 0xA) => self.opcode_0xFX0A(x),
-            (0xF, _, 1, 5) => self.opcode_0xFX15(x),
-            (0xF, _, 1, 8) => self.opcode_0xFX18(x),
-            (0xF, _, 1, 0xE) => self.opcode_0xFX1E(x),
-            (0xF, _, 2, 9) => self.opcode_0xFX29(x),
-            (0xF, _, 3, 0) => self.opcode_schip_0xFX30(x),
-            (0xF, _, 3, 3) => self.opcode_0xFX33(x),
-            (0xF, _, 5, 5) => self.opcode_0xFX55(x),
-            (0xF, _, 6, 5) => self.opcode_0xFX65(x),
-            (0xF, _, 7, 5) => self.opcode_schip_0xFX75(x),
-            (0xF, _, 8, 5) => self.opcode_schip_0xFX85(x),
-
-            _ => self.opcode_invalid(),
-        }
+        self.opcode_context = self.next_opcode_context();
+        self.history[self.history_pos] = (self.PC, self.opcode);
+        self.history_pos = (self.history_pos + 1) % Self::HISTORY_LEN;
+
+        // Decode and execute opcode
+        trace!(
+            "{:04X}: {:04X} {}",
+            self.PC,
+            self.opcode,
+            self.opcode_description()
+        );
+        self.execute()?;
 
         // Fetch next opcode
         self.prefetch_next_opcode()
     }
 
-    fn draw_sprite(&mut self, x: usize, y: usize, height: usize) {
+    /// Returns the range of `len` bytes at the address, or resets like the other errors if it
+    /// exceeds the memory.
+    fn memory_range(&mut self, address: usize, len: usize) -> Result<Range<usize>, Error> {
+        if address + len > self.mem.len() {
+            self.load_bootrom();
+            return Err(Error::MemoryOverflow);
+        }
+        Ok(address..address + len)
+    }
+
+    fn draw_sprite(&mut self, x: usize, y: usize, height: usize) -> Result<(), Error> {
         // Wrap around
         let x = x % self.vmem.width();
         let y = y % self.vmem.height();
@@ -388,7 +493,8 @@ impl CPU {
 
         for plane in &[Plane::First, Plane::Second] {
             if self.vmem.current_plane() == *plane || self.vmem.current_plane() == Plane::Both {
-                let sprite = &self.mem[i..i + len];
+                let range = self.memory_range(i, len)?;
+                let sprite = &self.mem[range];
                 i += len;
 
                 for (mut y, k) in (y..y + height).zip((0..sprite.len()).step_by(step)) {
@@ -430,15 +536,33 @@ impl CPU {
         }
 
         self.V[0xF] = collision as u8;
+        Ok(())
     }
 
-    fn get_next_opcode_description(&self) -> String {
-        let h = (self.next_opcode & 0xF000) as usize >> 12;
-        let x = (self.next_opcode & 0x0F00) as usize >> 8;
-        let y = (self.next_opcode & 0x00F0) as usize >> 4;
-        let n = (self.next_opcode & 0x000F) as u8;
-        let nn = (self.next_opcode & 0x00FF) as u8;
-        let nnn = (self.next_opcode & 0x0FFF) as u16;
+    fn next_opcode_context(&self) -> OpcodeContext {
+        OpcodeContext {
+            PC: self.PC,
+            opcode: self.next_opcode,
+            ext: self.next_opcode_ext,
+            V: self.V,
+            DT: self.DT,
+        }
+    }
+
+    fn describe(&self, context: &OpcodeContext) -> String {
+        let OpcodeContext {
+            PC,
+            opcode,
+            ext,
+            V,
+            DT,
+        } = *context;
+        let h = (opcode & 0xF000) as usize >> 12;
+        let x = (opcode & 0x0F00) as usize >> 8;
+        let y = (opcode & 0x00F0) as usize >> 4;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
 
         match (h, x, y, n) {
             (0, 0, 0xC, _) => format!("SCD {}", n),
@@ -459,7 +583,7 @@ impl CPU {
             }
 
             (1, 2, 6, 0) => {
-                if self.PC == 0x200 {
+                if PC == 0x200 {
                     String::from("HIRES [HiRes]")
                 } else {
                     format!("JP {:03X}", nnn)
@@ -469,73 +593,49 @@ impl CPU {
 
             (2, _, _, _) => format!("CALL {:03X}", nnn),
 
-            (3, _, _, _) => format!("SE V{:X} ({:02X}), {:02X}", x, self.V[x], nn),
+            (3, _, _, _) => format!("SE V{:X} ({:02X}), {:02X}", x, V[x], nn),
 
-            (4, _, _, _) => format!("SNE V{:X} ({:02X}), {:02X}", x, self.V[x], nn),
+            (4, _, _, _) => format!("SNE V{:X} ({:02X}), {:02X}", x, V[x], nn),
 
-            (5, _, _, 0) => format!(
-                "SE V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
+            (5, _, _, 0) => format!("SE V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
             (5, _, _, 2) => format!("LD [I], V{:X}, V{:X} [XO-CHIP]", x, y),
             (5, _, _, 3) => format!("LD V{:X}, V{:X}, [I] [XO-CHIP]", x, y),
 
             (6, _, _, _) => format!("LD V{:X}, {:02X}", x, nn),
 
-            (7, _, _, _) => format!("ADD V{:X} ({:02X}), {:02X}", x, self.V[x], nn),
+            (7, _, _, _) => format!("ADD V{:X} ({:02X}), {:02X}", x, V[x], nn),
 
-            (8, _, _, 0) => format!("LD V{:X}, V{:X} ({:02X})", x, y, self.V[y]),
-            (8, _, _, 1) => format!(
-                "OR V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
-            (8, _, _, 2) => format!(
-                "AND V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
-            (8, _, _, 3) => format!(
-                "XOR V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
-            (8, _, _, 4) => format!(
-                "ADD V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
-            (8, _, _, 5) => format!(
-                "SUB V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
+            (8, _, _, 0) => format!("LD V{:X}, V{:X} ({:02X})", x, y, V[y]),
+            (8, _, _, 1) => format!("OR V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
+            (8, _, _, 2) => format!("AND V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
+            (8, _, _, 3) => format!("XOR V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
+            (8, _, _, 4) => format!("ADD V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
+            (8, _, _, 5) => format!("SUB V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
             (8, _, _, 6) => {
                 if self.quirk_shift {
-                    format!("SHR V{:X} ({:02X})", x, self.V[x])
+                    format!("SHR V{:X} ({:02X})", x, V[x])
                 } else {
-                    format!("SHR V{:X}, V{:X} ({:02X})", x, y, self.V[y])
+                    format!("SHR V{:X}, V{:X} ({:02X})", x, y, V[y])
                 }
             }
-            (8, _, _, 7) => format!(
-                "SUBN V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
+            (8, _, _, 7) => format!("SUBN V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
             (8, _, _, 0xE) => {
                 if self.quirk_shift {
-                    format!("SHL V{:X} ({:02X})", x, self.V[x])
+                    format!("SHL V{:X} ({:02X})", x, V[x])
                 } else {
-                    format!("SHL V{:X}, V{:X} ({:02X})", x, y, self.V[y])
+                    format!("SHL V{:X}, V{:X} ({:02X})", x, y, V[y])
                 }
             }
 
-            (9, _, _, 0) => format!(
-                "SNE V{:X} ({:02X}), V{:X} ({:02X})",
-                x, self.V[x], y, self.V[y]
-            ),
+            (9, _, _, 0) => format!("SNE V{:X} ({:02X}), V{:X} ({:02X})", x, V[x], y, V[y]),
 
             (0xA, _, _, _) => format!("LD I, {:03X}", nnn),
 
             (0xB, _, _, _) => {
                 if self.quirk_jump {
-                    format!("JP V{:X} ({:03X}), {:03X}", x, self.V[x], nnn)
+                    format!("JP V{:X} ({:03X}), {:03X}", x, V[x], nnn)
                 } else {
-                    format!("JP V0 ({:02X}), {:03X}", self.V[0], nnn)
+                    format!("JP V0 ({:02X}), {:03X}", V[0], nnn)
                 }
             }
 
@@ -543,23 +643,23 @@ impl CPU {
 
             (0xD, _, _, _) => format!(
                 "DRW V{:X} ({:02X}), V{:X} ({:02X}), {:X}",
-                x, self.V[x], y, self.V[y], n
+                x, V[x], y, V[y], n
             ),
 
-            (0xE, _, 9, 0xE) => format!("SKP V{:X} ({:02X})", x, self.V[x]),
-            (0xE, _, 0xA, 1) => format!("SKNP V{:X} ({:02X})", x, self.V[x]),
+            (0xE, _, 9, 0xE) => format!("SKP V{:X} ({:02X})", x, V[x]),
+            (0xE, _, 0xA, 1) => format!("SKNP V{:X} ({:02X})", x, V[x]),
 
-            (0xF, 0, 0, 0) => format!("LD I, {:04X} [XO-CHIP]", self.next_opcode_ext),
+            (0xF, 0, 0, 0) => format!("LD I, {:04X} [XO-CHIP]", ext),
             (0xF, _, 0, 1) => format!("PLANE {} [XO-CHIP]", x),
             (0xF, 0, 0, 2) => String::from("AUDIO [XO-CHIP]"),
-            (0xF, _, 0, 7) => format!("LD V{:X}, DT ({:02X})", x, self.DT),
+            (0xF, _, 0, 7) => format!("LD V{:X}, DT ({:02X})", x, DT),
             (0xF, _, 0, 0xA) => format!("LD V{:X}, K", x),
-            (0xF, _, 1, 5) => format!("LD DT, V{:X} ({:02X})", x, self.V[x]),
-            (0xF, _, 1, 8) => format!("LD ST, V{:X} ({:02X})", x, self.V[x]),
-            (0xF, _, 1, 0xE) => format!("ADD I, V{:X} ({:02X})", x, self.V[x]),
-            (0xF, _, 2, 9) => format!("LD F, V{:X} ({:02X})", x, self.V[x]),
-            (0xF, _, 3, 0) => format!("LD F, V{:X} ({:02X}) [S-CHIP]", x, self.V[x]),
-            (0xF, _, 3, 3) => format!("LD B, V{:X} ({:02X})", x, self.V[x]),
+            (0xF, _, 1, 5) => format!("LD DT, V{:X} ({:02X})", x, V[x]),
+            (0xF, _, 1, 8) => format!("LD ST, V{:X} ({:02X})", x, V[x]),
+            (0xF, _, 1, 0xE) => format!("ADD I, V{:X} ({:02X})", x, V[x]),
+            (0xF, _, 2, 9) => format!("LD F, V{:X} ({:02X})", x, V[x]),
+            (0xF, _, 3, 0) => format!("LD F, V{:X} ({:02X}) [S-CHIP]", x, V[x]),
+            (0xF, _, 3, 3) => format!("LD B, V{:X} ({:02X})", x, V[x]),
             (0xF, _, 5, 5) => format!("LD [I], V{:X}", x),
             (0xF, _, 6, 5) => format!("LD V{:X}, [I]", x),
             (0xF, _, 7, 5) => format!("LD R, V{:X} [S-CHIP]", x),
@@ -571,5 +671,6 @@ impl CPU {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 #[path = "test.rs"]
 mod cpu_test;
@@ -5,6 +5,7 @@ impl CPU {
     // Invalid ipcode
     #[inline]
     pub(super) fn opcode_invalid(&mut self) {
+        debug!("Invalid opcode {:04X} at {:04X}", self.opcode, self.PC);
         self.PC += 2;
     }
 
@@ -34,9 +35,14 @@ impl CPU {
 
     // 0x00EE - Return from subroutine
     #[inline]
-    pub(super) fn opcode_0x00EE(&mut self) {
+    pub(super) fn opcode_0x00EE(&mut self) -> Result<(), Error> {
+        if self.sp == 0 {
+            self.load_bootrom();
+            return Err(Error::StackUnderflow);
+        }
         self.sp -= 1;
         self.PC = self.stack[self.sp] + 2;
+        Ok(())
     }
 
     // 0x00FB - SCHIP - Scroll display 4 pixels right
@@ -59,21 +65,25 @@ impl CPU {
     #[inline]
     pub(super) fn opcode_schip_0x00FD(&mut self) {
         // Instead of actually exiting, we're creating an endless loop
+        info!("Program exited at {:04X}", self.PC);
         self.mem[0x200..0x202].copy_from_slice(&[0x12, 0x00]);
+        self.decoded.invalidate(0x200..0x202);
         self.PC = 0x200;
     }
 
     // 0x00FE - SCHIP - Disable extended screen mode
     #[inline]
     pub(super) fn opcode_schip_0x00FE(&mut self) {
-        self.vmem.video_mode = VideoMode::Default;
+        self.vmem.set_video_mode(VideoMode::Default);
+        debug!("Switched to default screen mode");
         self.PC += 2;
     }
 
     // 0x00FF - SCHIP - Enable extended screen mode
     #[inline]
     pub(super) fn opcode_schip_0x00FF(&mut self) {
-        self.vmem.video_mode = VideoMode::Extended;
+        self.vmem.set_video_mode(VideoMode::Extended);
+        debug!("Switched to extended screen mode");
         self.PC += 2;
     }
 
@@ -105,7 +115,8 @@ impl CPU {
     #[inline]
     pub(super) fn opcode_0x1260(&mut self, nnn: u16) {
         if self.PC == 0x200 {
-            self.vmem.video_mode = VideoMode::HiRes;
+            self.vmem.set_video_mode(VideoMode::HiRes);
+            debug!("Switched to HiRes mode");
             self.PC = 0x2C0;
         } else {
             self.opcode_0x1NNN(nnn);
@@ -155,22 +166,25 @@ impl CPU {
 
     // 0x5XY2 - XO-CHIP - Store Vx - Vy
     #[inline]
-    pub(super) fn opcode_xochip_0x5XY2(&mut self, x: usize, y: usize) {
+    pub(super) fn opcode_xochip_0x5XY2(&mut self, x: usize, y: usize) -> Result<(), Error> {
         let first = std::cmp::min(x, y);
         let last = std::cmp::max(x, y);
-        self.mem[self.I as usize..self.I as usize + last - first + 1]
-            .copy_from_slice(&self.V[first..=last]);
+        let range = self.memory_range(self.I as usize, last - first + 1)?;
+        self.mem[range.clone()].copy_from_slice(&self.V[first..=last]);
+        self.decoded.invalidate(range);
         self.PC += 2;
+        Ok(())
     }
 
     // 0x5XY3 - XO-CHIP - Load Vx - Vy
     #[inline]
-    pub(super) fn opcode_xochip_0x5XY3(&mut self, x: usize, y: usize) {
+    pub(super) fn opcode_xochip_0x5XY3(&mut self, x: usize, y: usize) -> Result<(), Error> {
         let first = std::cmp::min(x, y);
         let last = std::cmp::max(x, y);
-        self.V[first..=last]
-            .copy_from_slice(&self.mem[self.I as usize..self.I as usize + last - first + 1]);
+        let range = self.memory_range(self.I as usize, last - first + 1)?;
+        self.V[first..=last].copy_from_slice(&self.mem[range]);
         self.PC += 2;
+        Ok(())
     }
 
     // 0x6XNN - Vx = nn
@@ -301,32 +315,36 @@ impl CPU {
     // 0xCXNN - Vx = rand() & nn
     #[inline]
     pub(super) fn opcode_0xCXNN(&mut self, x: usize, nn: u8) {
-        let mut rng = rand::thread_rng();
-        self.V[x] = rng.gen::<u8>() & nn;
+        let value = match &mut self.rng {
+            Some(rng) => rng.gen::<u8>(),
+            None => rand::thread_rng().gen::<u8>(),
+        };
+        self.V[x] = value & nn;
         self.PC += 2;
     }
 
     // 0xDXYN - draw(Vx, Vy, n)
     #[inline]
-    pub(super) fn opcode_0xDXYN(&mut self, x: usize, y: usize, n: usize) {
-        self.draw_sprite(self.V[x] as usize, self.V[y] as usize, n);
+    pub(super) fn opcode_0xDXYN(&mut self, x: usize, y: usize, n: usize) -> Result<(), Error> {
+        self.draw_sprite(self.V[x] as usize, self.V[y] as usize, n)?;
         self.draw = true;
         self.PC += 2;
+        Ok(())
     }
 
-    // 0xEX9E - Skip next instruction if key(Vx) is pressed
+    // 0xEX9E - Skip next instruction if key(Vx) is pressed, only the low nibble selects the key
     #[inline]
     pub(super) fn opcode_0xEX9E(&mut self, x: usize) {
-        if self.keys[self.V[x] as usize] {
+        if self.keys[self.V[x] as usize & 0xF] {
             self.skip_next_instruction();
         }
         self.PC += 2;
     }
 
-    // 0xEXA1 - Skip next instruction if key(Vx) is not pressed
+    // 0xEXA1 - Skip next instruction if key(Vx) is not pressed, only the low nibble selects the key
     #[inline]
     pub(super) fn opcode_0xEXA1(&mut self, x: usize) {
-        if !self.keys[self.V[x] as usize] {
+        if !self.keys[self.V[x] as usize & 0xF] {
             self.skip_next_instruction();
         }
         self.PC += 2;
@@ -353,12 +371,14 @@ impl CPU {
 
     // 0xF002 - XO-CHIP - Audio
     #[inline]
-    pub(super) fn opcode_xochip_0xF002(&mut self) {
+    pub(super) fn opcode_xochip_0xF002(&mut self) -> Result<(), Error> {
         use std::convert::TryInto;
-        if let Ok(buf) = self.mem[self.I as usize..self.I as usize + 16].try_into() {
+        let range = self.memory_range(self.I as usize, 16)?;
+        if let Ok(buf) = self.mem[range].try_into() {
             self.audio_buffer = Some(buf);
         }
         self.PC += 2;
+        Ok(())
     }
 
     // 0xFX07 - Vx = DT
@@ -393,7 +413,7 @@ impl CPU {
     // 0xFX1E - I += Vx
     #[inline]
     pub(super) fn opcode_0xFX1E(&mut self, x: usize) {
-        self.I += self.V[x] as u16;
+        self.I = self.I.wrapping_add(self.V[x] as u16);
         self.PC += 2;
     }
 
@@ -413,54 +433,58 @@ impl CPU {
 
     // 0xFX33 - set_BCD(Vx)
     #[inline]
-    pub(super) fn opcode_0xFX33(&mut self, x: usize) {
+    pub(super) fn opcode_0xFX33(&mut self, x: usize) -> Result<(), Error> {
         let hundreds = self.V[x] / 100;
         let tens = (self.V[x] % 100) / 10;
         let ones = self.V[x] % 10;
-        self.mem[self.I as usize] = hundreds;
-        self.mem[self.I as usize + 1] = tens;
-        self.mem[self.I as usize + 2] = ones;
+        let range = self.memory_range(self.I as usize, 3)?;
+        self.mem[range.clone()].copy_from_slice(&[hundreds, tens, ones]);
+        self.decoded.invalidate(range);
         self.PC += 2;
+        Ok(())
     }
 
     // 0xFX55 - reg_dump(Vx, &I)
     // Original: I is incremented
     // Quirk:    I is not incremented
     #[inline]
-    pub(super) fn opcode_0xFX55(&mut self, x: usize) {
-        let start = self.I as usize;
-        let end = self.I as usize + x;
-        self.mem[start..=end].copy_from_slice(&self.V[..=x]);
+    pub(super) fn opcode_0xFX55(&mut self, x: usize) -> Result<(), Error> {
+        let range = self.memory_range(self.I as usize, x + 1)?;
+        self.mem[range.clone()].copy_from_slice(&self.V[..=x]);
+        self.decoded.invalidate(range);
         if !self.quirk_load_store {
-            self.I += x as u16 + 1;
+            self.I = self.I.wrapping_add(x as u16 + 1);
         }
         self.PC += 2;
+        Ok(())
     }
 
     // 0xFX65 - reg_load(Vx, &I)
     // Original: I is incremented
     // Quirk:    I is not incremented
     #[inline]
-    pub(super) fn opcode_0xFX65(&mut self, x: usize) {
-        let start = self.I as usize;
-        let end = self.I as usize + x;
-        self.V[..=x].copy_from_slice(&self.mem[start..=end]);
+    pub(super) fn opcode_0xFX65(&mut self, x: usize) -> Result<(), Error> {
+        let range = self.memory_range(self.I as usize, x + 1)?;
+        self.V[..=x].copy_from_slice(&self.mem[range]);
         if !self.quirk_load_store {
-            self.I += x as u16 + 1;
+            self.I = self.I.wrapping_add(x as u16 + 1);
         }
         self.PC += 2;
+        Ok(())
     }
 
-    // 0xFX75 - SCHIP - Store V0..VX in RPL user flags (X < 8)
+    // 0xFX75 - SCHIP - Store V0..VX in RPL user flags (X < 8, larger X store all 8)
     #[inline]
     pub(super) fn opcode_schip_0xFX75(&mut self, x: usize) {
+        let x = x.min(self.RPL.len() - 1);
         self.RPL[..=x].copy_from_slice(&self.V[..=x]);
         self.PC += 2;
     }
 
-    // 0xFX85 - SCHIP - Read V0..VX from RPL user flags (X < 8)
+    // 0xFX85 - SCHIP - Read V0..VX from RPL user flags (X < 8, larger X read all 8)
     #[inline]
     pub(super) fn opcode_schip_0xFX85(&mut self, x: usize) {
+        let x = x.min(self.RPL.len() - 1);
         self.V[..=x].copy_from_slice(&self.RPL[..=x]);
         self.PC += 2;
     }
@@ -479,7 +503,7 @@ impl CPU {
         self.PC += 2;
 
         // Check if next instruction is a 4 byte instruction (XO-CHIP)
-        if self.mem[self.PC as usize..=self.PC as usize + 1] == [0xF0, 0] {
+        if self.mem.get(self.PC as usize..self.PC as usize + 2) == Some(&[0xF0, 0]) {
             self.PC += 2;
         }
     }
@@ -20,6 +20,71 @@ fn test_initial_state() {
     assert_eq!(CPU::FONTSET, &cpu.mem[..CPU::FONTSET.len()]);
 }
 
+#[test]
+fn test_history() {
+    let mut cpu = CPU::new();
+    let prog: &[u8] = &[0x60, 0x01, 0x61, 0x02, 0x12, 0x00];
+    let _ = cpu.load_rom(prog);
+    assert!(cpu.history().is_empty());
+
+    for _ in 0..3 {
+        let _ = cpu.emulate_cycle();
+    }
+    assert_eq!(
+        cpu.history(),
+        vec![(0x200, 0x6001), (0x202, 0x6102), (0x204, 0x1200)]
+    );
+
+    for _ in 0..CPU::HISTORY_LEN * 3 {
+        let _ = cpu.emulate_cycle();
+    }
+    let history = cpu.history();
+    assert_eq!(history.len(), CPU::HISTORY_LEN);
+    assert_eq!(history.last(), Some(&(0x204, 0x1200)));
+}
+
+#[test]
+fn test_is_stuck() {
+    let mut cpu = CPU::new();
+    let prog: &[u8] = &[0x60, 0x01, 0x12, 0x02];
+    let _ = cpu.load_rom(prog);
+    assert!(!cpu.is_stuck());
+    let _ = cpu.emulate_cycle();
+    assert!(cpu.is_stuck());
+    let _ = cpu.emulate_cycle();
+    assert!(cpu.is_stuck());
+}
+
+#[test]
+fn test_set_register() {
+    let mut cpu = CPU::new();
+    let _ = cpu.load_rom(&[0x60, 0x01, 0x12, 0x02]);
+    cpu.set_register(Register::PC, 0x202);
+    assert_eq!(cpu.next_opcode(), 0x1202);
+    cpu.set_register(Register::V(3), 0x1AB);
+    assert_eq!(cpu.V[3], 0xAB);
+    cpu.set_register(Register::PC, 0xFFFF);
+    assert_eq!(cpu.PC, 0xFFF9);
+}
+
+#[test]
+fn test_state_without_mem() {
+    let mut cpu = CPU::new();
+    let _ = cpu.load_rom(&[0x60, 0x01, 0x12, 0x02]);
+    let _ = cpu.emulate_cycle();
+    let state = cpu.save_state_without_mem().unwrap();
+    assert!(state.len() < cpu.save_state().unwrap().len() - cpu.mem.len());
+    assert_eq!(cpu.mem.len(), 65536);
+
+    let mut mem = cpu.mem().to_vec();
+    mem[0x300] = 0xAB;
+    let restored = CPU::from_state_with_mem(&state, &mem).unwrap();
+    assert_eq!(restored.PC, 0x202);
+    assert_eq!(restored.V[0], 1);
+    assert_eq!(restored.read_mem(0x300), 0xAB);
+    assert!(CPU::from_state_with_mem(&state, &mem[1..]).is_err());
+}
+
 #[test]
 fn test_load_rom() {
     let mut cpu = CPU::new();
@@ -468,7 +533,7 @@ fn test_opcodes() {
         let mut cpu = CPU::new();
         let _ = cpu.load_rom(&[0xF5, 0x55]);
         cpu.I = 0x300;
-        cpu.V.copy_from_slice(&reg);
+        cpu.V.copy_from_slice(reg);
         let _ = cpu.emulate_cycle();
         assert_eq!(&cpu.mem[0x300..=0x305], &reg[..=5]);
         assert_eq!(&cpu.mem[0x306], &0);
@@ -480,7 +545,7 @@ fn test_opcodes() {
         let mut cpu = CPU::new();
         let _ = cpu.load_rom(&[0xF5, 0x55]);
         cpu.I = 0x300;
-        cpu.V.copy_from_slice(&reg);
+        cpu.V.copy_from_slice(reg);
         cpu.quirk_load_store = false;
         let _ = cpu.emulate_cycle();
         assert_eq!(&cpu.mem[0x300..=0x305], &reg[..=5]);
@@ -581,7 +646,7 @@ fn test_opcodes_schip() {
     {
         let mut cpu = CPU::new();
         let _ = cpu.load_rom(&[0xF4, 0x75]);
-        cpu.V.copy_from_slice(&reg);
+        cpu.V.copy_from_slice(reg);
         let _ = cpu.emulate_cycle();
         assert_eq!(&cpu.RPL[..=4], &cpu.V[..=4]);
         assert_eq!(&cpu.RPL[5], &0);
@@ -695,13 +760,53 @@ fn test_opcodes_xochip() {
 
 #[test]
 fn test_invalid_opcodes() {
-    let opcodes = [0u16, 0x5005, 0x9999];
+    let opcodes = [
+        0u16, 0x0123, 0x00E1, 0x5005, 0x812F, 0x9999, 0xE19F, 0xF100, 0xF102, 0xF1FF,
+    ];
     for opcode in opcodes.iter() {
         let mut cpu = CPU::new();
         let _ = cpu.load_rom(&[(opcode >> 8) as u8, *opcode as u8]);
         let _ = cpu.emulate_cycle();
         assert_eq!(cpu.PC, 0x202);
+        assert_eq!(cpu.V, [0; 16]);
+    }
+}
+
+#[test]
+fn test_errors() {
+    // Return without call, and storing, loading and drawing beyond the memory
+    for rom in [
+        [0x00, 0xEE, 0x00, 0x00],
+        [0xFF, 0x55, 0x00, 0x00],
+        [0x50, 0xF3, 0x00, 0x00],
+        [0xD0, 0x10, 0x00, 0x00],
+    ] {
+        let mut cpu = CPU::new();
+        let _ = cpu.load_rom(&rom);
+        cpu.I = 0xFFFA;
+        let result = cpu.emulate_cycle().and_then(|_| cpu.emulate_cycle());
+        assert!(result.is_err(), "{:02X?} didn't fail", rom);
+        assert_eq!(cpu.PC, 0x200);
     }
+
+    // Keys and flags beyond the existing ones
+    let mut cpu = CPU::new();
+    let _ = cpu.load_rom(&[0xE0, 0x9E, 0x00, 0x00, 0xFF, 0x75, 0xFF, 0x85]);
+    cpu.V[0] = 0x12;
+    let mut keys = [false; 16];
+    keys[2] = true;
+    cpu.tick(&keys).unwrap();
+    assert_eq!(cpu.PC, 0x204);
+    cpu.tick(&[false; 16]).unwrap();
+    cpu.tick(&[false; 16]).unwrap();
+    assert_eq!(cpu.RPL[0], 0x12);
+
+    // Skipping a 4 byte instruction at the end of the memory
+    let mut cpu = CPU::new();
+    cpu.mem[0xFFF9..].copy_from_slice(&[0x30, 0x00, 0xF0, 0x00, 0x00, 0x00, 0x00]);
+    cpu.set_register(Register::PC, 0xFFFF);
+    assert_eq!(cpu.next_opcode, 0x3000);
+    assert!(cpu.emulate_cycle().is_err());
 }
 
 fn test_arithmetic(opcode: u16, v1: u8, v2: u8, res: u8, resv: Option<u8>) {
@@ -711,8 +816,8 @@ fn test_arithmetic(opcode: u16, v1: u8, v2: u8, res: u8, resv: Option<u8>) {
     cpu.V[1] = v2;
     let _ = cpu.emulate_cycle();
     assert_eq!(cpu.V[0], res, "Wrong value in V[0]");
-    if resv.is_some() {
-        assert_eq!(cpu.V[0xF], resv.unwrap(), "Wrong value in V[0xF]");
+    if let Some(resv) = resv {
+        assert_eq!(cpu.V[0xF], resv, "Wrong value in V[0xF]");
     }
     assert_eq!(cpu.PC, 0x202);
 }
@@ -741,8 +846,8 @@ fn test_arithmetic_8_noquirk(code: u8, v1: u8, v2: u8, res: u8, resv: Option<u8>
     cpu.V[1] = v2;
     let _ = cpu.emulate_cycle();
     assert_eq!(cpu.V[0], res, "Wrong value in V[0]");
-    if resv.is_some() {
-        assert_eq!(cpu.V[0xF], resv.unwrap(), "Wrong value in V[0xF]");
+    if let Some(resv) = resv {
+        assert_eq!(cpu.V[0xF], resv, "Wrong value in V[0xF]");
     }
     assert_eq!(cpu.PC, 0x202);
 }
@@ -802,3 +907,24 @@ fn test_skipped_opcode_0x0NNN() {
     let _ = cpu.emulate_cycle();
     assert_eq!(cpu.PC, 0x202);
 }
+
+#[test]
+fn test_self_modifying_code() {
+    let mut cpu = CPU::new();
+    // The first instruction turns itself from ADD V1, 1 into ADD V2, 1
+    let prog: &[u8] = &[0x71, 0x01, 0x60, 0x72, 0xA2, 0x00, 0xF0, 0x55, 0x12, 0x00];
+    let _ = cpu.load_rom(prog);
+    for _ in 0..6 {
+        let _ = cpu.emulate_cycle();
+    }
+    assert_eq!(cpu.V[1], 1);
+    assert_eq!(cpu.V[2], 1);
+    assert_eq!(cpu.opcode(), 0x7201);
+    assert_eq!(cpu.opcode_description(), "ADD V2 (00), 01");
+
+    cpu.write_mem(0x202, 0x63);
+    assert_eq!(cpu.next_opcode(), 0x6372);
+    assert_eq!(cpu.next_opcode_description(), "LD V3, 72");
+    let _ = cpu.emulate_cycle();
+    assert_eq!(cpu.V[3], 0x72);
+}
@@ -0,0 +1,470 @@
+use crate::symbols::Symbols;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Returns the mnemonic of an opcode independent of the CPU state, `ext` is the
+/// word following the opcode, which is only used by F000 NNNN.
+pub fn disassemble(opcode: u16, ext: u16) -> String {
+    let h = (opcode & 0xF000) >> 12;
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+
+    match (h, x, y, n) {
+        (0, 0, 0xC, _) => format!("SCD {}", n),
+        (0, 0, 0xD, _) => format!("SCU {}", n),
+        (0, 0, 0xE, 0) => String::from("CLS"),
+        (0, 0, 0xE, 0xE) => String::from("RET"),
+        (0, 0, 0xF, 0xB) => String::from("SCR"),
+        (0, 0, 0xF, 0xC) => String::from("SCL"),
+        (0, 0, 0xF, 0xD) => String::from("EXIT"),
+        (0, 0, 0xF, 0xE) => String::from("LOW"),
+        (0, 0, 0xF, 0xF) => String::from("HIGH"),
+        (0, _, _, _) => format!("SYS {:03X}", nnn),
+        (1, _, _, _) => format!("JP {:03X}", nnn),
+        (2, _, _, _) => format!("CALL {:03X}", nnn),
+        (3, _, _, _) => format!("SE V{:X}, {:02X}", x, nn),
+        (4, _, _, _) => format!("SNE V{:X}, {:02X}", x, nn),
+        (5, _, _, 0) => format!("SE V{:X}, V{:X}", x, y),
+        (5, _, _, 2) => format!("LD [I], V{:X}, V{:X}", x, y),
+        (5, _, _, 3) => format!("LD V{:X}, V{:X}, [I]", x, y),
+        (6, _, _, _) => format!("LD V{:X}, {:02X}", x, nn),
+        (7, _, _, _) => format!("ADD V{:X}, {:02X}", x, nn),
+        (8, _, _, 0) => format!("LD V{:X}, V{:X}", x, y),
+        (8, _, _, 1) => format!("OR V{:X}, V{:X}", x, y),
+        (8, _, _, 2) => format!("AND V{:X}, V{:X}", x, y),
+        (8, _, _, 3) => format!("XOR V{:X}, V{:X}", x, y),
+        (8, _, _, 4) => format!("ADD V{:X}, V{:X}", x, y),
+        (8, _, _, 5) => format!("SUB V{:X}, V{:X}", x, y),
+        (8, _, _, 6) => format!("SHR V{:X}, V{:X}", x, y),
+        (8, _, _, 7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (8, _, _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (9, _, _, 0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, {:03X}", nnn),
+        (0xB, _, _, _) => format!("JP V0, {:03X}", nnn),
+        (0xC, _, _, _) => format!("RND V{:X}, {:02X}", x, nn),
+        (0xD, _, _, _) => format!("DRW V{:X}, V{:X}, {:X}", x, y, n),
+        (0xE, _, 9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 1) => format!("SKNP V{:X}", x),
+        (0xF, 0, 0, 0) => format!("LD I, {:04X}", ext),
+        (0xF, _, 0, 1) => format!("PLANE {}", x),
+        (0xF, 0, 0, 2) => String::from("AUDIO"),
+        (0xF, _, 0, 7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 1, 5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 1, 8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 2, 9) => format!("LD F, V{:X}", x),
+        (0xF, _, 3, 0) => format!("LD HF, V{:X}", x),
+        (0xF, _, 3, 3) => format!("LD B, V{:X}", x),
+        (0xF, _, 3, 0xA) => format!("PITCH V{:X}", x),
+        (0xF, _, 5, 5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 6, 5) => format!("LD V{:X}, [I]", x),
+        (0xF, _, 7, 5) => format!("LD R, V{:X}", x),
+        (0xF, _, 8, 5) => format!("LD V{:X}, R", x),
+        _ => format!("DW {:04X}", opcode),
+    }
+}
+
+/// Renders a ROM as Octo source, so it can be studied and modified with Octo.
+///
+/// The instructions reachable from the start are disassembled, everything else is written as bytes.
+/// Jump and call targets and the addresses loaded into `i` get labels, either from the symbols
+/// or generated from their address, so the source can be changed without breaking references.
+pub fn export_octo(rom: &[u8], symbols: &Symbols) -> String {
+    let program = OctoProgram::analyze(rom, symbols, START);
+    let end = START + rom.len();
+    let mut source = String::from("# Disassembled by pich8\n");
+    let mut address = START;
+    while address < end {
+        if let Some(name) = program.labels.get(&address) {
+            source.push_str(&format!("\n: {}\n", name));
+        }
+        if let Some(line) = program.instruction(address) {
+            source.push_str(&format!("\t{}\n", line));
+            address += program.len(address);
+            continue;
+        }
+        // Data up to the next label or instruction
+        let data_end = (address + 1..end)
+            .find(|&next| program.labels.contains_key(&next) || program.code.contains(&next))
+            .unwrap_or(end);
+        for chunk in rom[address - START..data_end - START].chunks(8) {
+            let bytes: Vec<_> = chunk.iter().map(|b| format!("0x{:02X}", b)).collect();
+            source.push_str(&format!("\t{}\n", bytes.join(" ")));
+        }
+        address = data_end;
+    }
+    source
+}
+
+/// Lists the ROM as assembly with the address and bytes of every line, to read it in a terminal.
+///
+/// The instructions reachable from `start` are disassembled, everything else is listed as bytes.
+/// Jumps, calls and addresses loaded into `i` are labeled like in the Octo export.
+pub fn listing(rom: &[u8], start: u16, symbols: &Symbols) -> String {
+    let program = OctoProgram::analyze(rom, symbols, start as usize);
+    let end = START + rom.len();
+    let mut listing = String::new();
+    let mut address = (start as usize).max(START);
+    while address < end {
+        if let Some(name) = program.labels.get(&address) {
+            listing.push_str(&format!("\n{}:\n", name));
+        }
+        if let (true, Some(opcode)) = (program.code.contains(&address), program.word(address)) {
+            let len = program.len(address);
+            let ext = program.word(address + 2).unwrap_or(0);
+            let bytes = if len == 4 {
+                format!("{:04X} {:04X}", opcode, ext)
+            } else {
+                format!("{:04X}", opcode)
+            };
+            let line = format!(
+                "{:03X}  {:<11}  {}",
+                address,
+                bytes,
+                disassemble(opcode, ext)
+            );
+            match program.reference(address) {
+                Some(name) => listing.push_str(&format!("{:<40}; {}\n", line, name)),
+                None => listing.push_str(&format!("{}\n", line)),
+            }
+            address += len;
+            continue;
+        }
+        // Data up to the next label or instruction
+        let data_end = (address + 1..end)
+            .find(|&next| program.labels.contains_key(&next) || program.code.contains(&next))
+            .unwrap_or(end);
+        for (idx, chunk) in rom[address - START..data_end - START].chunks(4).enumerate() {
+            let bytes: Vec<_> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+            listing.push_str(&format!(
+                "{:03X}  {:<11}  DB {}\n",
+                address + idx * 4,
+                bytes.join(" "),
+                bytes.join(", ")
+            ));
+        }
+        address = data_end;
+    }
+    listing
+}
+
+pub(crate) const START: usize = 0x200;
+
+/// The control flow of a ROM, which the Octo export, the listing and the analysis build on.
+pub(crate) struct OctoProgram<'a> {
+    rom: &'a [u8],
+    /// The addresses of reachable instructions
+    pub(crate) code: BTreeSet<usize>,
+    pub(crate) labels: BTreeMap<usize, String>,
+}
+
+impl<'a> OctoProgram<'a> {
+    /// Follows the control flow from the entry point to tell instructions from data.
+    pub(crate) fn analyze(rom: &'a [u8], symbols: &Symbols, entry: usize) -> Self {
+        let mut program = Self {
+            rom,
+            code: BTreeSet::new(),
+            labels: BTreeMap::new(),
+        };
+        let label = |labels: &mut BTreeMap<usize, String>, address: usize, prefix: &str| {
+            let name = symbols
+                .label(address as u16)
+                .map_or_else(|| format!("{}-{:03x}", prefix, address), str::to_string);
+            labels.entry(address).or_insert(name);
+        };
+        // Octo programs start at main
+        program.labels.insert(START, "main".to_string());
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            let opcode = match program.word(address) {
+                Some(opcode) if !program.code.contains(&address) => opcode,
+                _ => continue,
+            };
+            program.code.insert(address);
+            let next = address + program.len(address);
+            let nnn = (opcode & 0x0FFF) as usize;
+            match opcode >> 12 {
+                0 if opcode == 0x00EE || opcode == 0x00FD => (),
+                1 => {
+                    label(&mut program.labels, nnn, "label");
+                    pending.push(nnn);
+                }
+                2 => {
+                    label(&mut program.labels, nnn, "sub");
+                    pending.extend([nnn, next]);
+                }
+                0xB => {
+                    label(&mut program.labels, nnn, "table");
+                    pending.push(nnn);
+                }
+                3 | 4 | 9 | 0xE => pending.extend([next, next + program.len(next)]),
+                5 if opcode & 0xF == 0 => pending.extend([next, next + program.len(next)]),
+                0xA => {
+                    label(&mut program.labels, nnn, "data");
+                    pending.push(next);
+                }
+                0xF if opcode == 0xF000 => {
+                    if let Some(target) = program.word(address + 2) {
+                        label(&mut program.labels, target as usize, "data");
+                    }
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+        // Labels outside of the program can't be defined
+        let end = START + rom.len();
+        program
+            .labels
+            .retain(|&address, _| (START..end).contains(&address));
+        program
+    }
+
+    pub(crate) fn word(&self, address: usize) -> Option<u16> {
+        let offset = address.checked_sub(START)?;
+        match self.rom.get(offset..offset + 2)? {
+            [high, low] => Some((*high as u16) << 8 | *low as u16),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn len(&self, address: usize) -> usize {
+        if self.word(address) == Some(0xF000) {
+            4
+        } else {
+            2
+        }
+    }
+
+    /// Returns the label of the address the instruction at the address jumps to, calls or loads.
+    fn reference(&self, address: usize) -> Option<&str> {
+        let opcode = self.word(address)?;
+        let target = match opcode >> 12 {
+            _ if opcode == 0xF000 => self.word(address + 2)? as usize,
+            1 | 2 | 0xA | 0xB => (opcode & 0x0FFF) as usize,
+            _ => return None,
+        };
+        self.labels.get(&target).map(String::as_str)
+    }
+
+    /// Returns the label of an address or the address itself if it has none.
+    fn target(&self, address: usize) -> String {
+        match self.labels.get(&address) {
+            Some(name) => name.clone(),
+            None => format!("0x{:03X}", address),
+        }
+    }
+
+    /// Returns the Octo statement of the instruction at the address, or `None` if there's no
+    /// instruction or it has to be written as bytes, e.g. as a label points into it.
+    fn instruction(&self, address: usize) -> Option<String> {
+        if !self.code.contains(&address) {
+            return None;
+        }
+        let len = self.len(address);
+        if (address + 1..address + len)
+            .any(|inner| self.labels.contains_key(&inner) || self.code.contains(&inner))
+        {
+            return None;
+        }
+        let opcode = self.word(address)?;
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let n = opcode & 0x000F;
+        let nn = opcode & 0x00FF;
+        let nnn = (opcode & 0x0FFF) as usize;
+        let statement = match (opcode >> 12, x, y, n) {
+            (0, 0, 0xC, _) => format!("scroll-down {}", n),
+            (0, 0, 0xD, _) => format!("scroll-up {}", n),
+            (0, 0, 0xE, 0) => "clear".to_string(),
+            (0, 0, 0xE, 0xE) => "return".to_string(),
+            (0, 0, 0xF, 0xB) => "scroll-right".to_string(),
+            (0, 0, 0xF, 0xC) => "scroll-left".to_string(),
+            (0, 0, 0xF, 0xD) => "exit".to_string(),
+            (0, 0, 0xF, 0xE) => "lores".to_string(),
+            (0, 0, 0xF, 0xF) => "hires".to_string(),
+            (0, _, _, _) => format!("native 0x{:03X}", nnn),
+            (1, _, _, _) => format!("jump {}", self.target(nnn)),
+            (2, _, _, _) if self.labels.contains_key(&nnn) => self.target(nnn),
+            (2, _, _, _) => format!(":call 0x{:03X}", nnn),
+            // The skips are conditions that have to be true to run the next statement
+            (3, _, _, _) => format!("if v{:x} != 0x{:02X} then", x, nn),
+            (4, _, _, _) => format!("if v{:x} == 0x{:02X} then", x, nn),
+            (5, _, _, 0) => format!("if v{:x} != v{:x} then", x, y),
+            (5, _, _, 2) => format!("save v{:x} - v{:x}", x, y),
+            (5, _, _, 3) => format!("load v{:x} - v{:x}", x, y),
+            (6, _, _, _) => format!("v{:x} := 0x{:02X}", x, nn),
+            (7, _, _, _) => format!("v{:x} += 0x{:02X}", x, nn),
+            (8, _, _, 0) => format!("v{:x} := v{:x}", x, y),
+            (8, _, _, 1) => format!("v{:x} |= v{:x}", x, y),
+            (8, _, _, 2) => format!("v{:x} &= v{:x}", x, y),
+            (8, _, _, 3) => format!("v{:x} ^= v{:x}", x, y),
+            (8, _, _, 4) => format!("v{:x} += v{:x}", x, y),
+            (8, _, _, 5) => format!("v{:x} -= v{:x}", x, y),
+            (8, _, _, 6) => format!("v{:x} >>= v{:x}", x, y),
+            (8, _, _, 7) => format!("v{:x} =- v{:x}", x, y),
+            (8, _, _, 0xE) => format!("v{:x} <<= v{:x}", x, y),
+            (9, _, _, 0) => format!("if v{:x} == v{:x} then", x, y),
+            (0xA, _, _, _) => format!("i := {}", self.target(nnn)),
+            (0xB, _, _, _) => format!("jump0 {}", self.target(nnn)),
+            (0xC, _, _, _) => format!("v{:x} := random 0x{:02X}", x, nn),
+            (0xD, _, _, _) => format!("sprite v{:x} v{:x} {}", x, y, n),
+            (0xE, _, 9, 0xE) => format!("if v{:x} -key then", x),
+            (0xE, _, 0xA, 1) => format!("if v{:x} key then", x),
+            (0xF, 0, 0, 0) => format!(
+                "i := long {}",
+                self.target(self.word(address + 2)? as usize)
+            ),
+            (0xF, _, 0, 1) => format!("plane {}", x),
+            (0xF, 0, 0, 2) => "audio".to_string(),
+            (0xF, _, 0, 7) => format!("v{:x} := delay", x),
+            (0xF, _, 0, 0xA) => format!("v{:x} := key", x),
+            (0xF, _, 1, 5) => format!("delay := v{:x}", x),
+            (0xF, _, 1, 8) => format!("buzzer := v{:x}", x),
+            (0xF, _, 1, 0xE) => format!("i += v{:x}", x),
+            (0xF, _, 2, 9) => format!("i := hex v{:x}", x),
+            (0xF, _, 3, 0) => format!("i := bighex v{:x}", x),
+            (0xF, _, 3, 3) => format!("bcd v{:x}", x),
+            (0xF, _, 3, 0xA) => format!("pitch := v{:x}", x),
+            (0xF, _, 5, 5) => format!("save v{:x}", x),
+            (0xF, _, 6, 5) => format!("load v{:x}", x),
+            (0xF, _, 7, 5) => format!("saveflags v{:x}", x),
+            (0xF, _, 8, 5) => format!("loadflags v{:x}", x),
+            _ => return None,
+        };
+        Some(statement)
+    }
+}
+
+/// A group of opcodes doing similar work, used to show what a program spends its time on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpcodeClass {
+    Draw,
+    Math,
+    Jump,
+    Skip,
+    Key,
+    Memory,
+    Timer,
+    Other,
+}
+
+impl OpcodeClass {
+    pub const ALL: [OpcodeClass; 8] = [
+        OpcodeClass::Draw,
+        OpcodeClass::Math,
+        OpcodeClass::Jump,
+        OpcodeClass::Skip,
+        OpcodeClass::Key,
+        OpcodeClass::Memory,
+        OpcodeClass::Timer,
+        OpcodeClass::Other,
+    ];
+
+    pub fn of(opcode: u16) -> Self {
+        match (opcode >> 12, opcode & 0x00FF) {
+            // Clear, scroll, resolution, plane and sprites
+            (0, 0xC0..=0xDF | 0xE0 | 0xFB..=0xFF) | (0xD, _) | (0xF, 0x01) => OpcodeClass::Draw,
+            (0, 0xEE) | (1, _) | (2, _) | (0xB, _) => OpcodeClass::Jump,
+            // XO-CHIP register ranges
+            (5, _) if opcode & 0x000E == 2 => OpcodeClass::Memory,
+            (3 | 4 | 5 | 9, _) => OpcodeClass::Skip,
+            (6 | 7 | 8 | 0xC, _) | (0xF, 0x1E) => OpcodeClass::Math,
+            (0xE, _) | (0xF, 0x0A) => OpcodeClass::Key,
+            (0xA, _) | (0xF, 0x00 | 0x29 | 0x30 | 0x33 | 0x55 | 0x65 | 0x75 | 0x85) => {
+                OpcodeClass::Memory
+            }
+            (0xF, 0x02 | 0x07 | 0x15 | 0x18 | 0x3A) => OpcodeClass::Timer,
+            _ => OpcodeClass::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OpcodeClass::Draw => "Draw",
+            OpcodeClass::Math => "Math",
+            OpcodeClass::Jump => "Jump/Call",
+            OpcodeClass::Skip => "Skip",
+            OpcodeClass::Key => "Key",
+            OpcodeClass::Memory => "Memory",
+            OpcodeClass::Timer => "Timer/Sound",
+            OpcodeClass::Other => "Other",
+        }
+    }
+}
+
+#[cfg(test)]
+mod disassembler_test {
+    use super::*;
+    use crate::assembler::assemble;
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(disassemble(0x00E0, 0), "CLS");
+        assert_eq!(disassemble(0x1228, 0), "JP 228");
+        assert_eq!(disassemble(0xD01F, 0), "DRW V0, V1, F");
+        assert_eq!(disassemble(0xF000, 0x1234), "LD I, 1234");
+        assert_eq!(disassemble(0x5AB1, 0), "DW 5AB1");
+    }
+
+    #[test]
+    fn test_export_octo() {
+        let rom = [
+            0x00, 0xE0, 0xA2, 0x0C, 0x22, 0x08, 0x12, 0x06, 0xD0, 0x15, 0x00, 0xEE, 0xF0, 0x90,
+        ];
+        let source = export_octo(&rom, &Symbols::default());
+        assert_eq!(
+            source,
+            "# Disassembled by pich8\n\n: main\n\tclear\n\ti := data-20c\n\tsub-208\n\n\
+             : label-206\n\tjump label-206\n\n: sub-208\n\tsprite v0 v1 5\n\treturn\n\n\
+             : data-20c\n\t0xF0 0x90\n"
+        );
+        // The assembled program starts with a jump to main, which moves everything by 2 bytes
+        let assembled = assemble(&source).unwrap().rom;
+        assert_eq!(
+            assembled,
+            [
+                0x12, 0x02, 0x00, 0xE0, 0xA2, 0x0E, 0x22, 0x0A, 0x12, 0x08, 0xD0, 0x15, 0x00, 0xEE,
+                0xF0, 0x90
+            ]
+        );
+
+        let symbols = Symbols::parse("draw 0x208").unwrap();
+        assert!(export_octo(&rom, &symbols).contains("\tdraw\n\n: label-206"));
+    }
+
+    #[test]
+    fn test_listing() {
+        let rom = [
+            0x00, 0xE0, 0xA2, 0x08, 0x12, 0x04, 0xF0, 0x00, 0x02, 0x08, 0xF0, 0x90,
+        ];
+        assert_eq!(
+            listing(&rom, 0x200, &Symbols::default()),
+            "\nmain:\n\
+             200  00E0         CLS\n\
+             202  A208         LD I, 208             ; data-208\n\
+             \nlabel-204:\n\
+             204  1204         JP 204                ; label-204\n\
+             206  F0 00        DB F0, 00\n\
+             \ndata-208:\n\
+             208  02 08 F0 90  DB 02, 08, F0, 90\n"
+        );
+    }
+
+    #[test]
+    fn test_opcode_class() {
+        assert_eq!(OpcodeClass::of(0x00E0), OpcodeClass::Draw);
+        assert_eq!(OpcodeClass::of(0xD01F), OpcodeClass::Draw);
+        assert_eq!(OpcodeClass::of(0x2228), OpcodeClass::Jump);
+        assert_eq!(OpcodeClass::of(0x5120), OpcodeClass::Skip);
+        assert_eq!(OpcodeClass::of(0x5122), OpcodeClass::Memory);
+        assert_eq!(OpcodeClass::of(0x8124), OpcodeClass::Math);
+        assert_eq!(OpcodeClass::of(0xE19E), OpcodeClass::Key);
+        assert_eq!(OpcodeClass::of(0xF40A), OpcodeClass::Key);
+        assert_eq!(OpcodeClass::of(0xF365), OpcodeClass::Memory);
+        assert_eq!(OpcodeClass::of(0xF015), OpcodeClass::Timer);
+    }
+}
@@ -0,0 +1,156 @@
+//! Shows the screen on displays with an [embedded-graphics](https://docs.rs/embedded-graphics) driver,
+//! e.g. SSD1306 or ST7789 modules in microcontroller projects.
+//!
+//! ```ignore
+//! let mut screen = EmbeddedDisplay::new(ssd1306, [BinaryColor::Off, BinaryColor::On, BinaryColor::On, BinaryColor::On]);
+//! loop {
+//!     runner.run_frame(&mut cpu, &keys)?;
+//!     screen.present(cpu.vmem())?;
+//!     ssd1306.flush()?;
+//! }
+//! ```
+use crate::video_memory::{Plane, VideoMemory};
+use embedded_graphics_core::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    primitives::Rectangle,
+};
+
+/// Draws the video memory on a `DrawTarget`, scaled by the largest integer factor fitting it and
+/// centered. Only pixels that changed since the last frame are drawn, as display buses are slow.
+pub struct EmbeddedDisplay<D: DrawTarget> {
+    target: D,
+    /// The background, first plane, second plane and both planes colors
+    colors: [D::Color; 4],
+    /// The last drawn pixels, where bit 0 is the first plane and bit 1 the second plane
+    last_pixels: Vec<u8>,
+    last_size: (usize, usize),
+}
+
+impl<D: DrawTarget> EmbeddedDisplay<D> {
+    pub fn new(target: D, colors: [D::Color; 4]) -> Self {
+        Self {
+            target,
+            colors,
+            last_pixels: Vec::new(),
+            last_size: (0, 0),
+        }
+    }
+
+    pub fn target(&self) -> &D {
+        &self.target
+    }
+
+    pub fn target_mut(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    pub fn into_target(self) -> D {
+        self.target
+    }
+
+    /// Sets the colors, which are used from the next frame on.
+    pub fn set_colors(&mut self, colors: [D::Color; 4]) {
+        self.colors = colors;
+        self.last_pixels.clear();
+    }
+
+    pub fn present(&mut self, vmem: &VideoMemory) -> Result<(), D::Error> {
+        let (width, height) = (vmem.render_width(), vmem.render_height());
+        let bounds = self.target.bounding_box();
+        let scale = (bounds.size.width as usize / width)
+            .min(bounds.size.height as usize / height)
+            .max(1);
+        let offset = bounds.top_left
+            + Point::new(
+                (bounds.size.width as usize).saturating_sub(width * scale) as i32 / 2,
+                (bounds.size.height as usize).saturating_sub(height * scale) as i32 / 2,
+            );
+        // Redraw everything after the resolution or the colors changed
+        if self.last_size != (width, height) || self.last_pixels.is_empty() {
+            self.target.clear(self.colors[0])?;
+            self.last_size = (width, height);
+            self.last_pixels = vec![0; width * height];
+        }
+        for idx in 0..width * height {
+            let value = vmem.get_index_plane(Plane::First, idx) as u8
+                | (vmem.get_index_plane(Plane::Second, idx) as u8) << 1;
+            if value == self.last_pixels[idx] {
+                continue;
+            }
+            self.last_pixels[idx] = value;
+            let (x, y) = ((idx % width * scale) as i32, (idx / width * scale) as i32);
+            let area = Rectangle::new(
+                offset + Point::new(x, y),
+                Size::new(scale as u32, scale as u32),
+            );
+            self.target.fill_solid(&area, self.colors[value as usize])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod embedded_test {
+    use super::*;
+    use embedded_graphics_core::{
+        geometry::{Dimensions, OriginDimensions},
+        pixelcolor::BinaryColor,
+        Pixel,
+    };
+
+    /// A 200x100 display recording the pixels drawn
+    struct TestDisplay {
+        pixels: Vec<Option<BinaryColor>>,
+        drawn: usize,
+    }
+
+    impl OriginDimensions for TestDisplay {
+        fn size(&self) -> Size {
+            Size::new(200, 100)
+        }
+    }
+
+    impl DrawTarget for TestDisplay {
+        type Color = BinaryColor;
+        type Error = ();
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), ()>
+        where
+            I: IntoIterator<Item = Pixel<BinaryColor>>,
+        {
+            for Pixel(point, color) in pixels {
+                if self.bounding_box().contains(point) {
+                    self.pixels[point.y as usize * 200 + point.x as usize] = Some(color);
+                    self.drawn += 1;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_present() {
+        use BinaryColor::{Off, On};
+        let display = TestDisplay {
+            pixels: vec![None; 200 * 100],
+            drawn: 0,
+        };
+        let mut screen = EmbeddedDisplay::new(display, [Off, On, On, On]);
+        let mut vmem = VideoMemory::new();
+        vmem.set_plane(Plane::First, 3, 2, true);
+        screen.present(&vmem).unwrap();
+        // 128x64 isn't scaled on 200x100, but centered, and low-res pixels are 2x2 pixels
+        let display = screen.target();
+        assert_eq!(display.drawn, 200 * 100 + 4);
+        assert_eq!(display.pixels[(18 + 5) * 200 + 36 + 7], Some(On));
+        assert_eq!(display.pixels[(18 + 5) * 200 + 36 + 8], Some(Off));
+
+        vmem.set_plane(Plane::First, 3, 2, false);
+        screen.present(&vmem).unwrap();
+        screen.present(&vmem).unwrap();
+        let display = screen.target();
+        assert_eq!(display.drawn, 200 * 100 + 8);
+        assert_eq!(display.pixels[(18 + 5) * 200 + 36 + 7], Some(Off));
+    }
+}
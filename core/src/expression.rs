@@ -0,0 +1,262 @@
+use crate::cpu::{Register, CPU};
+use std::{fmt::Write, str::FromStr};
+
+/// A value of the CPU state, e.g. `V3`, `I`, `DT`, `SP`, `mem[0x3a0]` or a number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Register(Register),
+    SP,
+    Memory(u16),
+    Constant(u16),
+}
+
+impl Operand {
+    pub fn value(self, cpu: &CPU) -> u16 {
+        match self {
+            Operand::Register(register) => cpu.register(register),
+            Operand::SP => cpu.sp() as u16,
+            Operand::Memory(address) => cpu.read_mem(address) as u16,
+            Operand::Constant(value) => value,
+        }
+    }
+
+    /// Formats a value with as many hex digits as the operand has.
+    fn format(self, value: u16) -> String {
+        match self {
+            Operand::Register(Register::PC | Register::I) | Operand::Constant(_) => {
+                format!("{:04X}", value)
+            }
+            _ => format!("{:02X}", value),
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let upper = value.to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "PC" => Operand::Register(Register::PC),
+            "I" => Operand::Register(Register::I),
+            "DT" => Operand::Register(Register::DT),
+            "ST" => Operand::Register(Register::ST),
+            "SP" => Operand::SP,
+            _ => {
+                if let Some(address) = memory_index(value) {
+                    Operand::Memory(parse_number(address)?)
+                } else if let Some(idx) = upper
+                    .strip_prefix('V')
+                    .filter(|idx| idx.len() == 1)
+                    .and_then(|idx| usize::from_str_radix(idx, 16).ok())
+                {
+                    Operand::Register(Register::V(idx))
+                } else {
+                    Operand::Constant(parse_number(value)?)
+                }
+            }
+        };
+        Ok(operand)
+    }
+}
+
+/// An expression in the watch panel, either a single value or a range of memory like `mem[0x3a0..0x3b0]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Watch {
+    Value(Operand),
+    /// Memory from `start` up to, but not including, `end`
+    Memory {
+        start: u16,
+        end: u16,
+    },
+}
+
+impl Watch {
+    /// The longest memory range a watch may show.
+    const MAX_LEN: u16 = 64;
+
+    /// Evaluates the watch and formats the result in hex.
+    pub fn evaluate(self, cpu: &CPU) -> String {
+        match self {
+            Watch::Value(operand) => operand.format(operand.value(cpu)),
+            Watch::Memory { start, end } => {
+                let mut result = String::new();
+                for address in start..end {
+                    let _ = write!(result, "{:02X} ", cpu.read_mem(address));
+                }
+                result.trim_end().to_string()
+            }
+        }
+    }
+}
+
+impl FromStr for Watch {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match memory_index(value.trim()).and_then(|index| index.split_once("..")) {
+            Some((start, end)) => {
+                let (start, end) = (parse_number(start)?, parse_number(end)?);
+                if start >= end || end - start > Self::MAX_LEN {
+                    return Err(format!(
+                        "A memory range has to contain 1 to {} bytes",
+                        Self::MAX_LEN
+                    ));
+                }
+                Ok(Watch::Memory { start, end })
+            }
+            None => value.parse().map(Watch::Value),
+        }
+    }
+}
+
+/// A breakpoint condition, either a comparison like `V4 == 0x1F` or a value
+/// which has to change, like `mem[0x3a0] changed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    Compare(Operand, Comparison, Operand),
+    Changed { operand: Operand, last: Option<u16> },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Condition {
+    /// Checks the condition, a `changed` condition remembers the value for the next check.
+    pub fn check(&mut self, cpu: &CPU) -> bool {
+        match self {
+            Condition::Compare(left, comparison, right) => {
+                let (left, right) = (left.value(cpu), right.value(cpu));
+                match comparison {
+                    Comparison::Equal => left == right,
+                    Comparison::NotEqual => left != right,
+                    Comparison::Less => left < right,
+                    Comparison::LessEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    Comparison::GreaterEqual => left >= right,
+                }
+            }
+            Condition::Changed { operand, last } => {
+                let value = operand.value(cpu);
+                last.replace(value).is_some_and(|last| last != value)
+            }
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        const COMPARISONS: &[(&str, Comparison)] = &[
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessEqual),
+            (">=", Comparison::GreaterEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ];
+        let value = value.trim();
+        if let Some(operand) = value
+            .strip_suffix("changed")
+            .filter(|operand| !operand.is_empty())
+        {
+            return Ok(Condition::Changed {
+                operand: operand.parse()?,
+                last: None,
+            });
+        }
+        for &(symbol, comparison) in COMPARISONS {
+            if let Some((left, right)) = value.split_once(symbol) {
+                return Ok(Condition::Compare(
+                    left.parse()?,
+                    comparison,
+                    right.parse()?,
+                ));
+            }
+        }
+        Err(format!(
+            "Invalid condition '{}', expected e.g. 'V4 == 0x1F' or 'mem[0x3a0] changed'",
+            value
+        ))
+    }
+}
+
+/// Returns the index of `mem[...]`.
+fn memory_index(value: &str) -> Option<&str> {
+    let lower = value.to_ascii_lowercase();
+    if lower.starts_with("mem[") && lower.ends_with(']') {
+        Some(&value[4..value.len() - 1])
+    } else {
+        None
+    }
+}
+
+/// Parses a number, which is hex if prefixed with `0x` or `$`, decimal otherwise.
+fn parse_number(value: &str) -> Result<u16, String> {
+    let value = value.trim();
+    let result = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .or_else(|| value.strip_prefix('$'))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    result.map_err(|_| format!("Invalid value '{}'", value))
+}
+
+#[cfg(test)]
+mod expression_test {
+    use super::*;
+
+    #[test]
+    fn test_watch() {
+        let mut cpu = CPU::new();
+        cpu.set_register(Register::V(3), 0x1F);
+        cpu.set_register(Register::I, 0x3A0);
+        cpu.write_mem(0x3A0, 0xAB);
+        cpu.write_mem(0x3A1, 0xCD);
+
+        let evaluate = |watch: &str| watch.parse::<Watch>().unwrap().evaluate(&cpu);
+        assert_eq!(evaluate("v3"), "1F");
+        assert_eq!(evaluate("I"), "03A0");
+        assert_eq!(evaluate("mem[0x3a0]"), "AB");
+        assert_eq!(evaluate("mem[0x3A0..0x3A2]"), "AB CD");
+        assert_eq!(evaluate("DT"), "00");
+
+        assert!("VG".parse::<Watch>().is_err());
+        assert!("mem[0x3a0..0x3a0]".parse::<Watch>().is_err());
+        assert!("mem[0x100..0x200]".parse::<Watch>().is_err());
+    }
+
+    #[test]
+    fn test_condition() {
+        let mut cpu = CPU::new();
+        cpu.set_register(Register::V(4), 0x1F);
+
+        let mut condition: Condition = "V4 == 0x1F".parse().unwrap();
+        assert!(condition.check(&cpu));
+        let mut condition: Condition = "v4<=30".parse().unwrap();
+        assert!(!condition.check(&cpu));
+
+        let mut condition: Condition = "mem[0x3a0] changed".parse().unwrap();
+        assert!(!condition.check(&cpu));
+        assert!(!condition.check(&cpu));
+        cpu.write_mem(0x3A0, 1);
+        assert!(condition.check(&cpu));
+        assert!(!condition.check(&cpu));
+
+        assert!("V4".parse::<Condition>().is_err());
+        assert!("V4 = 1".parse::<Condition>().is_err());
+        assert!("changed".parse::<Condition>().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! The emulation core of pich8: the CHIP-8, SUPER-CHIP and XO-CHIP CPU, its state files, and the
+//! disassembler and debugging helpers the frontend builds on.
+//!
+//! ```no_run
+//! use pich8::cpu::CPU;
+//!
+//! let rom = std::fs::read("game.ch8").unwrap();
+//! let mut cpu = CPU::new();
+//! cpu.load_rom(&rom).unwrap();
+//! let keys = [false; 16];
+//! loop {
+//!     for _ in 0..12 {
+//!         cpu.tick(&keys).unwrap();
+//!     }
+//!     cpu.update_timers();
+//!     // draw cpu.vmem() and play a tone while cpu.ST() > 0
+//! }
+//! ```
+#![allow(clippy::upper_case_acronyms, non_snake_case)]
+
+pub mod analysis;
+pub mod assembler;
+pub mod breakpoints;
+pub mod cpu;
+pub mod disassembler;
+#[cfg(feature = "embedded-graphics")]
+pub mod embedded;
+pub mod expression;
+pub mod lockstep;
+pub mod octo;
+pub mod patch;
+pub mod profiler;
+pub mod replay;
+pub mod rom;
+pub mod runner;
+pub mod snapshot;
+pub mod state;
+pub mod symbols;
+pub mod video_memory;
@@ -0,0 +1,221 @@
+use crate::cpu::{Register, CPU};
+use crate::disassembler::disassemble;
+use crate::runner::Runner;
+use std::{fmt::Write as _, fs, path::Path};
+
+/// A value logged by another emulator before executing an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceField {
+    Register(Register),
+    SP,
+    Opcode,
+}
+
+impl TraceField {
+    fn parse(name: &str) -> Option<Self> {
+        let field = match name.to_ascii_uppercase().as_str() {
+            "PC" => TraceField::Register(Register::PC),
+            "I" => TraceField::Register(Register::I),
+            "DT" => TraceField::Register(Register::DT),
+            "ST" => TraceField::Register(Register::ST),
+            "SP" => TraceField::SP,
+            "OP" | "OPCODE" => TraceField::Opcode,
+            name => {
+                let idx = name.strip_prefix('V')?;
+                TraceField::Register(Register::V(usize::from_str_radix(idx, 16).ok()?))
+            }
+        };
+        match field {
+            TraceField::Register(Register::V(idx)) if idx > 0xF => None,
+            field => Some(field),
+        }
+    }
+
+    fn name(self) -> String {
+        match self {
+            TraceField::Register(Register::PC) => "PC".to_string(),
+            TraceField::Register(Register::I) => "I".to_string(),
+            TraceField::Register(Register::DT) => "DT".to_string(),
+            TraceField::Register(Register::ST) => "ST".to_string(),
+            TraceField::Register(Register::V(idx)) => format!("V{:X}", idx),
+            TraceField::SP => "SP".to_string(),
+            TraceField::Opcode => "OP".to_string(),
+        }
+    }
+
+    fn value(self, cpu: &CPU) -> u16 {
+        match self {
+            TraceField::Register(register) => cpu.register(register),
+            TraceField::SP => cpu.sp() as u16,
+            TraceField::Opcode => cpu.next_opcode(),
+        }
+    }
+}
+
+/// The state another emulator logged before every instruction, to find the first instruction
+/// pich8 executes differently.
+///
+/// Each line lists hex values as `NAME=VALUE` or `NAME:VALUE`, e.g. `PC=0200 I=0000 V0=00 OP=6001`.
+/// The names are `PC`, `I`, `V0` to `VF`, `SP`, `DT`, `ST` and `OP`, only the listed ones are
+/// compared, so traces of emulators logging fewer values work too. Unknown names are ignored,
+/// as are empty lines and lines starting with `#`.
+pub struct ReferenceTrace {
+    /// The values of each instruction with their line number
+    steps: Vec<(usize, Vec<(TraceField, u16)>)>,
+}
+
+impl ReferenceTrace {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("invalid line {}: '{}'", idx + 1, line);
+            let mut values = Vec::new();
+            for pair in line.split(|c: char| c.is_whitespace() || c == ',') {
+                let (name, value) = match pair.split_once(['=', ':']) {
+                    Some(pair) => pair,
+                    None if pair.is_empty() => continue,
+                    None => return Err(invalid()),
+                };
+                if let Some(field) = TraceField::parse(name) {
+                    let value = value.trim_start_matches("0x");
+                    let value = u16::from_str_radix(value, 16).map_err(|_| invalid())?;
+                    values.push((field, value));
+                }
+            }
+            if values.is_empty() {
+                return Err(invalid());
+            }
+            steps.push((idx + 1, values));
+        }
+        if steps.is_empty() {
+            return Err("the trace is empty".to_string());
+        }
+        Ok(Self { steps })
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Runs the CPU along the trace without input, checking the values before every instruction.
+    /// Returns the number of matched instructions, or an error describing the first divergence
+    /// with the CPU state and the last executed instructions.
+    pub fn run(&self, cpu: &mut CPU, speed: u32) -> Result<usize, String> {
+        let mut runner = Runner::new(speed);
+        let keys = [false; 16];
+        for (step, (line, values)) in self.steps.iter().enumerate() {
+            let diverged: Vec<_> = values
+                .iter()
+                .filter(|&&(field, value)| field.value(cpu) != value)
+                .collect();
+            if !diverged.is_empty() {
+                let mut report = format!(
+                    "Diverged at instruction {} (line {} of the trace, frame {}):\n",
+                    step + 1,
+                    line,
+                    runner.frame()
+                );
+                for &&(field, value) in &diverged {
+                    let _ = writeln!(
+                        report,
+                        "  {:<3} expected {:04X}, got {:04X}",
+                        field.name(),
+                        value,
+                        field.value(cpu)
+                    );
+                }
+                report.push_str(&Self::context(cpu));
+                return Err(report);
+            }
+            runner.step(cpu, &keys).map_err(|e| {
+                format!(
+                    "Error at instruction {} (line {} of the trace): {}\n{}",
+                    step + 1,
+                    line,
+                    e,
+                    Self::context(cpu)
+                )
+            })?;
+        }
+        Ok(self.steps.len())
+    }
+
+    /// Returns the registers and the last executed instructions.
+    fn context(cpu: &CPU) -> String {
+        let mut context = format!(
+            "State: PC={:04X} I={:04X} SP={} DT={:02X} ST={:02X} OP={:04X} ({})\n",
+            cpu.PC(),
+            cpu.I(),
+            cpu.sp(),
+            cpu.DT(),
+            cpu.ST(),
+            cpu.next_opcode(),
+            cpu.next_opcode_description()
+        );
+        let registers: Vec<_> = cpu
+            .V()
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| format!("V{:X}={:02X}", idx, value))
+            .collect();
+        let _ = writeln!(context, "       {}", registers.join(" "));
+        context.push_str("Last instructions:\n");
+        for (address, opcode) in cpu.history() {
+            let _ = writeln!(
+                context,
+                "  {:04X}  {:04X}  {}",
+                address,
+                opcode,
+                disassemble(opcode, 0)
+            );
+        }
+        context
+    }
+}
+
+#[cfg(test)]
+mod lockstep_test {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let rom = [0x60, 0x05, 0x70, 0x01, 0xA3, 0x00, 0x12, 0x00];
+        let trace = ReferenceTrace::parse(
+            "# reference\n\
+             PC=0200 V0=00 OP=6005\n\
+             PC:0202, V0:05, I:0000\n\
+             pc=0x0204 v0=06 unknown=1\n\
+             PC=0206 I=0300 SP=0\n",
+        )
+        .unwrap();
+        assert_eq!(trace.len(), 4);
+        let mut cpu = CPU::new();
+        cpu.load_rom(&rom).unwrap();
+        assert_eq!(trace.run(&mut cpu, 720), Ok(4));
+
+        let trace = ReferenceTrace::parse("PC=0200\nPC=0202 V0=04\n").unwrap();
+        let mut cpu = CPU::new();
+        cpu.load_rom(&rom).unwrap();
+        let report = trace.run(&mut cpu, 720).unwrap_err();
+        assert!(report.starts_with("Diverged at instruction 2 (line 2 of the trace, frame 0):\n"));
+        assert!(report.contains("V0  expected 0004, got 0005"));
+        assert!(report.contains("  0200  6005  LD V0, 05\n"));
+
+        assert!(ReferenceTrace::parse("PC=02G0").is_err());
+        assert!(ReferenceTrace::parse("# empty").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::cpu::{Register, CPU};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// The registers and flags in the format of [Octo](https://github.com/JohnEarnest/Octo)'s emulator,
+/// so data saved with FX75 carries over when moving between Octo and pich8.
+///
+/// Octo keeps its 16 flag registers as a JSON array of numbers, which is accepted on its own as well.
+/// The return stack `r` is only exported, since a state can't be resumed in the other emulator anyway.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OctoState {
+    pub v: Vec<u8>,
+    pub i: u16,
+    pub pc: u16,
+    pub dt: u8,
+    pub st: u8,
+    #[serde(default)]
+    pub r: Vec<u16>,
+    pub flags: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OctoFile {
+    Flags(Vec<u8>),
+    State(OctoState),
+}
+
+impl OctoState {
+    /// The number of flag registers of XO-CHIP, of which S-CHIP only has the first 8
+    const FLAGS: usize = 16;
+
+    pub fn from_cpu(cpu: &CPU) -> Self {
+        let mut flags = cpu.RPL().to_vec();
+        flags.resize(Self::FLAGS, 0);
+        Self {
+            v: cpu.V().to_vec(),
+            i: cpu.I(),
+            pc: cpu.PC(),
+            dt: cpu.DT(),
+            st: cpu.ST(),
+            r: cpu.stack()[..cpu.sp()].to_vec(),
+            flags,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Parses an exported state, or Octo's flag registers only, which leaves `v` empty.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let file: OctoFile =
+            serde_json::from_str(json).map_err(|e| format!("Invalid Octo state: {}", e))?;
+        let state = match file {
+            OctoFile::Flags(flags) => Self {
+                flags,
+                ..Self::default()
+            },
+            OctoFile::State(state) => state,
+        };
+        if state.v.len() > 16 || state.flags.len() > Self::FLAGS {
+            return Err("Invalid Octo state: more than 16 registers".to_string());
+        }
+        Ok(state)
+    }
+
+    /// Sets the flags and, unless only the flags were given, the registers and timers.
+    pub fn apply(&self, cpu: &mut CPU) {
+        let mut flags = cpu.RPL();
+        let len = self.flags.len().min(flags.len());
+        flags[..len].copy_from_slice(&self.flags[..len]);
+        if self.flags[len..].iter().any(|&flag| flag != 0) {
+            warn!("Ignoring flags 8 and above, which only XO-CHIP supports");
+        }
+        cpu.set_RPL(flags);
+        if self.v.is_empty() {
+            return;
+        }
+        for (idx, &value) in self.v.iter().enumerate() {
+            cpu.set_register(Register::V(idx), value as u16);
+        }
+        cpu.set_register(Register::I, self.i);
+        cpu.set_register(Register::DT, self.dt as u16);
+        cpu.set_register(Register::ST, self.st as u16);
+        cpu.set_register(Register::PC, self.pc);
+    }
+}
+
+#[cfg(test)]
+mod octo_test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut cpu = CPU::new();
+        cpu.set_RPL([1, 2, 3, 4, 5, 6, 7, 8]);
+        cpu.set_register(Register::V(3), 0x42);
+        cpu.set_register(Register::I, 0x300);
+        let json = OctoState::from_cpu(&cpu).to_json();
+
+        let mut other = CPU::new();
+        OctoState::parse(&json).unwrap().apply(&mut other);
+        assert_eq!(other.RPL(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(other.V()[3], 0x42);
+        assert_eq!(other.I(), 0x300);
+        assert_eq!(other.PC(), cpu.PC());
+    }
+
+    #[test]
+    fn test_flags_only() {
+        let mut cpu = CPU::new();
+        cpu.set_register(Register::V(0), 9);
+        let state = OctoState::parse("[5, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]").unwrap();
+        state.apply(&mut cpu);
+        assert_eq!(cpu.RPL(), [5, 0, 7, 0, 0, 0, 0, 0]);
+        assert_eq!(cpu.V()[0], 9);
+        assert!(OctoState::parse("[256]").is_err());
+        assert!(OctoState::parse("{}").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::{convert::TryInto, path::Path};
+
+/// Applies an IPS or BPS patch to a ROM, the format is detected by the patch's magic.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if let Some(records) = patch.strip_prefix(IPS_MAGIC) {
+        apply_ips(rom, records)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err("Unknown patch format, only IPS and BPS patches are supported!".to_string())
+    }
+}
+
+/// Reads a patch file and applies it to a ROM.
+pub fn apply_file(rom: &[u8], path: &Path) -> Result<Vec<u8>, String> {
+    let patch =
+        std::fs::read(path).map_err(|e| format!("Cannot open patch {}: {}", path.display(), e))?;
+    apply(rom, &patch).map_err(|msg| format!("Cannot apply {}: {}", path.display(), msg))
+}
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454f46;
+const BPS_MAGIC: &[u8] = b"BPS1";
+const TRUNCATED: &str = "The patch is truncated!";
+
+/// IPS patches are a list of records, each replacing bytes at an offset, until `EOF`.
+/// Some tools append the size the ROM is truncated to after `EOF`.
+fn apply_ips(rom: &[u8], mut records: &[u8]) -> Result<Vec<u8>, String> {
+    let mut rom = rom.to_vec();
+    loop {
+        let offset = read_be(&mut records, 3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let (len, value) = match read_be(&mut records, 2)? {
+            // Run-length encoded record
+            0 => {
+                let len = read_be(&mut records, 2)?;
+                (len, read_bytes(&mut records, 1)?[0])
+            }
+            len => {
+                let data = read_bytes(&mut records, len)?;
+                write(&mut rom, offset, data);
+                continue;
+            }
+        };
+        write(&mut rom, offset, &vec![value; len]);
+    }
+    match records.len() {
+        0 => (),
+        3 => rom.truncate(read_be(&mut records, 3)?),
+        _ => return Err("Unexpected data after the end of the patch!".to_string()),
+    }
+    Ok(rom)
+}
+
+fn read_bytes<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err(TRUNCATED.to_string());
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes)
+}
+
+fn read_be(data: &mut &[u8], len: usize) -> Result<usize, String> {
+    Ok(read_bytes(data, len)?
+        .iter()
+        .fold(0, |value, &b| value << 8 | b as usize))
+}
+
+fn write(rom: &mut Vec<u8>, offset: usize, data: &[u8]) {
+    if rom.len() < offset + data.len() {
+        rom.resize(offset + data.len(), 0);
+    }
+    rom[offset..offset + data.len()].copy_from_slice(data);
+}
+
+/// BPS patches describe the patched ROM as a sequence of copies from the original ROM,
+/// the patched ROM itself and the patch, and protect all three with CRC32 checksums.
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + 12 {
+        return Err(TRUNCATED.to_string());
+    }
+    let (body, footer) = patch.split_at(patch.len() - 12);
+    let checksum = |idx: usize| u32::from_le_bytes(footer[idx..idx + 4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != checksum(8) {
+        return Err("The patch is corrupted!".to_string());
+    }
+    if crc32(rom) != checksum(0) {
+        return Err("The patch is made for a different ROM!".to_string());
+    }
+
+    let mut data = &body[BPS_MAGIC.len()..];
+    let source_size = read_varint(&mut data)?;
+    let target_size = read_varint(&mut data)?;
+    let metadata_size = read_varint(&mut data)?;
+    read_bytes(&mut data, metadata_size)?;
+    if source_size != rom.len() {
+        return Err("The patch is made for a different ROM!".to_string());
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    let out_of_bounds = || "The patch refers to data outside the ROM!".to_string();
+    while !data.is_empty() {
+        let action = read_varint(&mut data)?;
+        let len = (action >> 2) + 1;
+        if target.len() + len > target_size {
+            return Err(out_of_bounds());
+        }
+        match action & 3 {
+            // Source read
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + len).ok_or_else(out_of_bounds)?);
+            }
+            // Target read
+            1 => target.extend_from_slice(read_bytes(&mut data, len)?),
+            // Source copy
+            2 => {
+                source_offset = relative_offset(source_offset, read_varint(&mut data)?)
+                    .ok_or_else(out_of_bounds)?;
+                target.extend_from_slice(
+                    rom.get(source_offset..source_offset + len)
+                        .ok_or_else(out_of_bounds)?,
+                );
+                source_offset += len;
+            }
+            // Target copy, the copied range may overlap with what's being written
+            _ => {
+                target_offset = relative_offset(target_offset, read_varint(&mut data)?)
+                    .ok_or_else(out_of_bounds)?;
+                for _ in 0..len {
+                    let byte = *target.get(target_offset).ok_or_else(out_of_bounds)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size || crc32(&target) != checksum(4) {
+        return Err("Patching failed, the result doesn't match the expected ROM!".to_string());
+    }
+    Ok(target)
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<usize, String> {
+    let mut value = 0usize;
+    let mut shift = 1usize;
+    loop {
+        let byte = read_bytes(data, 1)?[0];
+        value = (byte as usize & 0x7f)
+            .checked_mul(shift)
+            .and_then(|n| n.checked_add(value))
+            .ok_or("Invalid number in patch!")?;
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift = shift.checked_shl(7).ok_or("Invalid number in patch!")?;
+        value = value.checked_add(shift).ok_or("Invalid number in patch!")?;
+    }
+}
+
+/// The lowest bit of a relative offset is its sign.
+fn relative_offset(offset: usize, encoded: usize) -> Option<usize> {
+    if encoded & 1 == 0 {
+        offset.checked_add(encoded >> 1)
+    } else {
+        offset.checked_sub(encoded >> 1)
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[cfg(test)]
+mod patch_test {
+    use super::*;
+
+    #[test]
+    fn test_ips() {
+        let rom = [0x00, 0xe0, 0x12, 0x00];
+        let mut patch = b"PATCH".to_vec();
+        // Replace the jump, then append three bytes with a run-length encoded record
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0x12, 0x02]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xff]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(
+            apply(&rom, &patch).unwrap(),
+            vec![0x00, 0xe0, 0x12, 0x02, 0xff, 0xff, 0xff]
+        );
+
+        patch.extend_from_slice(&[0x00, 0x00, 0x02]);
+        assert_eq!(apply(&rom, &patch).unwrap(), vec![0x00, 0xe0]);
+        assert!(apply(&rom, b"PATCH\x00\x00").is_err());
+        assert!(apply(&rom, b"NOPATCH").is_err());
+    }
+
+    #[test]
+    fn test_bps() {
+        let rom = [0x00, 0xe0, 0x12, 0x00];
+        let target = [0x00, 0xe0, 0x12, 0x02, 0x12, 0x02];
+        let mut patch = b"BPS1".to_vec();
+        // Sizes 4 and 6, no metadata
+        patch.extend_from_slice(&[0x84, 0x86, 0x80]);
+        // Read three bytes from the source, one byte from the patch,
+        // then copy two bytes from the start of the jump in the target
+        patch.extend_from_slice(&[(2 << 2) | 0x80, 0x01 | 0x80, 0x02]);
+        patch.extend_from_slice(&[(1 << 2) | 3 | 0x80, (2 << 1) | 0x80]);
+        patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+        patch.extend_from_slice(&crc32(&target).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+        assert_eq!(apply(&rom, &patch).unwrap(), target);
+
+        // Wrong ROM
+        assert!(apply(&[0x00, 0xe0, 0x12, 0x04], &patch).is_err());
+        // Corrupted patch
+        let last = patch.len() - 1;
+        patch[last] ^= 1;
+        assert!(apply(&rom, &patch).is_err());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
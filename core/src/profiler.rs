@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+/// The executed instructions of a subroutine.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct RoutineStats {
+    pub calls: u64,
+    /// Instructions executed in the subroutine itself, including its RET
+    pub own: u64,
+    /// Instructions executed until the subroutine returned, including the subroutines it called
+    pub total: u64,
+}
+
+/// Counts the instructions spent per subroutine by following CALL and RET.
+/// Instructions outside of any subroutine are attributed to the main program.
+#[derive(Default)]
+pub struct Profiler {
+    /// The called subroutines with the instruction count at the time of the call
+    stack: Vec<(u16, u64)>,
+    instructions: u64,
+    main: u64,
+    routines: HashMap<u16, RoutineStats>,
+}
+
+impl Profiler {
+    /// The CPU's stack depth, deeper calls mean the program doesn't return from its subroutines.
+    const MAX_DEPTH: usize = 16;
+
+    pub fn record(&mut self, opcode: u16) {
+        self.instructions += 1;
+        match self.stack.last() {
+            Some(&(address, _)) => self.routines.entry(address).or_default().own += 1,
+            None => self.main += 1,
+        }
+        if opcode & 0xF000 == 0x2000 {
+            let address = opcode & 0x0FFF;
+            self.routines.entry(address).or_default().calls += 1;
+            if self.stack.len() == Self::MAX_DEPTH {
+                self.stack.remove(0);
+            }
+            self.stack.push((address, self.instructions));
+        } else if opcode == 0x00EE {
+            if let Some((address, start)) = self.stack.pop() {
+                self.routines.entry(address).or_default().total += self.instructions - start;
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Instructions executed outside of subroutines.
+    pub fn main(&self) -> u64 {
+        self.main
+    }
+
+    /// Returns the subroutines by their address, the most expensive first.
+    pub fn routines(&self) -> Vec<(u16, RoutineStats)> {
+        let mut routines: Vec<_> = self
+            .routines
+            .iter()
+            .map(|(&address, &stats)| (address, stats))
+            .collect();
+        routines.sort_by_key(|&(address, stats)| (std::cmp::Reverse(stats.total), address));
+        routines
+    }
+}
+
+#[cfg(test)]
+mod profiler_test {
+    use super::*;
+
+    #[test]
+    fn test_profiler() {
+        let mut profiler = Profiler::default();
+        // main: CALL 300, JP; 300: LD, CALL 400, RET; 400: RET
+        for opcode in [0x2300, 0x6001, 0x2400, 0x00EE, 0x00EE, 0x1202] {
+            profiler.record(opcode);
+        }
+        assert_eq!(profiler.instructions(), 6);
+        assert_eq!(profiler.main(), 2);
+        assert_eq!(
+            profiler.routines(),
+            vec![
+                (
+                    0x300,
+                    RoutineStats {
+                        calls: 1,
+                        own: 3,
+                        total: 4
+                    }
+                ),
+                (
+                    0x400,
+                    RoutineStats {
+                        calls: 1,
+                        own: 1,
+                        total: 1
+                    }
+                ),
+            ]
+        );
+    }
+}
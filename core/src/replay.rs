@@ -0,0 +1,141 @@
+use std::{fs, path::Path};
+
+/// A recording of the keypad input for headless replays.
+///
+/// Each line contains a frame number and the keys held down from that frame on
+/// as hex bitmask (bit n = key n), e.g. `120 0010` presses key 4 in frame 120.
+/// The replay ends with the last listed frame or at an `end <frame>` line.
+/// Empty lines and lines starting with `#` are ignored.
+pub struct Recording {
+    events: Vec<(u64, u16)>,
+    end: u64,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut events: Vec<(u64, u16)> = Vec::new();
+        let mut end = None;
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("invalid line {}: '{}'", idx + 1, line);
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("end"), Some(frame), None) => {
+                    end = Some(frame.parse().map_err(|_| invalid())?);
+                }
+                (Some(frame), Some(keys), None) => {
+                    let frame: u64 = frame.parse().map_err(|_| invalid())?;
+                    let keys = u16::from_str_radix(keys, 16).map_err(|_| invalid())?;
+                    if events.last().is_some_and(|&(last, _)| frame < last) {
+                        return Err(format!("frames must be ascending in line {}", idx + 1));
+                    }
+                    events.push((frame, keys));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        let end = end
+            .or_else(|| events.last().map(|&(frame, _)| frame))
+            .ok_or("the recording is empty")?;
+        Ok(Self { events, end })
+    }
+
+    /// The frame after which the replay is finished.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Returns the keys held down in the given frame.
+    pub fn keys(&self, frame: u64) -> [bool; 16] {
+        let mask = self
+            .events
+            .iter()
+            .take_while(|&&(event_frame, _)| event_frame <= frame)
+            .last()
+            .map_or(0, |&(_, keys)| keys);
+        let mut keys = [false; 16];
+        for (idx, key) in keys.iter_mut().enumerate() {
+            *key = mask >> idx & 1 == 1;
+        }
+        keys
+    }
+}
+
+/// Records the keypad input in the format read by `Recording::parse`.
+#[derive(Default)]
+pub struct Recorder {
+    events: Vec<(u64, u16)>,
+}
+
+impl Recorder {
+    /// Records the keys held down in the given frame, only changes are stored.
+    pub fn record(&mut self, frame: u64, keys: &[bool; 16]) {
+        let mask = keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (idx, &key)| mask | (key as u16) << idx);
+        if self
+            .events
+            .last()
+            .map_or(mask != 0, |&(_, last)| last != mask)
+        {
+            self.events.push((frame, mask));
+        }
+    }
+
+    /// Returns the recording ending at the given frame as text.
+    pub fn finish(&self, end: u64) -> String {
+        let mut content = format!("# {} input recording\n", env!("CARGO_PKG_NAME"));
+        for (frame, keys) in &self.events {
+            content.push_str(&format!("{} {:04x}\n", frame, keys));
+        }
+        content.push_str(&format!("end {}\n", end));
+        content
+    }
+}
+
+#[cfg(test)]
+mod replay_test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let recording = Recording::parse("# test\n0 0000\n10 0011\n\n20 0000\nend 30\n").unwrap();
+        assert_eq!(recording.end(), 30);
+        assert_eq!(recording.keys(5), [false; 16]);
+        let keys = recording.keys(15);
+        assert!(keys[0] && keys[4] && !keys[1]);
+        assert_eq!(recording.keys(25), [false; 16]);
+
+        assert_eq!(Recording::parse("5 0001\n").unwrap().end(), 5);
+        assert!(Recording::parse("").is_err());
+        assert!(Recording::parse("10 0001\n5 0000\n").is_err());
+        assert!(Recording::parse("10 xyz\n").is_err());
+    }
+
+    #[test]
+    fn test_recorder() {
+        let mut recorder = Recorder::default();
+        let mut keys = [false; 16];
+        recorder.record(0, &keys);
+        keys[4] = true;
+        recorder.record(10, &keys);
+        recorder.record(11, &keys);
+        keys[4] = false;
+        recorder.record(20, &keys);
+
+        let recording = Recording::parse(&recorder.finish(30)).unwrap();
+        assert_eq!(recording.end(), 30);
+        assert!(recording.keys(15)[4]);
+        assert_eq!(recording.keys(25), [false; 16]);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::cpu::CPU;
+use crate::state::StateFile;
+use sha1::{Digest, Sha1};
+use std::{
+    fs,
+    io::{self, Cursor, Read},
+    path::Path,
+};
+use zip::ZipArchive;
+
+/// Returns the lowercase hex encoded SHA-1 hash of the given data.
+/// It's used to identify ROMs independent of their file name.
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The path which stands for the standard input, e.g. to pipe a freshly assembled ROM into pich8.
+pub const STDIN_PATH: &str = "-";
+
+/// Reads a file, or the standard input if the path is `-`.
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    if path == Path::new(STDIN_PATH) {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Cannot read from stdin: {}", e))?;
+        Ok(data)
+    } else {
+        fs::read(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))
+    }
+}
+
+/// File extensions commonly used for ROMs, e.g. to find them in directories and archives.
+pub const ROM_EXTENSIONS: &[&str] = &["ch8", "c8", "sc8", "xo8", "hc8", "ch10"];
+
+pub fn has_rom_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|rom_ext| rom_ext.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Returns the names of the ROMs in a zip archive, in the order they're stored.
+pub fn zip_roms(data: &[u8]) -> Result<Vec<String>, String> {
+    let archive =
+        ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Invalid zip archive: {}", e))?;
+    Ok(archive
+        .file_names()
+        .filter(|name| has_rom_extension(Path::new(name)))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Extracts a file from a zip archive.
+pub fn read_from_zip(data: &[u8], name: &str) -> Result<Vec<u8>, String> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Invalid zip archive: {}", e))?;
+    let mut file = archive
+        .by_name(name)
+        .map_err(|e| format!("Cannot extract {}: {}", name, e))?;
+    if file.size() > CPU::MAX_ROM_SIZE as u64 {
+        return Err(format!("{} is too big to be a ROM!", name));
+    }
+    let mut rom = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut rom)
+        .map_err(|e| format!("Cannot extract {}: {}", name, e))?;
+    Ok(rom)
+}
+
+/// The kinds of files pich8 can open, independent of their extension.
+#[derive(Debug, PartialEq)]
+pub enum FileType {
+    Rom,
+    State,
+    OctoSource,
+    Zip,
+}
+
+impl FileType {
+    const ZIP_MAGIC: &'static [u8] = b"PK\x03\x04";
+
+    /// Detects the file type by the magic bytes of state files and the text content of Octo source.
+    /// Anything else is assumed to be a ROM, since ROMs have no header.
+    pub fn detect(data: &[u8]) -> Self {
+        if StateFile::is_state_file(data) {
+            FileType::State
+        } else if data.starts_with(Self::ZIP_MAGIC) {
+            FileType::Zip
+        } else if is_octo_source(data) {
+            FileType::OctoSource
+        } else {
+            FileType::Rom
+        }
+    }
+}
+
+/// The CHIP-8 variants a ROM can be written for, ordered by the instructions they support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SChip,
+    XoChip,
+}
+
+impl Platform {
+    /// Guesses the platform by the instructions only the extensions have.
+    /// Sprites and other data can look like instructions too, so this is only a hint.
+    pub fn detect(rom: &[u8]) -> Self {
+        rom.chunks_exact(2)
+            .map(|word| Self::of((word[0] as u16) << 8 | word[1] as u16))
+            .max()
+            .unwrap_or(Platform::Chip8)
+    }
+
+    /// Returns the first platform supporting the opcode.
+    pub fn of(opcode: u16) -> Self {
+        match (opcode & 0xF000, opcode & 0x00FF, opcode & 0x000F) {
+            (0x0000, _, _) if opcode & 0xFFF0 == 0x00D0 => Platform::XoChip,
+            (0x5000, _, 2 | 3) => Platform::XoChip,
+            (0xF000, 0x01 | 0x02 | 0x3A, _) => Platform::XoChip,
+            _ if opcode == 0xF000 => Platform::XoChip,
+            (0x0000, _, _) if (0x00FB..=0x00FF).contains(&opcode) || opcode & 0xFFF0 == 0x00C0 => {
+                Platform::SChip
+            }
+            (0xF000, 0x30 | 0x75 | 0x85, _) => Platform::SChip,
+            _ => Platform::Chip8,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SChip => "S-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+/// Checks if the data can be loaded as a ROM, otherwise returns a message explaining why not.
+pub fn validate(data: &[u8]) -> Result<(), String> {
+    if data.is_empty() {
+        return Err("The file is empty!".to_string());
+    }
+    match FileType::detect(data) {
+        FileType::Rom => (),
+        FileType::State => {
+            return Err(
+                "This is a p8s state file, not a ROM. Open it as a state instead!".to_string(),
+            )
+        }
+        FileType::Zip => {
+            return Err("This is a zip archive, open it to load a ROM inside it!".to_string())
+        }
+        FileType::OctoSource => return Err(
+            "This looks like Octo source code (.8o), it has to be compiled into a ROM first, e.g. with Octo!"
+                .to_string(),
+        ),
+    }
+    if data.len() > CPU::MAX_ROM_SIZE {
+        return Err(format!(
+            "The ROM is too big ({} bytes), at most {} bytes fit into memory!",
+            data.len(),
+            CPU::MAX_ROM_SIZE
+        ));
+    }
+    Ok(())
+}
+
+/// Octo source is plain text and, unlike ROMs, consists only of printable characters.
+fn is_octo_source(data: &[u8]) -> bool {
+    let is_text = data
+        .iter()
+        .all(|&b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+    let text = String::from_utf8_lossy(data);
+    is_text && (text.contains(": main") || text.contains(":=") || text.contains(":alias"))
+}
+
+#[cfg(test)]
+mod rom_test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&[0x00, 0xE0, 0x12, 0x00]).is_ok());
+        assert!(validate(&[]).is_err());
+        assert!(validate(b"p8s\x9c").is_err());
+        assert!(validate(b": main\n  i := 0x200\n  loop again\n").is_err());
+        assert!(validate(&vec![0; CPU::MAX_ROM_SIZE]).is_ok());
+        assert!(validate(&vec![0; CPU::MAX_ROM_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(FileType::detect(&[0x00, 0xE0, 0x12, 0x00]), FileType::Rom);
+        assert_eq!(FileType::detect(b"p8sT\x01\x01abc"), FileType::State);
+        assert_eq!(
+            FileType::detect(b": main\n  loop again\n"),
+            FileType::OctoSource
+        );
+        // Text without Octo syntax, e.g. a ROM consisting of printable bytes only
+        assert_eq!(FileType::detect(b"abcd"), FileType::Rom);
+    }
+
+    #[test]
+    fn test_platform() {
+        assert_eq!(Platform::detect(&[0x00, 0xE0, 0x12, 0x00]), Platform::Chip8);
+        assert_eq!(Platform::detect(&[0x00, 0xFF, 0xF1, 0x75]), Platform::SChip);
+        assert_eq!(
+            Platform::detect(&[0x00, 0xFF, 0xF0, 0x00]),
+            Platform::XoChip
+        );
+        assert_eq!(Platform::detect(&[0x52, 0x32]), Platform::XoChip);
+    }
+
+    #[test]
+    fn test_zip() {
+        let mut archive = Vec::new();
+        {
+            let mut writer = zip::ZipWriter::new(Cursor::new(&mut archive));
+            let options = zip::write::SimpleFileOptions::default();
+            writer.start_file("readme.txt", options).unwrap();
+            writer.write_all(b"hello").unwrap();
+            writer.start_file("roms/test.ch8", options).unwrap();
+            writer.write_all(&[0x00, 0xE0]).unwrap();
+            writer.finish().unwrap();
+        }
+        assert_eq!(FileType::detect(&archive), FileType::Zip);
+        assert_eq!(zip_roms(&archive).unwrap(), vec!["roms/test.ch8"]);
+        assert_eq!(
+            read_from_zip(&archive, "roms/test.ch8").unwrap(),
+            vec![0x00, 0xE0]
+        );
+        assert!(read_from_zip(&archive, "missing.ch8").is_err());
+        assert!(zip_roms(b"PK\x03\x04").is_err());
+    }
+}
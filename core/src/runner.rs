@@ -0,0 +1,156 @@
+use crate::cpu::{self, CPU};
+
+/// Runs the CPU in frames without blocking, so the host decides when to run, e.g. a browser's
+/// animation frame callback instead of a thread sleeping between frames.
+/// A frame consists of the instructions executed within 1/60 s at the given speed, followed by a timer update.
+/// If the speed isn't a multiple of the frame rate, the instructions left over are carried to the
+/// next frames like in the window, so all frontends run the same instructions at the same speed.
+pub struct Runner {
+    speed: u32,
+    /// Instructions of the running frame
+    frame_cycles: u32,
+    /// Instructions left over from the last frame
+    cycle_remainder: u32,
+    /// Instructions executed in the running frame
+    cycle: u32,
+    frame: u64,
+    instructions: u64,
+    /// Elapsed time not emulated yet, in seconds
+    pending: f64,
+}
+
+impl Runner {
+    pub const FRAMES_PER_SECOND: u32 = 60;
+    /// The most frames `advance` catches up on, e.g. after the host was suspended for a while
+    const MAX_CATCH_UP: u32 = 4;
+
+    /// Creates a runner executing the given number of instructions per second.
+    pub fn new(speed: u32) -> Self {
+        let mut runner = Self {
+            speed,
+            frame_cycles: 0,
+            cycle_remainder: 0,
+            cycle: 0,
+            frame: 0,
+            instructions: 0,
+            pending: 0.0,
+        };
+        runner.start_frame();
+        runner
+    }
+
+    /// The instructions executed per second
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    fn start_frame(&mut self) {
+        let cycles = self.speed + self.cycle_remainder;
+        self.cycle_remainder = cycles % Self::FRAMES_PER_SECOND;
+        self.frame_cycles = cycles / Self::FRAMES_PER_SECOND;
+    }
+
+    /// Returns the number of completed frames.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns the number of executed instructions.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Executes a single instruction and returns whether it completed a frame,
+    /// the timers are updated after each frame's instructions.
+    /// Below 60 instructions per second, frames without an instruction complete right away.
+    pub fn step(&mut self, cpu: &mut CPU, keys: &[bool; 16]) -> Result<bool, cpu::Error> {
+        if self.cycle < self.frame_cycles {
+            cpu.tick(keys)?;
+            self.cycle += 1;
+            self.instructions += 1;
+            if self.cycle < self.frame_cycles {
+                return Ok(false);
+            }
+        }
+        self.cycle = 0;
+        cpu.update_timers();
+        self.frame += 1;
+        self.start_frame();
+        Ok(true)
+    }
+
+    /// Runs the remaining instructions of the running frame and updates the timers.
+    pub fn run_frame(&mut self, cpu: &mut CPU, keys: &[bool; 16]) -> Result<(), cpu::Error> {
+        while !self.step(cpu, keys)? {}
+        Ok(())
+    }
+
+    /// Runs the frames due after the given number of seconds passed and returns how many ran.
+    pub fn advance(
+        &mut self,
+        cpu: &mut CPU,
+        keys: &[bool; 16],
+        elapsed: f64,
+    ) -> Result<u32, cpu::Error> {
+        let frame_time = 1.0 / Self::FRAMES_PER_SECOND as f64;
+        self.pending += elapsed.max(0.0);
+        let due = (self.pending / frame_time) as u32;
+        let frames = due.min(Self::MAX_CATCH_UP);
+        self.pending = if due > frames {
+            0.0
+        } else {
+            self.pending - frames as f64 * frame_time
+        };
+        for _ in 0..frames {
+            self.run_frame(cpu, keys)?;
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod runner_test {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let mut cpu = CPU::new();
+        // Jumps to itself
+        cpu.load_rom(&[0x12, 0x00]).unwrap();
+        let mut runner = Runner::new(600);
+        let keys = [false; 16];
+        for _ in 0..9 {
+            assert!(!runner.step(&mut cpu, &keys).unwrap());
+        }
+        assert!(runner.step(&mut cpu, &keys).unwrap());
+        assert_eq!(runner.frame(), 1);
+
+        assert_eq!(runner.advance(&mut cpu, &keys, 0.01).unwrap(), 0);
+        assert_eq!(runner.advance(&mut cpu, &keys, 0.03).unwrap(), 2);
+        assert_eq!(runner.advance(&mut cpu, &keys, 10.0).unwrap(), 4);
+        assert_eq!(runner.frame(), 7);
+    }
+
+    #[test]
+    fn test_cycle_remainder() {
+        let mut cpu = CPU::new();
+        // ADD V0, 1 over and over, so the PC counts the executed instructions
+        cpu.load_rom(&[0x70, 0x01].repeat(100)).unwrap();
+        let keys = [false; 16];
+        let executed = |runner: &mut Runner, cpu: &mut CPU| {
+            let pc = cpu.PC();
+            runner.run_frame(cpu, &keys).unwrap();
+            (cpu.PC() - pc) / 2
+        };
+        // 12.5 instructions per frame alternate between 12 and 13 like in the window
+        let mut runner = Runner::new(750);
+        let frame_cycles: Vec<_> = (0..4).map(|_| executed(&mut runner, &mut cpu)).collect();
+        assert_eq!(frame_cycles, [12, 13, 12, 13]);
+
+        // Every other frame has no instruction at 30 instructions per second
+        let mut runner = Runner::new(30);
+        let frame_cycles: Vec<_> = (0..4).map(|_| executed(&mut runner, &mut cpu)).collect();
+        assert_eq!(frame_cycles, [0, 1, 0, 1]);
+        assert_eq!((runner.frame(), runner.instructions()), (4, 2));
+    }
+}
@@ -0,0 +1,134 @@
+//! Golden-frame tests: a ROM runs for a number of frames with scripted input, and the final screen
+//! is compared with a snapshot file, so changes to the CPU or the video memory that alter what
+//! a ROM draws fail `cargo test`.
+//!
+//! ```no_run
+//! use pich8::{cpu::CPU, replay::Recording, snapshot::Snapshot};
+//!
+//! let mut cpu = CPU::new();
+//! cpu.load_rom(&std::fs::read("game.ch8").unwrap()).unwrap();
+//! // Press key 5 in frame 30 and release it in frame 40, stop after frame 120
+//! let input = Recording::parse("30 0020\n40 0000\nend 120").unwrap();
+//! Snapshot::run(&mut cpu, &input, 720).unwrap().assert_matches("snapshots/game.txt");
+//! ```
+//!
+//! Snapshots are text with a `.` for every dark pixel, `1` and `2` for pixels set on one of the
+//! planes and `3` for both, so changes are readable in a diff. Missing snapshot files are created,
+//! and all are rewritten with the current screens if `PICH8_UPDATE_SNAPSHOTS` is set.
+use crate::cpu::CPU;
+use crate::replay::Recording;
+use crate::runner::Runner;
+use crate::video_memory::VideoMemory;
+use std::{env, fs, path::Path};
+
+/// The screen of a frame as text, see the module documentation for the format.
+#[derive(Debug, PartialEq)]
+pub struct Snapshot {
+    text: String,
+}
+
+impl Snapshot {
+    /// Setting this environment variable rewrites the snapshot files instead of comparing them
+    pub const UPDATE_VAR: &'static str = "PICH8_UPDATE_SNAPSHOTS";
+    /// The random numbers are seeded, so snapshots of ROMs using them are reproducible
+    const SEED: u64 = 0;
+
+    pub fn capture(vmem: &VideoMemory) -> Self {
+        let (width, height) = (vmem.render_width(), vmem.render_height());
+        let mut text = String::with_capacity((width + 1) * height);
+        for y in 0..height {
+            for x in 0..width {
+                text.push(match vmem.pixel(vmem.to_index(x, y)) {
+                    0 => '.',
+                    value => (b'0' + value) as char,
+                });
+            }
+            text.push('\n');
+        }
+        Self { text }
+    }
+
+    /// Runs the CPU, which has the ROM loaded, until the end of the input at the given speed and
+    /// captures the final screen.
+    pub fn run(cpu: &mut CPU, input: &Recording, speed: u32) -> Result<Self, String> {
+        cpu.seed_rng(Self::SEED);
+        let mut runner = Runner::new(speed);
+        while runner.frame() < input.end() {
+            runner
+                .run_frame(cpu, &input.keys(runner.frame()))
+                .map_err(|e| format!("Error in frame {}: {}", runner.frame(), e))?;
+        }
+        Ok(Self::capture(cpu.vmem()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Compares the screen with the snapshot file, which is written instead if it doesn't exist
+    /// or updating is requested. The error names the first differing row.
+    pub fn compare(&self, path: &Path) -> Result<(), String> {
+        if !path.exists() || env::var_os(Self::UPDATE_VAR).is_some() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+            }
+            return fs::write(path, &self.text)
+                .map_err(|e| format!("Cannot write {}: {}", path.display(), e));
+        }
+        let expected = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        if expected == self.text {
+            return Ok(());
+        }
+        let (mut expected_rows, mut actual_rows) = (expected.lines(), self.text.lines());
+        let mut row = 0;
+        loop {
+            match (expected_rows.next(), actual_rows.next()) {
+                (Some(expected), Some(actual)) if expected == actual => row += 1,
+                (expected, actual) => {
+                    return Err(format!(
+                        "The screen differs from {} in row {}:\nexpected {}\n  actual {}\nSet {} to accept the new screen.",
+                        path.display(),
+                        row,
+                        expected.unwrap_or("<none>"),
+                        actual.unwrap_or("<none>"),
+                        Self::UPDATE_VAR
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Compares the screen with the snapshot file like `compare`, panicking if they differ.
+    pub fn assert_matches(&self, path: impl AsRef<Path>) {
+        if let Err(msg) = self.compare(path.as_ref()) {
+            panic!("{}\n\nScreen:\n{}", msg, self.text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod snapshot_test {
+    use super::*;
+
+    #[test]
+    fn test_bootrom() {
+        let mut cpu = CPU::new();
+        cpu.load_bootrom();
+        let input = Recording::parse("end 120").unwrap();
+        let snapshot = Snapshot::run(&mut cpu, &input, 720).unwrap();
+        snapshot.assert_matches(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("data/snapshots/pich8-logo.txt"),
+        );
+
+        if env::var_os(Snapshot::UPDATE_VAR).is_some() {
+            return;
+        }
+        let path = env::temp_dir().join("pich8_snapshot_test.txt");
+        fs::write(&path, snapshot.as_str().replacen('.', "1", 1)).unwrap();
+        let error = snapshot.compare(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.contains("in row 0:"));
+    }
+}
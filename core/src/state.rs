@@ -0,0 +1,143 @@
+use crate::cpu::CPU;
+use crate::runner::Runner;
+
+/// A downscaled snapshot of the screen stored in state files, as RGB pixels.
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Thumbnail {
+    pub const WIDTH: usize = 64;
+    pub const HEIGHT: usize = 32;
+}
+
+/// The contents of a p8s state file.
+///
+/// The file starts with the magic, optionally followed by a thumbnail section
+/// (tag, width, height, pixels) and then the serialized CPU. Files written by
+/// older versions have no thumbnail section.
+pub struct StateFile<'a> {
+    pub thumbnail: Option<Thumbnail>,
+    pub cpu_state: &'a [u8],
+}
+
+impl<'a> StateFile<'a> {
+    pub const MAGIC: &'static [u8] = b"p8s";
+    // Can't be confused with the start of the serialized CPU, which is always a msgpack array
+    const THUMBNAIL_TAG: u8 = b'T';
+
+    pub fn is_state_file(data: &[u8]) -> bool {
+        data.starts_with(Self::MAGIC)
+    }
+
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        let data = data
+            .strip_prefix(Self::MAGIC)
+            .ok_or("Not a valid state file!")?;
+        match data {
+            [Self::THUMBNAIL_TAG, width, height, rest @ ..] => {
+                let (width, height) = (*width as usize, *height as usize);
+                let len = width * height * 3;
+                if rest.len() < len {
+                    return Err("State file is truncated!".to_string());
+                }
+                Ok(Self {
+                    thumbnail: Some(Thumbnail {
+                        width,
+                        height,
+                        pixels: rest[..len].to_vec(),
+                    }),
+                    cpu_state: &rest[len..],
+                })
+            }
+            _ => Ok(Self {
+                thumbnail: None,
+                cpu_state: data,
+            }),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Self::MAGIC.to_vec();
+        if let Some(thumbnail) = &self.thumbnail {
+            data.push(Self::THUMBNAIL_TAG);
+            data.push(thumbnail.width as u8);
+            data.push(thumbnail.height as u8);
+            data.extend_from_slice(&thumbnail.pixels);
+        }
+        data.extend_from_slice(self.cpu_state);
+        data
+    }
+}
+
+/// Checks that the state captures everything affecting the emulation: the CPU is saved and
+/// restored into a second one, which has to serialize to the same bytes and stay identical to
+/// the original while both run the given number of frames without input.
+///
+/// The random numbers aren't part of the state, so both CPUs are seeded alike. The original CPU
+/// is run as well.
+pub fn check_round_trip(cpu: &mut CPU, frames: u64, speed: u32, seed: u64) -> Result<(), String> {
+    let state = cpu.save_state()?;
+    let mut restored = CPU::from_state(&state)?;
+    if restored.save_state()? != state {
+        return Err("The restored state differs from the saved one!".to_string());
+    }
+    cpu.seed_rng(seed);
+    restored.seed_rng(seed);
+    let (mut runner, mut restored_runner) = (Runner::new(speed), Runner::new(speed));
+    let keys = [false; 16];
+    for frame in 1..=frames {
+        match (
+            runner.run_frame(cpu, &keys),
+            restored_runner.run_frame(&mut restored, &keys),
+        ) {
+            (Ok(()), Ok(())) if cpu.save_state()? == restored.save_state()? => (),
+            // Both stopped the same way
+            (Err(e), Err(restored_e)) if e.to_string() == restored_e.to_string() => return Ok(()),
+            _ => return Err(format!("The restored CPU diverged in frame {}!", frame)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod state_test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let state = StateFile {
+            thumbnail: Some(Thumbnail {
+                width: 2,
+                height: 1,
+                pixels: vec![1, 2, 3, 4, 5, 6],
+            }),
+            cpu_state: &[0x9c, 0xff],
+        };
+        let data = state.to_bytes();
+        let parsed = StateFile::parse(&data).unwrap();
+        assert_eq!(parsed.thumbnail.unwrap().pixels, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(parsed.cpu_state, &[0x9c, 0xff]);
+    }
+
+    #[test]
+    fn test_check_round_trip() {
+        let mut cpu = CPU::new();
+        cpu.load_bootrom();
+        check_round_trip(&mut cpu, 30, 720, 0).unwrap();
+        // Midway, with the screen partially drawn
+        check_round_trip(&mut cpu, 30, 720, 0).unwrap();
+    }
+
+    #[test]
+    fn test_without_thumbnail() {
+        let parsed = StateFile::parse(b"p8s\x9c\xff").unwrap();
+        assert!(parsed.thumbnail.is_none());
+        assert_eq!(parsed.cpu_state, &[0x9c, 0xff]);
+
+        assert!(StateFile::parse(b"p8sT\x02\x02\x00").is_err());
+        assert!(StateFile::parse(b"\x00\xe0").is_err());
+    }
+}
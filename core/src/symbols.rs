@@ -0,0 +1,139 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The labels of a program, shown in the disassembly, call stack and breakpoint views.
+///
+/// Symbol files contain one label per line, a name and a hex or decimal address in either
+/// order and optionally separated by `=`, e.g. `main 0x202`, `0x202 main` or `main = 514`.
+/// Octo's `:const` declarations are read as well. Alternatively a JSON object mapping the
+/// names to the addresses, as exported by Octo, is accepted.
+/// Empty lines and lines starting with `#` or `;` are ignored.
+#[derive(Default)]
+pub struct Symbols {
+    labels: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub const EXTENSION: &'static str = "sym";
+
+    /// Returns the symbol file next to the ROM, if there is one.
+    pub fn find(rom_path: &Path) -> Option<PathBuf> {
+        Some(rom_path.with_extension(Self::EXTENSION)).filter(|path| path.exists())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        if content.trim_start().starts_with('{') {
+            return Self::parse_json(content);
+        }
+        let mut symbols = Self::default();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let invalid = || format!("invalid line {}: '{}'", idx + 1, line);
+            let line = line.strip_prefix(":const").unwrap_or(line);
+            let parts: Vec<_> = line
+                .split(|c: char| c.is_whitespace() || c == '=')
+                .filter(|part| !part.is_empty())
+                .collect();
+            let (name, address) = match parts.as_slice() {
+                [first, second] => match (parse_address(first), parse_address(second)) {
+                    (Some(address), None) => (*second, address),
+                    (_, Some(address)) => (*first, address),
+                    (None, None) => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            };
+            symbols.insert(name, address);
+        }
+        Ok(symbols)
+    }
+
+    fn parse_json(content: &str) -> Result<Self, String> {
+        let labels: BTreeMap<String, u16> =
+            serde_json::from_str(content).map_err(|e| format!("invalid JSON: {}", e))?;
+        let mut symbols = Self::default();
+        for (name, address) in &labels {
+            symbols.insert(name, *address);
+        }
+        Ok(symbols)
+    }
+
+    /// Keeps the first label of an address, e.g. a routine's name rather than a later alias.
+    pub(crate) fn insert(&mut self, name: &str, address: u16) {
+        self.labels
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+    }
+
+    /// Returns the label at exactly this address.
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// Formats an address with its label, e.g. `0202 (main)`.
+    pub fn describe(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(label) => format!("{:04X} ({})", address, label),
+            None => format!("{:04X}", address),
+        }
+    }
+
+    /// Returns the address of a label.
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.labels
+            .iter()
+            .find(|(_, label)| label.as_str() == name)
+            .map(|(&address, _)| address)
+    }
+}
+
+/// Parses an address, which is hex if prefixed with `0x` or `$`, decimal otherwise.
+fn parse_address(value: &str) -> Option<u16> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .or_else(|| value.strip_prefix('$'))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod symbols_test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let symbols = Symbols::parse(
+            "# Symbols\nmain 0x202\n0x228 draw-player\nscore = 0x3A0\n:const lives 930\n\nalias 0x202\n",
+        )
+        .unwrap();
+        assert_eq!(symbols.label(0x202), Some("main"));
+        assert_eq!(symbols.label(0x228), Some("draw-player"));
+        assert_eq!(symbols.label(0x3A0), Some("score"));
+        assert_eq!(symbols.label(930), Some("lives"));
+        assert_eq!(symbols.address("draw-player"), Some(0x228));
+        assert_eq!(symbols.describe(0x202), "0202 (main)");
+        assert_eq!(symbols.describe(0x204), "0204");
+
+        let symbols = Symbols::parse("{\"main\": 514, \"loop\": 520}").unwrap();
+        assert_eq!(symbols.label(0x202), Some("main"));
+        assert_eq!(symbols.label(0x208), Some("loop"));
+
+        assert!(Symbols::parse("main").is_err());
+        assert!(Symbols::parse("main loop").is_err());
+        assert!(Symbols::parse("{\"main\": \"x\"}").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum VideoMode {
@@ -21,6 +22,15 @@ pub struct VideoMemory {
     vmem2: Box<[bool]>,
     pub video_mode: VideoMode,
     plane: Plane,
+    // Whether the screen changed since it was last taken, so unchanged frames needn't be rendered
+    #[serde(skip, default = "VideoMemory::dirty_default")]
+    dirty: bool,
+}
+
+impl Default for VideoMemory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VideoMemory {
@@ -37,9 +47,25 @@ impl VideoMemory {
             vmem2: vec![false; 128 * 64].into_boxed_slice(),
             video_mode: VideoMode::Default,
             plane: Plane::First,
+            dirty: true,
         }
     }
 
+    fn dirty_default() -> bool {
+        true
+    }
+
+    /// Returns whether the screen changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// Changes the video mode, which redraws the screen in the new size.
+    pub fn set_video_mode(&mut self, video_mode: VideoMode) {
+        self.dirty |= self.video_mode != video_mode;
+        self.video_mode = video_mode;
+    }
+
     pub fn select_plane(&mut self, plane: Plane) {
         self.plane = plane;
     }
@@ -66,15 +92,18 @@ impl VideoMemory {
         if index >= self.render_width() * self.render_height() {
             panic!("Index out of bounds");
         }
-        match plane {
-            Plane::None => (),
-            Plane::First => self.vmem1[index] = value,
-            Plane::Second => self.vmem2[index] = value,
+        let changed = match plane {
+            Plane::None => false,
+            Plane::First => std::mem::replace(&mut self.vmem1[index], value) != value,
+            Plane::Second => std::mem::replace(&mut self.vmem2[index], value) != value,
             Plane::Both => {
+                let changed = self.vmem1[index] != value || self.vmem2[index] != value;
                 self.vmem1[index] = value;
                 self.vmem2[index] = value;
+                changed
             }
-        }
+        };
+        self.dirty |= changed;
     }
 
     pub fn clear(&mut self) {
@@ -82,6 +111,7 @@ impl VideoMemory {
     }
 
     pub fn set_all(&mut self, value: bool) {
+        self.dirty |= self.plane != Plane::None;
         match self.plane {
             Plane::None => (),
             Plane::First => self.vmem1.iter_mut().for_each(|x| *x = value),
@@ -143,6 +173,35 @@ impl VideoMemory {
         }
     }
 
+    /// Returns the pixel at the render index, where bit 0 is the first plane and bit 1 the second plane.
+    pub fn pixel(&self, index: usize) -> u8 {
+        if index >= self.render_width() * self.render_height() {
+            panic!("Index out of bounds");
+        }
+        self.vmem1[index] as u8 | (self.vmem2[index] as u8) << 1
+    }
+
+    /// Returns the pixels of the screen in render size like `pixel`, read directly from the planes.
+    pub fn pixels(&self) -> impl ExactSizeIterator<Item = u8> + '_ {
+        self.pixel_range(0..self.render_width() * self.render_height())
+    }
+
+    /// Returns the pixels of a row in render size like `pixel`, read directly from the planes.
+    pub fn row(&self, y: usize) -> impl ExactSizeIterator<Item = u8> + '_ {
+        if y >= self.render_height() {
+            panic!("Index out of bounds");
+        }
+        let start = self.to_index(0, y);
+        self.pixel_range(start..start + self.render_width())
+    }
+
+    fn pixel_range(&self, range: Range<usize>) -> impl ExactSizeIterator<Item = u8> + '_ {
+        self.vmem1[range.clone()]
+            .iter()
+            .zip(&self.vmem2[range])
+            .map(|(&first, &second)| first as u8 | (second as u8) << 1)
+    }
+
     pub fn get_plane(&self, plane: Plane, x: usize, y: usize) -> bool {
         let (x, y) = if self.video_mode == VideoMode::Default {
             (x * 2, y * 2)
@@ -246,6 +305,7 @@ impl VideoMemory {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod video_memory_test {
     use super::*;
 
@@ -485,12 +545,12 @@ mod video_memory_test {
 
     #[test]
     fn test_planes() {
-        for plane in vec![Plane::None, Plane::First, Plane::Second, Plane::Both] {
+        for plane in [Plane::None, Plane::First, Plane::Second, Plane::Both] {
             let plane1 = plane == Plane::First || plane == Plane::Both;
             let plane2 = plane == Plane::Second || plane == Plane::Both;
 
             let mut vmem = VideoMemory::new();
-            vmem.select_plane(plane.clone());
+            vmem.select_plane(plane);
             assert_eq!(vmem.plane, plane);
 
             // Set all
@@ -586,4 +646,50 @@ mod video_memory_test {
             }
         }
     }
+
+    #[test]
+    fn test_dirty() {
+        let mut vmem = VideoMemory::new();
+        assert!(vmem.take_dirty());
+        assert!(!vmem.take_dirty());
+
+        vmem.set_plane(Plane::First, 3, 4, false);
+        assert!(!vmem.take_dirty());
+        vmem.set_plane(Plane::First, 3, 4, true);
+        assert!(vmem.take_dirty());
+        vmem.set_plane(Plane::Both, 3, 4, true);
+        assert!(vmem.take_dirty());
+
+        vmem.set_video_mode(VideoMode::Default);
+        assert!(!vmem.take_dirty());
+        vmem.set_video_mode(VideoMode::Extended);
+        assert!(vmem.take_dirty());
+
+        vmem.select_plane(Plane::None);
+        vmem.clear();
+        assert!(!vmem.take_dirty());
+        vmem.select_plane(Plane::Second);
+        vmem.clear();
+        assert!(vmem.take_dirty());
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut vmem = VideoMemory::new();
+        vmem.set_video_mode(VideoMode::HiRes);
+        vmem.set_plane(Plane::First, 1, 0, true);
+        vmem.set_plane(Plane::Second, 2, 0, true);
+        vmem.set_plane(Plane::Both, 3, 63, true);
+        assert_eq!(vmem.pixel(1), 1);
+        assert_eq!(vmem.pixel(2), 2);
+        assert_eq!(vmem.pixel(vmem.to_index(3, 63)), 3);
+        assert_eq!(vmem.pixels().len(), 64 * 64);
+        assert!(vmem
+            .pixels()
+            .enumerate()
+            .all(|(idx, pixel)| pixel == vmem.pixel(idx)));
+        assert_eq!(vmem.row(0).take(4).collect::<Vec<_>>(), [0, 1, 2, 0]);
+        assert_eq!(vmem.row(63).len(), 64);
+        assert_eq!(vmem.row(63).nth(3), Some(3));
+    }
 }
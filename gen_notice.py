@@ -1,6 +1,5 @@
 import subprocess
 import json
-import toml
 import requests
 import asyncio
 from os import path
@@ -17,15 +16,15 @@ class NoticeGenerator:
         loop.close()
 
     async def main(self):
-        package_name = self.get_package_name()
-
         notices = HEADER
-        metadata = subprocess.check_output('cargo metadata --format-version 1')
-        packages = json.loads(metadata)['packages']
+        metadata = json.loads(subprocess.check_output('cargo metadata --format-version 1'))
+        packages = metadata['packages']
+        # The pich8 library and binary themselves
+        workspace_members = set(metadata['workspace_members'])
 
         tasks = []
         for pkg in packages:
-            if pkg['name'] != package_name:
+            if pkg['id'] not in workspace_members:
                 tasks.append(asyncio.create_task(self.get_license(pkg)))
 
         results = await asyncio.gather(*tasks)
@@ -36,15 +35,6 @@ class NoticeGenerator:
             f.write(notices.strip())
 
 
-    def get_package_name(self):
-        if not path.exists('Cargo.toml'):
-            raise Exception('Cargo.toml not found')
-        with open('Cargo.toml') as f:
-            cargo_toml = f.read()
-        t = toml.loads(cargo_toml)
-        return t['package']['name']
-
-
     async def get_license(self, pkg):
         notice = ''
 
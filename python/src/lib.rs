@@ -0,0 +1,172 @@
+//! Python bindings of the pich8 emulation core, built with `maturin build --release` in this directory.
+//!
+//! ```python
+//! import numpy as np
+//! import pich8
+//!
+//! emu = pich8.Emulator(speed=720)
+//! emu.load_rom(open("rom.ch8", "rb").read())
+//! emu.press(5)
+//! emu.run_frame()
+//! screen = np.frombuffer(emu.framebuffer(), dtype=np.uint8).reshape(emu.height, emu.width)
+//! ```
+#![cfg(feature = "python")]
+
+use pich8::cpu::CPU;
+use pich8::rom;
+use pich8::runner::Runner;
+use pich8::video_memory::Plane;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// A CHIP-8, SUPER-CHIP and XO-CHIP emulator running the given number of instructions per second.
+/// A frame consists of the instructions executed within 1/60 s, followed by a timer update.
+#[pyclass(module = "pich8")]
+pub struct Emulator {
+    cpu: CPU,
+    runner: Runner,
+    keys: [bool; 16],
+}
+
+impl Emulator {
+    fn check_key(key: usize) -> PyResult<usize> {
+        if key < 16 {
+            Ok(key)
+        } else {
+            Err(PyValueError::new_err(format!("Invalid key {}", key)))
+        }
+    }
+
+    fn pixels(&self) -> Vec<u8> {
+        let vmem = self.cpu.vmem();
+        (0..vmem.render_width() * vmem.render_height())
+            .map(|idx| {
+                vmem.get_index_plane(Plane::First, idx) as u8
+                    | (vmem.get_index_plane(Plane::Second, idx) as u8) << 1
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl Emulator {
+    #[new]
+    #[pyo3(signature = (speed = 720))]
+    fn new(speed: u32) -> Self {
+        Self {
+            cpu: CPU::new(),
+            runner: Runner::new(speed),
+            keys: [false; 16],
+        }
+    }
+
+    /// Resets the CPU and loads a ROM, raises ValueError for invalid ROMs.
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        rom::validate(rom).map_err(PyValueError::new_err)?;
+        let mut cpu = CPU::new();
+        cpu.load_rom(rom).map_err(PyValueError::new_err)?;
+        self.cpu = cpu;
+        Ok(())
+    }
+
+    /// Makes the random numbers reproducible, e.g. for training runs.
+    fn seed(&mut self, seed: u64) {
+        self.cpu.seed_rng(seed);
+    }
+
+    /// Like `Runner::step`.
+    fn tick(&mut self) -> PyResult<bool> {
+        self.runner
+            .step(&mut self.cpu, &self.keys)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Like `Runner::run_frame`.
+    fn run_frame(&mut self) -> PyResult<()> {
+        self.runner
+            .run_frame(&mut self.cpu, &self.keys)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    fn press(&mut self, key: usize) -> PyResult<()> {
+        self.keys[Self::check_key(key)?] = true;
+        Ok(())
+    }
+
+    fn release(&mut self, key: usize) -> PyResult<()> {
+        self.keys[Self::check_key(key)?] = false;
+        Ok(())
+    }
+
+    /// The pressed keys as bitmask, bit 0 is key 0 and bit 15 key F.
+    #[getter]
+    fn keys(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (idx, &pressed)| mask | (pressed as u16) << idx)
+    }
+
+    #[setter]
+    fn set_keys(&mut self, keys: u16) {
+        for (idx, key) in self.keys.iter_mut().enumerate() {
+            *key = keys >> idx & 1 == 1;
+        }
+    }
+
+    /// Returns the screen row by row, one byte per pixel as in `VideoMemory::pixel`.
+    fn framebuffer<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.pixels())
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.cpu.vmem().render_width()
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.cpu.vmem().render_height()
+    }
+
+    /// The number of completed frames.
+    #[getter]
+    fn frame(&self) -> u64 {
+        self.runner.frame()
+    }
+
+    /// Whether the sound timer is running, so a tone should be played.
+    #[getter]
+    fn sound_active(&self) -> bool {
+        self.cpu.ST() > 0
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "pich8")]
+fn pich8_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Emulator>()
+}
+
+#[cfg(test)]
+mod python_test {
+    use super::*;
+
+    #[test]
+    fn test_emulator() {
+        let mut emu = Emulator::new(600);
+        assert!(emu.load_rom(&[]).is_err());
+        // Draws the font sprite of key 0 while it's pressed, then jumps back
+        emu.load_rom(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x00])
+            .unwrap();
+        assert!(emu.press(16).is_err());
+        emu.set_keys(0b1);
+        assert_eq!(emu.keys(), 1);
+        emu.run_frame().unwrap();
+        emu.run_frame().unwrap();
+        assert_eq!(emu.frame(), 2);
+        assert_eq!((emu.width(), emu.height()), (128, 64));
+        assert!(emu.pixels().contains(&1));
+    }
+}
@@ -0,0 +1,447 @@
+//! A libretro core running pich8 inside RetroArch and other libretro frontends.
+//!
+//! The CHIP-8 keys are mapped to the same keyboard keys as in the desktop version, and the
+//! joypad's d-pad to 5, 7, 8 and 9 with A as 6 and B as 4, the keys most games move and act with.
+mod ffi;
+
+use ffi::*;
+use pich8::cpu::CPU;
+use pich8::rom;
+use pich8::runner::Runner;
+use pich8::video_memory::{Plane, VideoMemory};
+use std::os::raw::{c_char, c_uint, c_void};
+use std::sync::Mutex;
+use std::{ptr, slice};
+
+/// The libretro keycodes of the CHIP-8 keys 0 - F, the same physical keys as in the desktop version
+const KEYBOARD: [c_uint; 16] = [
+    b'x' as c_uint,
+    b'1' as c_uint,
+    b'2' as c_uint,
+    b'3' as c_uint,
+    b'q' as c_uint,
+    b'w' as c_uint,
+    b'e' as c_uint,
+    b'a' as c_uint,
+    b's' as c_uint,
+    b'd' as c_uint,
+    b'z' as c_uint,
+    b'c' as c_uint,
+    b'4' as c_uint,
+    b'r' as c_uint,
+    b'f' as c_uint,
+    b'v' as c_uint,
+];
+/// The joypad buttons and the CHIP-8 keys they press
+const JOYPAD: [(c_uint, usize); 6] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x5),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x7),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x9),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x6),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x4),
+];
+
+/// The callbacks the frontend registered.
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+/// The loaded game with the video and audio of the last frame.
+struct Core {
+    cpu: CPU,
+    runner: Runner,
+    rom: Vec<u8>,
+    /// XRGB8888 pixels in the render resolution
+    frame_buffer: Vec<u32>,
+    /// Interleaved stereo samples
+    audio: Vec<i16>,
+    /// Audio samples played since the game was loaded, the position of the waveforms
+    sample: u64,
+}
+
+impl Core {
+    const SPEED: u32 = 720;
+    const SAMPLE_RATE: u32 = 44100;
+    const BEEP_FREQ: u32 = 440;
+    /// The rate at which XO-CHIP plays the bits of its audio pattern
+    const BUF_FREQ: u32 = 4000;
+    const VOLUME: i16 = i16::MAX / 8;
+    /// Background, first plane, second plane and both planes
+    const COLORS: [u32; 4] = [0x000000, 0xFFFFFF, 0x555555, 0xAAAAAA];
+    /// The buffer size RetroArch reserves for a state, larger than any serialized CPU
+    const STATE_SIZE: usize = 256 * 1024;
+
+    fn new(rom: &[u8]) -> Result<Self, String> {
+        rom::validate(rom)?;
+        let mut core = Self {
+            cpu: CPU::new(),
+            runner: Runner::new(Self::SPEED),
+            rom: rom.to_vec(),
+            frame_buffer: Vec::new(),
+            audio: Vec::new(),
+            sample: 0,
+        };
+        core.reset()?;
+        Ok(core)
+    }
+
+    fn reset(&mut self) -> Result<(), String> {
+        self.cpu = CPU::new();
+        self.runner = Runner::new(Self::SPEED);
+        self.cpu.load_rom(&self.rom)
+    }
+
+    fn run_frame(&mut self, keys: &[bool; 16]) {
+        if let Err(e) = self.runner.run_frame(&mut self.cpu, keys) {
+            // There's no way to report errors, a crashed game keeps showing its last frame
+            eprintln!("pich8: {}", e);
+        }
+        self.render();
+        self.mix_audio();
+    }
+
+    fn render(&mut self) {
+        let vmem = self.cpu.vmem();
+        self.frame_buffer.clear();
+        self.frame_buffer.extend(
+            (0..vmem.render_width() * vmem.render_height()).map(|idx| Self::color(vmem, idx)),
+        );
+    }
+
+    fn color(vmem: &VideoMemory, idx: usize) -> u32 {
+        match (
+            vmem.get_index_plane(Plane::First, idx),
+            vmem.get_index_plane(Plane::Second, idx),
+        ) {
+            (true, true) => Self::COLORS[3],
+            (true, false) => Self::COLORS[1],
+            (false, true) => Self::COLORS[2],
+            (false, false) => Self::COLORS[0],
+        }
+    }
+
+    /// Fills the audio of a frame with the XO-CHIP pattern or a beep while the sound timer runs.
+    fn mix_audio(&mut self) {
+        let samples = (Self::SAMPLE_RATE / Runner::FRAMES_PER_SECOND) as u64;
+        let playing = self.cpu.ST() > 0;
+        let pattern = self.cpu.audio_buffer();
+        self.audio.clear();
+        for sample in self.sample..self.sample + samples {
+            let high = match pattern {
+                Some(pattern) => {
+                    let bit = (sample * Self::BUF_FREQ as u64 / Self::SAMPLE_RATE as u64) % 128;
+                    pattern[bit as usize / 8] >> (7 - bit % 8) & 1 == 1
+                }
+                None => (sample * Self::BEEP_FREQ as u64 * 2 / Self::SAMPLE_RATE as u64)
+                    .is_multiple_of(2),
+            };
+            let value = match (playing, high) {
+                (false, _) => 0,
+                (true, true) => Self::VOLUME,
+                (true, false) => -Self::VOLUME,
+            };
+            self.audio.extend_from_slice(&[value, value]);
+        }
+        self.sample += samples;
+    }
+
+    /// Writes the state prefixed with its length, as the buffer is larger than the state.
+    fn serialize(&self, data: &mut [u8]) -> Result<(), String> {
+        let state = self.cpu.save_state()?;
+        if state.len() + 4 > data.len() {
+            return Err("The state doesn't fit into the buffer".to_string());
+        }
+        data[..4].copy_from_slice(&(state.len() as u32).to_le_bytes());
+        data[4..4 + state.len()].copy_from_slice(&state);
+        data[4 + state.len()..].fill(0);
+        Ok(())
+    }
+
+    fn unserialize(&mut self, data: &[u8]) -> Result<(), String> {
+        let invalid = || "Invalid state".to_string();
+        let len = data.get(..4).ok_or_else(invalid)?;
+        let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let state = data.get(4..4 + len).ok_or_else(invalid)?;
+        self.cpu = CPU::from_state(state)?;
+        Ok(())
+    }
+}
+
+fn callbacks() -> std::sync::MutexGuard<'static, Callbacks> {
+    CALLBACKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn core() -> std::sync::MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+/// # Safety
+/// `info` must point to a `retro_system_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: "pich8\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: "ch8|c8|sc8|xo8|hc8|ch10\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` must point to a `retro_system_av_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let vmem = VideoMemory::new();
+    let (width, height) = (
+        vmem.render_width() as c_uint,
+        vmem.render_height() as c_uint,
+    );
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: width,
+            base_height: height,
+            max_width: width,
+            max_height: height,
+            aspect_ratio: width as f32 / height as f32,
+        },
+        timing: SystemTiming {
+            fps: Runner::FRAMES_PER_SECOND as f64,
+            sample_rate: Core::SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    callbacks().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    callbacks().video_refresh = Some(callback);
+}
+
+/// Unused, the audio of a frame is passed at once with `retro_set_audio_sample_batch`'s callback.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    callbacks().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    callbacks().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    callbacks().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut() {
+        if let Err(msg) = core.reset() {
+            eprintln!("pich8: {}", msg);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let (video_refresh, audio_sample_batch, input_poll, input_state) = {
+        let callbacks = callbacks();
+        (
+            callbacks.video_refresh,
+            callbacks.audio_sample_batch,
+            callbacks.input_poll,
+            callbacks.input_state,
+        )
+    };
+    let mut keys = [false; 16];
+    if let (Some(input_poll), Some(input_state)) = (input_poll, input_state) {
+        unsafe {
+            input_poll();
+            for (key, &keycode) in KEYBOARD.iter().enumerate() {
+                keys[key] = input_state(0, RETRO_DEVICE_KEYBOARD, 0, keycode) != 0;
+            }
+            for &(button, key) in JOYPAD.iter() {
+                keys[key] |= input_state(0, RETRO_DEVICE_JOYPAD, 0, button) != 0;
+            }
+        }
+    }
+
+    let mut core = core();
+    let core = match core.as_mut() {
+        Some(core) => core,
+        None => return,
+    };
+    core.run_frame(&keys);
+    let vmem = core.cpu.vmem();
+    let (width, height) = (vmem.render_width(), vmem.render_height());
+    unsafe {
+        if let Some(video_refresh) = video_refresh {
+            video_refresh(
+                core.frame_buffer.as_ptr() as *const c_void,
+                width as c_uint,
+                height as c_uint,
+                width * 4,
+            );
+        }
+        if let Some(audio_sample_batch) = audio_sample_batch {
+            audio_sample_batch(core.audio.as_ptr(), core.audio.len() / 2);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    Core::STATE_SIZE
+}
+
+/// # Safety
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    match core().as_ref() {
+        Some(core) => core
+            .serialize(slice::from_raw_parts_mut(data as *mut u8, size))
+            .is_ok(),
+        None => false,
+    }
+}
+
+/// # Safety
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    match core().as_mut() {
+        Some(core) => core
+            .unserialize(slice::from_raw_parts(data as *const u8, size))
+            .is_ok(),
+        None => false,
+    }
+}
+
+/// Cheat codes aren't supported, pich8's cheat files are only read by the desktop version.
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// `game` must be null or point to a `retro_game_info` with `size` bytes of `data`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let game = match game.as_ref() {
+        Some(game) if !game.data.is_null() => game,
+        _ => return false,
+    };
+    let environment = callbacks().environment;
+    if let Some(environment) = environment {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        ) {
+            eprintln!("pich8: The frontend doesn't support XRGB8888");
+            return false;
+        }
+    }
+    match Core::new(slice::from_raw_parts(game.data as *const u8, game.size)) {
+        Ok(loaded) => {
+            *core() = Some(loaded);
+            true
+        }
+        Err(msg) => {
+            eprintln!("pich8: {}", msg);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod libretro_test {
+    use super::*;
+
+    #[test]
+    fn test_core() {
+        // Starts the sound timer and jumps to itself
+        let mut core = Core::new(&[0x60, 0x10, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        core.run_frame(&[false; 16]);
+        assert_eq!(core.frame_buffer.len(), 128 * 64);
+        assert_eq!(core.audio.len(), 735 * 2);
+        assert!(core.audio.iter().any(|&sample| sample != 0));
+
+        let mut state = vec![0xFF; Core::STATE_SIZE];
+        core.serialize(&mut state).unwrap();
+        let st = core.cpu.ST();
+        core.run_frame(&[false; 16]);
+        core.unserialize(&state).unwrap();
+        assert_eq!(core.cpu.ST(), st);
+        assert!(core.unserialize(&[1, 0]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Where screenshots, input recordings and states are saved and how they're named.
+///
+/// File names are templates which can contain `{rom}` (file name of the ROM
+/// without extension), `{hash}` (SHA-1 of the ROM), `{timestamp}` and, for
+/// state slots, `{slot}`. A directory of `None` means the default location.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub screenshot_dir: Option<PathBuf>,
+    pub screenshot_name: String,
+    pub recording_dir: Option<PathBuf>,
+    pub recording_name: String,
+    pub state_dir: Option<PathBuf>,
+    pub state_name: String,
+    pub slot_name: String,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            screenshot_dir: None,
+            screenshot_name: "{rom}-{timestamp}.png".to_string(),
+            recording_dir: None,
+            recording_name: "{rom}-{timestamp}.txt".to_string(),
+            state_dir: None,
+            state_name: "{rom}-{timestamp}.p8s".to_string(),
+            slot_name: "{hash}/slot{slot}.p8s".to_string(),
+        }
+    }
+}
+
+/// The values filled into the file name templates.
+pub struct CaptureInfo<'a> {
+    pub rom_name: Option<&'a str>,
+    pub rom_hash: Option<&'a str>,
+    pub slot: Option<usize>,
+}
+
+impl CaptureConfig {
+    const TIMESTAMP_FORMAT: &'static str = "%Y-%m-%d_%H-%M-%S";
+
+    pub fn screenshot_path(&self, info: &CaptureInfo) -> PathBuf {
+        let dir = self
+            .screenshot_dir
+            .clone()
+            .or_else(|| dirs::picture_dir().map(|dir| dir.join(env!("CARGO_CRATE_NAME"))));
+        Self::path(dir, &self.screenshot_name, info)
+    }
+
+    pub fn recording_path(&self, info: &CaptureInfo) -> PathBuf {
+        Self::path(
+            self.recording_dir
+                .clone()
+                .or_else(|| Self::data_dir("recordings")),
+            &self.recording_name,
+            info,
+        )
+    }
+
+    pub fn state_path(&self, info: &CaptureInfo) -> PathBuf {
+        Self::path(self.state_dir(), &self.state_name, info)
+    }
+
+    pub fn slot_path(&self, info: &CaptureInfo) -> PathBuf {
+        Self::path(self.state_dir(), &self.slot_name, info)
+    }
+
+    /// The directory of state files, which also holds the autosaves.
+    pub fn state_dir(&self) -> Option<PathBuf> {
+        self.state_dir.clone().or_else(|| Self::data_dir("states"))
+    }
+
+    fn data_dir(name: &str) -> Option<PathBuf> {
+        Config::data_dir().map(|dir| dir.join(name))
+    }
+
+    /// Joins the directory and the expanded template, falling back to the working directory.
+    fn path(dir: Option<PathBuf>, template: &str, info: &CaptureInfo) -> PathBuf {
+        let timestamp = chrono::Local::now()
+            .format(Self::TIMESTAMP_FORMAT)
+            .to_string();
+        dir.unwrap_or_default()
+            .join(Self::expand(template, info, &timestamp))
+    }
+
+    fn expand(template: &str, info: &CaptureInfo, timestamp: &str) -> String {
+        template
+            .replace("{rom}", info.rom_name.unwrap_or(env!("CARGO_CRATE_NAME")))
+            .replace("{hash}", info.rom_hash.unwrap_or("unknown"))
+            .replace(
+                "{slot}",
+                &info.slot.map_or(String::new(), |slot| slot.to_string()),
+            )
+            .replace("{timestamp}", timestamp)
+    }
+}
+
+#[cfg(test)]
+mod capture_test {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let info = CaptureInfo {
+            rom_name: Some("blinky"),
+            rom_hash: Some("abc"),
+            slot: Some(3),
+        };
+        assert_eq!(
+            CaptureConfig::expand("{rom}/{hash}-{slot}_{timestamp}.p8s", &info, "now"),
+            "blinky/abc-3_now.p8s"
+        );
+        let info = CaptureInfo {
+            rom_name: None,
+            rom_hash: None,
+            slot: None,
+        };
+        assert_eq!(
+            CaptureConfig::expand("{rom}-{hash}{slot}", &info, "now"),
+            "pich8-unknown"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::Config;
+use pich8::cpu::CPU;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A cheat writing values into memory, either once when it's enabled (poke)
+/// or after every frame (freeze), e.g. to keep the number of lives constant.
+#[derive(Clone)]
+pub struct Cheat {
+    pub name: String,
+    pub freeze: bool,
+    pub writes: Vec<(u16, u8)>,
+    pub enabled: bool,
+    pending: bool,
+}
+
+/// The cheats of a game.
+///
+/// Cheat files contain one cheat per line, a name followed by `freeze` or `poke`
+/// and the memory writes as hex `address=value` pairs, e.g.
+/// `Infinite lives: freeze 3e8=03`. A `+` in front of the name enables the cheat
+/// right away. Empty lines and lines starting with `#` are ignored.
+#[derive(Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub const EXTENSION: &'static str = "cht";
+    const DIR: &'static str = "cheats";
+
+    /// Returns the cheat file of a ROM, which is either stored next to the ROM
+    /// or named by its hash in the cheats directory.
+    pub fn find(rom_path: Option<&Path>, rom_hash: &str) -> Option<PathBuf> {
+        rom_path
+            .map(|path| path.with_extension(Self::EXTENSION))
+            .filter(|path| path.exists())
+            .or_else(|| {
+                Config::data_dir()
+                    .map(|dir| {
+                        dir.join(Self::DIR)
+                            .join(rom_hash)
+                            .with_extension(Self::EXTENSION)
+                    })
+                    .filter(|path| path.exists())
+            })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut cheats = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("invalid line {}: '{}'", idx + 1, line);
+            let (name, definition) = line.rsplit_once(':').ok_or_else(invalid)?;
+            let (name, enabled) = match name.trim().strip_prefix('+') {
+                Some(name) => (name.trim(), true),
+                None => (name.trim(), false),
+            };
+            let mut parts = definition.split_whitespace();
+            let freeze = match parts.next() {
+                Some("freeze") => true,
+                Some("poke") => false,
+                _ => return Err(invalid()),
+            };
+            let writes = parts
+                .map(|write| {
+                    let (address, value) = write.split_once('=')?;
+                    let address = u16::from_str_radix(address.trim_start_matches("0x"), 16);
+                    let value = u8::from_str_radix(value.trim_start_matches("0x"), 16);
+                    Some((address.ok()?, value.ok()?))
+                })
+                .collect::<Option<Vec<_>>>()
+                .filter(|writes| !writes.is_empty() && !name.is_empty())
+                .ok_or_else(invalid)?;
+            cheats.push(Cheat {
+                name: name.to_string(),
+                freeze,
+                writes,
+                enabled,
+                pending: enabled,
+            });
+        }
+        Ok(Self { cheats })
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn set_enabled(&mut self, idx: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.get_mut(idx) {
+            cheat.enabled = enabled;
+            cheat.pending = enabled;
+        }
+    }
+
+    /// Enables the cheat with the given name (ignoring case), returns false if there's none.
+    pub fn enable(&mut self, name: &str) -> bool {
+        match self
+            .cheats
+            .iter()
+            .position(|cheat| cheat.name.eq_ignore_ascii_case(name))
+        {
+            Some(idx) => {
+                self.set_enabled(idx, true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the enabled cheats after a reset, e.g. pokes have to be written again.
+    pub fn restart(&mut self) {
+        for cheat in &mut self.cheats {
+            cheat.pending = cheat.enabled;
+        }
+    }
+
+    /// Writes the frozen values and pending pokes into memory, called after every frame.
+    pub fn apply(&mut self, cpu: &mut CPU) {
+        for cheat in self.cheats.iter_mut().filter(|cheat| cheat.pending) {
+            for &(address, value) in &cheat.writes {
+                cpu.write_mem(address, value);
+            }
+            cheat.pending = cheat.freeze;
+        }
+    }
+}
+
+#[cfg(test)]
+mod cheats_test {
+    use super::*;
+
+    #[test]
+    fn test_cheats() {
+        let mut cheats = Cheats::parse(
+            "# BLINKY\n+Infinite lives: freeze 0x3e8=03\nSkip level: poke 2a0=01 2a1=ff\n",
+        )
+        .unwrap();
+        assert_eq!(cheats.cheats().len(), 2);
+        assert!(cheats.cheats()[0].enabled);
+        assert_eq!(
+            cheats.cheats()[1].writes,
+            vec![(0x2a0, 0x01), (0x2a1, 0xff)]
+        );
+
+        let mut cpu = CPU::new();
+        assert!(cheats.enable("skip level"));
+        assert!(!cheats.enable("missing"));
+        cheats.apply(&mut cpu);
+        assert_eq!(cpu.read_mem(0x3e8), 0x03);
+        assert_eq!(cpu.read_mem(0x2a1), 0xff);
+
+        // Pokes are written once, freezes every frame
+        cpu.write_mem(0x3e8, 0);
+        cpu.write_mem(0x2a1, 0);
+        cheats.apply(&mut cpu);
+        assert_eq!(cpu.read_mem(0x3e8), 0x03);
+        assert_eq!(cpu.read_mem(0x2a1), 0);
+
+        assert!(Cheats::parse("Lives: freeze").is_err());
+        assert!(Cheats::parse("Lives: set 3e8=03").is_err());
+        assert!(Cheats::parse("Lives: poke 3e8=fff").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::config::{Palette, PaletteConfig, Quirks, QuirksConfig, Settings};
+use log::warn;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+/// Metadata of a program in the chip8Archive (https://github.com/JohnEarnest/chip8Archive),
+/// as listed in its `programs.json`.
+#[derive(Clone, Deserialize)]
+pub struct ProgramInfo {
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    pub event: Option<String>,
+    pub release: Option<String>,
+    pub desc: Option<String>,
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub options: ProgramOptions,
+}
+
+/// The options the program is meant to be run with in Octo.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProgramOptions {
+    pub tickrate: Option<u32>,
+    pub fill_color: Option<String>,
+    pub fill_color2: Option<String>,
+    pub blend_color: Option<String>,
+    pub background_color: Option<String>,
+    pub shift_quirks: bool,
+    pub load_store_quirks: bool,
+    pub vf_order_quirks: bool,
+    pub clip_quirks: bool,
+    pub jump_quirks: bool,
+}
+
+impl ProgramInfo {
+    pub const FILE_NAME: &'static str = "programs.json";
+
+    #[cfg(feature = "rom-download")]
+    pub const INDEX_URL: &'static str =
+        "https://raw.githubusercontent.com/JohnEarnest/chip8Archive/master/programs.json";
+
+    /// Returns the URL of the ROM with the given key in `programs.json`.
+    #[cfg(feature = "rom-download")]
+    pub fn rom_url(key: &str) -> String {
+        format!(
+            "https://raw.githubusercontent.com/JohnEarnest/chip8Archive/master/roms/{}.ch8",
+            key
+        )
+    }
+
+    /// Parses the content of `programs.json`, the programs are keyed by the ROM's file name without extension.
+    /// Programs whose key isn't a plain file name are left out, since downloaded ROMs are saved by their key.
+    pub fn parse_index(content: &str) -> Result<HashMap<String, ProgramInfo>, String> {
+        let mut programs: HashMap<String, ProgramInfo> =
+            serde_json::from_str(content).map_err(|e| e.to_string())?;
+        programs.retain(|key, _| {
+            let valid = Self::is_valid_key(key);
+            if !valid {
+                warn!("Ignoring the program with the invalid key '{}'", key);
+            }
+            valid
+        });
+        Ok(programs)
+    }
+
+    /// Returns whether the key only consists of ASCII letters, digits, `_` and `-`, so it can't
+    /// name a path outside the ROM directory.
+    pub fn is_valid_key(key: &str) -> bool {
+        !key.is_empty()
+            && key
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+    }
+
+    /// Looks up the metadata of the given ROM file.
+    /// The archive stores ROMs as `roms/<name>.ch8` next to `programs.json`, so we check the directory of the ROM and its parent.
+    pub fn find(rom_path: &Path) -> Option<Self> {
+        let name = rom_path.file_stem()?.to_str()?;
+        let dir = rom_path.parent()?;
+        [Some(dir), dir.parent()]
+            .iter()
+            .flatten()
+            .map(|dir| dir.join(Self::FILE_NAME))
+            .find(|path| path.exists())
+            .and_then(|path| {
+                let content = fs::read_to_string(&path).ok()?;
+                match Self::parse_index(&content) {
+                    Ok(mut programs) => programs.remove(name),
+                    Err(e) => {
+                        warn!("Failed to parse {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            })
+    }
+
+    /// Returns the recommended settings for this program.
+    pub fn settings(&self) -> Settings {
+        let options = &self.options;
+
+        // Octo's quirk flags partially have the opposite meaning of ours
+        let quirks = Quirks {
+            load_store: options.load_store_quirks,
+            shift: options.shift_quirks,
+            draw: true,
+            jump: options.jump_quirks,
+            vf_order: !options.vf_order_quirks,
+            partial_wrap_h: !options.clip_quirks,
+            partial_wrap_v: !options.clip_quirks,
+        };
+
+        let color = |color: &Option<String>| color.as_deref().and_then(|c| c.parse().ok());
+        let palette = match (
+            color(&options.background_color),
+            color(&options.fill_color),
+            color(&options.fill_color2),
+            color(&options.blend_color),
+        ) {
+            (Some(background), Some(plane_1), Some(plane_2), Some(plane_both)) => {
+                Some(PaletteConfig::Custom(Palette {
+                    background,
+                    plane_1,
+                    plane_2,
+                    plane_both,
+                }))
+            }
+            _ => None,
+        };
+
+        Settings {
+            // The tickrate is given in instructions per frame
+            speed: options.tickrate.map(|tickrate| tickrate * 60),
+            quirks: Some(QuirksConfig::Custom(quirks)),
+            palette,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod chip8_archive_test {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let programs: HashMap<String, ProgramInfo> = serde_json::from_str(
+            r##"{
+                "test": {
+                    "title": "Test",
+                    "authors": ["Someone"],
+                    "platform": "xochip",
+                    "options": {
+                        "tickrate": 100,
+                        "fillColor": "#FFCC00",
+                        "fillColor2": "#FF6600",
+                        "blendColor": "#662200",
+                        "backgroundColor": "#996600",
+                        "shiftQuirks": true,
+                        "clipQuirks": true,
+                        "screenRotation": 0
+                    }
+                }
+            }"##,
+        )
+        .unwrap();
+        let settings = programs["test"].settings();
+        assert_eq!(settings.speed, Some(6000));
+        match settings.quirks {
+            Some(QuirksConfig::Custom(quirks)) => {
+                assert!(quirks.shift);
+                assert!(!quirks.load_store);
+                assert!(quirks.vf_order);
+                assert!(!quirks.partial_wrap_h);
+            }
+            _ => panic!("expected custom quirks"),
+        }
+        match settings.palette {
+            Some(PaletteConfig::Custom(palette)) => assert_eq!(palette.plane_1.0[0], 1.0),
+            _ => panic!("expected custom palette"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let programs = ProgramInfo::parse_index(
+            r#"{
+                "good_rom-2": {"title": "Good"},
+                "../../.bashrc": {"title": "Evil"},
+                "roms/evil": {"title": "Evil"},
+                "": {"title": "Empty"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(programs.keys().collect::<Vec<_>>(), ["good_rom-2"]);
+        assert!(!ProgramInfo::is_valid_key("..\\evil"));
+    }
+}
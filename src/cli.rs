@@ -0,0 +1,373 @@
+use crate::config::{PaletteConfig, QuirksConfig, Settings};
+use crate::frame_limiter::FramePacing;
+use crate::gui::{ColorPreset, QuirksPreset};
+use clap::{ArgAction, ArgGroup, Parser, Subcommand};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+#[command(group(ArgGroup::new("realtime").args(["stream", "fbdev"])))]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// ROM or state file to load on startup, multiple files or a directory form a playlist
+    #[arg(value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+
+    /// Open the N-th most recently opened file (defaults to the last one)
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "1", conflicts_with = "files")]
+    pub recent: Option<usize>,
+
+    /// State file to resume from, together with a ROM the ROM's settings and save slots are used
+    #[arg(long, value_name = "FILE")]
+    pub state: Option<PathBuf>,
+
+    /// IPS or BPS patch to apply to the ROM when loading it
+    #[arg(long, value_name = "FILE", requires = "files")]
+    pub patch: Option<PathBuf>,
+
+    /// Enable the cheat with the given name from the ROM's cheat file, can be repeated
+    #[arg(long = "cheat", value_name = "NAME", requires = "files")]
+    pub cheats: Vec<String>,
+
+    /// Rhai script to run, e.g. to automate tests or as a trainer
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Publish every changed frame into the file for capture tools, e.g. /dev/shm/pich8-frame
+    #[arg(long, value_name = "PATH")]
+    pub export_frames: Option<PathBuf>,
+
+    /// Load an output plugin, e.g. for an LED matrix, given as PATH or PATH=ARGS, can be repeated
+    #[cfg(feature = "plugins")]
+    #[arg(long = "plugin", value_name = "PATH[=ARGS]")]
+    pub plugins: Vec<String>,
+
+    /// Accept remote control commands over WebSocket on the port of localhost
+    #[cfg(feature = "remote")]
+    #[arg(long, value_name = "PORT")]
+    pub remote: Option<u16>,
+
+    /// Accept JSON-RPC commands on the unix socket, e.g. from editor plugins and build scripts
+    #[cfg(all(feature = "remote", unix))]
+    #[arg(long, value_name = "PATH")]
+    pub rpc: Option<PathBuf>,
+
+    /// Read commands like `pause` or `load rom.ch8` line by line from the named pipe, which is created if needed
+    #[cfg(all(feature = "remote", unix))]
+    #[arg(long, value_name = "PATH")]
+    pub control_pipe: Option<PathBuf>,
+
+    /// Host a netplay session on the port of all network interfaces, sharing the keypad with the player connecting to it
+    #[arg(long, value_name = "PORT", requires = "files")]
+    pub netplay_host: Option<u16>,
+
+    /// Join the netplay session hosted at the address, e.g. 192.168.0.2:7700
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        requires = "files",
+        conflicts_with = "netplay_host"
+    )]
+    pub netplay_connect: Option<String>,
+
+    /// Play back an input recording in the window, e.g. to watch a recorded run
+    #[arg(
+        long,
+        value_name = "RECORDING",
+        requires = "files",
+        conflicts_with_all = ["replay", "netplay_host", "netplay_connect"]
+    )]
+    pub play: Option<PathBuf>,
+
+    /// Switch to the next ROM of the playlist after the given number of seconds
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub cycle: Option<u64>,
+
+    /// Path of the config file to use instead of the default location
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Turn on vsync, same as --frame-pacing vsync
+    #[arg(long, conflicts_with = "frame_pacing")]
+    pub vsync: bool,
+
+    /// How to wait for the next frame
+    #[arg(long, value_name = "STRATEGY")]
+    pub frame_pacing: Option<FramePacing>,
+
+    /// Start paused after loading, so it can be stepped through from the first instruction
+    #[arg(long)]
+    pub paused: bool,
+
+    /// Start in fullscreen mode
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Start with audio muted
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Don't open an audio device, e.g. on machines without one
+    #[arg(long, conflicts_with = "mute")]
+    pub no_sound: bool,
+
+    /// CPU speed in instructions per second
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..))]
+    pub speed: Option<u32>,
+
+    /// Quirks preset to start with
+    #[arg(long, value_name = "PRESET")]
+    pub quirks: Option<QuirksPreset>,
+
+    /// Color palette to start with
+    #[arg(long, value_name = "PRESET")]
+    pub palette: Option<ColorPreset>,
+
+    /// Initial window size as a multiple of the 64x32 CHIP-8 screen
+    #[arg(long, value_name = "FACTOR", value_parser = clap::value_parser!(u32).range(8..=64))]
+    pub scale: Option<u32>,
+
+    /// Run the ROM for the given number of frames without window and sound, then print performance statistics
+    #[arg(long, value_name = "FRAMES", requires = "files")]
+    pub bench: Option<u32>,
+
+    /// Play back an input recording without window and sound, then print the hash of the final screen
+    #[arg(
+        long,
+        value_name = "RECORDING",
+        requires = "files",
+        conflicts_with = "bench"
+    )]
+    pub replay: Option<PathBuf>,
+
+    /// Random seed for --replay, --play, --run-for, --stream and --fbdev
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub seed: u64,
+
+    /// Expected screen hash for --replay, exits with an error on mismatch
+    #[arg(long, value_name = "SHA1", requires = "replay")]
+    pub expect: Option<String>,
+
+    /// Run the ROM for the given number of frames (or seconds with an `s` suffix, e.g. `10s`) without window and sound, then exit
+    #[arg(
+        long,
+        value_name = "LENGTH",
+        value_parser = parse_run_length,
+        requires = "files",
+        conflicts_with_all = ["bench", "replay"]
+    )]
+    pub run_for: Option<u64>,
+
+    /// Save the screen as PNG after --run-for
+    #[arg(long, value_name = "PATH", requires = "run_for")]
+    pub screenshot: Option<PathBuf>,
+
+    /// Save the state as p8s file after --run-for
+    #[arg(long, value_name = "PATH", requires = "run_for")]
+    pub save_state: Option<PathBuf>,
+
+    /// Check after --run-for that a saved and restored state runs identically for the given number of frames
+    #[arg(long, value_name = "FRAMES", requires = "run_for")]
+    pub check_state: Option<u64>,
+
+    /// Run the ROM without window and sound, comparing the registers before every instruction with a trace of another emulator
+    #[arg(
+        long,
+        value_name = "TRACE",
+        requires = "files",
+        conflicts_with_all = ["bench", "replay", "run_for", "debug_repl", "gdb", "stream", "fbdev"]
+    )]
+    pub lockstep: Option<PathBuf>,
+
+    /// Debug the ROM with commands on stdin (step, break, dump, watch, ...) without window and sound
+    #[arg(
+        long,
+        requires = "files",
+        conflicts_with_all = ["bench", "replay", "run_for"]
+    )]
+    pub debug_repl: bool,
+
+    /// Debug the ROM with GDB or LLDB, serving the GDB remote protocol on the port of localhost
+    #[arg(
+        long,
+        value_name = "PORT",
+        requires = "files",
+        conflicts_with_all = ["bench", "replay", "run_for", "debug_repl"]
+    )]
+    pub gdb: Option<u16>,
+
+    /// Run the ROM in real time without window and sound, streaming the screen to viewers connecting to the port of localhost,
+    /// or of the given address like 0.0.0.0:7800 to accept viewers from other machines
+    #[arg(
+        long,
+        value_name = "[ADDRESS:]PORT",
+        value_parser = parse_listen_address,
+        requires = "files",
+        conflicts_with_all = ["bench", "replay", "run_for", "debug_repl", "gdb"]
+    )]
+    pub stream: Option<SocketAddr>,
+
+    /// Run the ROM in real time on the Linux framebuffer device, e.g. /dev/fb0, for setups without X or Wayland
+    #[arg(
+        long,
+        value_name = "DEVICE",
+        requires = "files",
+        conflicts_with_all = ["bench", "replay", "run_for", "debug_repl", "gdb", "stream"]
+    )]
+    pub fbdev: Option<PathBuf>,
+
+    /// Serve metrics of --stream or --fbdev, like frames and instructions per second, over HTTP on the port of localhost,
+    /// or of the given address like 0.0.0.0:9100 to be scraped from other machines
+    #[arg(
+        long,
+        value_name = "[ADDRESS:]PORT",
+        value_parser = parse_listen_address,
+        requires = "realtime"
+    )]
+    pub metrics: Option<SocketAddr>,
+
+    /// Read the keys for --fbdev from the evdev keyboard device, e.g. /dev/input/event0
+    #[arg(long, value_name = "DEVICE", requires = "fbdev")]
+    pub evdev: Option<PathBuf>,
+
+    /// Show the screen streamed by an instance running with --stream in the terminal, e.g. 192.168.0.2:7800
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "files")]
+    pub view: Option<String>,
+
+    /// Keep config and data next to the executable instead of the platform directories
+    #[arg(long)]
+    pub portable: bool,
+
+    /// Log more details, can be repeated up to three times (RUST_LOG takes precedence)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+}
+
+/// Tools working on a ROM without running it.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the ROM as assembly with addresses, bytes and labels
+    Dasm {
+        /// ROM, archive or Octo source to disassemble, - reads the standard input
+        #[arg(value_name = "ROM")]
+        rom: PathBuf,
+
+        /// Address to follow the program from, e.g. 0x300 for ROMs jumping into a table
+        #[arg(long, value_name = "ADDRESS", default_value = "0x200", value_parser = parse_address)]
+        start: u16,
+
+        /// Print Octo source instead, which can be assembled again
+        #[arg(long, conflicts_with = "start")]
+        octo: bool,
+    },
+
+    /// Report the opcodes, required extensions, data and keys of a ROM without running it
+    Analyze {
+        /// ROM, archive or Octo source to analyze, - reads the standard input
+        #[arg(value_name = "ROM")]
+        rom: PathBuf,
+    },
+
+    /// Run the bundled workloads and print the instructions emulated per second
+    Bench {
+        /// Instructions per run of each workload, the fastest of 3 runs is reported
+        #[arg(long, value_name = "COUNT", default_value_t = 10_000_000)]
+        instructions: u32,
+    },
+
+    /// Run the test ROMs in a directory under every quirk preset and compare their screens
+    Verify {
+        /// Directory with Timendus' test suite and corax89's opcode test
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Record the screens as the expected ones, after checking them by hand
+        #[arg(long)]
+        record: bool,
+    },
+}
+
+/// Parses a hex address like `0x200` (or `200`).
+fn parse_address(value: &str) -> Result<u16, String> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("'{}' is no hex address", value))
+}
+
+/// Parses a port to listen on localhost, or an address with a port like `0.0.0.0:7800` to listen
+/// on other interfaces, so serving other machines is always explicit.
+fn parse_listen_address(value: &str) -> Result<SocketAddr, String> {
+    match value.parse::<u16>() {
+        Ok(port) => Ok((Ipv4Addr::LOCALHOST, port).into()),
+        Err(_) => value
+            .parse()
+            .map_err(|_| format!("'{}' is neither a port nor an address with a port", value)),
+    }
+}
+
+/// Parses a run length into a number of frames, a `s` suffix means seconds at 60 frames per second.
+fn parse_run_length(value: &str) -> Result<u64, String> {
+    let (number, factor) = match value.strip_suffix('s') {
+        Some(seconds) => (seconds, 60),
+        None => (value, 1),
+    };
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("'{}' is neither a number of frames nor seconds", value))?;
+    number
+        .checked_mul(factor)
+        .ok_or_else(|| format!("'{}' is too long to run for", value))
+}
+
+impl Cli {
+    /// Returns whether pich8 runs without window, e.g. for benchmarks and replays.
+    pub fn headless(&self) -> bool {
+        self.command.is_some()
+            || self.bench.is_some()
+            || self.replay.is_some()
+            || self.run_for.is_some()
+            || self.lockstep.is_some()
+            || self.debug_repl
+            || self.gdb.is_some()
+            || self.stream.is_some()
+            || self.fbdev.is_some()
+            || self.view.is_some()
+    }
+
+    /// Returns the settings given on the command line, which take precedence over the config file.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            speed: self.speed,
+            quirks: self.quirks.map(QuirksConfig::Preset),
+            palette: self.palette.map(PaletteConfig::Preset),
+            mute: if self.mute { Some(true) } else { None },
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod cli_test {
+    use super::*;
+
+    #[test]
+    fn test_run_length() {
+        let run_for = |value: &str| {
+            Cli::try_parse_from(["pich8", "--run-for", value, "rom.ch8"]).map(|cli| cli.run_for)
+        };
+        assert_eq!(run_for("10").unwrap(), Some(10));
+        assert_eq!(run_for("10s").unwrap(), Some(600));
+        assert!(run_for("ten").is_err());
+        // More than u64::MAX frames
+        let error = run_for("307445734561825861s").unwrap_err();
+        assert!(error.to_string().contains("too long"), "{}", error);
+    }
+}
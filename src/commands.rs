@@ -0,0 +1,158 @@
+use crate::cli::Command;
+use crate::headless::Headless;
+use crate::verify;
+use pich8::analysis::Analysis;
+use pich8::assembler;
+use pich8::cpu::CPU;
+use pich8::disassembler;
+use pich8::rom::Platform;
+use pich8::runner::Runner;
+use pich8::symbols::Symbols;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The workloads of `pich8 bench` as Octo source, each looping forever
+const WORKLOADS: [(&str, &str); 3] = [
+    ("draw", include_str!("../data/bench/draw.8o")),
+    ("math", include_str!("../data/bench/math.8o")),
+    ("bcd", include_str!("../data/bench/bcd.8o")),
+];
+
+/// Runs a tool given as subcommand, printing its output to stdout.
+pub fn run(command: &Command) -> Result<(), String> {
+    match command {
+        Command::Dasm { rom, start, octo } => dasm(rom, *start, *octo),
+        Command::Analyze { rom } => analyze(rom),
+        Command::Bench { instructions } => bench(*instructions),
+        Command::Verify { dir, record } => verify::run(dir, *record),
+    }
+}
+
+fn dasm(path: &Path, start: u16, octo: bool) -> Result<(), String> {
+    let rom = Headless::read_rom(path)?;
+    let symbols = match Symbols::find(path) {
+        Some(path) => Symbols::load(&path)?,
+        None => Symbols::default(),
+    };
+    if octo {
+        print!("{}", disassembler::export_octo(&rom, &symbols));
+    } else {
+        print!("{}", disassembler::listing(&rom, start, &symbols));
+    }
+    Ok(())
+}
+
+fn analyze(path: &Path) -> Result<(), String> {
+    let rom = Headless::read_rom(path)?;
+    let analysis = Analysis::new(&rom);
+    println!("Platform: {}", analysis.platform.name());
+    for &(address, opcode) in &analysis.extensions {
+        println!(
+            "  {:03X}  {:04X}  {} ({})",
+            address,
+            opcode,
+            disassembler::disassemble(opcode, 0),
+            Platform::of(opcode).name()
+        );
+    }
+
+    let data: usize = analysis.data.iter().map(ExactSizeIterator::len).sum();
+    println!(
+        "\nInstructions: {}, data: {} bytes",
+        analysis.instructions, data
+    );
+    for usage in &analysis.opcodes {
+        println!(
+            "  {:<9}  {:<5}  {}",
+            usage.pattern, usage.mnemonic, usage.count
+        );
+    }
+
+    println!("\nData regions:");
+    for range in &analysis.data {
+        println!(
+            "  {:03X}-{:03X}  {} bytes",
+            range.start,
+            range.end - 1,
+            range.len()
+        );
+    }
+
+    let keys: Vec<_> = analysis
+        .keys
+        .iter()
+        .map(|key| format!("{:X}", key))
+        .collect();
+    println!(
+        "\nKeys: {}",
+        if keys.is_empty() {
+            "none".to_string()
+        } else {
+            keys.join(" ")
+        }
+    );
+    if analysis.unknown_keys {
+        println!("  Some keys are computed at runtime");
+    }
+    if analysis.waits_for_key {
+        println!("  Waits for any key with FX0A");
+    }
+    Ok(())
+}
+
+/// Runs every workload without sleeping between frames, the numbers are comparable between
+/// versions on the same machine and build profile.
+fn bench(instructions: u32) -> Result<(), String> {
+    const RUNS: usize = 3;
+    println!("pich8 {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "{:<8}  {:>12}  {:>10}  {:>8}",
+        "Workload", "Instructions", "Time", "MIPS"
+    );
+    for (name, source) in WORKLOADS {
+        let rom = assembler::assemble(source)
+            .map_err(|e| format!("Cannot assemble workload {}: {}", name, e))?
+            .rom;
+        let mut fastest = Duration::MAX;
+        for _ in 0..RUNS {
+            let mut cpu = CPU::new();
+            cpu.load_rom(&rom)?;
+            // The timers run as they would at 60,000 instructions per second
+            let mut runner = Runner::new(60_000);
+            let start = Instant::now();
+            for _ in 0..instructions {
+                runner
+                    .step(&mut cpu, &[false; 16])
+                    .map_err(|e| format!("Workload {} failed: {}", name, e))?;
+            }
+            fastest = fastest.min(start.elapsed());
+        }
+        println!(
+            "{:<8}  {:>12}  {:>8.3} s  {:>8.2}",
+            name,
+            instructions,
+            fastest.as_secs_f64(),
+            instructions as f64 / fastest.as_secs_f64() / 1e6
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod commands_test {
+    use super::*;
+
+    #[test]
+    fn test_workloads() {
+        for (name, source) in WORKLOADS {
+            let rom = assembler::assemble(source).unwrap().rom;
+            let mut cpu = CPU::new();
+            cpu.load_rom(&rom).unwrap();
+            let mut runner = Runner::new(60_000);
+            for _ in 0..10_000 {
+                runner.step(&mut cpu, &[false; 16]).unwrap();
+            }
+            assert!(!cpu.is_stuck(), "{} stopped", name);
+        }
+    }
+}
@@ -0,0 +1,667 @@
+use crate::capture::CaptureConfig;
+use crate::frame_limiter::FramePacing;
+use crate::gui::{ColorPreset, Quirk, QuirksPreset};
+use log::{info, warn};
+use pich8::cpu::CPU;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::BTreeMap, fs, path::Path, path::PathBuf, str::FromStr, sync::OnceLock};
+
+/// Settings which can be set globally and overridden per game.
+/// A value of `None` means the setting isn't specified on this level.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub speed: Option<u32>,
+    pub quirks: Option<QuirksConfig>,
+    pub palette: Option<PaletteConfig>,
+    pub keys: Option<KeyMap>,
+    pub volume: Option<u8>,
+    pub mute: Option<bool>,
+    pub persist: Option<MemoryRange>,
+}
+
+impl Settings {
+    /// Returns a copy of these settings with all values specified in `other` taking precedence.
+    pub fn merged(&self, other: &Settings) -> Settings {
+        Settings {
+            speed: other.speed.or(self.speed),
+            quirks: other.quirks.or(self.quirks),
+            palette: other.palette.or(self.palette),
+            keys: other.keys.or(self.keys),
+            volume: other.volume.or(self.volume),
+            mute: other.mute.or(self.mute),
+            persist: other.persist.or(self.persist),
+        }
+    }
+
+    /// Returns the values of these settings which are also specified in `other`.
+    pub fn specified_in(&self, other: &Settings) -> Settings {
+        Settings {
+            speed: other.speed.and(self.speed),
+            quirks: other.quirks.and(self.quirks),
+            palette: other.palette.and(self.palette),
+            keys: other.keys.and(self.keys),
+            volume: other.volume.and(self.volume),
+            mute: other.mute.and(self.mute),
+            persist: other.persist.and(self.persist),
+        }
+    }
+}
+
+static PORTABLE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub settings: Settings,
+
+    /// Save the state on exit and offer to resume it the next time the ROM is loaded
+    pub autosave: bool,
+
+    /// Load ROMs and states paused, e.g. to set up breakpoints before the first frame
+    pub start_paused: bool,
+
+    /// Seconds a ROM may spin in a jump to itself before offering to reset or pause it, 0 turns it off
+    pub watchdog_timeout: u64,
+
+    /// Ask before quitting while recording input or after playing a while without saving
+    pub confirm_exit: bool,
+
+    /// Seconds after which the next ROM of a playlist is loaded
+    pub playlist_interval: Option<u64>,
+
+    /// How to wait for the next frame: hybrid, sleep, busy or vsync
+    pub frame_pacing: FramePacing,
+
+    /// Directory ROMs downloaded from the chip8Archive are saved to
+    pub rom_dir: Option<PathBuf>,
+
+    /// Application ID to publish the played ROM to Discord Rich Presence with (requires the `discord` feature)
+    pub discord_app_id: Option<String>,
+
+    /// Port of localhost to accept remote control commands on over WebSocket (requires the `remote` feature)
+    pub remote_port: Option<u16>,
+
+    /// Unix socket to accept JSON-RPC commands on, e.g. from editor plugins (requires the `remote` feature)
+    pub rpc_socket: Option<PathBuf>,
+
+    /// Named pipe to read commands like `pause` from, e.g. for window manager key bindings (requires the `remote` feature)
+    pub control_pipe: Option<PathBuf>,
+
+    /// Output plugins to load, as `PATH` or `PATH=ARGS` (requires the `plugins` feature)
+    pub plugins: Vec<String>,
+
+    /// File every changed frame is published to for capture tools, e.g. `/dev/shm/pich8-frame`
+    pub export_frames: Option<PathBuf>,
+
+    /// Where screenshots, recordings and states are saved and how they're named
+    pub capture: CaptureConfig,
+
+    /// The debug windows open at the last exit, restored on the next start
+    pub debug_layout: DebugLayout,
+
+    /// Per game overrides, keyed by the SHA-1 hash of the ROM
+    pub game: BTreeMap<String, Settings>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            settings: Settings {
+                speed: Some(720),
+                quirks: Some(QuirksConfig::Preset(QuirksPreset::Default)),
+                palette: Some(PaletteConfig::Preset(ColorPreset::Default)),
+                keys: Some(KeyMap::default()),
+                volume: Some(25),
+                mute: Some(false),
+                persist: None,
+            },
+            autosave: false,
+            start_paused: false,
+            watchdog_timeout: 5,
+            confirm_exit: true,
+            playlist_interval: None,
+            frame_pacing: FramePacing::default(),
+            rom_dir: None,
+            discord_app_id: None,
+            remote_port: None,
+            rpc_socket: None,
+            control_pipe: None,
+            plugins: Vec::new(),
+            export_frames: None,
+            capture: CaptureConfig::default(),
+            debug_layout: DebugLayout::default(),
+            game: BTreeMap::new(),
+        }
+    }
+}
+
+/// Which debug windows are open and where, saved on exit.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugLayout {
+    /// Whether the debug windows (F7) are shown
+    pub enabled: bool,
+    /// The open windows of the Debug menu, e.g. `disassembly`
+    pub windows: Vec<String>,
+    /// Positions and sizes of the windows in Dear ImGui's ini format
+    pub positions: String,
+}
+
+impl Config {
+    const FILE_NAME: &'static str = "config.toml";
+    const HEADER: &'static str = "# pich8 configuration\n\
+        #\n\
+        # Global settings are defined at the top level, they can be overridden for\n\
+        # specific ROMs in [game.\"<sha1 of the rom>\"] sections, e.g.\n\
+        #\n\
+        # [game.\"0123456789abcdef0123456789abcdef01234567\"]\n\
+        # speed = 1200\n\
+        # quirks = \"octo\"\n\
+        #\n\
+        # A memory range like persist = \"0x3e0-0x3ff\" is saved on exit and\n\
+        # restored when the ROM is loaded again, e.g. to keep high scores.\n\
+        #\n\
+        # Keys are listed in CHIP-8 key order (0 - F), either as the name of the key\n\
+        # on a US keyboard layout or as a raw scancode.\n\
+        #\n\
+        # If autosave is enabled, the state is saved on exit and pich8 offers to\n\
+        # resume it the next time the same ROM is loaded.\n\
+        #\n\
+        # The [capture] section defines where screenshots, input recordings and\n\
+        # states are saved. File names can contain {rom}, {hash}, {timestamp} and,\n\
+        # for state slots, {slot}.\n\n";
+
+    /// Keeps all files next to the executable instead of the platform directories, e.g. on a USB stick.
+    /// Portable mode is also used if a file with this name exists next to the executable.
+    pub const PORTABLE_MARKER: &'static str = "portable.txt";
+
+    /// Switches to portable mode, has to be called before any directory is used.
+    pub fn set_portable() -> Result<(), String> {
+        let exe =
+            std::env::current_exe().map_err(|e| format!("Cannot locate executable: {}", e))?;
+        let dir = exe.parent().ok_or("Cannot locate executable")?;
+        let _ = PORTABLE_DIR.set(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Returns whether the portable marker file exists next to the executable.
+    pub fn portable_marker_exists() -> bool {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(Self::PORTABLE_MARKER)))
+            .is_some_and(|marker| marker.exists())
+    }
+
+    /// Returns the directory where the config, the game database and the recent files are stored.
+    pub fn dir() -> Option<PathBuf> {
+        match PORTABLE_DIR.get() {
+            Some(dir) => Some(dir.clone()),
+            None => dirs::config_dir().map(|dir| dir.join(env!("CARGO_CRATE_NAME"))),
+        }
+    }
+
+    /// Returns the directory where states, recordings, RPL flags and crash reports are stored.
+    pub fn data_dir() -> Option<PathBuf> {
+        match PORTABLE_DIR.get() {
+            Some(dir) => Some(dir.clone()),
+            None => dirs::data_dir().map(|dir| dir.join(env!("CARGO_CRATE_NAME"))),
+        }
+    }
+
+    /// Moves data stored in the config directory by older versions into the data directory.
+    pub fn migrate_data(names: &[&str]) {
+        let (config_dir, data_dir) = match (Self::dir(), Self::data_dir()) {
+            (Some(config_dir), Some(data_dir)) if config_dir != data_dir => (config_dir, data_dir),
+            _ => return,
+        };
+        for name in names {
+            let (old, new) = (config_dir.join(name), data_dir.join(name));
+            if old.is_dir() && !new.exists() {
+                let result = fs::create_dir_all(&data_dir).and_then(|_| fs::rename(&old, &new));
+                match result {
+                    Ok(_) => info!("Moved {} to {}", old.display(), new.display()),
+                    Err(e) => warn!("Failed to move {}: {}", old.display(), e),
+                }
+            }
+        }
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join(Self::FILE_NAME))
+    }
+
+    /// Loads the config from the given path.
+    /// If the file doesn't exist yet, it's created with the default settings.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            let config = Self::default();
+            config.save(path)?;
+            return Ok(config);
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content =
+            toml::to_string(self).map_err(|e| format!("Failed to serialize config: {}", e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        fs::write(path, format!("{}{}", Self::HEADER, content))
+            .map_err(|e| format!("Failed to write config {}: {}", path.display(), e))
+    }
+
+    /// Returns the effective settings for the ROM with the given hash.
+    /// Known settings (e.g. from the game database) are applied on top of the global settings,
+    /// but the user's per game overrides always win.
+    pub fn settings_for(&self, rom_hash: Option<&str>, known: Option<&Settings>) -> Settings {
+        self.settings.merged(&self.overrides_for(rom_hash, known))
+    }
+
+    /// Returns the settings replacing the global ones for the ROM with the given hash, i.e. only
+    /// the known settings and the per game overrides.
+    pub fn overrides_for(&self, rom_hash: Option<&str>, known: Option<&Settings>) -> Settings {
+        let mut settings = known.cloned().unwrap_or_default();
+        if let Some(game) = rom_hash.and_then(|hash| self.game.get(hash)) {
+            settings = settings.merged(game);
+        }
+        settings
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum QuirksConfig {
+    Preset(QuirksPreset),
+    Custom(Quirks),
+}
+
+impl QuirksConfig {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            QuirksConfig::Preset(preset) => {
+                let values = preset.values();
+                Quirks {
+                    load_store: values[Quirk::LoadStore as usize],
+                    shift: values[Quirk::Shift as usize],
+                    draw: values[Quirk::Draw as usize],
+                    jump: values[Quirk::Jump as usize],
+                    vf_order: values[Quirk::VfOrder as usize],
+                    partial_wrap_h: values[Quirk::PartialWrapH as usize],
+                    partial_wrap_v: values[Quirk::PartialWrapV as usize],
+                }
+            }
+            QuirksConfig::Custom(quirks) => quirks,
+        }
+    }
+}
+
+impl Quirks {
+    pub fn apply(&self, cpu: &mut CPU) {
+        cpu.quirk_load_store = self.load_store;
+        cpu.quirk_shift = self.shift;
+        cpu.quirk_draw = self.draw;
+        cpu.quirk_jump = self.jump;
+        cpu.quirk_vf_order = self.vf_order;
+        cpu.quirk_partialwrap_h = self.partial_wrap_h;
+        cpu.quirk_partialwrap_v = self.partial_wrap_v;
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Quirks {
+    pub load_store: bool,
+    pub shift: bool,
+    pub draw: bool,
+    pub jump: bool,
+    pub vf_order: bool,
+    pub partial_wrap_h: bool,
+    pub partial_wrap_v: bool,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PaletteConfig {
+    Preset(ColorPreset),
+    Custom(Palette),
+}
+
+impl PaletteConfig {
+    /// Returns the colors of this palette, indexed by `Color`.
+    pub fn colors(self) -> [[f32; 3]; 4] {
+        match self {
+            PaletteConfig::Preset(preset) => preset.values(),
+            PaletteConfig::Custom(palette) => [
+                palette.background.0,
+                palette.plane_1.0,
+                palette.plane_2.0,
+                palette.plane_both.0,
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Palette {
+    pub background: HexColor,
+    pub plane_1: HexColor,
+    pub plane_2: HexColor,
+    pub plane_both: HexColor,
+}
+
+/// A color written as `#rrggbb` in the config file.
+#[derive(Copy, Clone)]
+pub struct HexColor(pub [f32; 3]);
+
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b] = self.0;
+        serializer.serialize_str(&format!(
+            "#{:02x}{:02x}{:02x}",
+            (r * 255.0).round() as u8,
+            (g * 255.0).round() as u8,
+            (b * 255.0).round() as u8
+        ))
+    }
+}
+
+impl FromStr for HexColor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let hex = value.trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(format!("invalid color '{}'", value));
+        }
+        let mut color = [0.0; 3];
+        for (i, c) in color.iter_mut().enumerate() {
+            let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("invalid color '{}'", value))?;
+            *c = byte as f32 / 255.0;
+        }
+        Ok(HexColor(color))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// An inclusive range of memory addresses, written as `0x3e0-0x3ff` in the config file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MemoryRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Serialize for MemoryRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#05x}-{:#05x}", self.start, self.end))
+    }
+}
+
+impl FromStr for MemoryRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid memory range '{}'", value);
+        let parse = |address: &str| {
+            let address = address.trim();
+            u16::from_str_radix(address.trim_start_matches("0x"), 16).map_err(|_| invalid())
+        };
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start > end {
+            return Err(invalid());
+        }
+        Ok(MemoryRange { start, end })
+    }
+}
+
+impl<'de> Deserialize<'de> for MemoryRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Maps the 16 CHIP-8 keys (0 - F) to keyboard scancodes.
+#[derive(Copy, Clone, PartialEq)]
+pub struct KeyMap(pub [u32; 16]);
+
+impl KeyMap {
+    // Scancodes of the keys on a US keyboard layout
+    const KEY_NAMES: &'static [(&'static str, u32)] = &[
+        ("1", 2),
+        ("2", 3),
+        ("3", 4),
+        ("4", 5),
+        ("5", 6),
+        ("6", 7),
+        ("7", 8),
+        ("8", 9),
+        ("9", 10),
+        ("0", 11),
+        ("Q", 16),
+        ("W", 17),
+        ("E", 18),
+        ("R", 19),
+        ("T", 20),
+        ("Y", 21),
+        ("U", 22),
+        ("I", 23),
+        ("O", 24),
+        ("P", 25),
+        ("A", 30),
+        ("S", 31),
+        ("D", 32),
+        ("F", 33),
+        ("G", 34),
+        ("H", 35),
+        ("J", 36),
+        ("K", 37),
+        ("L", 38),
+        ("Z", 44),
+        ("X", 45),
+        ("C", 46),
+        ("V", 47),
+        ("B", 48),
+        ("N", 49),
+        ("M", 50),
+        ("Space", 57),
+    ];
+
+    /// Returns the CHIP-8 key mapped to the given scancode.
+    pub fn key(&self, scancode: u32) -> Option<usize> {
+        self.0.iter().position(|&s| s == scancode)
+    }
+
+    /// Returns the name of the keyboard key mapped to a CHIP-8 key, or its scancode if it has no name.
+    pub fn key_name(&self, key: usize) -> String {
+        match Self::scancode_name(self.0[key]) {
+            Some(name) => name.to_string(),
+            None => format!("#{}", self.0[key]),
+        }
+    }
+
+    fn scancode_name(scancode: u32) -> Option<&'static str> {
+        Self::KEY_NAMES
+            .iter()
+            .find(|(_, s)| *s == scancode)
+            .map(|(name, _)| *name)
+    }
+
+    fn name_scancode(name: &str) -> Option<u32> {
+        Self::KEY_NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, s)| *s)
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap([45, 2, 3, 4, 16, 17, 18, 30, 31, 32, 44, 46, 5, 19, 33, 47])
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Key {
+    Name(String),
+    Scancode(u32),
+}
+
+impl Serialize for KeyMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let keys: Vec<Key> = self
+            .0
+            .iter()
+            .map(|&scancode| match Self::scancode_name(scancode) {
+                Some(name) => Key::Name(name.to_string()),
+                None => Key::Scancode(scancode),
+            })
+            .collect();
+        keys.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let keys = Vec::<Key>::deserialize(deserializer)?;
+        if keys.len() != 16 {
+            return Err(de::Error::custom("exactly 16 keys are required"));
+        }
+        let mut map = [0; 16];
+        for (scancode, key) in map.iter_mut().zip(keys) {
+            *scancode = match key {
+                Key::Name(name) => Self::name_scancode(&name)
+                    .ok_or_else(|| de::Error::custom(format!("unknown key '{}'", name)))?,
+                Key::Scancode(scancode) => scancode,
+            };
+        }
+        Ok(KeyMap(map))
+    }
+}
+
+/// A key mapping saved as standalone file, so layouts for specific games can be shared.
+#[derive(Serialize, Deserialize)]
+pub struct KeyProfile {
+    pub name: String,
+    /// The game the layout was made for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    pub keys: KeyMap,
+}
+
+impl KeyProfile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read key profile {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse key profile {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content =
+            toml::to_string(self).map_err(|e| format!("Failed to serialize key profile: {}", e))?;
+        fs::write(path, content)
+            .map_err(|e| format!("Failed to write key profile {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod config_test {
+    use super::*;
+
+    #[test]
+    fn test_default_round_trip() {
+        let content = toml::to_string(&Config::default()).unwrap();
+        let config: Config = toml::from_str(&content).unwrap();
+        assert_eq!(config.settings.speed, Some(720));
+        assert!(config.settings.keys == Some(KeyMap::default()));
+        assert!(config.game.is_empty());
+    }
+
+    #[test]
+    fn test_game_override() {
+        let config: Config = toml::from_str(
+            r##"
+            speed = 600
+            palette = { background = "#000000", plane_1 = "#ffffff", plane_2 = "#555555", plane_both = "#aaaaaa" }
+
+            [game."abc"]
+            speed = 1200
+            quirks = "octo"
+            keys = ["x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", 57]
+            persist = "0x3e0-0x3ff"
+            "##,
+        )
+        .unwrap();
+
+        let settings = config.settings_for(None, None);
+        assert_eq!(settings.speed, Some(600));
+        assert!(settings.quirks.is_none());
+        match settings.palette {
+            Some(PaletteConfig::Custom(palette)) => assert_eq!(palette.plane_1.0, [1.0; 3]),
+            _ => panic!("expected custom palette"),
+        }
+
+        let settings = config.settings_for(Some("abc"), None);
+        assert_eq!(settings.speed, Some(1200));
+        assert!(matches!(
+            settings.quirks,
+            Some(QuirksConfig::Preset(QuirksPreset::Octo))
+        ));
+        assert!(settings.palette.is_some());
+        assert_eq!(settings.keys.unwrap().key(57), Some(0xF));
+        assert_eq!(
+            settings.persist,
+            Some(MemoryRange {
+                start: 0x3e0,
+                end: 0x3ff
+            })
+        );
+
+        let overrides = config.overrides_for(Some("abc"), None);
+        assert_eq!(overrides.speed, Some(1200));
+        assert!(overrides.palette.is_none());
+        let replaced = config.settings.specified_in(&overrides);
+        assert_eq!(replaced.speed, Some(600));
+        assert!(replaced.palette.is_none());
+    }
+
+    #[test]
+    fn test_key_profile() {
+        let profile: KeyProfile = toml::from_str(
+            r#"
+            name = "BLINKY wasd"
+            keys = ["x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", 57]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.keys.key(57), Some(0xF));
+        let content = toml::to_string(&profile).unwrap();
+        assert!(!content.contains("game"));
+        assert!(content.contains("\"Space\""));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(toml::from_str::<Config>("keys = [\"x\"]").is_err());
+        assert!(toml::from_str::<Config>("palette = { background = \"#00\" }").is_err());
+        assert!(toml::from_str::<Config>("persist = \"0x3ff-0x3e0\"").is_err());
+    }
+}
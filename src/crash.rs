@@ -0,0 +1,62 @@
+use crate::config::Config;
+use std::{
+    backtrace::Backtrace,
+    fs, panic,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
+
+const CRASHES_DIR: &str = "crashes";
+
+/// Installs a panic hook which keeps the panic message and backtrace for the crash report.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut message) = PANIC_MESSAGE.lock() {
+            *message = Some(format!("{}\n\n{}", info, Backtrace::force_capture()));
+        }
+        default_hook(info);
+    }));
+}
+
+/// Writes a crash report with the given emulator details and the state file, if available.
+/// Returns the path of the report.
+pub fn write_report(details: &str, state: Option<&[u8]>) -> Result<PathBuf, String> {
+    let dir = Config::data_dir()
+        .map(|dir| dir.join(CRASHES_DIR))
+        .ok_or("No data directory available")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let name = format!("crash-{}", timestamp);
+
+    let panic_message = PANIC_MESSAGE
+        .lock()
+        .ok()
+        .and_then(|message| message.clone())
+        .unwrap_or_else(|| "Unknown panic".to_string());
+    let mut report = format!(
+        "{} {} crashed\n\n{}\n\n{}",
+        env!("CARGO_CRATE_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        details,
+        panic_message
+    );
+
+    if let Some(state) = state {
+        let state_path = dir.join(format!("{}.p8s", name));
+        if fs::write(&state_path, state).is_ok() {
+            report.push_str(&format!("\nState saved to {}\n", state_path.display()));
+        }
+    }
+
+    let path = dir.join(format!("{}.txt", name));
+    fs::write(&path, report).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
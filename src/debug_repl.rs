@@ -0,0 +1,325 @@
+use crate::headless::Headless;
+use pich8::breakpoints::BreakpointList;
+use pich8::disassembler;
+use pich8::expression::Watch;
+use pich8::symbols::Symbols;
+use std::io::{BufRead, Write};
+
+/// The debugger on stdin/stdout, e.g. to debug over SSH or to drive it from a script.
+///
+/// Addresses are hex or labels of the symbol file, counts are decimal. `help` lists the commands.
+pub struct DebugRepl {
+    headless: Headless,
+    symbols: Symbols,
+    breakpoints: BreakpointList,
+    watches: Vec<(String, Watch)>,
+    keys: [bool; 16],
+}
+
+impl DebugRepl {
+    /// Frames `continue` runs at most before giving up on hitting a breakpoint, a minute of emulated time
+    const CONTINUE_FRAMES: u64 = 60 * 60;
+    const HELP: &'static str = "\
+step [N]                 execute N instructions (default 1)
+frame [N]                run N frames (default 1)
+continue [FRAMES]        run until a breakpoint is hit
+break ADDRESS [if COND]  break at an address, optionally only if a condition holds
+break if COND            break whenever a condition holds, e.g. 'V4 == 0x1F'
+delete N | delete all    remove a breakpoint
+breakpoints              list the breakpoints
+watch EXPR               print a value after every stop, e.g. 'I' or 'mem[0x3a0..0x3b0]'
+unwatch N                remove a watch
+regs                     print the registers
+dump [ADDRESS] [LEN]     print memory, at I by default
+disasm [ADDRESS] [N]     disassemble N instructions, at the PC by default
+press KEY | release KEY  press or release a key (hex)
+quit                     exit";
+
+    pub fn new(headless: Headless, symbols: Symbols) -> Self {
+        Self {
+            headless,
+            symbols,
+            breakpoints: BreakpointList::default(),
+            watches: Vec::new(),
+            keys: [false; 16],
+        }
+    }
+
+    /// Reads commands until `quit` or the end of the input.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> Result<(), String> {
+        let io_error = |e: std::io::Error| e.to_string();
+        self.print_location(&mut output).map_err(io_error)?;
+        write!(output, "> ").map_err(io_error)?;
+        output.flush().map_err(io_error)?;
+        for line in input.lines() {
+            let line = line.map_err(io_error)?;
+            let line = line.trim();
+            if line == "quit" || line == "q" {
+                break;
+            }
+            if !line.is_empty() {
+                if let Err(msg) = self.execute(line, &mut output) {
+                    writeln!(output, "Error: {}", msg).map_err(io_error)?;
+                }
+            }
+            write!(output, "> ").map_err(io_error)?;
+            output.flush().map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, line: &str, output: &mut impl Write) -> Result<(), String> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        let stopped = match command {
+            "step" | "s" => {
+                for _ in 0..self.count(args, 1)? {
+                    self.headless.step(&self.keys).map_err(|e| e.to_string())?;
+                }
+                true
+            }
+            "frame" | "f" => {
+                for _ in 0..self.count(args, 1)? {
+                    self.headless
+                        .run_frame(&self.keys)
+                        .map_err(|e| e.to_string())?;
+                }
+                true
+            }
+            "continue" | "c" => {
+                let frames = self.count(args, Self::CONTINUE_FRAMES)?;
+                if !self.run_to_breakpoint(frames)? {
+                    writeln!(output, "No breakpoint hit within {} frames", frames)
+                        .map_err(|e| e.to_string())?;
+                }
+                true
+            }
+            "break" | "b" => {
+                let (address, condition) = match args.strip_prefix("if ") {
+                    Some(condition) => (None, condition),
+                    None => {
+                        let (address, condition) = args.split_once(" if ").unwrap_or((args, ""));
+                        (Some(self.address(address)?), condition)
+                    }
+                };
+                self.breakpoints.add(address, condition)?;
+                false
+            }
+            "delete" | "d" if args == "all" => {
+                self.breakpoints.clear();
+                false
+            }
+            "delete" | "d" => {
+                let idx = self.count(args, 0)? as usize;
+                if idx >= self.breakpoints.iter().count() {
+                    return Err(format!("There's no breakpoint {}", idx));
+                }
+                self.breakpoints.remove(idx);
+                false
+            }
+            "breakpoints" => {
+                for (idx, bp) in self.breakpoints.iter().enumerate() {
+                    let address = bp.address.map_or_else(
+                        || "any".to_string(),
+                        |address| self.symbols.describe(address),
+                    );
+                    match bp.condition {
+                        Some(_) => writeln!(output, "{}: {} if {}", idx, address, bp.description),
+                        None => writeln!(output, "{}: {}", idx, address),
+                    }
+                    .map_err(|e| e.to_string())?;
+                }
+                false
+            }
+            "watch" | "w" => {
+                let watch = args.parse()?;
+                self.watches.push((args.to_string(), watch));
+                self.print_watches(output).map_err(|e| e.to_string())?;
+                false
+            }
+            "unwatch" => {
+                let idx = self.count(args, 0)? as usize;
+                if idx >= self.watches.len() {
+                    return Err(format!("There's no watch {}", idx));
+                }
+                self.watches.remove(idx);
+                false
+            }
+            "regs" | "r" => {
+                self.print_registers(output).map_err(|e| e.to_string())?;
+                false
+            }
+            "dump" | "x" => {
+                let mut args = args.split_whitespace();
+                let start = match args.next() {
+                    Some(address) => self.address(address)?,
+                    None => self.headless.cpu().I(),
+                };
+                let len = args.next().map_or(Ok(64), |len| self.count(len, 64))?;
+                self.print_memory(output, start, len.min(u16::MAX as u64) as u16)
+                    .map_err(|e| e.to_string())?;
+                false
+            }
+            "disasm" | "u" => {
+                let mut args = args.split_whitespace();
+                let start = match args.next() {
+                    Some(address) => self.address(address)?,
+                    None => self.headless.cpu().PC(),
+                };
+                let count = args.next().map_or(Ok(10), |count| self.count(count, 10))?;
+                for idx in 0..count as u16 {
+                    self.print_instruction(output, start.wrapping_add(idx * 2))
+                        .map_err(|e| e.to_string())?;
+                }
+                false
+            }
+            "press" | "release" => {
+                let key = usize::from_str_radix(args, 16)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| format!("Invalid key '{}'", args))?;
+                self.keys[key] = command == "press";
+                false
+            }
+            "help" | "h" => {
+                writeln!(output, "{}", Self::HELP).map_err(|e| e.to_string())?;
+                false
+            }
+            _ => return Err(format!("Unknown command '{}', try 'help'", command)),
+        };
+        if stopped {
+            self.print_location(output).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Runs until a breakpoint is hit, returns false if none was hit within the frames.
+    fn run_to_breakpoint(&mut self, frames: u64) -> Result<bool, String> {
+        let end = self.headless.frame() + frames;
+        while self.headless.frame() < end {
+            self.headless.step(&self.keys).map_err(|e| e.to_string())?;
+            if self.breakpoints.check(self.headless.cpu()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn count(&self, value: &str, default: u64) -> Result<u64, String> {
+        match value.trim() {
+            "" => Ok(default),
+            value => value
+                .parse()
+                .map_err(|_| format!("Invalid number '{}'", value)),
+        }
+    }
+
+    fn address(&self, value: &str) -> Result<u16, String> {
+        let value = value.trim();
+        self.symbols
+            .address(value)
+            .or_else(|| u16::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| format!("Invalid address '{}'", value))
+    }
+
+    fn print_location(&self, output: &mut impl Write) -> std::io::Result<()> {
+        write!(output, "[frame {}] ", self.headless.frame())?;
+        self.print_instruction(output, self.headless.cpu().PC())?;
+        self.print_watches(output)
+    }
+
+    fn print_instruction(&self, output: &mut impl Write, address: u16) -> std::io::Result<()> {
+        let cpu = self.headless.cpu();
+        let word = |address: u16| {
+            (cpu.read_mem(address) as u16) << 8 | cpu.read_mem(address.wrapping_add(1)) as u16
+        };
+        let opcode = word(address);
+        let label = self
+            .symbols
+            .label(address)
+            .map_or_else(String::new, |label| format!("{}: ", label));
+        writeln!(
+            output,
+            "{:04X}  {:04X}  {}{}",
+            address,
+            opcode,
+            label,
+            disassembler::disassemble(opcode, word(address.wrapping_add(2)))
+        )
+    }
+
+    fn print_watches(&self, output: &mut impl Write) -> std::io::Result<()> {
+        for (idx, (expression, watch)) in self.watches.iter().enumerate() {
+            writeln!(
+                output,
+                "  {}: {} = {}",
+                idx,
+                expression,
+                watch.evaluate(self.headless.cpu())
+            )?;
+        }
+        Ok(())
+    }
+
+    fn print_registers(&self, output: &mut impl Write) -> std::io::Result<()> {
+        let cpu = self.headless.cpu();
+        for (idx, value) in cpu.V().iter().enumerate() {
+            write!(output, "V{:X}={:02X} ", idx, value)?;
+        }
+        writeln!(output)?;
+        writeln!(
+            output,
+            "PC={:04X} I={:04X} DT={:02X} ST={:02X} SP={:X}",
+            cpu.PC(),
+            cpu.I(),
+            cpu.DT(),
+            cpu.ST(),
+            cpu.sp()
+        )
+    }
+
+    fn print_memory(&self, output: &mut impl Write, start: u16, len: u16) -> std::io::Result<()> {
+        let cpu = self.headless.cpu();
+        for row in (0..len).step_by(16) {
+            let address = start.wrapping_add(row);
+            write!(output, "{:04X}:", address)?;
+            for offset in 0..(len - row).min(16) {
+                write!(
+                    output,
+                    " {:02X}",
+                    cpu.read_mem(address.wrapping_add(offset))
+                )?;
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod debug_repl_test {
+    use super::*;
+    use crate::config::Settings;
+
+    #[test]
+    fn test_repl() {
+        let rom = std::fs::read("core/data/bootrom/pich8-logo.ch8").unwrap();
+        let headless = Headless::new(&rom, &Settings::default()).unwrap();
+        let mut repl = DebugRepl::new(headless, Symbols::parse("start 0x200").unwrap());
+        let mut output = Vec::new();
+        repl.run(
+            "regs\nbreak 204\ncontinue\nwatch PC\nstep\nbreakpoints\nfoo\nquit\nregs\n".as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("[frame 0] 0200  "), "{}", output);
+        assert!(output.contains("start: "));
+        assert!(output.contains("PC=0200 I=0000"));
+        assert!(output.contains("[frame 0] 0204  "));
+        assert!(output.contains("0: PC = 0206"));
+        assert!(output.contains("0: 0204\n"));
+        assert!(output.contains("Error: Unknown command 'foo'"));
+        assert_eq!(output.matches("PC=").count(), 1);
+    }
+}
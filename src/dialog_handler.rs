@@ -2,7 +2,13 @@ use std::sync::mpsc::Receiver;
 
 pub enum FileDialogType {
     OpenRom,
-    SaveState,
+    OpenRomWithPatch,
+    SaveState(String),
+    ExportOcto(String),
+    ImportOctoState,
+    ExportOctoState(String),
+    ImportKeyProfile,
+    ExportKeyProfile(String),
 
     #[cfg(feature = "rom-download")]
     InputUrl,
@@ -11,7 +17,13 @@ pub enum FileDialogType {
 pub enum FileDialogResult {
     None,
     OpenRom(String),
+    OpenRomWithPatch(String, String),
     SaveState(String),
+    ExportOcto(String),
+    ImportOctoState(String),
+    ExportOctoState(String),
+    ImportKeyProfile(String),
+    ExportKeyProfile(String),
 
     #[cfg(feature = "rom-download")]
     InputUrl(String),
@@ -26,8 +38,16 @@ pub struct DialogHandler {
 }
 
 impl DialogHandler {
+    const PATCH_FILTER_PATT: &'static [&'static str] = &["*.ips", "*.bps"];
+    const PATCH_FILTER_DESC: &'static str = "IPS or BPS Patch (*.ips, *.bps)";
     const STATE_FILTER_PATT: &'static [&'static str] = &["*.p8s"];
     const STATE_FILTER_DESC: &'static str = "pich8 State (*.p8s)";
+    const OCTO_FILTER_PATT: &'static [&'static str] = &["*.8o"];
+    const OCTO_FILTER_DESC: &'static str = "Octo Source (*.8o)";
+    const OCTO_STATE_FILTER_PATT: &'static [&'static str] = &["*.json"];
+    const OCTO_STATE_FILTER_DESC: &'static str = "Octo State or Flags (*.json)";
+    const KEY_PROFILE_FILTER_PATT: &'static [&'static str] = &["*.toml"];
+    const KEY_PROFILE_FILTER_DESC: &'static str = "Key Profile (*.toml)";
 
     pub fn new() -> Self {
         Self {
@@ -54,11 +74,43 @@ impl DialogHandler {
                         result = FileDialogResult::OpenRom(file_path);
                     }
                 },
-                FileDialogType::SaveState => {
-                    if let Some(file_path) = tinyfiledialogs::save_file_dialog_with_filter("Save State", "", DialogHandler::STATE_FILTER_PATT, DialogHandler::STATE_FILTER_DESC) {
+                FileDialogType::OpenRomWithPatch => {
+                    if let Some(rom_path) = tinyfiledialogs::open_file_dialog("Open ROM", "", None) {
+                        if let Some(patch_path) = tinyfiledialogs::open_file_dialog("Open Patch", &rom_path, Some((DialogHandler::PATCH_FILTER_PATT, DialogHandler::PATCH_FILTER_DESC))) {
+                            result = FileDialogResult::OpenRomWithPatch(rom_path, patch_path);
+                        }
+                    }
+                },
+                FileDialogType::SaveState(default_path) => {
+                    if let Some(file_path) = tinyfiledialogs::save_file_dialog_with_filter("Save State", &default_path, DialogHandler::STATE_FILTER_PATT, DialogHandler::STATE_FILTER_DESC) {
                         result = FileDialogResult::SaveState(if file_path.contains('.') { file_path } else { format!("{}.p8s", file_path) });
                     }
                 },
+                FileDialogType::ExportOcto(default_path) => {
+                    if let Some(file_path) = tinyfiledialogs::save_file_dialog_with_filter("Export as Octo Source", &default_path, DialogHandler::OCTO_FILTER_PATT, DialogHandler::OCTO_FILTER_DESC) {
+                        result = FileDialogResult::ExportOcto(if file_path.contains('.') { file_path } else { format!("{}.8o", file_path) });
+                    }
+                },
+                FileDialogType::ImportOctoState => {
+                    if let Some(file_path) = tinyfiledialogs::open_file_dialog("Import Octo State", "", Some((DialogHandler::OCTO_STATE_FILTER_PATT, DialogHandler::OCTO_STATE_FILTER_DESC))) {
+                        result = FileDialogResult::ImportOctoState(file_path);
+                    }
+                },
+                FileDialogType::ExportOctoState(default_path) => {
+                    if let Some(file_path) = tinyfiledialogs::save_file_dialog_with_filter("Export Octo State", &default_path, DialogHandler::OCTO_STATE_FILTER_PATT, DialogHandler::OCTO_STATE_FILTER_DESC) {
+                        result = FileDialogResult::ExportOctoState(if file_path.contains('.') { file_path } else { format!("{}.json", file_path) });
+                    }
+                },
+                FileDialogType::ImportKeyProfile => {
+                    if let Some(file_path) = tinyfiledialogs::open_file_dialog("Import Key Profile", "", Some((DialogHandler::KEY_PROFILE_FILTER_PATT, DialogHandler::KEY_PROFILE_FILTER_DESC))) {
+                        result = FileDialogResult::ImportKeyProfile(file_path);
+                    }
+                },
+                FileDialogType::ExportKeyProfile(default_path) => {
+                    if let Some(file_path) = tinyfiledialogs::save_file_dialog_with_filter("Export Key Profile", &default_path, DialogHandler::KEY_PROFILE_FILTER_PATT, DialogHandler::KEY_PROFILE_FILTER_DESC) {
+                        result = FileDialogResult::ExportKeyProfile(if file_path.contains('.') { file_path } else { format!("{}.toml", file_path) });
+                    }
+                },
 
                 #[cfg(feature = "rom-download")]
                 FileDialogType::InputUrl => {
@@ -0,0 +1,61 @@
+use discord_rich_presence::{
+    activity::{Activity, Timestamps},
+    DiscordIpc, DiscordIpcClient,
+};
+use log::{info, warn};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Shows the played ROM and the time since it was loaded in the user's Discord profile.
+/// Discord doesn't have to be running, in which case nothing is published.
+pub struct DiscordPresence {
+    client: Option<DiscordIpcClient>,
+}
+
+impl DiscordPresence {
+    pub fn new(app_id: &str) -> Self {
+        let mut client = DiscordIpcClient::new(app_id);
+        match client.connect() {
+            Ok(_) => Self {
+                client: Some(client),
+            },
+            Err(e) => {
+                info!("Discord Rich Presence unavailable: {}", e);
+                Self { client: None }
+            }
+        }
+    }
+
+    /// Publishes the title of a newly loaded ROM, the elapsed time starts now.
+    pub fn set_playing(&mut self, title: &str) {
+        if let Some(client) = &mut self.client {
+            let start = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis() as i64);
+            let activity = Activity::new()
+                .details(title)
+                .state("Playing CHIP-8")
+                .timestamps(Timestamps::new().start(start));
+            if let Err(e) = client.set_activity(activity) {
+                warn!("Failed to update Discord Rich Presence: {}", e);
+                self.client = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(client) = &mut self.client {
+            if let Err(e) = client.clear_activity() {
+                warn!("Failed to clear Discord Rich Presence: {}", e);
+                self.client = None;
+            }
+        }
+    }
+}
+
+impl Drop for DiscordPresence {
+    fn drop(&mut self) {
+        if let Some(client) = &mut self.client {
+            let _ = client.close();
+        }
+    }
+}
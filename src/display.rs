@@ -1,4 +1,4 @@
-use crate::video_memory::{Plane, VideoMemory};
+use crate::error::Pich8Error;
 use glium::{
     glutin::{
         dpi::LogicalSize,
@@ -6,21 +6,140 @@ use glium::{
         window::{Icon, WindowBuilder},
         ContextBuilder,
     },
-    implement_vertex,
-    texture::{RawImage2d, Texture2d},
+    texture::{ClientFormat, RawImage2d, Texture2d},
     uniforms::MagnifySamplerFilter,
-    Display, Frame, Surface,
+    Display, Frame as GlFrame, Rect, Surface,
 };
+use log::{debug, info};
+use pich8::state::Thumbnail;
+use pich8::video_memory::VideoMemory;
+use std::borrow::Cow;
+
+/// A screen as one byte per pixel, where bit 0 is the first plane and bit 1 the second plane.
+/// Unlike the video memory it can be sent to other threads and over the network.
+#[derive(Debug, Default, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// Copies the screen in the video memory, reusing the pixel buffer.
+    pub fn copy_from(&mut self, vmem: &VideoMemory) {
+        self.width = vmem.render_width();
+        self.height = vmem.render_height();
+        self.pixels.clear();
+        self.pixels.extend(vmem.pixels());
+    }
+}
+
+/// Returns the color of a pixel, `colors` being the background, first plane, second plane and both planes.
+pub fn pixel_color(vmem: &VideoMemory, idx: usize, colors: &[[u8; 3]; 4]) -> [u8; 3] {
+    colors[vmem.pixel(idx) as usize]
+}
+
+/// Draws the screen into the image in the colors, only reallocating it if the resolution changed.
+pub fn copy_screenshot(vmem: &VideoMemory, colors: &[[u8; 3]; 4], image: &mut image::RgbImage) {
+    let (width, height) = (vmem.render_width() as u32, vmem.render_height() as u32);
+    if image.dimensions() != (width, height) {
+        *image = image::RgbImage::new(width, height);
+    }
+    for (pixel, value) in image.pixels_mut().zip(vmem.pixels()) {
+        *pixel = image::Rgb(colors[value as usize]);
+    }
+}
+
+/// Shows the screen in the colors of a palette. Implemented by the window, the framebuffer device
+/// and the stream, so the real time loop of the headless mode runs on any of them.
+/// The window additionally implements `WindowOutput` for the emulator and its GUI.
+pub trait DisplayOutput {
+    /// The background, first plane, second plane and both planes colors
+    fn colors(&self) -> [[u8; 3]; 4];
+
+    fn set_colors(&mut self, colors: [[u8; 3]; 4]);
+
+    /// Shows a screen captured from the video memory.
+    fn present(&mut self, frame: &Frame) -> Result<(), String>;
+
+    /// Returns the screen downscaled to the thumbnail size, in the current (undimmed) colors.
+    fn thumbnail(&self, vmem: &VideoMemory) -> Thumbnail {
+        let colors = self.colors();
+        let step_x = vmem.render_width() / Thumbnail::WIDTH;
+        let step_y = vmem.render_height() / Thumbnail::HEIGHT;
+        let mut pixels = Vec::with_capacity(Thumbnail::WIDTH * Thumbnail::HEIGHT * 3);
+        for y in 0..Thumbnail::HEIGHT {
+            for x in 0..Thumbnail::WIDTH {
+                let idx = y * step_y * vmem.render_width() + x * step_x;
+                pixels.extend_from_slice(&pixel_color(vmem, idx, &colors));
+            }
+        }
+        Thumbnail {
+            width: Thumbnail::WIDTH,
+            height: Thumbnail::HEIGHT,
+            pixels,
+        }
+    }
+
+    /// Returns the screen content in full resolution with the current colors.
+    fn screenshot(&self, vmem: &VideoMemory) -> image::RgbImage {
+        let mut image = image::RgbImage::new(0, 0);
+        self.copy_screenshot(vmem, &mut image);
+        image
+    }
+
+    /// Draws the screen into the image like `screenshot`, only reallocating it if the resolution changed.
+    fn copy_screenshot(&self, vmem: &VideoMemory, image: &mut image::RgbImage) {
+        copy_screenshot(vmem, &self.colors(), image);
+    }
+}
+
+/// The parts of an output only a window has, which the emulator needs for its GUI: the GL context
+/// the imgui overlay is drawn with, drawing the overlay on top of the screen, fullscreen and
+/// dimming while paused.
+pub trait WindowOutput: DisplayOutput + Sized {
+    fn open(
+        event_loop: &EventLoop<()>,
+        vsync: bool,
+        scale: Option<u32>,
+    ) -> Result<Self, Pich8Error>;
+
+    /// The GL context the overlay is drawn with
+    fn gl(&self) -> &Display;
+
+    fn request_redraw(&self);
+
+    fn fullscreen(&self) -> bool;
+
+    fn toggle_fullscreen(&mut self) -> Result<(), Pich8Error>;
+
+    fn dimmed(&self) -> bool;
+
+    /// Dims the displayed screen, e.g. while the emulation is paused.
+    /// Takes effect the next time the screen is copied.
+    fn set_dimmed(&mut self, dimmed: bool);
+
+    /// Draws the screen below the menu, which is `menu_height` pixels high, with `compare` next
+    /// to it and `overlay` on top. `vmem` is only given if it changed.
+    fn present_with_overlay(
+        &mut self,
+        vmem: Option<&VideoMemory>,
+        compare: Option<&VideoMemory>,
+        menu_height: u32,
+        overlay: impl FnOnce(&Display, &mut GlFrame) -> Result<(), Pich8Error>,
+    ) -> Result<(), Pich8Error>;
+}
 
 pub struct WindowDisplay {
     display: Display,
-    frame_buffer: [u8; 2 * WindowDisplay::C8_WIDTH * 2 * WindowDisplay::C8_HEIGHT * 3],
+    frame_buffer: Vec<u8>,
     width: u32,
     height: u32,
-    pub color_bg: [u8; 3],
-    pub color_plane_1: [u8; 3],
-    pub color_plane_2: [u8; 3],
-    pub color_plane_both: [u8; 3],
+    colors: [[u8; 3]; 4],
+    dimmed: bool,
+    // The last copied frame, only uploaded again when the screen changed and only recreated when
+    // the resolution changed
+    texture: Option<Texture2d>,
 }
 
 impl WindowDisplay {
@@ -30,18 +149,30 @@ impl WindowDisplay {
         / (WindowDisplay::C8_WIDTH as f32 / WindowDisplay::C8_HEIGHT as f32);
     const C8_WIDTH: usize = 64;
     const C8_HEIGHT: usize = 32;
+    const DIM_FACTOR: u16 = 3;
 
-    pub fn new(event_loop: &EventLoop<()>, vsync: bool) -> Result<Self, String> {
+    pub fn new(
+        event_loop: &EventLoop<()>,
+        vsync: bool,
+        scale: Option<u32>,
+    ) -> Result<Self, Pich8Error> {
         // Load icon
         let icon_file = include_bytes!("../data/icon/pich8_32.png");
         let icon_image = image::load_from_memory_with_format(icon_file, image::ImageFormat::Png)
-            .map_err(|e| format!("Failed to load icon: {}", e))?
+            .map_err(|e| Pich8Error::Icon(e.to_string()))?
             .into_rgba8();
         let (width, height) = icon_image.dimensions();
         let icon = Icon::from_rgba(icon_image.into_raw(), width, height)
-            .map_err(|e| format!("Failed to parse icon: {}", e))?;
+            .map_err(|e| Pich8Error::Icon(e.to_string()))?;
 
         // Create window
+        let (window_width, window_height) = match scale {
+            Some(scale) => (
+                (scale as usize * WindowDisplay::C8_WIDTH) as f32,
+                (scale as usize * WindowDisplay::C8_HEIGHT) as f32,
+            ),
+            None => (WindowDisplay::WINDOW_WIDTH, WindowDisplay::WINDOW_HEIGHT),
+        };
         let context = ContextBuilder::new().with_vsync(vsync);
         let builder = WindowBuilder::new()
             .with_window_icon(Some(icon))
@@ -50,12 +181,14 @@ impl WindowDisplay {
                 8.0 * WindowDisplay::C8_WIDTH as f32,
                 8.0 * WindowDisplay::C8_HEIGHT as f32,
             ))
-            .with_inner_size(LogicalSize::new(
-                WindowDisplay::WINDOW_WIDTH,
-                WindowDisplay::WINDOW_HEIGHT,
-            ));
-        let display = Display::new(builder, context, event_loop)
-            .map_err(|e| format!("Failed to create display: {}", e))?;
+            .with_inner_size(LogicalSize::new(window_width, window_height));
+        let display = Display::new(builder, context, event_loop)?;
+        info!(
+            "Created {}x{} window (vsync {})",
+            window_width,
+            window_height,
+            if vsync { "on" } else { "off" }
+        );
 
         {
             // Unfortunately, the position cannot be set before constructing the window.
@@ -82,97 +215,161 @@ impl WindowDisplay {
             color_bg[2] as f32 / 255.0,
             1.0,
         );
-        target
-            .finish()
-            .map_err(|e| format!("Failed to swap buffers: {}", e))?;
+        target.finish()?;
 
         Ok(Self {
             display,
-            frame_buffer: [0; 2 * Self::C8_WIDTH * 2 * Self::C8_HEIGHT * 3],
+            frame_buffer: Vec::with_capacity(2 * Self::C8_WIDTH * 2 * Self::C8_HEIGHT * 3),
             width: 0,
             height: 0,
-            color_bg,
-            color_plane_1: [0; 3],
-            color_plane_2: [0; 3],
-            color_plane_both: [0; 3],
+            colors: [color_bg; 4],
+            dimmed: false,
+            texture: None,
         })
     }
 
-    pub fn display(&self) -> &Display {
-        &self.display
+    fn dim(&self, color: [u8; 3]) -> [u8; 3] {
+        if self.dimmed {
+            let dim = |c: u8| (c as u16 / Self::DIM_FACTOR) as u8;
+            [dim(color[0]), dim(color[1]), dim(color[2])]
+        } else {
+            color
+        }
     }
 
-    fn copy_frame(&mut self, vmem: &VideoMemory) {
-        for idx in 0..vmem.render_width() * vmem.render_height() {
-            let buf_idx = idx * 3;
-            if vmem.get_index_plane(Plane::First, idx) && vmem.get_index_plane(Plane::Second, idx) {
-                self.frame_buffer[buf_idx..buf_idx + 3].copy_from_slice(&self.color_plane_both);
-            } else if vmem.get_index_plane(Plane::First, idx) {
-                self.frame_buffer[buf_idx..buf_idx + 3].copy_from_slice(&self.color_plane_1);
-            } else if vmem.get_index_plane(Plane::Second, idx) {
-                self.frame_buffer[buf_idx..buf_idx + 3].copy_from_slice(&self.color_plane_2);
-            } else {
-                self.frame_buffer[buf_idx..buf_idx + 3].copy_from_slice(&self.color_bg);
+    /// Copies the screen into the frame buffer, with `compare` next to it if given.
+    /// If the resolutions differ, `compare` is scaled to the resolution of `vmem`.
+    /// The rows are stored bottom to top like in textures, so the frame buffer can be uploaded as is.
+    fn copy_frame(&mut self, vmem: &VideoMemory, compare: Option<&VideoMemory>) {
+        let colors = self.colors.map(|color| self.dim(color));
+        let (width, height) = (vmem.render_width(), vmem.render_height());
+        let screens = [Some(vmem), compare];
+        let row_len = width * 3 * screens.iter().flatten().count();
+        self.frame_buffer.resize(row_len * height, 0);
+        let rows = self.frame_buffer.chunks_exact_mut(row_len);
+        for (row, y) in rows.zip((0..height).rev()) {
+            let parts = row.chunks_exact_mut(width * 3);
+            for (part, screen) in parts.zip(screens.iter().flatten()) {
+                let (screen_width, screen_height) = (screen.render_width(), screen.render_height());
+                let screen_y = y * screen_height / height;
+                let pixels = part.chunks_exact_mut(3);
+                if screen_width == width {
+                    for (pixel, value) in pixels.zip(screen.row(screen_y)) {
+                        pixel.copy_from_slice(&colors[value as usize]);
+                    }
+                } else {
+                    for (x, pixel) in pixels.enumerate() {
+                        let idx = screen_y * screen_width + x * screen_width / width;
+                        pixel.copy_from_slice(&pixel_color(screen, idx, &colors));
+                    }
+                }
             }
         }
-        self.width = vmem.render_width() as u32;
-        self.height = vmem.render_height() as u32;
+        self.width = (row_len / 3) as u32;
+        self.height = height as u32;
     }
 
-    pub fn prepare(
-        &mut self,
-        vmem: Option<&VideoMemory>,
-        menu_height: u32,
-    ) -> Result<Frame, String> {
-        // Copy over new frame
-        if let Some(vmem) = vmem {
-            self.copy_frame(vmem);
+    /// Uploads the frame buffer into the texture.
+    fn upload(&mut self) -> Result<(), Pich8Error> {
+        let img = RawImage2d {
+            data: Cow::Borrowed(&self.frame_buffer),
+            width: self.width,
+            height: self.height,
+            format: ClientFormat::U8U8U8,
+        };
+        match &self.texture {
+            Some(texture) if texture.dimensions() == (self.width, self.height) => {
+                let rect = Rect {
+                    left: 0,
+                    bottom: 0,
+                    width: self.width,
+                    height: self.height,
+                };
+                texture.write(rect, img);
+            }
+            _ => self.texture = Some(Texture2d::new(&self.display, img)?),
         }
-        let frame_len = self.width as usize * self.height as usize * 3;
+        Ok(())
+    }
 
-        // Prepare texture
+    /// Starts a frame showing the texture above the menu, which is `menu_height` pixels high.
+    fn draw(&self, menu_height: u32) -> GlFrame {
         let mut frame = self.display.draw();
+        let color_bg = self.dim(self.colors[0]);
         frame.clear_color(
-            self.color_bg[0] as f32 / 255.0,
-            self.color_bg[1] as f32 / 255.0,
-            self.color_bg[2] as f32 / 255.0,
+            color_bg[0] as f32 / 255.0,
+            color_bg[1] as f32 / 255.0,
+            color_bg[2] as f32 / 255.0,
             1.0,
         );
-        let img = RawImage2d::from_raw_rgb_reversed(
-            &self.frame_buffer[..frame_len],
-            (self.width, self.height),
-        );
-        let texture = Texture2d::new(&self.display, img)
-            .map_err(|e| format!("Failed to create texture: {}", e))?;
-
-        let window_size = self.display.gl_window().window().inner_size();
-        let height = window_size.height - menu_height;
-        texture.as_surface().blit_whole_color_to(
-            &frame,
-            &glium::BlitTarget {
-                left: 0,
-                bottom: 0,
-                width: window_size.width as i32,
-                height: height as i32,
-            },
-            MagnifySamplerFilter::Nearest,
-        );
+        if let Some(texture) = &self.texture {
+            let window_size = self.display.gl_window().window().inner_size();
+            let height = window_size.height - menu_height;
+            texture.as_surface().blit_whole_color_to(
+                &frame,
+                &glium::BlitTarget {
+                    left: 0,
+                    bottom: 0,
+                    width: window_size.width as i32,
+                    height: height as i32,
+                },
+                MagnifySamplerFilter::Nearest,
+            );
+        }
+        frame
+    }
+}
 
-        Ok(frame)
+impl DisplayOutput for WindowDisplay {
+    fn colors(&self) -> [[u8; 3]; 4] {
+        self.colors
     }
 
-    pub fn render(&self, frame: Frame) -> Result<(), String> {
-        frame
-            .finish()
-            .map_err(|e| format!("Failed to swap buffers: {}", e))?;
-        Ok(())
+    fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+        self.colors = colors;
     }
 
-    pub fn fullscreen(&self) -> bool {
-        self.display.gl_window().window().fullscreen() != None
+    /// Draws the screen filling the window, without an overlay.
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        let colors = self.colors.map(|color| self.dim(color));
+        self.frame_buffer.resize(frame.width * frame.height * 3, 0);
+        // Bottom to top like in `copy_frame`
+        let rows = self.frame_buffer.chunks_exact_mut(frame.width * 3).rev();
+        for (row, values) in rows.zip(frame.pixels.chunks_exact(frame.width)) {
+            for (pixel, &value) in row.chunks_exact_mut(3).zip(values) {
+                pixel.copy_from_slice(&colors[value as usize & 3]);
+            }
+        }
+        self.width = frame.width as u32;
+        self.height = frame.height as u32;
+        self.upload()
+            .and_then(|()| Ok(self.draw(0).finish()?))
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl WindowOutput for WindowDisplay {
+    fn open(
+        event_loop: &EventLoop<()>,
+        vsync: bool,
+        scale: Option<u32>,
+    ) -> Result<Self, Pich8Error> {
+        Self::new(event_loop, vsync, scale)
     }
 
-    pub fn toggle_fullscreen(&mut self) -> Result<(), String> {
+    fn gl(&self) -> &Display {
+        &self.display
+    }
+
+    fn request_redraw(&self) {
+        self.display.gl_window().window().request_redraw();
+    }
+
+    fn fullscreen(&self) -> bool {
+        self.display.gl_window().window().fullscreen().is_some()
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<(), Pich8Error> {
         let gl_window = self.display.gl_window();
         let monitor_handle = gl_window.window().current_monitor();
         let state = if gl_window.window().fullscreen().is_none() {
@@ -182,15 +379,42 @@ impl WindowDisplay {
         } else {
             None
         };
-        gl_window.window().set_cursor_visible(state == None);
+        debug!(
+            "{} fullscreen",
+            if state.is_some() {
+                "Entering"
+            } else {
+                "Leaving"
+            }
+        );
+        gl_window.window().set_cursor_visible(state.is_none());
         gl_window.window().set_fullscreen(state);
         Ok(())
     }
-}
 
-#[derive(Copy, Clone)]
-struct Vertex {
-    position: [f32; 2],
-    tex_coords: [f32; 2],
+    fn dimmed(&self) -> bool {
+        self.dimmed
+    }
+
+    fn set_dimmed(&mut self, dimmed: bool) {
+        self.dimmed = dimmed;
+    }
+
+    fn present_with_overlay(
+        &mut self,
+        vmem: Option<&VideoMemory>,
+        compare: Option<&VideoMemory>,
+        menu_height: u32,
+        overlay: impl FnOnce(&Display, &mut GlFrame) -> Result<(), Pich8Error>,
+    ) -> Result<(), Pich8Error> {
+        if let Some(vmem) = vmem {
+            self.copy_frame(vmem, compare);
+            self.upload()?;
+        }
+        let mut frame = self.draw(menu_height);
+        // A frame must be finished even if drawing the overlay failed
+        let drawn = overlay(&self.display, &mut frame);
+        frame.finish()?;
+        drawn
+    }
 }
-implement_vertex!(Vertex, position, tex_coords);
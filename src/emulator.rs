@@ -1,18 +1,266 @@
-use crate::cpu::{Breakpoint, CPU};
+use crate::capture::CaptureInfo;
+use crate::cheats::Cheats;
+use crate::chip8_archive::ProgramInfo;
+use crate::cli::Cli;
+use crate::config::{
+    Config, HexColor, KeyMap, KeyProfile, MemoryRange, Palette, PaletteConfig, Quirks,
+    QuirksConfig, Settings,
+};
+use crate::crash;
 use crate::dialog_handler::{DialogHandler, FileDialogResult, FileDialogType};
-use crate::display::WindowDisplay;
+use crate::display::{WindowDisplay, WindowOutput};
+use crate::error::Pich8Error;
 use crate::fps_counter::FpsCounter;
-use crate::gui::GUI;
+use crate::frame_export::FrameExport;
+use crate::frame_limiter::{FrameLimiter, FramePacing};
+use crate::game_db::GameDb;
 use crate::gui::{Color, Quirk};
-use crate::sound::AudioPlayer;
+use crate::gui::{RomDetails, SpeedInfo, GUI};
+use crate::input::{Control, InputSource, Keypad};
+use crate::netplay::Netplay;
+use crate::playlist::Playlist;
+use crate::recent::RecentFiles;
+use crate::render_thread::RenderThread;
+use crate::sound::{AudioPlayer, SoundOutput};
+use crate::step_history::StepHistory;
 use glium::glutin::{
     event::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
 };
-use std::{fs, time::Instant};
+use log::{debug, info, warn};
+use pich8::assembler;
+use pich8::cpu::{self, Breakpoint, CPU};
+use pich8::disassembler;
+use pich8::octo::OctoState;
+use pich8::patch;
+use pich8::replay::{Recorder, Recording};
+use pich8::rom::{self, FileType, Platform};
+use pich8::state::{StateFile, Thumbnail};
+use pich8::symbols::Symbols;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "discord")]
+use crate::discord::DiscordPresence;
+#[cfg(feature = "remote")]
+use crate::remote::{RemoteCommand, RemoteResponse, RemoteServer, RemoteState};
 #[cfg(feature = "rom-download")]
 use crate::rom_downloader::{DownloadResult, RomDownloader};
+#[cfg(feature = "plugins")]
+use crate::{display::DisplayOutput, plugin::Plugin};
+#[cfg(feature = "scripting")]
+use crate::{gui::ScriptCommand, scripting::Script};
+
+/// What a download is for, since ROMs, the chip8Archive index and its ROMs are handled differently.
+#[cfg(feature = "rom-download")]
+enum DownloadTarget {
+    Url,
+    ArchiveIndex,
+    ArchiveRom(String),
+}
+
+/// What happened in a frame run by `Emulator::run_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameResult {
+    /// The frame's instructions ran and the timers were updated
+    Completed,
+    /// The emulation is paused, e.g. because a breakpoint was hit within the frame
+    Paused,
+    /// The frame has to wait for the keys of the netplay peer
+    Waiting,
+}
+
+/// Configures an `Emulator` before opening its window, e.g.
+/// `EmulatorBuilder::new().sound(false).quirks(QuirksConfig::Preset(QuirksPreset::Octo)).speed(1200)`.
+/// Options that aren't set are taken from the config file.
+#[derive(Default)]
+pub struct EmulatorBuilder {
+    config_path: Option<PathBuf>,
+    settings: Settings,
+    no_sound: bool,
+    scale: Option<u32>,
+    fullscreen: bool,
+    frame_pacing: Option<FramePacing>,
+    paused: bool,
+    playlist_interval: Option<u64>,
+    cheats: Vec<String>,
+    netplay_host: Option<u16>,
+    netplay_connect: Option<String>,
+    export_frames: Option<PathBuf>,
+    #[cfg(feature = "plugins")]
+    plugins: Vec<String>,
+    #[cfg(feature = "remote")]
+    remote_port: Option<u16>,
+    #[cfg(all(feature = "remote", unix))]
+    rpc_socket: Option<PathBuf>,
+    #[cfg(all(feature = "remote", unix))]
+    control_pipe: Option<PathBuf>,
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the options passed on the command line.
+    pub fn from_cli(cli: &Cli) -> Self {
+        let mut builder = Self::new()
+            .config(cli.config.clone())
+            .sound(!cli.no_sound)
+            .display(cli.scale, cli.fullscreen)
+            .speed(cli.speed)
+            .quirks(cli.quirks.map(QuirksConfig::Preset))
+            .palette(cli.palette.map(PaletteConfig::Preset))
+            .mute(cli.mute)
+            .paused(cli.paused)
+            .playlist_interval(cli.cycle)
+            .cheats(cli.cheats.clone());
+        if cli.vsync {
+            builder = builder.frame_pacing(FramePacing::Vsync);
+        } else if let Some(frame_pacing) = cli.frame_pacing {
+            builder = builder.frame_pacing(frame_pacing);
+        }
+        if let Some(port) = cli.netplay_host {
+            builder = builder.host_netplay(port);
+        } else if let Some(address) = &cli.netplay_connect {
+            builder = builder.join_netplay(address.clone());
+        }
+        if let Some(path) = &cli.export_frames {
+            builder = builder.export_frames(path.clone());
+        }
+        #[cfg(feature = "plugins")]
+        for plugin in &cli.plugins {
+            builder = builder.plugin(plugin.clone());
+        }
+        #[cfg(feature = "remote")]
+        if let Some(port) = cli.remote {
+            builder = builder.remote(port);
+        }
+        #[cfg(all(feature = "remote", unix))]
+        if let Some(path) = &cli.rpc {
+            builder = builder.rpc(path.clone());
+        }
+        #[cfg(all(feature = "remote", unix))]
+        if let Some(path) = &cli.control_pipe {
+            builder = builder.control_pipe(path.clone());
+        }
+        builder
+    }
+
+    /// Path of the config file, the default location is used if `None`.
+    pub fn config(mut self, path: Option<PathBuf>) -> Self {
+        self.config_path = path;
+        self
+    }
+
+    /// Without sound, no audio device is opened, so the emulator also runs on machines without one.
+    pub fn sound(mut self, sound: bool) -> Self {
+        self.no_sound = !sound;
+        self
+    }
+
+    /// Initial window size as a multiple of the 64x32 CHIP-8 screen and whether to start in fullscreen mode.
+    pub fn display(mut self, scale: impl Into<Option<u32>>, fullscreen: bool) -> Self {
+        self.scale = scale.into();
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn frame_pacing(mut self, frame_pacing: FramePacing) -> Self {
+        self.frame_pacing = Some(frame_pacing);
+        self
+    }
+
+    pub fn speed(mut self, speed: impl Into<Option<u32>>) -> Self {
+        self.settings.speed = speed.into();
+        self
+    }
+
+    pub fn quirks(mut self, quirks: impl Into<Option<QuirksConfig>>) -> Self {
+        self.settings.quirks = quirks.into();
+        self
+    }
+
+    pub fn palette(mut self, palette: impl Into<Option<PaletteConfig>>) -> Self {
+        self.settings.palette = palette.into();
+        self
+    }
+
+    /// Starts with audio muted, otherwise the config decides.
+    pub fn mute(mut self, mute: bool) -> Self {
+        self.settings.mute = mute.then_some(true);
+        self
+    }
+
+    /// Starts paused after loading, otherwise the config decides.
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    pub fn playlist_interval(mut self, seconds: Option<u64>) -> Self {
+        self.playlist_interval = seconds;
+        self
+    }
+
+    /// Names of the cheats to enable from the ROM's cheat file.
+    pub fn cheats(mut self, cheats: Vec<String>) -> Self {
+        self.cheats = cheats;
+        self
+    }
+
+    pub fn host_netplay(mut self, port: u16) -> Self {
+        self.netplay_host = Some(port);
+        self
+    }
+
+    pub fn join_netplay(mut self, address: String) -> Self {
+        self.netplay_connect = Some(address);
+        self
+    }
+
+    /// File to publish the frames to for capture tools, otherwise the config decides.
+    pub fn export_frames(mut self, path: PathBuf) -> Self {
+        self.export_frames = Some(path);
+        self
+    }
+
+    /// Loads an output plugin given as `PATH` or `PATH=ARGS`, in addition to those in the config.
+    #[cfg(feature = "plugins")]
+    pub fn plugin(mut self, spec: String) -> Self {
+        self.plugins.push(spec);
+        self
+    }
+
+    /// Port of the remote control server, otherwise the config decides.
+    #[cfg(feature = "remote")]
+    pub fn remote(mut self, port: u16) -> Self {
+        self.remote_port = Some(port);
+        self
+    }
+
+    /// Unix socket of the JSON-RPC server, otherwise the config decides.
+    #[cfg(all(feature = "remote", unix))]
+    pub fn rpc(mut self, path: PathBuf) -> Self {
+        self.rpc_socket = Some(path);
+        self
+    }
+
+    /// Named pipe to read plain commands from, otherwise the config decides.
+    #[cfg(all(feature = "remote", unix))]
+    pub fn control_pipe(mut self, path: PathBuf) -> Self {
+        self.control_pipe = Some(path);
+        self
+    }
+
+    /// Opens the window and, unless disabled, the audio device.
+    pub fn build(self, event_loop: &EventLoop<()>) -> Result<Emulator, Pich8Error> {
+        Emulator::new(event_loop, self)
+    }
+}
 
 enum LoadedType {
     Nothing,
@@ -20,127 +268,1431 @@ enum LoadedType {
     State(Vec<u8>),
 }
 
-pub struct Emulator {
+/// The emulator with its GUI in a window, which is shown by `D`.
+pub struct Emulator<D: WindowOutput = WindowDisplay> {
     cpu: CPU,
+    compare_cpu: Option<CPU>,
     cpu_speed: u32,
-    display: WindowDisplay,
+    display: D,
     gui: GUI,
     sound: AudioPlayer,
     fps_counter: FpsCounter,
+    emulated_fps_counter: FpsCounter,
+    frame_count: u64,
+    unsaved_frames: u64,
+    stuck_frames: u64,
+    saved_rpl: [u8; 8],
+    persist: Option<MemoryRange>,
+    persisted: Option<(PathBuf, MemoryRange)>,
+    // The session settings replaced by the overrides of the loaded ROM, restored for the next one
+    replaced_settings: Settings,
+    cheats: Cheats,
+    cheat_names: Vec<String>,
+    frame_limiter: FrameLimiter,
     mute: bool,
-    input: [bool; 16],
+    keypad: Keypad,
+    /// Replaces the keys of the keypad while set, e.g. to play back a recording
+    playback: Option<Box<dyn InputSource>>,
     loaded: LoadedType,
     pause: bool,
     step: bool,
     step_timers: bool,
+    step_back: bool,
+    /// Stack depth to run to after a step over or out, pauses once the stack is shallower
+    return_depth: Option<usize>,
+    step_history: StepHistory,
     frame_time: Instant,
-    last_timer: Instant,
-    last_cycle: Instant,
     pause_time: Instant,
     dialog_handler: DialogHandler,
     modifiers_state: ModifiersState,
-    last_correction_cpu: Instant,
-    counter_cpu: u32,
-    last_correction_timer: Instant,
-    counter_timer: u32,
+    /// Instructions left over from the last frame if the speed isn't a multiple of the frame rate
+    cycle_remainder: u32,
     force_redraw: bool,
+    config: Config,
+    config_path: Option<PathBuf>,
+    rom_hash: Option<String>,
+    rom_name: Option<String>,
+    recorder: Option<Recorder>,
+    netplay: Option<Netplay>,
+    /// Written on a render thread, unlike the window, which presents on the event loop thread
+    frame_export: Option<RenderThread<image::RgbImage>>,
+    /// The keys the CPU sees in the running frame, which are the keys of both players during netplay
+    frame_input: [bool; 16],
+    keymap: KeyMap,
+    recent_files: RecentFiles,
+    game_db: GameDb,
+    rom_info: Option<ProgramInfo>,
+    playlist: Option<Playlist>,
+    pending_zip: Option<(Vec<u8>, Option<PathBuf>)>,
+
+    #[cfg(feature = "rom-download")]
+    download_sha1: Option<String>,
+    #[cfg(feature = "rom-download")]
+    download_target: DownloadTarget,
+    #[cfg(feature = "rom-download")]
+    archive_index: Option<String>,
 
     #[cfg(feature = "rom-download")]
     rom_downloader: RomDownloader,
+
+    #[cfg(feature = "discord")]
+    discord: Option<DiscordPresence>,
+    #[cfg(feature = "discord")]
+    presence_hash: Option<String>,
+
+    #[cfg(feature = "scripting")]
+    script: Script,
+
+    #[cfg(feature = "plugins")]
+    plugins: Vec<Plugin>,
+
+    #[cfg(feature = "remote")]
+    remote: Option<RemoteServer>,
 }
 
-impl Emulator {
+impl<D: WindowOutput> Emulator<D> {
     const CPU_FREQUENCY: u16 = 720;
     const TIMER_FREQUENCY: u8 = 60;
-    const NANOS_PER_TIMER: u64 = 1_000_000_000 / Emulator::TIMER_FREQUENCY as u64;
     const MAX_FILE_SIZE: u32 = u16::MAX as u32 + 10000;
+    const AUTOSAVE_FILE: &'static str = "autosave.p8s";
+    const RPL_FLAGS_DIR: &'static str = "flags";
+    const MEMORY_DIR: &'static str = "memory";
+    #[cfg(feature = "rom-download")]
+    const ROMS_DIR: &'static str = "roms";
+    #[cfg(feature = "rom-download")]
+    const MAX_INDEX_SIZE: u64 = 16 * 1024 * 1024;
+    // Ask before quitting after 5 minutes of playing without saving
+    const UNSAVED_FRAMES_WARNING: u64 = 5 * 60 * Self::TIMER_FREQUENCY as u64;
+
+    fn new(event_loop: &EventLoop<()>, options: EmulatorBuilder) -> Result<Self, Pich8Error> {
+        // Load config, settings passed to the builder take precedence
+        let config_path = options.config_path.or_else(Config::default_path);
+        let config_result = match &config_path {
+            Some(path) => {
+                Config::load(path).inspect(|_| info!("Loaded config from {}", path.display()))
+            }
+            None => Ok(Config::default()),
+        };
+        let mut config = config_result.as_ref().cloned().unwrap_or_default();
+        config.settings = config.settings.merged(&options.settings);
+        config.start_paused |= options.paused;
+        config.playlist_interval = options.playlist_interval.or(config.playlist_interval);
+        config.frame_pacing = options.frame_pacing.unwrap_or(config.frame_pacing);
 
-    pub fn new(event_loop: &EventLoop<()>, vsync: bool) -> Result<Self, String> {
-        let display = WindowDisplay::new(&event_loop, vsync)?;
+        let vsync = config.frame_pacing == FramePacing::Vsync;
+        let display = D::open(event_loop, vsync, options.scale)?;
         let mut cpu = CPU::new();
         cpu.load_bootrom();
-        cpu.draw = true;
-        let cpu_speed = Emulator::CPU_FREQUENCY as u32;
+        let cpu_speed = Self::CPU_FREQUENCY as u32;
 
         // Initialize GUI
-        let mut gui = GUI::new(display.display());
+        let mut gui = GUI::new(display.gl())?;
         gui.cpu_speed = cpu_speed;
         gui.volume = 0.25;
+        gui.flag_fullscreen = options.fullscreen;
+        if let Err(msg) = config_result {
+            gui.display_error(&msg);
+        }
+        let game_db = GameDb::load(Config::dir().as_deref()).unwrap_or_else(|msg| {
+            gui.display_error(&msg);
+            GameDb::default()
+        });
 
         let now = Instant::now();
-        Ok(Self {
+        let mut emu = Self {
             cpu,
+            compare_cpu: None,
             cpu_speed,
             display,
             gui,
-            sound: AudioPlayer::new().expect("Failed to create sound output device"),
+            sound: if options.no_sound {
+                AudioPlayer::silent()
+            } else {
+                AudioPlayer::new()?
+            },
             mute: false,
-            input: [false; 16],
+            keypad: Keypad::default(),
+            playback: None,
             loaded: LoadedType::Nothing,
             pause: false,
             step: false,
             step_timers: false,
+            step_back: false,
+            return_depth: None,
+            step_history: StepHistory::new(),
             frame_time: now,
-            last_timer: now,
-            last_cycle: now,
             pause_time: now,
             dialog_handler: DialogHandler::new(),
             fps_counter: FpsCounter::new(),
+            emulated_fps_counter: FpsCounter::new(),
+            frame_count: 0,
+            unsaved_frames: 0,
+            stuck_frames: 0,
+            saved_rpl: [0; 8],
+            persist: None,
+            persisted: None,
+            replaced_settings: Settings::default(),
+            cheats: Cheats::default(),
+            cheat_names: options.cheats,
+            frame_limiter: FrameLimiter::new(config.frame_pacing),
             modifiers_state: ModifiersState::empty(),
-            last_correction_cpu: Instant::now(),
-            counter_cpu: 0,
-            last_correction_timer: Instant::now(),
-            counter_timer: 0,
+            cycle_remainder: 0,
             force_redraw: true,
+            config,
+            config_path,
+            rom_hash: None,
+            rom_name: None,
+            recorder: None,
+            netplay: None,
+            frame_export: None,
+            frame_input: [false; 16],
+            keymap: KeyMap::default(),
+            recent_files: RecentFiles::load(Config::dir().as_deref()),
+            game_db,
+            rom_info: None,
+            playlist: None,
+            pending_zip: None,
+
+            #[cfg(feature = "rom-download")]
+            download_sha1: None,
+            #[cfg(feature = "rom-download")]
+            download_target: DownloadTarget::Url,
+            #[cfg(feature = "rom-download")]
+            archive_index: None,
+
+            #[cfg(feature = "rom-download")]
+            rom_downloader: RomDownloader::new(),
+
+            #[cfg(feature = "discord")]
+            discord: None,
+            #[cfg(feature = "discord")]
+            presence_hash: None,
+
+            #[cfg(feature = "scripting")]
+            script: Script::new(),
+
+            #[cfg(feature = "plugins")]
+            plugins: Vec::new(),
+
+            #[cfg(feature = "remote")]
+            remote: None,
+        };
+        #[cfg(feature = "discord")]
+        if let Some(app_id) = &emu.config.discord_app_id {
+            emu.discord = Some(DiscordPresence::new(app_id));
+        }
+        if let Some(port) = options.netplay_host {
+            match Netplay::host(port) {
+                Ok(netplay) => {
+                    emu.netplay = Some(netplay);
+                    emu.gui.display_notice("Waiting for netplay peer");
+                }
+                Err(msg) => emu.gui.display_error(&msg),
+            }
+        } else if let Some(address) = options.netplay_connect {
+            emu.netplay = Some(Netplay::connect(address));
+        }
+        if let Some(path) = options.export_frames.or(emu.config.export_frames.clone()) {
+            let export = FrameExport::open(&path).and_then(|mut export| {
+                RenderThread::spawn("frame export", move |image| export.publish(image))
+            });
+            match export {
+                Ok(export) => emu.frame_export = Some(export),
+                Err(msg) => emu.gui.display_error(&msg),
+            }
+        }
+        #[cfg(feature = "plugins")]
+        for spec in emu.config.plugins.clone().iter().chain(&options.plugins) {
+            match Plugin::load(spec) {
+                Ok(plugin) => emu.plugins.push(plugin),
+                Err(msg) => emu.gui.display_error(&msg),
+            }
+        }
+        #[cfg(feature = "remote")]
+        {
+            let server = RemoteServer::new();
+            let mut listening = Vec::new();
+            if let Some(port) = options.remote_port.or(emu.config.remote_port) {
+                listening.push(server.listen(port));
+            }
+            #[cfg(unix)]
+            if let Some(path) = options.rpc_socket.or(emu.config.rpc_socket.clone()) {
+                listening.push(server.listen_rpc(&path));
+            }
+            #[cfg(unix)]
+            if let Some(path) = options.control_pipe.or(emu.config.control_pipe.clone()) {
+                listening.push(server.listen_pipe(&path));
+            }
+            for result in &listening {
+                if let Err(msg) = result {
+                    emu.gui.display_error(msg);
+                }
+            }
+            if listening.iter().any(Result::is_ok) {
+                emu.remote = Some(server);
+            }
+        }
+        let settings = emu.config.settings.clone();
+        emu.apply_settings(&settings);
+        emu.gui.set_recent_files(emu.recent_files.files());
+        emu.gui.set_debug_layout(&emu.config.debug_layout);
+
+        Ok(emu)
+    }
+
+    /// Applies the known and per-game settings of the currently loaded ROM on top of the session
+    /// settings, after restoring the session settings the previous ROM's overrides replaced.
+    fn apply_game_settings(&mut self) {
+        let replaced = std::mem::take(&mut self.replaced_settings);
+        self.apply_settings(&replaced);
+
+        let mut known = self.rom_info.as_ref().map(|info| {
+            info!("Applying recommended settings for {}", info.title);
+            info.settings()
+        });
+        if let Some(hash) = &self.rom_hash {
+            if let Some(entry) = self.game_db.get(hash) {
+                info!(
+                    "Applying known settings for {}",
+                    entry.title.as_deref().unwrap_or(hash)
+                );
+                known = Some(known.unwrap_or_default().merged(&entry.settings));
+            }
+            if self.config.game.contains_key(hash) {
+                info!("Applying per-game settings");
+            }
+        }
+        let overrides = self
+            .config
+            .overrides_for(self.rom_hash.as_deref(), known.as_ref());
+        self.replaced_settings = self.current_settings().specified_in(&overrides);
+        self.persist = overrides.persist.or(self.config.settings.persist);
+        self.apply_settings(&overrides);
+    }
+
+    /// Applies the specified values of the settings to the menus and the emulator.
+    fn apply_settings(&mut self, settings: &Settings) {
+        if let Some(speed) = settings.speed {
+            self.gui.cpu_speed = speed;
+        }
+        match settings.quirks {
+            Some(QuirksConfig::Preset(preset)) => self.gui.set_quirks_preset(preset),
+            Some(QuirksConfig::Custom(quirks)) => {
+                let quirks_settings = self.gui.quirks_settings_mut();
+                *quirks_settings.get_mut(Quirk::LoadStore) = quirks.load_store;
+                *quirks_settings.get_mut(Quirk::Shift) = quirks.shift;
+                *quirks_settings.get_mut(Quirk::Draw) = quirks.draw;
+                *quirks_settings.get_mut(Quirk::Jump) = quirks.jump;
+                *quirks_settings.get_mut(Quirk::VfOrder) = quirks.vf_order;
+                *quirks_settings.get_mut(Quirk::PartialWrapH) = quirks.partial_wrap_h;
+                *quirks_settings.get_mut(Quirk::PartialWrapV) = quirks.partial_wrap_v;
+            }
+            None => (),
+        }
+        match settings.palette {
+            Some(PaletteConfig::Preset(preset)) => self.gui.set_color_preset(preset),
+            Some(PaletteConfig::Custom(palette)) => {
+                let color_settings = self.gui.color_settings();
+                *color_settings.get_mut(Color::Background) = palette.background.0;
+                *color_settings.get_mut(Color::Plane1) = palette.plane_1.0;
+                *color_settings.get_mut(Color::Plane2) = palette.plane_2.0;
+                *color_settings.get_mut(Color::PlaneBoth) = palette.plane_both.0;
+                color_settings.changed = true;
+            }
+            None => (),
+        }
+        if let Some(keys) = settings.keys {
+            self.keymap = keys;
+            self.gui.set_keymap(keys);
+        }
+        if let Some(volume) = settings.volume {
+            self.gui.volume = volume.min(100) as f32 / 100.0;
+        }
+        if let Some(mute) = settings.mute {
+            self.gui.flag_mute = mute;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.save_memory();
+        self.step_history.clear();
+        match &self.loaded {
+            LoadedType::Rom(rom) => {
+                self.cpu = CPU::new();
+                match self.cpu.load_rom(rom) {
+                    Ok(_) => {
+                        if self.config.start_paused {
+                            self.gui.flag_pause = true;
+                        } else if !self.gui.flag_debug {
+                            self.gui.flag_pause = false;
+                        }
+                    }
+                    Err(_) => self.gui.display_error("Data is not a valid ROM!"),
+                }
+            }
+            LoadedType::State(state) => {
+                match CPU::from_state(state) {
+                    Ok(cpu) => self.cpu = cpu,
+                    Err(msg) => self.gui.display_error(&msg),
+                }
+                self.gui.flag_pause = self.config.start_paused;
+            }
+            _ => (),
+        }
+
+        // Continue in the same window with the current settings, without catching up on the time
+        // spent e.g. in the file dialog
+        self.apply_quirks();
+        if let LoadedType::Rom(_) = self.loaded {
+            self.load_rpl_flags();
+            self.load_memory();
+        } else {
+            self.persisted = None;
+        }
+        self.start_compare();
+        self.cheats.restart();
+        self.frame_limiter.reset(Instant::now());
+        self.cycle_remainder = 0;
+        self.force_redraw = true;
+        self.stop_recording();
+        self.playback = None;
+        self.frame_count = 0;
+        self.unsaved_frames = 0;
+        self.stuck_frames = 0;
+    }
+
+    /// Loads a ROM, `info` is its metadata from the chip8Archive if available and `name` its file
+    /// name without extension, which the capture templates use for `{rom}`.
+    pub fn load_rom(&mut self, rom: &[u8], info: Option<ProgramInfo>, name: Option<String>) {
+        if let Err(msg) = rom::validate(rom) {
+            self.gui.display_error(&msg);
+            return;
+        }
+        self.rom_info = info;
+        self.rom_name = name;
+        self.loaded = LoadedType::Rom(rom.to_vec());
+        self.rom_hash = Some(rom::sha1_hex(rom));
+        info!(
+            "Loading ROM ({} bytes, SHA-1 {})",
+            rom.len(),
+            self.rom_hash.as_deref().unwrap_or_default()
+        );
+        self.apply_game_settings();
+        self.set_rom_details(rom);
+        self.gui.set_symbols(Symbols::default());
+        self.reset();
+        self.update_slot_thumbnails();
+        self.load_cheats(None);
+
+        // Offer to resume where the user left off
+        if self.config.autosave && self.autosave_path().is_some_and(|path| path.exists()) {
+            self.gui.flag_resume_prompt = true;
+        }
+    }
+
+    fn set_rom_details(&mut self, rom: &[u8]) {
+        let hash = rom::sha1_hex(rom);
+        let entry = self.game_db.get(&hash);
+        let mut settings_sources = Vec::new();
+        if self.rom_info.is_some() {
+            settings_sources.push("chip8Archive");
+        }
+        if entry.is_some() {
+            settings_sources.push("game database");
+        }
+        if self.config.game.contains_key(&hash) {
+            settings_sources.push("per-game config");
+        }
+        self.gui.set_rom_details(Some(RomDetails {
+            file_name: None,
+            size: rom.len(),
+            platform: Platform::detect(rom).name().to_string(),
+            title: entry.and_then(|entry| entry.title.clone()),
+            settings_sources,
+            info: self.rom_info.clone(),
+            hash,
+        }));
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) {
+        self.loaded = LoadedType::State(state.to_vec());
+        self.rom_hash = None;
+        self.rom_name = None;
+        self.rom_info = None;
+        self.gui.set_rom_details(None);
+        self.cheats = Cheats::default();
+        self.gui.set_cheats(self.cheats.cheats());
+        info!("Loading state ({} bytes)", state.len());
+        self.apply_game_settings();
+        self.reset();
+        self.update_slot_thumbnails();
+    }
+
+    /// Loads the n-th most recently opened file, starting at 1.
+    pub fn load_recent(&mut self, n: usize) {
+        match self.recent_files.files().get(n.wrapping_sub(1)).cloned() {
+            Some(file_path) => self.load_file(file_path),
+            None => self
+                .gui
+                .display_error(&format!("There's no recent file #{}!", n)),
+        }
+    }
+
+    pub fn load_file<P: AsRef<Path>>(&mut self, file_path: P) {
+        match fs::metadata(&file_path) {
+            Ok(metadata) => {
+                if metadata.len() <= Self::MAX_FILE_SIZE as u64 {
+                    match fs::read(&file_path) {
+                        Ok(file) => {
+                            self.load_data(&file, Some(file_path.as_ref()));
+                            self.recent_files.add(file_path.as_ref());
+                            self.gui.set_recent_files(self.recent_files.files());
+                        }
+                        Err(err) => self.gui.display_error(&format!("Error: {}", err)),
+                    }
+                } else {
+                    self.gui.display_error("File is too big!");
+                }
+            }
+            Err(err) => self.gui.display_error(&format!("Error: {}", err)),
+        }
+    }
+
+    /// Loads a ROM or state, `path` is where it came from if it's a file.
+    pub fn load_data(&mut self, data: &[u8], path: Option<&Path>) {
+        if data.len() > Self::MAX_FILE_SIZE as usize {
+            self.gui.display_error("File is too big!");
+            return;
+        }
+        // Decide by the content, the extension may be anything
+        match FileType::detect(data) {
+            FileType::State => match StateFile::parse(data) {
+                Ok(state_file) => self.load_state(state_file.cpu_state),
+                Err(msg) => self.gui.display_error(&msg),
+            },
+            FileType::Zip => match rom::zip_roms(data) {
+                Ok(names) => match names.as_slice() {
+                    [] => self
+                        .gui
+                        .display_error("The archive doesn't contain any ROMs!"),
+                    [name] => self.load_zip_entry(data, name, path),
+                    _ => {
+                        // Let the user choose
+                        self.pending_zip = Some((data.to_vec(), path.map(Path::to_path_buf)));
+                        self.gui.zip_entries = names;
+                    }
+                },
+                Err(msg) => self.gui.display_error(&msg),
+            },
+            FileType::OctoSource => match assembler::assemble(&String::from_utf8_lossy(data)) {
+                Ok(program) => {
+                    self.load_data(&program.rom, path);
+                    self.gui.set_symbols(program.symbols);
+                }
+                Err(msg) => self
+                    .gui
+                    .display_error(&format!("Cannot assemble the Octo source: {}", msg)),
+            },
+            FileType::Rom => {
+                let name = path
+                    .and_then(Path::file_stem)
+                    .map(|name| name.to_string_lossy().into_owned());
+                self.load_rom(data, path.and_then(ProgramInfo::find), name);
+                self.gui.set_rom_file_name(
+                    path.and_then(Path::file_name)
+                        .map(|name| name.to_string_lossy().into_owned()),
+                );
+                match path
+                    .and_then(Symbols::find)
+                    .map(|path| Symbols::load(&path))
+                {
+                    Some(Ok(symbols)) => self.gui.set_symbols(symbols),
+                    Some(Err(msg)) => warn!("{}", msg),
+                    None => (),
+                }
+                // A cheat file next to the ROM takes precedence
+                if path.is_some_and(|path| path.with_extension(Cheats::EXTENSION).exists()) {
+                    self.load_cheats(path);
+                }
+            }
+        }
+    }
+
+    /// Loads a ROM with an IPS or BPS patch applied, e.g. a translation or a bugfix.
+    pub fn load_patched(&mut self, rom_path: &Path, patch_path: &Path) {
+        match rom::read(rom_path).and_then(|rom| patch::apply_file(&rom, patch_path)) {
+            Ok(rom) => {
+                let path = Some(rom_path).filter(|path| *path != Path::new(rom::STDIN_PATH));
+                self.load_data(&rom, path);
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Loads a ROM from a zip archive as if it was stored next to the archive.
+    fn load_zip_entry(&mut self, archive: &[u8], name: &str, archive_path: Option<&Path>) {
+        match rom::read_from_zip(archive, name) {
+            Ok(data) => {
+                let file_name = Path::new(name).file_name().unwrap_or_default();
+                let path = archive_path.map(|path| path.with_file_name(file_name));
+                self.load_data(&data, path.as_deref());
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Loads a state file on top of the current ROM, or on its own if no ROM is loaded.
+    pub fn resume_state(&mut self, path: &Path) {
+        if self.rom_hash.is_none() {
+            self.load_file(path);
+        } else if let Err(msg) = self.load_rom_state(path) {
+            self.gui.display_error(&msg);
+        }
+    }
+
+    /// Cycles through the given ROMs, directories are replaced by the ROMs they contain.
+    pub fn start_playlist(&mut self, paths: &[PathBuf]) -> Result<(), String> {
+        let interval = self.config.playlist_interval.map(Duration::from_secs);
+        let playlist = Playlist::new(paths, interval)?;
+        info!("Playing {} ROMs", playlist.len());
+        let file = playlist.current().to_path_buf();
+        self.playlist = Some(playlist);
+        self.load_file(file);
+        Ok(())
+    }
+
+    fn advance_playlist(&mut self, forward: bool) {
+        if let Some(playlist) = &mut self.playlist {
+            let file = playlist.advance(forward).to_path_buf();
+            self.load_file(&file);
+            if let Some(name) = file.file_name() {
+                self.gui.display_notice(&name.to_string_lossy());
+            }
+        }
+    }
+
+    /// Returns the current CPU state in the p8s file format.
+    fn state_file(&self) -> Result<Vec<u8>, String> {
+        let cpu_state = self.cpu.save_state()?;
+        let state_file = StateFile {
+            thumbnail: Some(self.display.thumbnail(self.cpu.vmem())),
+            cpu_state: &cpu_state,
+        };
+        Ok(state_file.to_bytes())
+    }
+
+    /// Writes a crash report with the current CPU state, returns the path of the report.
+    pub fn write_crash_report(&self) -> Result<PathBuf, String> {
+        let cpu = &self.cpu;
+        let mut details = format!(
+            "ROM: {}\nSpeed: {} Hz\nQuirks: load/store {}, shift {}, draw {}, jump {}, vf order {}, wrap h {}, wrap v {}\n\n",
+            self.rom_hash.as_deref().unwrap_or("none"),
+            self.cpu_speed,
+            cpu.quirk_load_store,
+            cpu.quirk_shift,
+            cpu.quirk_draw,
+            cpu.quirk_jump,
+            cpu.quirk_vf_order,
+            cpu.quirk_partialwrap_h,
+            cpu.quirk_partialwrap_v,
+        );
+        details.push_str(&format!(
+            "PC: {:04X}  I: {:04X}  SP: {}  DT: {:02X}  ST: {:02X}\n",
+            cpu.PC(),
+            cpu.I(),
+            cpu.sp(),
+            cpu.DT(),
+            cpu.ST()
+        ));
+        for (idx, v) in cpu.V().iter().enumerate() {
+            details.push_str(&format!("V{:X}: {:02X}  ", idx, v));
+        }
+        details.push_str("\nStack:");
+        for addr in &cpu.stack()[..cpu.sp().min(16)] {
+            details.push_str(&format!(" {:04X}", addr));
+        }
+        details.push_str("\n\nLast executed opcodes:\n");
+        for (addr, opcode) in cpu.history() {
+            details.push_str(&format!("{:04X}: {:04X}\n", addr, opcode));
+        }
+        details.push_str(&format!(
+            "Next: {:04X}: {:04X} {}\n",
+            cpu.PC(),
+            cpu.next_opcode(),
+            cpu.next_opcode_description()
+        ));
+
+        crash::write_report(&details, self.state_file().ok().as_deref())
+    }
+
+    /// Returns the path of the given state slot for the currently loaded ROM.
+    fn capture_info(&self, slot: Option<usize>) -> CaptureInfo<'_> {
+        CaptureInfo {
+            rom_name: self.rom_name.as_deref(),
+            rom_hash: self.rom_hash.as_deref(),
+            slot,
+        }
+    }
+
+    fn slot_path(&self, slot: usize) -> Option<PathBuf> {
+        self.rom_hash.as_ref()?;
+        Some(
+            self.config
+                .capture
+                .slot_path(&self.capture_info(Some(slot))),
+        )
+    }
+
+    fn save_screenshot(&mut self) {
+        let path = self
+            .config
+            .capture
+            .screenshot_path(&self.capture_info(None));
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                self.display
+                    .screenshot(self.cpu.vmem())
+                    .save(&path)
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(_) => self
+                .gui
+                .display_notice(&format!("Saved screenshot to {}", path.display())),
+            Err(e) => self.gui.display_error(&format!("Error: {}", e)),
+        }
+    }
+
+    /// Writes the loaded ROM as Octo source, with the labels of its symbols.
+    fn export_octo(&mut self, path: &str) {
+        let rom = match &self.loaded {
+            LoadedType::Rom(rom) => rom,
+            _ => return,
+        };
+        let source = disassembler::export_octo(rom, self.gui.symbols());
+        match fs::write(path, source) {
+            Ok(()) => self.gui.display_notice(&format!("Exported to {}", path)),
+            Err(e) => self
+                .gui
+                .display_error(&format!("Cannot write {}: {}", path, e)),
+        }
+    }
+
+    /// Takes the registers and flags from an Octo state, or only the flags from Octo's flag registers.
+    fn import_octo_state(&mut self, path: &str) {
+        let result = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path, e))
+            .and_then(|json| OctoState::parse(&json));
+        match result {
+            Ok(state) => {
+                state.apply(&mut self.cpu);
+                self.gui.display_notice(&format!("Imported {}", path));
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    fn export_octo_state(&mut self, path: &str) {
+        match fs::write(path, OctoState::from_cpu(&self.cpu).to_json()) {
+            Ok(()) => self.gui.display_notice(&format!("Exported to {}", path)),
+            Err(e) => self
+                .gui
+                .display_error(&format!("Cannot write {}: {}", path, e)),
+        }
+    }
+
+    /// Maps the keys like the profile, until saved in the settings only for this session.
+    fn import_key_profile(&mut self, path: &str) {
+        match KeyProfile::load(Path::new(path)) {
+            Ok(profile) => {
+                self.keymap = profile.keys;
+                self.gui.set_keymap(profile.keys);
+                self.gui
+                    .display_notice(&format!("Loaded key profile '{}'", profile.name));
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    fn export_key_profile(&mut self, path: &str) {
+        let profile = KeyProfile {
+            name: Path::new(path)
+                .file_stem()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+            game: self.rom_name.clone(),
+            keys: self.keymap,
+        };
+        match profile.save(Path::new(path)) {
+            Ok(()) => self.gui.display_notice(&format!("Exported to {}", path)),
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Restarts the ROM with a fixed random seed and records the input, so it can be replayed with --replay.
+    fn start_recording(&mut self) {
+        if self.rom_hash.is_none() {
+            self.gui
+                .display_error("Input can only be recorded for ROMs!");
+            return;
+        }
+        self.reset();
+        self.cpu.seed_rng(0);
+        self.recorder = Some(Recorder::default());
+        self.gui.recording = true;
+        self.gui.display_notice("Recording input");
+    }
+
+    /// Restarts the ROM with the given random seed and plays back the recorded input instead of the keypad's.
+    pub fn play_recording(&mut self, path: &Path, seed: u64) {
+        if self.rom_hash.is_none() {
+            self.gui
+                .display_error("Recordings can only be played back for ROMs!");
+            return;
+        }
+        match Recording::load(path) {
+            Ok(recording) => {
+                self.reset();
+                self.cpu.seed_rng(seed);
+                self.playback = Some(Box::new(recording));
+                self.gui.display_notice("Playing back recording");
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Returns the keys of the next frame and applies the control events.
+    fn poll_input(&mut self) -> [bool; 16] {
+        let mut input = self.keypad.poll(self.frame_count);
+        if self
+            .playback
+            .as_ref()
+            .is_some_and(|playback| playback.finished(self.frame_count))
+        {
+            self.playback = None;
+            self.gui.flag_pause = true;
+            self.gui.display_notice("Playback finished");
+        }
+        if let Some(playback) = &mut self.playback {
+            let played = playback.poll(self.frame_count);
+            input.keys = played.keys;
+            input.controls.extend(played.controls);
+        }
+        for control in input.controls {
+            match control {
+                Control::TogglePause => self.gui.flag_pause = !self.gui.flag_pause,
+                Control::Reset => self.gui.flag_reset = true,
+                Control::Step => self.gui.flag_step = true,
+                Control::QuickSave => self.gui.flag_quick_save = true,
+                Control::QuickLoad => self.gui.flag_quick_load = true,
+            }
+        }
+        input.keys
+    }
+
+    fn stop_recording(&mut self) {
+        let recorder = match self.recorder.take() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+        self.gui.recording = false;
+        let path = self.config.capture.recording_path(&self.capture_info(None));
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, recorder.finish(self.frame_count)));
+        match result {
+            Ok(_) => self
+                .gui
+                .display_notice(&format!("Saved recording to {}", path.display())),
+            Err(e) => self.gui.display_error(&format!("Error: {}", e)),
+        }
+    }
+
+    fn quick_save(&mut self) {
+        let slot = self.gui.state_slot;
+        let path = match self.slot_path(slot) {
+            Some(path) => path,
+            None => {
+                self.gui
+                    .display_error("State slots are only available for ROMs!");
+                return;
+            }
+        };
+        let result = self.state_file().and_then(|state| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| format!("Error: {}", e))?;
+            }
+            fs::write(&path, state).map_err(|e| format!("Error: {}", e))
+        });
+        match result {
+            Ok(_) => {
+                self.unsaved_frames = 0;
+                self.update_slot_thumbnails();
+                self.gui
+                    .display_notice(&format!("Saved state to slot {}", slot));
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        let slot = self.gui.state_slot;
+        let path = match self.slot_path(slot) {
+            Some(path) => path,
+            None => {
+                self.gui
+                    .display_error("State slots are only available for ROMs!");
+                return;
+            }
+        };
+        if !path.exists() {
+            self.gui
+                .display_notice(&format!("State slot {} is empty", slot));
+        } else if let Err(msg) = self.load_rom_state(&path) {
+            self.gui.display_error(&msg);
+        } else {
+            self.gui
+                .display_notice(&format!("Loaded state from slot {}", slot));
+        }
+    }
+
+    /// Loads a state belonging to the current ROM.
+    /// In contrast to `load_state` the ROM hash is kept, so slots and per-game settings stay available.
+    fn load_rom_state(&mut self, path: &Path) -> Result<(), String> {
+        let file = fs::read(path).map_err(|e| format!("Error: {}", e))?;
+        let state_file = StateFile::parse(&file)?;
+        self.loaded = LoadedType::State(state_file.cpu_state.to_vec());
+        self.reset();
+        Ok(())
+    }
+
+    /// Passes the thumbnails of the state slots of the current ROM to the GUI.
+    fn update_slot_thumbnails(&mut self) {
+        let thumbnails: Vec<Option<Thumbnail>> = (0..GUI::NUM_STATE_SLOTS)
+            .map(|slot| {
+                let file = fs::read(self.slot_path(slot)?).ok()?;
+                StateFile::parse(&file).ok()?.thumbnail
+            })
+            .collect();
+        self.gui.set_slot_thumbnails(self.display.gl(), &thumbnails);
+    }
+
+    fn autosave_path(&self) -> Option<PathBuf> {
+        let rom_hash = self.rom_hash.as_ref()?;
+        self.config
+            .capture
+            .state_dir()
+            .map(|dir| dir.join(rom_hash).join(Self::AUTOSAVE_FILE))
+    }
+
+    /// Saves the state on exit if autosave is enabled.
+    fn autosave(&mut self) {
+        if !self.config.autosave {
+            return;
+        }
+        if let Some(path) = self.autosave_path() {
+            if let Ok(state) = self.state_file() {
+                if let Some(dir) = path.parent() {
+                    let _ = fs::create_dir_all(dir);
+                }
+                let _ = fs::write(path, state);
+            }
+        }
+    }
+
+    fn resume_autosave(&mut self) {
+        if let Some(path) = self.autosave_path() {
+            if let Err(msg) = self.load_rom_state(&path) {
+                self.gui.display_error(&msg);
+            }
+        }
+    }
+
+    fn rpl_flags_path(&self) -> Option<PathBuf> {
+        let rom_hash = self.rom_hash.as_ref()?;
+        Config::data_dir().map(|dir| {
+            dir.join(Self::RPL_FLAGS_DIR)
+                .join(format!("{}.bin", rom_hash))
+        })
+    }
+
+    /// Loads a script and runs its top level statements.
+    #[cfg(feature = "scripting")]
+    pub fn load_script(&mut self, path: &Path) {
+        let result = self.script.load(path, &mut self.cpu);
+        self.handle_script_output();
+        match result {
+            Ok(()) => self
+                .gui
+                .script_output(vec![format!("Loaded {}", path.display())]),
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Calls the frame hook of the script, pauses if the script fails.
+    #[cfg(feature = "scripting")]
+    fn run_script_frame(&mut self) {
+        let result = self.script.on_frame(&mut self.cpu);
+        self.handle_script_output();
+        if let Err(msg) = result {
+            self.gui.script_output(vec![format!("Error: {}", msg)]);
+            self.gui.flag_pause = true;
+        }
+    }
+
+    /// Passes the printed lines to the console and the keys the script pressed to the input.
+    #[cfg(feature = "scripting")]
+    fn handle_script_output(&mut self) {
+        let output = self.script.take_output();
+        if !output.is_empty() {
+            self.gui.script_output(output);
+        }
+        for (key, pressed) in self.script.take_keys() {
+            self.keypad.set(key, pressed);
+        }
+    }
+
+    #[cfg(feature = "scripting")]
+    fn handle_script_command(&mut self, command: ScriptCommand) {
+        match command {
+            ScriptCommand::Eval(line) => {
+                let result = self.script.eval(&line, &mut self.cpu);
+                self.gui.script_output(vec![format!("> {}", line)]);
+                self.handle_script_output();
+                match result {
+                    Ok(value) if value.is_empty() => (),
+                    Ok(value) => self.gui.script_output(vec![value]),
+                    Err(msg) => self.gui.script_output(vec![format!("Error: {}", msg)]),
+                }
+            }
+            ScriptCommand::Load(path) => self.load_script(&path),
+        }
+    }
+
+    /// Executes the commands of remote clients.
+    #[cfg(feature = "remote")]
+    fn handle_remote(&mut self) {
+        while let Some(request) = self.remote.as_ref().and_then(RemoteServer::poll) {
+            let response = match &request.command {
+                RemoteCommand::Load { path } => {
+                    self.load_file(path);
+                    RemoteResponse::Ok
+                }
+                RemoteCommand::Pause => {
+                    self.gui.flag_pause = true;
+                    RemoteResponse::Ok
+                }
+                RemoteCommand::Resume => {
+                    self.gui.flag_pause = false;
+                    RemoteResponse::Ok
+                }
+                RemoteCommand::TogglePause => {
+                    self.gui.flag_pause = !self.gui.flag_pause;
+                    RemoteResponse::Ok
+                }
+                RemoteCommand::Step if self.gui.flag_pause => {
+                    self.gui.flag_step = true;
+                    RemoteResponse::Ok
+                }
+                RemoteCommand::Step => RemoteResponse::Error("Pause before stepping".to_string()),
+                RemoteCommand::State => RemoteResponse::State(RemoteState {
+                    paused: self.pause,
+                    rom: self.rom_name.clone(),
+                    pc: self.cpu.PC(),
+                    i: self.cpu.I(),
+                    v: self.cpu.V(),
+                    dt: self.cpu.DT(),
+                    st: self.cpu.ST(),
+                    sp: self.cpu.sp(),
+                    stack: self.cpu.stack(),
+                    keys: self.frame_input,
+                }),
+                RemoteCommand::Press { key } | RemoteCommand::Release { key } if *key < 16 => {
+                    self.keypad
+                        .set(*key, matches!(request.command, RemoteCommand::Press { .. }));
+                    RemoteResponse::Ok
+                }
+                RemoteCommand::Press { key } | RemoteCommand::Release { key } => {
+                    RemoteResponse::Error(format!("Invalid key {}", key))
+                }
+                RemoteCommand::Screenshot => {
+                    let mut png = Vec::new();
+                    match self.display.screenshot(self.cpu.vmem()).write_to(
+                        &mut std::io::Cursor::new(&mut png),
+                        image::ImageOutputFormat::Png,
+                    ) {
+                        Ok(()) => RemoteResponse::Png(png),
+                        Err(e) => RemoteResponse::Error(e.to_string()),
+                    }
+                }
+            };
+            request.reply(response);
+        }
+    }
+
+    /// Restarts the ROM when the netplay peer connects and ends the session on errors.
+    fn handle_netplay(&mut self) {
+        let result = match &mut self.netplay {
+            Some(netplay) => netplay.poll(),
+            None => return,
+        };
+        let result = result.and_then(|connected| {
+            if !connected {
+                return Ok(());
+            }
+            let rom_hash = self.rom_hash.clone().ok_or("Netplay requires a ROM!")?;
+            self.reset();
+            self.cpu.seed_rng(0);
+            self.gui.display_notice("Netplay peer connected");
+            match &mut self.netplay {
+                Some(netplay) => netplay.start(&rom_hash, self.cpu_speed),
+                None => Ok(()),
+            }
+        });
+        if let Err(msg) = result {
+            self.netplay = None;
+            self.gui.display_error(&msg);
+        }
+    }
+
+    /// Runs one frame's worth of instructions with the given keys, followed by a timer update.
+    /// The main loop only decides when frames are due, so other hosts can drive the emulation
+    /// at their own pace. During netplay, the frame waits until the peer's keys are known.
+    pub fn run_frame(&mut self, input: [bool; 16]) -> FrameResult {
+        if self.pause {
+            return FrameResult::Paused;
+        }
+        let keys = match &mut self.netplay {
+            Some(netplay) => netplay.frame(&input),
+            None => Ok(Some(input)),
+        };
+        self.frame_input = match keys {
+            Ok(Some(keys)) => keys,
+            Ok(None) => return FrameResult::Waiting,
+            Err(msg) => {
+                self.netplay = None;
+                self.gui.display_error(&msg);
+                return FrameResult::Waiting;
+            }
+        };
+        // Allow stepping back frame by frame after hitting a breakpoint, the state is remembered
+        // before the frame runs, so the first Step Back undoes the frame up to the breakpoint
+        if self.gui.flag_debug {
+            self.push_step_history();
+        }
+        // A fixed number of instructions per frame keeps netplay instances in sync
+        let cycles = self.cpu_speed + self.cycle_remainder;
+        self.cycle_remainder = cycles % Self::TIMER_FREQUENCY as u32;
+        self.run_cycles(cycles / Self::TIMER_FREQUENCY as u32);
+
+        if self.cpu.ST() > 0 && !self.mute {
+            for output in self.sound_outputs() {
+                match self.cpu.audio_buffer() {
+                    Some(buffer) => output.play_buffer(buffer),
+                    None => output.beep(),
+                }
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(self.frame_count, &input);
+        }
+        self.cpu.update_timers();
+        self.cheats.apply(&mut self.cpu);
+        #[cfg(feature = "scripting")]
+        self.run_script_frame();
+        if let Some(compare_cpu) = &mut self.compare_cpu {
+            compare_cpu.update_timers();
+        }
+        self.frame_count += 1;
+        self.unsaved_frames += 1;
+        self.emulated_fps_counter.tick();
+        self.check_watchdog();
+        self.save_rpl_flags();
 
-            #[cfg(feature = "rom-download")]
-            rom_downloader: RomDownloader::new(),
-        })
+        if self.gui.flag_pause {
+            FrameResult::Paused
+        } else {
+            FrameResult::Completed
+        }
     }
 
-    fn reset(&mut self) {
-        match &self.loaded {
-            LoadedType::Rom(rom) => {
-                self.cpu = CPU::new();
-                match self.cpu.load_rom(&rom) {
-                    Ok(_) => {
-                        if !self.gui.flag_debug {
-                            self.gui.flag_pause = false;
-                        }
-                    }
-                    Err(_) => self.gui.display_error("Data is not a valid ROM!"),
-                }
+    /// Publishes the played ROM to Discord whenever another one is loaded.
+    #[cfg(feature = "discord")]
+    fn update_presence(&mut self) {
+        if self.discord.is_none() || self.presence_hash == self.rom_hash {
+            return;
+        }
+        self.presence_hash = self.rom_hash.clone();
+        let title = self.rom_hash.as_ref().map(|hash| {
+            self.rom_info
+                .as_ref()
+                .map(|info| info.title.clone())
+                .or_else(|| self.game_db.get(hash).and_then(|entry| entry.title.clone()))
+                .or_else(|| self.rom_name.clone())
+                .unwrap_or_else(|| "Unknown ROM".to_string())
+        });
+        if let Some(discord) = &mut self.discord {
+            match title {
+                Some(title) => discord.set_playing(&title),
+                None => discord.clear(),
             }
-            LoadedType::State(state) => {
-                match CPU::from_state(&state) {
-                    Ok(cpu) => self.cpu = cpu,
-                    Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Restores the memory range the game's settings declare as persistent, e.g. to keep high scores.
+    fn load_memory(&mut self) {
+        let path = self.rom_hash.as_ref().and_then(|rom_hash| {
+            Config::data_dir()
+                .map(|dir| dir.join(Self::MEMORY_DIR).join(format!("{}.bin", rom_hash)))
+        });
+        self.persisted = self.persist.zip(path).map(|(range, path)| (path, range));
+        if let Some((path, range)) = &self.persisted {
+            if let Ok(data) = fs::read(path) {
+                for (address, &value) in (range.start..=range.end).zip(&data) {
+                    self.cpu.write_mem(address, value);
                 }
-                self.gui.flag_pause = false;
+                debug!("Restored memory from {}", path.display());
             }
-            _ => (),
         }
     }
 
-    pub fn load_rom(&mut self, rom: &[u8]) {
-        self.loaded = LoadedType::Rom(rom.to_vec());
-        self.reset();
+    /// Saves the persistent memory range before the ROM is reset or replaced and on exit.
+    fn save_memory(&self) {
+        if let Some((path, range)) = &self.persisted {
+            let data: Vec<u8> = (range.start..=range.end)
+                .map(|address| self.cpu.read_mem(address))
+                .collect();
+            let result = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(path, data));
+            if let Err(e) = result {
+                warn!("Failed to save memory to {}: {}", path.display(), e);
+            }
+        }
     }
 
-    pub fn load_state(&mut self, state: &[u8]) {
-        self.loaded = LoadedType::State(state.to_vec());
-        self.reset();
+    /// Loads the cheats of the current ROM and enables the ones given on the command line.
+    fn load_cheats(&mut self, rom_path: Option<&Path>) {
+        let path = self
+            .rom_hash
+            .as_deref()
+            .and_then(|hash| Cheats::find(rom_path, hash));
+        self.cheats = match path.map(|path| Cheats::load(&path)) {
+            Some(Ok(cheats)) => cheats,
+            Some(Err(msg)) => {
+                self.gui.display_error(&msg);
+                Cheats::default()
+            }
+            None => Cheats::default(),
+        };
+        for name in &self.cheat_names {
+            if !self.cheats.enable(name) {
+                warn!("There's no cheat named '{}' for this ROM", name);
+            }
+        }
+        self.gui.set_cheats(self.cheats.cheats());
+    }
+
+    /// Restores the RPL user flags (FX75/FX85) the ROM stored in a previous session.
+    fn load_rpl_flags(&mut self) {
+        self.saved_rpl = [0; 8];
+        if let Some(flags) = self.rpl_flags_path().and_then(|path| fs::read(path).ok()) {
+            let len = flags.len().min(self.saved_rpl.len());
+            self.saved_rpl[..len].copy_from_slice(&flags[..len]);
+            debug!("Restored RPL flags {:02X?}", self.saved_rpl);
+        }
+        self.cpu.set_RPL(self.saved_rpl);
+    }
+
+    /// Persists the RPL user flags if the ROM changed them, like the HP48 calculators did.
+    fn save_rpl_flags(&mut self) {
+        let flags = self.cpu.RPL();
+        if flags == self.saved_rpl {
+            return;
+        }
+        self.saved_rpl = flags;
+        if let Some(path) = self.rpl_flags_path() {
+            let result = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, flags));
+            if let Err(e) = result {
+                warn!("Failed to save RPL flags to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Runs a copy of the CPU with the quirks preset chosen for comparison, both with the same random seed.
+    fn start_compare(&mut self) {
+        self.compare_cpu = None;
+        let preset = match self.gui.compare_quirks {
+            Some(preset) if !matches!(self.loaded, LoadedType::Nothing) => preset,
+            _ => return,
+        };
+        match self
+            .cpu
+            .save_state()
+            .and_then(|state| CPU::from_state(&state))
+        {
+            Ok(mut compare_cpu) => {
+                QuirksConfig::Preset(preset)
+                    .quirks()
+                    .apply(&mut compare_cpu);
+                let seed = rand::random();
+                self.cpu.seed_rng(seed);
+                compare_cpu.seed_rng(seed);
+                self.compare_cpu = Some(compare_cpu);
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Offers to reset or pause the ROM once it has been stuck in an endless loop for a while.
+    fn check_watchdog(&mut self) {
+        if self.config.watchdog_timeout == 0 || !self.cpu.is_stuck() {
+            self.stuck_frames = 0;
+            return;
+        }
+        self.stuck_frames += 1;
+        if self.stuck_frames == self.config.watchdog_timeout * Self::TIMER_FREQUENCY as u64 {
+            info!("Stuck in an endless loop at {:#05X}", self.cpu.PC());
+            self.gui.stuck_prompt_text = format!(
+                "The program is stuck in an endless loop at {:#05X}.",
+                self.cpu.PC()
+            );
+        }
+    }
+
+    /// Exits, unless there's something to lose, in which case the user is asked first.
+    fn request_exit(&mut self, ctrl_flow: &mut ControlFlow) {
+        let reason = if !self.config.confirm_exit {
+            None
+        } else if self.recorder.is_some() {
+            Some("Input is being recorded.")
+        } else if !self.config.autosave
+            && self.rom_hash.is_some()
+            && self.unsaved_frames >= Self::UNSAVED_FRAMES_WARNING
+        {
+            Some("The game hasn't been saved in a while.")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                self.gui.exit_prompt_text = format!("{} Do you really want to quit?", reason)
+            }
+            None => *ctrl_flow = ControlFlow::Exit,
+        }
+    }
+
+    /// The audio device and the plugins playing sound.
+    fn sound_outputs(&self) -> Vec<&dyn SoundOutput> {
+        #[allow(unused_mut)]
+        let mut outputs: Vec<&dyn SoundOutput> = vec![&self.sound];
+        #[cfg(feature = "plugins")]
+        outputs.extend(self.plugins.iter().map(|plugin| plugin as &dyn SoundOutput));
+        outputs
     }
 
     fn set_pause(&mut self, pause: bool) {
+        debug!("{}", if pause { "Paused" } else { "Resumed" });
         self.pause = pause;
         if pause {
             // Store timestamp
             self.pause_time = Instant::now();
+            for output in self.sound_outputs() {
+                output.pause();
+            }
         } else {
             // "Subtract" paused time so the simulation doesn't jump
-            let diff = Instant::now() - self.pause_time;
-            self.last_cycle += diff;
+            self.frame_limiter.resume(Instant::now() - self.pause_time);
+            for output in self.sound_outputs() {
+                output.resume();
+            }
+        }
+    }
+
+    #[cfg(feature = "rom-download")]
+    fn start_download(&mut self, url: &str, target: DownloadTarget) {
+        match RomDownloader::parse_url(url) {
+            Ok(url) => {
+                // A hash can be given as #sha1=... to verify the download
+                self.download_sha1 = url
+                    .fragment()
+                    .and_then(|fragment| fragment.strip_prefix("sha1="))
+                    .map(str::to_ascii_lowercase);
+                let max_size = match target {
+                    DownloadTarget::ArchiveIndex => Self::MAX_INDEX_SIZE,
+                    _ => CPU::MAX_ROM_SIZE as u64,
+                };
+                self.download_target = target;
+                self.gui.flag_downloading = true;
+                self.gui.download_progress = None;
+                self.rom_downloader.download(url, max_size);
+            }
+            Err(msg) => self.gui.display_error(&msg),
+        }
+    }
+
+    /// Keeps the downloaded chip8Archive index and passes its programs to the browser.
+    #[cfg(feature = "rom-download")]
+    fn set_archive_index(&mut self, data: Vec<u8>) {
+        let programs = String::from_utf8(data)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                ProgramInfo::parse_index(&content).map(|programs| (content, programs))
+            });
+        match programs {
+            Ok((content, programs)) => {
+                self.gui
+                    .set_archive_programs(programs.into_iter().collect());
+                self.archive_index = Some(content);
+            }
+            Err(e) => self
+                .gui
+                .display_error(&format!("Invalid chip8Archive index: {}", e)),
+        }
+    }
+
+    /// Saves a ROM downloaded from the chip8Archive along with the index, so its metadata is found, then loads it.
+    #[cfg(feature = "rom-download")]
+    fn save_archive_rom(&mut self, key: &str, data: &[u8]) {
+        if !ProgramInfo::is_valid_key(key) {
+            self.gui
+                .display_error(&format!("Invalid chip8Archive program '{}'!", key));
+            return;
+        }
+        let dir = match self
+            .config
+            .rom_dir
+            .clone()
+            .or_else(|| Config::data_dir().map(|dir| dir.join(Self::ROMS_DIR)))
+        {
+            Some(dir) => dir,
+            None => {
+                self.gui.display_error("No directory for ROMs available!");
+                return;
+            }
+        };
+        let path = dir.join(format!("{}.ch8", key));
+        let result = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&path, data))
+            .and_then(|_| match &self.archive_index {
+                Some(index) => fs::write(dir.join(ProgramInfo::FILE_NAME), index),
+                None => Ok(()),
+            });
+        match result {
+            Ok(_) => {
+                info!("Saved {}", path.display());
+                self.load_file(&path);
+            }
+            Err(e) => self
+                .gui
+                .display_error(&format!("Failed to save {}: {}", path.display(), e)),
         }
     }
 
@@ -150,7 +1702,35 @@ impl Emulator {
             match self.rom_downloader.check_result() {
                 DownloadResult::Success(data) => {
                     self.gui.flag_downloading = false;
-                    self.load_rom(&data);
+                    match std::mem::replace(&mut self.download_target, DownloadTarget::Url) {
+                        DownloadTarget::Url => (),
+                        DownloadTarget::ArchiveIndex => {
+                            self.set_archive_index(data);
+                            return;
+                        }
+                        DownloadTarget::ArchiveRom(key) => {
+                            self.save_archive_rom(&key, &data);
+                            return;
+                        }
+                    }
+                    let hash = rom::sha1_hex(&data);
+                    match self.download_sha1.take() {
+                        Some(expected) if expected != hash => {
+                            self.gui.display_error(&format!(
+                                "The downloaded ROM doesn't match the expected SHA-1 hash!\nExpected: {}\nActual: {}",
+                                expected, hash
+                            ));
+                            return;
+                        }
+                        _ => (),
+                    }
+                    self.load_rom(&data, None, None);
+                    if let Some(title) = self.game_db.get(&hash).and_then(|e| e.title.as_ref()) {
+                        self.gui.display_notice(&format!("Downloaded {}", title));
+                    }
+                }
+                DownloadResult::Progress { received, total } => {
+                    self.gui.download_progress = Some((received, total));
                 }
                 DownloadResult::Fail(msg) => {
                     self.gui.flag_downloading = false;
@@ -161,50 +1741,40 @@ impl Emulator {
         }
     }
 
-    pub fn handle_event(&mut self, event: Event<()>, ctrl_flow: &mut ControlFlow) {
+    pub fn handle_event(
+        &mut self,
+        event: Event<()>,
+        ctrl_flow: &mut ControlFlow,
+    ) -> Result<(), Pich8Error> {
         // Handle file dialogs
         if self.dialog_handler.is_open() {
             match self.dialog_handler.check_result() {
-                FileDialogResult::OpenRom(file_path) => {
-                    match fs::metadata(&file_path) {
-                        Ok(metadata) => {
-                            if metadata.len() <= Self::MAX_FILE_SIZE as u64 {
-                                match fs::read(&file_path) {
-                                    Ok(file) => {
-                                        // Check if it's a p8s state file, otherwise expect ROM
-                                        if &file[0..3] == b"p8s" {
-                                            self.load_state(&file[3..]);
-                                        } else {
-                                            self.load_rom(&file);
-                                        }
-                                    }
-                                    Err(err) => self.gui.display_error(&format!("Error: {}", err)),
-                                }
-                            } else {
-                                self.gui.display_error("File is too big!");
-                            }
-                        }
-                        Err(err) => self.gui.display_error(&format!("Error: {}", err)),
-                    }
+                FileDialogResult::OpenRom(file_path) => self.load_file(file_path),
+                FileDialogResult::OpenRomWithPatch(rom_path, patch_path) => {
+                    self.load_patched(Path::new(&rom_path), Path::new(&patch_path))
                 }
-                FileDialogResult::SaveState(file_path) => match self.cpu.save_state().as_mut() {
+                FileDialogResult::SaveState(file_path) => match self.state_file() {
                     Ok(state) => {
-                        state.splice(0..0, b"p8s".iter().cloned());
                         if fs::write(file_path, state).is_err() {
                             self.gui.display_error("Failed to write to file!");
+                        } else {
+                            self.unsaved_frames = 0;
                         }
                     }
                     Err(msg) => self.gui.display_error(&msg),
                 },
+                FileDialogResult::ExportOcto(file_path) => self.export_octo(&file_path),
+                FileDialogResult::ImportOctoState(file_path) => self.import_octo_state(&file_path),
+                FileDialogResult::ExportOctoState(file_path) => self.export_octo_state(&file_path),
+                FileDialogResult::ImportKeyProfile(file_path) => {
+                    self.import_key_profile(&file_path)
+                }
+                FileDialogResult::ExportKeyProfile(file_path) => {
+                    self.export_key_profile(&file_path)
+                }
 
                 #[cfg(feature = "rom-download")]
-                FileDialogResult::InputUrl(url) => {
-                    self.gui.flag_downloading = true;
-                    match url::Url::parse(&url) {
-                        Ok(url) => self.rom_downloader.download(url),
-                        Err(e) => self.gui.display_error(&format!("Invalid URL: {}", e)),
-                    }
-                }
+                FileDialogResult::InputUrl(url) => self.start_download(&url, DownloadTarget::Url),
 
                 FileDialogResult::None => (),
             }
@@ -214,88 +1784,69 @@ impl Emulator {
         #[cfg(feature = "rom-download")]
         self.handle_downloads();
 
+        #[cfg(feature = "discord")]
+        self.update_presence();
+
+        #[cfg(feature = "remote")]
+        self.handle_remote();
+
+        self.handle_netplay();
+
         // Handle events
         if !self.dialog_handler.is_open() {
-            self.gui.handle_event(self.display.display(), &event);
+            self.gui.handle_event(self.display.gl(), &event);
             match event {
                 Event::NewEvents(_) => {
                     self.handle_gui_flags(ctrl_flow);
+
+                    if self.pause {
+                        // Don't switch away from a paused ROM
+                        if let Some(playlist) = &mut self.playlist {
+                            playlist.restart_timer();
+                        }
+                    } else if self.playlist.as_ref().is_some_and(|p| p.is_due()) {
+                        self.advance_playlist(true);
+                    }
                 }
                 Event::MainEventsCleared => {
                     if !self.pause {
-                        // Perform emulation
-                        let nanos_per_cycle = 1_000_000_000 / self.cpu_speed as u64;
-                        if self.last_cycle.elapsed().as_nanos() as u64 >= nanos_per_cycle * 10 {
-                            let mut cycles = (self.last_cycle.elapsed().as_nanos() as f64
-                                / nanos_per_cycle as f64)
-                                as u32;
-                            self.last_cycle = Instant::now();
-
-                            // Check if additional cycles are needed
-                            if self.last_correction_cpu.elapsed().as_secs_f64() >= 0.25 {
-                                let target = self.cpu_speed / 4;
-                                if self.counter_cpu < target {
-                                    cycles += target - self.counter_cpu;
-                                }
-                                self.last_correction_cpu = Instant::now();
-                                self.counter_cpu = 0;
-                            } else {
-                                self.counter_cpu += cycles;
-                            }
-
-                            for _ in 0..cycles {
-                                if let Err(e) = self.cpu.tick(&self.input) {
-                                    self.gui.display_error(&format!("Error: {}", e));
-                                    continue;
-                                }
-                                if self.gui.flag_debug && self.check_breakpoints() {
-                                    self.gui.flag_pause = true;
-                                    break;
-                                }
+                        // Perform emulation, frames are due at the timer frequency
+                        for _ in 0..self.frame_limiter.due_frames(Instant::now()) {
+                            let keys = self.poll_input();
+                            if self.run_frame(keys) != FrameResult::Completed {
+                                break;
                             }
                         }
-                        // Update CPU timers
-                        if self.last_timer.elapsed().as_nanos() as u64 >= Emulator::NANOS_PER_TIMER
-                        {
-                            self.last_timer = Instant::now();
-                            let mut reps = 1;
-
-                            // Check and correct frequency regularly
-                            if self.last_correction_timer.elapsed().as_secs_f64() >= 0.25 {
-                                let target = Self::TIMER_FREQUENCY as u32 / 4;
-                                if self.counter_timer + 1 < target {
-                                    reps += target - self.counter_timer - 1;
-                                }
-                                self.last_correction_timer = Instant::now();
-                                self.counter_timer = 0;
-                            } else {
-                                self.counter_timer += reps;
+                    } else {
+                        let keys = self.poll_input();
+                        if self.step_back {
+                            self.pop_step_history();
+                        } else if self.step {
+                            self.push_step_history();
+                            self.frame_input = keys;
+                            if let Some(compare_cpu) = &mut self.compare_cpu {
+                                let _ = compare_cpu.tick(&keys);
                             }
-
-                            for _ in 0..reps {
-                                if self.cpu.ST() > 0 && !self.mute {
-                                    if self.cpu.audio_buffer().is_some() {
-                                        self.sound.play_buffer(self.cpu.audio_buffer().unwrap());
-                                    } else {
-                                        self.sound.beep();
-                                    }
-                                }
-                                self.cpu.update_timers();
+                            if let Err(e) = self.tick_cpu() {
+                                self.gui.display_error(&format!("Error: {}", e));
                             }
+                        } else if self.step_timers {
+                            self.push_step_history();
+                            self.cpu.update_timers();
+                            if let Some(compare_cpu) = &mut self.compare_cpu {
+                                compare_cpu.update_timers();
+                            }
+                            self.frame_count += 1;
                         }
-                    } else if self.step {
-                        if let Err(e) = self.cpu.tick(&self.input) {
-                            self.gui.display_error(&format!("Error: {}", e));
-                        }
-                    } else if self.step_timers {
-                        self.cpu.update_timers();
                     }
 
-                    // Always request redrawing to keep the GUI updated
-                    self.gui
-                        .prepare_frame(self.display.display())
-                        .expect("Failed to prepare UI frame");
-                    self.display.display().gl_window().window().request_redraw();
+                    // Always request redrawing when a frame is due to keep the GUI updated
+                    if self.frame_limiter.update(ctrl_flow) {
+                        self.gui
+                            .prepare_frame(self.display.gl())
+                            .map_err(Pich8Error::Gui)?;
+                        self.display.request_redraw();
+                    }
                 }
                 Event::RedrawRequested(_) => {
                     let fps = self.fps_counter.tick();
@@ -308,44 +1859,211 @@ impl Emulator {
                     } else {
                         self.gui.menu_height()
                     };
-                    let vmem = if self.force_redraw || self.cpu.draw {
-                        self.cpu.draw = false;
+                    let compare_changed = self
+                        .compare_cpu
+                        .as_mut()
+                        .is_some_and(|compare_cpu| compare_cpu.take_vmem_dirty());
+                    let changed = self.cpu.take_vmem_dirty() | compare_changed;
+                    // Without the GUI on top, a frame showing the same screen needn't be drawn,
+                    // unless presenting it paces the event loop with vsync
+                    if is_fullscreen
+                        && !changed
+                        && !self.force_redraw
+                        && self.config.frame_pacing != FramePacing::Vsync
+                    {
+                        return Ok(());
+                    }
+                    let vmem = if self.force_redraw || changed {
                         Some(self.cpu.vmem())
                     } else {
                         None
                     };
-                    let mut frame = self
-                        .display
-                        .prepare(vmem, height)
-                        .expect("Failed to prepare frame");
-                    if !is_fullscreen {
-                        self.gui
-                            .render(
-                                frame_duration,
-                                self.display.display(),
-                                &mut frame,
-                                fps,
-                                &self.cpu,
-                            )
-                            .expect("Failed to render GUI");
+                    if let (Some(export), Some(vmem)) = (&mut self.frame_export, vmem) {
+                        let result = match export.frame_mut() {
+                            Ok(frame) => {
+                                self.display.copy_screenshot(vmem, frame);
+                                export.submit()
+                            }
+                            Err(msg) => Err(msg),
+                        };
+                        if let Err(msg) = result {
+                            self.frame_export = None;
+                            self.gui.display_error(&msg);
+                        }
+                    }
+                    #[cfg(feature = "plugins")]
+                    if let Some(vmem) = vmem {
+                        for plugin in &self.plugins {
+                            plugin.present_vmem(vmem);
+                        }
                     }
-                    self.display.render(frame).expect("Failed to render frame");
+                    let compare_vmem = self.compare_cpu.as_ref().map(|cpu| cpu.vmem());
+                    let speed = SpeedInfo {
+                        cycles_per_frame: self.cpu_speed / Self::TIMER_FREQUENCY as u32,
+                        frame: self.frame_count,
+                        multiplier: self.emulated_fps_counter.fps() / Self::TIMER_FREQUENCY as f64,
+                    };
+                    // On this thread, which owns the GL context, so with vsync the wait for the
+                    // display delays the next frames until the frame limiter catches up
+                    let (gui, cpu) = (&mut self.gui, &self.cpu);
+                    self.display.present_with_overlay(
+                        vmem,
+                        compare_vmem,
+                        height,
+                        |display, frame| {
+                            if is_fullscreen {
+                                return Ok(());
+                            }
+                            gui.render(frame_duration, display, frame, fps, &speed, cpu)
+                                .map_err(Pich8Error::Gui)
+                        },
+                    )?;
+                    self.frame_limiter.presented(Instant::now());
                 }
                 Event::WindowEvent {
                     event: WindowEvent::KeyboardInput { input, .. },
                     ..
                 } => self.handle_input(input, ctrl_flow),
+                Event::LoopDestroyed => {
+                    self.stop_recording();
+                    self.save_memory();
+                    self.autosave();
+                    self.save_debug_layout();
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::DroppedFile(file_path),
+                    ..
+                } => self.load_file(file_path),
                 Event::WindowEvent {
                     event: WindowEvent::CloseRequested,
                     ..
-                } => *ctrl_flow = ControlFlow::Exit,
+                } => self.request_exit(ctrl_flow),
                 Event::WindowEvent {
                     event: WindowEvent::ModifiersChanged(modifiers_state),
                     ..
                 } => self.modifiers_state = modifiers_state,
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(_),
+                    ..
+                } => self.force_redraw = true,
                 _ => (),
             }
         }
+
+        Ok(())
+    }
+
+    /// Returns the settings currently used, as changed in the menus and the settings dialog.
+    fn current_settings(&mut self) -> Settings {
+        let quirks = self.gui.quirks_settings();
+        let quirks = Quirks {
+            load_store: quirks.get(Quirk::LoadStore),
+            shift: quirks.get(Quirk::Shift),
+            draw: quirks.get(Quirk::Draw),
+            jump: quirks.get(Quirk::Jump),
+            vf_order: quirks.get(Quirk::VfOrder),
+            partial_wrap_h: quirks.get(Quirk::PartialWrapH),
+            partial_wrap_v: quirks.get(Quirk::PartialWrapV),
+        };
+        let colors = self.gui.color_settings();
+        let palette = Palette {
+            background: HexColor(colors.get(Color::Background)),
+            plane_1: HexColor(colors.get(Color::Plane1)),
+            plane_2: HexColor(colors.get(Color::Plane2)),
+            plane_both: HexColor(colors.get(Color::PlaneBoth)),
+        };
+        Settings {
+            speed: Some(self.gui.cpu_speed),
+            quirks: Some(QuirksConfig::Custom(quirks)),
+            palette: Some(PaletteConfig::Custom(palette)),
+            keys: Some(self.keymap),
+            volume: Some((self.gui.volume * 100.0).round() as u8),
+            mute: Some(self.gui.flag_mute),
+            persist: None,
+        }
+    }
+
+    /// Saves the current settings to the config file, either globally or for the loaded ROM.
+    /// The config file is read again, so settings passed on the command line aren't saved.
+    fn save_settings(&mut self, for_game: bool) {
+        let settings = self.current_settings();
+        let result = (|| {
+            let path = self
+                .config_path
+                .as_ref()
+                .ok_or("No config directory found")?;
+            let mut config = Config::load(path)?;
+            if for_game {
+                let hash = self.rom_hash.as_ref().ok_or("No ROM loaded")?;
+                for config in [&mut config, &mut self.config] {
+                    let game = config.game.entry(hash.clone()).or_default();
+                    *game = game.merged(&settings);
+                }
+            } else {
+                config.settings = config.settings.merged(&settings);
+                self.config.settings = self.config.settings.merged(&settings);
+            }
+            config.save(path)?;
+            Ok::<_, String>(path.clone())
+        })();
+        match result {
+            Ok(path) => self
+                .gui
+                .display_notice(&format!("Saved settings to {}", path.display())),
+            Err(msg) => self.gui.display_error(&format!("Error: {}", msg)),
+        }
+    }
+
+    /// Saves the open debug windows and their positions to the config file if they changed.
+    fn save_debug_layout(&mut self) {
+        let layout = self.gui.debug_layout();
+        if layout == self.config.debug_layout {
+            return;
+        }
+        if let Some(path) = &self.config_path {
+            let result = Config::load(path).and_then(|mut config| {
+                config.debug_layout = layout;
+                config.save(path)
+            });
+            if let Err(msg) = result {
+                warn!("Cannot save the debug layout: {}", msg);
+            }
+        }
+    }
+
+    /// Remembers the CPU state before a step, so it can be undone with Step Back.
+    fn push_step_history(&mut self) {
+        if let Err(msg) = self.step_history.push(&mut self.cpu) {
+            warn!("Cannot save the state for stepping back: {}", msg);
+        }
+    }
+
+    /// Remembers the CPU state before the first edit since the last step, so Step Back undoes the
+    /// edits together.
+    fn push_edit_history(&mut self) {
+        if let Err(msg) = self.step_history.push_edit(&mut self.cpu) {
+            warn!("Cannot save the state for stepping back: {}", msg);
+        }
+    }
+
+    /// Restores the CPU state before the last step or, while running with the debug windows open, frame.
+    fn pop_step_history(&mut self) {
+        match self.step_history.pop() {
+            Some(Ok(cpu)) => self.cpu = cpu,
+            Some(Err(msg)) => self.gui.display_error(&msg),
+            None => (),
+        }
+    }
+
+    fn apply_quirks(&mut self) {
+        let quirks = self.gui.quirks_settings();
+        self.cpu.quirk_load_store = quirks.get(Quirk::LoadStore);
+        self.cpu.quirk_shift = quirks.get(Quirk::Shift);
+        self.cpu.quirk_draw = quirks.get(Quirk::Draw);
+        self.cpu.quirk_jump = quirks.get(Quirk::Jump);
+        self.cpu.quirk_vf_order = quirks.get(Quirk::VfOrder);
+        self.cpu.quirk_partialwrap_h = quirks.get(Quirk::PartialWrapH);
+        self.cpu.quirk_partialwrap_v = quirks.get(Quirk::PartialWrapV);
     }
 
     #[inline]
@@ -363,6 +2081,11 @@ impl Emulator {
                 .open_file_dialog(FileDialogType::OpenRom);
             self.gui.flag_open = false;
         }
+        if self.gui.flag_open_with_patch {
+            self.dialog_handler
+                .open_file_dialog(FileDialogType::OpenRomWithPatch);
+            self.gui.flag_open_with_patch = false;
+        }
 
         #[cfg(feature = "rom-download")]
         if self.gui.flag_open_rom_url {
@@ -370,25 +2093,174 @@ impl Emulator {
                 .open_file_dialog(FileDialogType::InputUrl);
             self.gui.flag_open_rom_url = false;
         }
+        #[cfg(feature = "rom-download")]
+        if self.gui.flag_archive_refresh && !self.rom_downloader.is_active() {
+            self.start_download(ProgramInfo::INDEX_URL, DownloadTarget::ArchiveIndex);
+            self.gui.flag_archive_refresh = false;
+        }
+        #[cfg(feature = "rom-download")]
+        if let Some(key) = self.gui.archive_choice.take() {
+            if !self.rom_downloader.is_active() {
+                let url = ProgramInfo::rom_url(&key);
+                self.start_download(&url, DownloadTarget::ArchiveRom(key));
+            }
+        }
+
+        #[cfg(feature = "scripting")]
+        if let Some(command) = self.gui.script_command.take() {
+            self.handle_script_command(command);
+        }
+        // Edits can be undone with Step Back like a step, all edits since the last step at once
+        if let Some((register, value)) = self.gui.register_edit.take() {
+            self.push_edit_history();
+            self.cpu.set_register(register, value);
+        }
+        if let Some((address, value)) = self.gui.memory_edit.take() {
+            self.push_edit_history();
+            self.cpu.write_mem(address, value);
+        }
+        if let Some(for_game) = self.gui.settings_save.take() {
+            self.save_settings(for_game);
+        }
+        if let Some((idx, enabled)) = self.gui.cheat_toggled.take() {
+            self.cheats.set_enabled(idx, enabled);
+        }
+        if let Some(idx) = self.gui.zip_choice.take() {
+            if let Some((archive, path)) = self.pending_zip.take() {
+                if let Some(name) = rom::zip_roms(&archive)
+                    .ok()
+                    .and_then(|names| names.get(idx).cloned())
+                {
+                    self.load_zip_entry(&archive, &name, path.as_deref());
+                }
+            }
+        }
+        if let Some(idx) = self.gui.open_recent.take() {
+            self.load_recent(idx + 1);
+        }
 
         if self.gui.flag_save_state {
+            let path = self.config.capture.state_path(&self.capture_info(None));
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
             self.dialog_handler
-                .open_file_dialog(FileDialogType::SaveState);
+                .open_file_dialog(FileDialogType::SaveState(
+                    path.to_string_lossy().into_owned(),
+                ));
             self.gui.flag_save_state = false;
         }
+        if self.gui.flag_export_octo {
+            if let LoadedType::Rom(_) = self.loaded {
+                let name = self.rom_name.as_deref().unwrap_or("program");
+                self.dialog_handler
+                    .open_file_dialog(FileDialogType::ExportOcto(format!("{}.8o", name)));
+            } else {
+                self.gui.display_error("Only ROMs can be exported!");
+            }
+            self.gui.flag_export_octo = false;
+        }
+        if self.gui.flag_import_octo_state {
+            self.dialog_handler
+                .open_file_dialog(FileDialogType::ImportOctoState);
+            self.gui.flag_import_octo_state = false;
+        }
+        if self.gui.flag_export_octo_state {
+            let name = self.rom_name.as_deref().unwrap_or("program");
+            self.dialog_handler
+                .open_file_dialog(FileDialogType::ExportOctoState(format!("{}.json", name)));
+            self.gui.flag_export_octo_state = false;
+        }
+        if self.gui.flag_import_keys {
+            self.dialog_handler
+                .open_file_dialog(FileDialogType::ImportKeyProfile);
+            self.gui.flag_import_keys = false;
+        }
+        if self.gui.flag_export_keys {
+            let name = match &self.rom_name {
+                Some(name) => format!("{} keys.toml", name),
+                None => String::from("keys.toml"),
+            };
+            self.dialog_handler
+                .open_file_dialog(FileDialogType::ExportKeyProfile(name));
+            self.gui.flag_export_keys = false;
+        }
+        if self.gui.flag_screenshot {
+            self.save_screenshot();
+            self.gui.flag_screenshot = false;
+        }
+        if self.gui.flag_record {
+            if self.recorder.is_some() {
+                self.stop_recording();
+            } else {
+                self.start_recording();
+            }
+            self.gui.flag_record = false;
+        }
+        if let Some(resume) = self.gui.resume_choice.take() {
+            if resume {
+                self.resume_autosave();
+            }
+        }
+
+        if self.gui.flag_quick_save {
+            self.quick_save();
+            self.gui.flag_quick_save = false;
+        }
+        if self.gui.flag_quick_load {
+            self.quick_load();
+            self.gui.flag_quick_load = false;
+        }
         if self.gui.flag_reset {
             self.reset();
             self.gui.flag_reset = false;
         }
         if self.gui.flag_exit {
-            *ctrl_flow = ControlFlow::Exit;
+            self.request_exit(ctrl_flow);
             self.gui.flag_exit = false;
         }
+        if self.gui.flag_compare_changed {
+            self.reset();
+            self.gui.flag_compare_changed = false;
+        }
+        if let Some(reset) = self.gui.stuck_choice.take() {
+            if reset {
+                self.reset();
+            } else {
+                self.gui.flag_pause = true;
+            }
+        }
+        if let Some(exit) = self.gui.exit_choice.take() {
+            if exit {
+                *ctrl_flow = ControlFlow::Exit;
+            }
+        }
         if self.gui.flag_fullscreen != fullscreen {
             let _ = self.display.toggle_fullscreen();
         }
+        if self.gui.flag_pause && self.gui.flag_step_over {
+            if self.cpu.next_opcode() & 0xF000 == 0x2000 {
+                // Run the whole subroutine
+                self.return_depth = Some(self.cpu.sp() + 1);
+                self.gui.flag_pause = false;
+            } else {
+                self.gui.flag_step = true;
+            }
+        }
+        if self.gui.flag_pause && self.gui.flag_step_out {
+            match self.cpu.sp() {
+                0 => self.gui.display_notice("Not in a subroutine"),
+                sp => {
+                    self.return_depth = Some(sp);
+                    self.gui.flag_pause = false;
+                }
+            }
+        }
+        self.gui.flag_step_over = false;
+        self.gui.flag_step_out = false;
         if self.gui.flag_pause {
             pause = true;
+            self.return_depth = None;
         }
 
         let color_settings = self.gui.color_settings();
@@ -396,59 +2268,90 @@ impl Emulator {
         if color_settings.changed {
             color_settings.changed = false;
 
-            let color_bg = color_settings.get(Color::Background);
-            self.display.color_bg = [
-                (color_bg[0] * 255.0) as u8,
-                (color_bg[1] * 255.0) as u8,
-                (color_bg[2] * 255.0) as u8,
-            ];
-            let color_plane_1 = color_settings.get(Color::Plane1);
-            self.display.color_plane_1 = [
-                (color_plane_1[0] * 255.0) as u8,
-                (color_plane_1[1] * 255.0) as u8,
-                (color_plane_1[2] * 255.0) as u8,
-            ];
-            let color_plane_2 = color_settings.get(Color::Plane2);
-            self.display.color_plane_2 = [
-                (color_plane_2[0] * 255.0) as u8,
-                (color_plane_2[1] * 255.0) as u8,
-                (color_plane_2[2] * 255.0) as u8,
-            ];
-            let color_plane_both = color_settings.get(Color::PlaneBoth);
-            self.display.color_plane_both = [
-                (color_plane_both[0] * 255.0) as u8,
-                (color_plane_both[1] * 255.0) as u8,
-                (color_plane_both[2] * 255.0) as u8,
-            ];
-        }
-
-        self.cpu_speed = self.gui.cpu_speed as u32;
+            let colors = [
+                Color::Background,
+                Color::Plane1,
+                Color::Plane2,
+                Color::PlaneBoth,
+            ]
+            .map(|color| color_settings.get(color).map(|c| (c * 255.0) as u8));
+            self.display.set_colors(colors);
+            #[cfg(feature = "plugins")]
+            for plugin in &mut self.plugins {
+                plugin.set_colors(colors);
+            }
+        }
+
+        if self.cpu_speed != self.gui.cpu_speed {
+            debug!("CPU speed set to {} Hz", self.gui.cpu_speed);
+        }
+        self.cpu_speed = self.gui.cpu_speed;
         self.mute = self.gui.flag_mute;
-        self.sound.set_volume(self.gui.volume);
+        for output in self.sound_outputs() {
+            output.set_volume(self.gui.volume);
+        }
 
-        let quirks = self.gui.quirks_settings();
-        self.cpu.quirk_load_store = quirks.get(Quirk::LoadStore);
-        self.cpu.quirk_shift = quirks.get(Quirk::Shift);
-        self.cpu.quirk_draw = quirks.get(Quirk::Draw);
-        self.cpu.quirk_jump = quirks.get(Quirk::Jump);
-        self.cpu.quirk_vf_order = quirks.get(Quirk::VfOrder);
-        self.cpu.quirk_partialwrap_h = quirks.get(Quirk::PartialWrapH);
-        self.cpu.quirk_partialwrap_v = quirks.get(Quirk::PartialWrapV);
+        self.apply_quirks();
 
         self.step = self.gui.flag_step;
         self.gui.flag_step = false;
         self.step_timers = self.gui.flag_step_timers;
         self.gui.flag_step_timers = false;
+        self.step_back = self.gui.flag_step_back;
+        self.gui.flag_step_back = false;
+        self.gui.can_step_back = !self.step_history.is_empty();
 
         if pause != self.pause {
             self.set_pause(pause);
         }
+
+        // Dim the screen while paused by the user
+        if self.gui.flag_pause != self.display.dimmed() {
+            self.display.set_dimmed(self.gui.flag_pause);
+            self.force_redraw = true;
+        }
+    }
+
+    /// Executes the next instruction and passes it to the GUI if a window analyzes the
+    /// program flow. Nothing is executed while waiting for a key.
+    #[inline]
+    fn run_cycles(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            if let Some(compare_cpu) = &mut self.compare_cpu {
+                // A failing comparison must not stop the main CPU
+                let _ = compare_cpu.tick(&self.frame_input);
+            }
+            if let Err(e) = self.tick_cpu() {
+                self.gui.display_error(&format!("Error: {}", e));
+                continue;
+            }
+            // Conditional breakpoints are always checked to track changed values
+            let hit = self.gui.breakpoints_mut().check(&self.cpu);
+            let returned = self.return_depth.is_some_and(|depth| self.cpu.sp() < depth);
+            if hit || returned || (self.gui.flag_debug && self.check_breakpoints()) {
+                self.gui.flag_pause = true;
+                break;
+            }
+        }
+    }
+
+    fn tick_cpu(&mut self) -> Result<(), cpu::Error> {
+        let input = self.frame_input;
+        if !self.gui.records_instructions() {
+            return self.cpu.tick(&input);
+        }
+        let waiting = self.cpu.key_wait().is_some();
+        let (address, opcode, i) = (self.cpu.PC(), self.cpu.next_opcode(), self.cpu.I());
+        self.cpu.tick(&input)?;
+        if !waiting || self.cpu.key_wait().is_none() {
+            self.gui.record_instruction(address, opcode, i);
+        }
+        Ok(())
     }
 
     #[inline]
     fn check_breakpoints(&mut self) -> bool {
         // Check breakpoints
-        use std::u16;
         if self.gui.flag_breakpoint_pc() {
             if let Ok(bp) = u16::from_str_radix(self.gui.breakpoint_pc(), 16) {
                 if self.cpu.check_breakpoint(Breakpoint::PC(bp)) {
@@ -486,22 +2389,14 @@ impl Emulator {
     ) {
         use ElementState::*;
         use VirtualKeyCode::*;
-        const SCANCODE_1: u32 = 2;
-        const SCANCODE_2: u32 = 3;
-        const SCANCODE_3: u32 = 4;
-        const SCANCODE_4: u32 = 5;
-        const SCANCODE_Q: u32 = 16;
-        const SCANCODE_W: u32 = 17;
-        const SCANCODE_E: u32 = 18;
-        const SCANCODE_R: u32 = 19;
-        const SCANCODE_A: u32 = 30;
-        const SCANCODE_S: u32 = 31;
-        const SCANCODE_D: u32 = 32;
-        const SCANCODE_F: u32 = 33;
-        const SCANCODE_Z: u32 = 44;
-        const SCANCODE_X: u32 = 45;
-        const SCANCODE_C: u32 = 46;
-        const SCANCODE_V: u32 = 47;
+
+        if state == Pressed {
+            if let Some(key) = self.gui.key_binding.take() {
+                self.keymap.0[key] = scancode;
+                self.gui.set_keymap(self.keymap);
+                return;
+            }
+        }
 
         if let Some(keycode) = virtual_keycode {
             let ctrl = self.modifiers_state.ctrl();
@@ -517,29 +2412,77 @@ impl Emulator {
                     if self.gui.flag_fullscreen {
                         self.gui.flag_fullscreen = false;
                     } else {
-                        *ctrl_flow = ControlFlow::Exit;
+                        self.request_exit(ctrl_flow);
                     }
                 }
+                (_, PageDown, Pressed, _, _) if self.playlist.is_some() => {
+                    self.advance_playlist(true);
+                }
+                (_, PageUp, Pressed, _, _) if self.playlist.is_some() => {
+                    self.advance_playlist(false);
+                }
+                (_, F2, Pressed, _, _) => {
+                    self.keypad.push(Control::QuickSave);
+                }
+                (_, F3, Pressed, _, _) => {
+                    self.keypad.push(Control::QuickLoad);
+                }
+                (_, Key0, Pressed, true, _)
+                | (_, Key1, Pressed, true, _)
+                | (_, Key2, Pressed, true, _)
+                | (_, Key3, Pressed, true, _)
+                | (_, Key4, Pressed, true, _)
+                | (_, Key5, Pressed, true, _)
+                | (_, Key6, Pressed, true, _)
+                | (_, Key7, Pressed, true, _)
+                | (_, Key8, Pressed, true, _)
+                | (_, Key9, Pressed, true, _) => {
+                    // The key codes 1 - 9 are followed by 0
+                    self.gui.state_slot = (keycode as usize + 1 - Key1 as usize) % 10;
+                    self.gui
+                        .display_notice(&format!("Selected state slot {}", self.gui.state_slot));
+                }
+                (_, F1, Pressed, _, true) => {
+                    self.gui.flag_display_speed = !self.gui.flag_display_speed;
+                }
                 (_, F1, Pressed, _, _) => {
                     self.gui.flag_display_fps = !self.gui.flag_display_fps;
                 }
                 (_, F5, Pressed, _, _) => {
-                    self.gui.flag_reset = true;
+                    self.keypad.push(Control::Reset);
                 }
                 (_, F7, Pressed, _, _) => {
                     self.gui.flag_debug = !self.gui.flag_debug;
                 }
+                (_, F8, Pressed, _, true) => {
+                    self.gui.flag_step_back = true;
+                }
                 (_, F8, Pressed, _, _) => {
-                    self.gui.flag_step = true;
+                    self.keypad.push(Control::Step);
+                }
+                (_, F10, Pressed, _, true) => {
+                    self.gui.flag_step_out = true;
+                }
+                (_, F10, Pressed, _, _) => {
+                    self.gui.flag_step_over = true;
                 }
                 (_, F9, Pressed, _, _) => {
                     self.gui.flag_step_timers = true;
                 }
+                (_, F12, Pressed, _, true) => {
+                    self.gui.flag_record = true;
+                }
+                (_, F12, Pressed, _, _) => {
+                    self.gui.flag_screenshot = true;
+                }
                 (_, F11, Pressed, _, _) => {
                     self.gui.flag_fullscreen = !self.gui.flag_fullscreen;
                 }
                 (_, P, Pressed, _, _) => {
-                    self.gui.flag_pause = !self.gui.flag_pause;
+                    self.keypad.push(Control::TogglePause);
+                }
+                (_, Space, Pressed, _, _) if self.keymap.key(scancode).is_none() => {
+                    self.keypad.push(Control::TogglePause);
                 }
                 (_, M, Pressed, _, _) => {
                     self.gui.flag_mute = !self.gui.flag_mute;
@@ -552,40 +2495,11 @@ impl Emulator {
                 }
 
                 // Chip8 keys - using scancode instead of VirtualKeyCode to account for different keyboard layouts
-                (SCANCODE_1, _, Pressed, _, _) => self.input[1] = true,
-                (SCANCODE_1, _, Released, _, _) => self.input[1] = false,
-                (SCANCODE_2, _, Pressed, _, _) => self.input[2] = true,
-                (SCANCODE_2, _, Released, _, _) => self.input[2] = false,
-                (SCANCODE_3, _, Pressed, _, _) => self.input[3] = true,
-                (SCANCODE_3, _, Released, _, _) => self.input[3] = false,
-                (SCANCODE_4, _, Pressed, _, _) => self.input[0xC] = true,
-                (SCANCODE_4, _, Released, _, _) => self.input[0xC] = false,
-                (SCANCODE_Q, _, Pressed, _, _) => self.input[4] = true,
-                (SCANCODE_Q, _, Released, _, _) => self.input[4] = false,
-                (SCANCODE_W, _, Pressed, _, _) => self.input[5] = true,
-                (SCANCODE_W, _, Released, _, _) => self.input[5] = false,
-                (SCANCODE_E, _, Pressed, _, _) => self.input[6] = true,
-                (SCANCODE_E, _, Released, _, _) => self.input[6] = false,
-                (SCANCODE_R, _, Pressed, _, _) => self.input[0xD] = true,
-                (SCANCODE_R, _, Released, _, _) => self.input[0xD] = false,
-                (SCANCODE_A, _, Pressed, _, _) => self.input[7] = true,
-                (SCANCODE_A, _, Released, _, _) => self.input[7] = false,
-                (SCANCODE_S, _, Pressed, _, _) => self.input[8] = true,
-                (SCANCODE_S, _, Released, _, _) => self.input[8] = false,
-                (SCANCODE_D, _, Pressed, _, _) => self.input[9] = true,
-                (SCANCODE_D, _, Released, _, _) => self.input[9] = false,
-                (SCANCODE_F, _, Pressed, _, _) => self.input[0xE] = true,
-                (SCANCODE_F, _, Released, _, _) => self.input[0xE] = false,
-                (SCANCODE_Z, _, Pressed, _, _) => self.input[0xA] = true,
-                (SCANCODE_Z, _, Released, _, _) => self.input[0xA] = false,
-                (SCANCODE_X, _, Pressed, _, _) => self.input[0] = true,
-                (SCANCODE_X, _, Released, _, _) => self.input[0] = false,
-                (SCANCODE_C, _, Pressed, _, _) => self.input[0xB] = true,
-                (SCANCODE_C, _, Released, _, _) => self.input[0xB] = false,
-                (SCANCODE_V, _, Pressed, _, _) => self.input[0xF] = true,
-                (SCANCODE_V, _, Released, _, _) => self.input[0xF] = false,
-
-                _ => (),
+                _ => {
+                    if let Some(key) = self.keymap.key(scancode) {
+                        self.keypad.set(key, state == Pressed);
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,40 @@
+use glium::{
+    backend::glutin::DisplayCreationError, texture::TextureCreationError, SwapBuffersError,
+};
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// Errors which prevent the emulator from starting or continuing to run.
+/// Recoverable errors (e.g. an invalid ROM) are displayed in the GUI instead.
+#[derive(Debug, Error)]
+pub enum Pich8Error {
+    #[error("Cannot open {}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error("Failed to create window: {0}")]
+    DisplayCreation(#[from] DisplayCreationError),
+
+    #[error("Failed to load icon: {0}")]
+    Icon(String),
+
+    #[error("Failed to create texture: {0}")]
+    Texture(#[from] TextureCreationError),
+
+    #[error("Failed to swap buffers: {0}")]
+    SwapBuffers(#[from] SwapBuffersError),
+
+    #[error("UI error: {0}")]
+    Gui(String),
+
+    #[error("No audio output device available: {0}")]
+    Audio(String),
+
+    #[error("{0}")]
+    Headless(String),
+
+    #[error("{0}")]
+    Playlist(String),
+
+    #[error("{0}")]
+    Stdin(String),
+}
@@ -0,0 +1,312 @@
+use crate::config::KeyMap;
+use crate::display::{DisplayOutput, Frame};
+use crate::input::{Input, InputSource};
+use log::{info, warn};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::mpsc::{channel, Receiver},
+    thread,
+};
+
+/// The Linux framebuffer device, e.g. `/dev/fb0`, for appliances and kiosks without X or Wayland.
+/// DRM/KMS isn't used directly, but its drivers usually provide a framebuffer device too.
+/// 16 (RGB565) and 32 (XRGB8888) bits per pixel are supported.
+pub struct Framebuffer {
+    file: File,
+    /// Where the visible area starts, which is panned within a larger virtual screen if the
+    /// framebuffer is double buffered
+    offset: u64,
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+    stride: usize,
+    colors: [[u8; 3]; 4],
+    buffer: Vec<u8>,
+    /// The last presented pixels, to skip writing unchanged frames
+    last_pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("Invalid framebuffer device {}", path.display()))?;
+        let sysfs = Path::new("/sys/class/graphics").join(name).join("stride");
+        let stride = fs::read_to_string(&sysfs)
+            .map_err(|e| format!("Cannot read {}: {}", sysfs.display(), e))?;
+        let stride = stride
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid stride of {}: '{}'", path.display(), stride.trim()))?;
+
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        // The visible resolution, the virtual one is e.g. twice as high for double buffering
+        let info = Self::screen_info(&file)
+            .map_err(|e| format!("Cannot read the screen info of {}: {}", path.display(), e))?;
+        let (width, height) = (info.xres as usize, info.yres as usize);
+        let bits_per_pixel = info.bits_per_pixel;
+        let bytes_per_pixel = match bits_per_pixel {
+            16 => 2,
+            32 => 4,
+            _ => {
+                return Err(format!(
+                    "Unsupported {} bits per pixel of {}",
+                    bits_per_pixel,
+                    path.display()
+                ))
+            }
+        };
+        let offset =
+            info.yoffset as u64 * stride as u64 + info.xoffset as u64 * bytes_per_pixel as u64;
+        info!(
+            "Opened {}x{} framebuffer {} ({}x{} virtual, {} bits per pixel)",
+            width,
+            height,
+            path.display(),
+            info.xres_virtual,
+            info.yres_virtual,
+            bits_per_pixel
+        );
+        Ok(Self {
+            file,
+            offset,
+            width,
+            height,
+            bytes_per_pixel,
+            stride,
+            colors: [[0; 3]; 4],
+            buffer: Vec::new(),
+            last_pixels: Vec::new(),
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn screen_info(file: &File) -> Result<ScreenInfo, String> {
+        use std::os::unix::io::AsRawFd;
+        const FBIOGET_VSCREENINFO: u64 = 0x4600;
+        let mut info = ScreenInfo::default();
+        // SAFETY: FBIOGET_VSCREENINFO fills a `struct fb_var_screeninfo`, which `ScreenInfo` mirrors
+        let result = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                FBIOGET_VSCREENINFO as _,
+                &mut info as *mut ScreenInfo,
+            )
+        };
+        match result {
+            0 => Ok(info),
+            _ => Err(std::io::Error::last_os_error().to_string()),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn screen_info(_file: &File) -> Result<ScreenInfo, String> {
+        Err("framebuffer devices are only supported on Linux".to_string())
+    }
+
+    fn encode(&self, [r, g, b]: [u8; 3]) -> [u8; 4] {
+        if self.bytes_per_pixel == 2 {
+            let rgb565 = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3;
+            let [low, high] = rgb565.to_le_bytes();
+            [low, high, 0, 0]
+        } else {
+            [b, g, r, 0xFF]
+        }
+    }
+}
+
+/// The start of `struct fb_var_screeninfo`, padded to its full size.
+#[repr(C)]
+struct ScreenInfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    /// The color layout, timings and flags, which aren't needed
+    rest: [u32; 33],
+}
+
+impl Default for ScreenInfo {
+    fn default() -> Self {
+        Self {
+            xres: 0,
+            yres: 0,
+            xres_virtual: 0,
+            yres_virtual: 0,
+            xoffset: 0,
+            yoffset: 0,
+            bits_per_pixel: 0,
+            rest: [0; 33],
+        }
+    }
+}
+
+impl DisplayOutput for Framebuffer {
+    fn colors(&self) -> [[u8; 3]; 4] {
+        self.colors
+    }
+
+    fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+        self.colors = colors;
+        self.last_pixels.clear();
+    }
+
+    /// Draws the screen scaled by the largest integer factor fitting the framebuffer, centered.
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        if frame.pixels == self.last_pixels {
+            return Ok(());
+        }
+        let (width, height) = (frame.width, frame.height);
+
+        let scale = (self.width / width).min(self.height / height).max(1);
+        let left = self.width.saturating_sub(width * scale) / 2;
+        let top = self.height.saturating_sub(height * scale) / 2;
+        let colors = self.colors.map(|color| self.encode(color));
+        let bytes = self.bytes_per_pixel;
+        let background = &colors[0][..bytes];
+        self.buffer.resize(self.stride * self.height, 0);
+        for (y, row) in self.buffer.chunks_exact_mut(self.stride).enumerate() {
+            let screen_y = y.checked_sub(top).map(|y| y / scale);
+            for (x, pixel) in row[..self.width * bytes]
+                .chunks_exact_mut(bytes)
+                .enumerate()
+            {
+                let screen_x = x.checked_sub(left).map(|x| x / scale);
+                match (screen_x, screen_y) {
+                    (Some(screen_x), Some(screen_y)) if screen_x < width && screen_y < height => {
+                        let value = frame.pixels[screen_y * width + screen_x] & 3;
+                        pixel.copy_from_slice(&colors[value as usize][..bytes]);
+                    }
+                    _ => pixel.copy_from_slice(background),
+                }
+            }
+        }
+        self.file
+            .seek(SeekFrom::Start(self.offset))
+            .and_then(|_| self.file.write_all(&self.buffer))
+            .map_err(|e| format!("Cannot write to the framebuffer: {}", e))?;
+        self.last_pixels.clone_from(&frame.pixels);
+        Ok(())
+    }
+}
+
+/// Reads the key events of an evdev device, e.g. `/dev/input/event0`, in the background.
+/// The keys are mapped like in the window, since its scancodes are the evdev key codes on Linux.
+pub struct EvdevKeyboard {
+    events: Receiver<(u32, bool)>,
+    keymap: KeyMap,
+    keys: [bool; 16],
+}
+
+impl EvdevKeyboard {
+    const EV_KEY: u16 = 1;
+    /// The size of `struct input_event`, whose time takes 8 or 16 bytes depending on the target,
+    /// followed by type, code and value
+    #[cfg(target_os = "linux")]
+    const EVENT_SIZE: usize = std::mem::size_of::<libc::input_event>();
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn open(path: &Path, _keymap: KeyMap) -> Result<Self, String> {
+        Err(format!(
+            "Cannot open {}: evdev devices are only supported on Linux",
+            path.display()
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn open(path: &Path, keymap: KeyMap) -> Result<Self, String> {
+        let mut file =
+            File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let mut event = [0; Self::EVENT_SIZE];
+            loop {
+                if let Err(e) = file.read_exact(&mut event) {
+                    warn!("Cannot read key events: {}", e);
+                    return;
+                }
+                let fields = &event[Self::EVENT_SIZE - 8..];
+                let kind = u16::from_ne_bytes([fields[0], fields[1]]);
+                let code = u16::from_ne_bytes([fields[2], fields[3]]);
+                let value = i32::from_ne_bytes([fields[4], fields[5], fields[6], fields[7]]);
+                // Auto-repeated keys (value 2) are still held down
+                if kind == Self::EV_KEY && tx.send((code as u32, value != 0)).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Self {
+            events: rx,
+            keymap,
+            keys: [false; 16],
+        })
+    }
+}
+
+impl InputSource for EvdevKeyboard {
+    fn poll(&mut self, _frame: u64) -> Input {
+        while let Ok((scancode, pressed)) = self.events.try_recv() {
+            if let Some(key) = self.keymap.key(scancode) {
+                self.keys[key] = pressed;
+            }
+        }
+        Input {
+            keys: self.keys,
+            controls: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod fbdev_test {
+    use super::*;
+
+    #[test]
+    fn test_present() {
+        let path = std::env::temp_dir().join("pich8_fbdev_test");
+        let mut framebuffer = Framebuffer {
+            file: File::create(&path).unwrap(),
+            offset: 0,
+            width: 10,
+            height: 6,
+            bytes_per_pixel: 2,
+            stride: 24,
+            colors: [[0; 3]; 4],
+            buffer: Vec::new(),
+            last_pixels: Vec::new(),
+        };
+        framebuffer.set_colors([[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 0, 255]]);
+        let frame = Frame {
+            width: 4,
+            height: 2,
+            pixels: vec![1, 0, 0, 2, 0, 0, 0, 3],
+        };
+        framebuffer.present(&frame).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        // Scaled by 2 and centered, with a border of 1 pixel
+        let pixel = |x: usize, y: usize| {
+            let offset = y * 24 + x * 2;
+            u16::from_le_bytes([data[offset], data[offset + 1]])
+        };
+        assert_eq!(data.len(), 24 * 6);
+        assert_eq!((pixel(0, 1), pixel(1, 0)), (0, 0));
+        assert_eq!((pixel(1, 1), pixel(2, 2)), (0xFFFF, 0xFFFF));
+        assert_eq!(pixel(9, 2), 0);
+        assert_eq!((pixel(8, 1), pixel(7, 4)), (0xF800, 0x001F));
+    }
+
+    #[test]
+    fn test_screen_info_layout() {
+        // The size of `struct fb_var_screeninfo`
+        assert_eq!(std::mem::size_of::<ScreenInfo>(), 160);
+    }
+}
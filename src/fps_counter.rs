@@ -17,6 +17,10 @@ impl FpsCounter {
         }
     }
 
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
     pub fn tick(&mut self) -> f64 {
         self.frames += 1;
 
@@ -0,0 +1,87 @@
+use log::info;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Publishes every changed frame into a file, meant to be on a RAM-backed file system like
+/// `/dev/shm` on Linux, so capture tools can map it and read the frames losslessly.
+///
+/// The file starts with a header of little-endian fields:
+/// `P8FB` magic (4 bytes), version (u32), sequence number (u64), width (u32), height (u32), and
+/// 8 reserved bytes, followed by the RGB pixels row by row. The sequence number is odd while
+/// a frame is written and increases by two with every frame, so readers can detect torn frames.
+pub struct FrameExport {
+    file: File,
+    sequence: u64,
+}
+
+impl FrameExport {
+    const MAGIC: &'static [u8; 4] = b"P8FB";
+    const VERSION: u32 = 1;
+    const HEADER_SIZE: usize = 32;
+    /// The largest screen, so the file doesn't need to grow when the resolution changes
+    const MAX_PIXELS_SIZE: usize = 128 * 64 * 3;
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        let mut header = Vec::with_capacity(Self::HEADER_SIZE);
+        header.extend_from_slice(Self::MAGIC);
+        header.extend_from_slice(&Self::VERSION.to_le_bytes());
+        header.resize(Self::HEADER_SIZE + Self::MAX_PIXELS_SIZE, 0);
+        file.write_all(&header)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        info!("Exporting frames to {}", path.display());
+        Ok(Self { file, sequence: 0 })
+    }
+
+    pub fn publish(&mut self, image: &image::RgbImage) -> Result<(), String> {
+        let mut frame = Vec::with_capacity(16 + image.len());
+        frame.extend_from_slice(&image.width().to_le_bytes());
+        frame.extend_from_slice(&image.height().to_le_bytes());
+        frame.extend_from_slice(&[0; 8]);
+        frame.extend_from_slice(image.as_raw());
+        self.write_sequence(self.sequence + 1)?;
+        self.file
+            .write_all(&frame)
+            .map_err(|e| format!("Cannot export frame: {}", e))?;
+        self.sequence += 2;
+        self.write_sequence(self.sequence)
+    }
+
+    /// Writes the sequence number, followed by the frame if the file position isn't changed.
+    fn write_sequence(&mut self, sequence: u64) -> Result<(), String> {
+        self.file
+            .seek(SeekFrom::Start(8))
+            .and_then(|_| self.file.write_all(&sequence.to_le_bytes()))
+            .map_err(|e| format!("Cannot export frame: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod frame_export_test {
+    use super::*;
+
+    #[test]
+    fn test_publish() {
+        let path = std::env::temp_dir().join("pich8_frame_export_test");
+        let mut export = FrameExport::open(&path).unwrap();
+        let image = image::RgbImage::from_fn(128, 64, |x, _| image::Rgb([x as u8, 0, 255]));
+        export.publish(&image).unwrap();
+        export.publish(&image).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.len(), 32 + 128 * 64 * 3);
+        assert_eq!(&data[..8], b"P8FB\x01\0\0\0");
+        assert_eq!(&data[8..16], &4u64.to_le_bytes());
+        assert_eq!(&data[16..24], &[128, 0, 0, 0, 64, 0, 0, 0]);
+        assert_eq!(&data[32 + 5 * 3..32 + 6 * 3], &[5, 0, 255]);
+    }
+}
@@ -0,0 +1,205 @@
+use glium::glutin::event_loop::ControlFlow;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How the emulator waits for the next frame.
+#[derive(Copy, Clone, PartialEq, Debug, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FramePacing {
+    /// Sleep until shortly before the next frame, then spin for accuracy
+    #[default]
+    Hybrid,
+    /// Only sleep, uses the least CPU but depends on the OS timer resolution
+    Sleep,
+    /// Never sleep and render as often as possible
+    Busy,
+    /// Let the display's vertical sync limit the frame rate, and emulate one frame per refresh
+    /// if the display runs at about 60 Hz
+    Vsync,
+}
+
+/// Decides when the next frame is rendered, according to the frame pacing strategy, and when the
+/// next frames are emulated.
+///
+/// The emulated frames follow a fixed timeline instead of waiting a frame's duration after each
+/// of them, so late frames are caught up rather than slowing the emulation down over time.
+pub struct FrameLimiter {
+    pacing: FramePacing,
+    next_frame: Instant,
+    next_emulated: Instant,
+    last_presented: Option<Instant>,
+    /// The moving average of the measured time between presented frames, in seconds
+    refresh_interval: Option<f64>,
+}
+
+impl FrameLimiter {
+    const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+    const SPIN_DURATION: Duration = Duration::from_millis(2);
+    /// Falling further behind, e.g. after a breakpoint or a stalled window, restarts the timeline
+    /// instead of emulating all missed frames at once
+    const MAX_FRAMES_BEHIND: u32 = 6;
+    /// How much the refresh rate may differ from 60 Hz for the emulation to follow it
+    const REFRESH_TOLERANCE: f64 = 0.02;
+    /// The weight of a new measurement in the average refresh interval
+    const REFRESH_SMOOTHING: f64 = 0.05;
+
+    pub fn new(pacing: FramePacing) -> Self {
+        let now = Instant::now();
+        Self {
+            pacing,
+            next_frame: now,
+            next_emulated: now,
+            last_presented: None,
+            refresh_interval: None,
+        }
+    }
+
+    /// Restarts the timeline of the emulated frames, e.g. when a ROM is loaded.
+    pub fn reset(&mut self, now: Instant) {
+        self.next_emulated = now;
+    }
+
+    /// Moves the timeline of the emulated frames by the time the emulation was paused.
+    pub fn resume(&mut self, paused: Duration) {
+        self.next_emulated += paused;
+    }
+
+    /// Measures the time between the presented frames, called whenever a frame is presented.
+    pub fn presented(&mut self, now: Instant) {
+        if let Some(last) = self.last_presented {
+            let interval = (now - last).as_secs_f64();
+            self.refresh_interval = Some(match self.refresh_interval {
+                Some(average) => average + (interval - average) * Self::REFRESH_SMOOTHING,
+                None => interval,
+            });
+        }
+        self.last_presented = Some(now);
+    }
+
+    /// Returns whether the emulation follows the display's refresh, which is the case with vsync
+    /// at about 60 Hz.
+    pub fn follows_refresh(&self) -> bool {
+        let frame = Self::FRAME_DURATION.as_secs_f64();
+        self.pacing == FramePacing::Vsync
+            && self
+                .refresh_interval
+                .is_some_and(|interval| (interval - frame).abs() <= frame * Self::REFRESH_TOLERANCE)
+    }
+
+    /// Returns how many frames are due to be emulated.
+    ///
+    /// When following the display's refresh, a frame is emulated on every call until the
+    /// emulation is a whole frame ahead of the timeline, and an extra one once it's a frame
+    /// behind, so it runs in step with the display while the drift is compensated.
+    pub fn due_frames(&mut self, now: Instant) -> u32 {
+        let lead = if self.follows_refresh() {
+            Self::FRAME_DURATION
+        } else {
+            Duration::ZERO
+        };
+        if now + lead < self.next_emulated {
+            return 0;
+        }
+        let behind = now.saturating_duration_since(self.next_emulated);
+        let frames = 1 + (behind.as_nanos() / Self::FRAME_DURATION.as_nanos()) as u32;
+        if frames > Self::MAX_FRAMES_BEHIND {
+            self.next_emulated = now + Self::FRAME_DURATION;
+            return 1;
+        }
+        self.next_emulated += Self::FRAME_DURATION * frames;
+        frames
+    }
+
+    /// Returns whether a new frame is due and lets the event loop wait accordingly until the next one.
+    pub fn update(&mut self, ctrl_flow: &mut ControlFlow) -> bool {
+        // Don't override a requested exit
+        if *ctrl_flow == ControlFlow::Exit {
+            return false;
+        }
+
+        let now = Instant::now();
+        let due = match self.pacing {
+            FramePacing::Busy | FramePacing::Vsync => true,
+            FramePacing::Hybrid | FramePacing::Sleep => {
+                if now >= self.next_frame {
+                    // Advance by a fixed step to avoid drifting, unless we're lagging behind too much
+                    self.next_frame += Self::FRAME_DURATION;
+                    if self.next_frame < now {
+                        self.next_frame = now + Self::FRAME_DURATION;
+                    }
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        // Also wake up for the next emulated frame, unless it's overdue because of a pause
+        let wake = match self.next_emulated {
+            next_emulated if next_emulated > now => self.next_frame.min(next_emulated),
+            _ => self.next_frame,
+        };
+        *ctrl_flow = match self.pacing {
+            FramePacing::Busy | FramePacing::Vsync => ControlFlow::Poll,
+            FramePacing::Sleep => ControlFlow::WaitUntil(wake),
+            FramePacing::Hybrid => {
+                if wake.saturating_duration_since(now) > Self::SPIN_DURATION {
+                    ControlFlow::WaitUntil(wake - Self::SPIN_DURATION)
+                } else {
+                    ControlFlow::Poll
+                }
+            }
+        };
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod frame_limiter_test {
+    use super::*;
+
+    #[test]
+    fn test_due_frames() {
+        let frame = FrameLimiter::FRAME_DURATION;
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(FramePacing::Hybrid);
+        limiter.reset(start);
+        assert_eq!(limiter.due_frames(start), 1);
+        assert_eq!(limiter.due_frames(start + frame / 2), 0);
+        // A late frame doesn't delay the following ones
+        assert_eq!(limiter.due_frames(start + frame * 3 / 2), 1);
+        assert_eq!(limiter.due_frames(start + frame * 2), 1);
+        // Missed frames are caught up, unless too many
+        assert_eq!(limiter.due_frames(start + frame * 11 / 2), 3);
+        assert_eq!(limiter.due_frames(start + frame * 20), 1);
+        assert_eq!(limiter.due_frames(start + frame * 41 / 2), 0);
+        limiter.resume(frame * 10);
+        assert_eq!(limiter.due_frames(start + frame * 30), 0);
+        assert_eq!(limiter.due_frames(start + frame * 31), 1);
+    }
+
+    #[test]
+    fn test_follows_refresh() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(FramePacing::Vsync);
+        limiter.reset(start);
+        // A 59.94 Hz display
+        let refresh = Duration::from_secs_f64(1.0 / 59.94);
+        let mut frames = 0;
+        for idx in 0..3600 {
+            let now = start + refresh * idx;
+            limiter.presented(now);
+            frames += limiter.due_frames(now);
+        }
+        assert!(limiter.follows_refresh());
+        // One frame per refresh, with the few extra ones keeping up with 60 Hz
+        assert!((3600..3605).contains(&frames), "{}", frames);
+
+        let mut limiter = FrameLimiter::new(FramePacing::Vsync);
+        for idx in 0..10 {
+            limiter.presented(start + Duration::from_secs_f64(idx as f64 / 144.0));
+        }
+        assert!(!limiter.follows_refresh());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::config::Settings;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GameEntry {
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub settings: Settings,
+}
+
+/// Database of known ROMs with the settings they need to run correctly.
+/// The database shipped with pich8 can be extended by a games.toml in the config directory.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct GameDb {
+    game: BTreeMap<String, GameEntry>,
+}
+
+impl GameDb {
+    const FILE_NAME: &'static str = "games.toml";
+    const BUILTIN: &'static str = include_str!("../data/games.toml");
+
+    /// Loads the builtin database and merges the user's database from the given directory, if it exists.
+    pub fn load(dir: Option<&Path>) -> Result<Self, String> {
+        let mut db = Self::parse(Self::BUILTIN)
+            .map_err(|e| format!("Failed to parse builtin game database: {}", e))?;
+
+        if let Some(path) = dir.map(|dir| dir.join(Self::FILE_NAME)) {
+            if path.exists() {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let user_db = Self::parse(&content)
+                    .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+                db.game.extend(user_db.game);
+            }
+        }
+
+        Ok(db)
+    }
+
+    fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        let mut db: Self = toml::from_str(content)?;
+        // Hashes are compared in lowercase
+        db.game = db
+            .game
+            .into_iter()
+            .map(|(hash, entry)| (hash.to_lowercase(), entry))
+            .collect();
+        Ok(db)
+    }
+
+    pub fn get(&self, rom_hash: &str) -> Option<&GameEntry> {
+        self.game.get(rom_hash)
+    }
+}
+
+#[cfg(test)]
+mod game_db_test {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        assert!(GameDb::load(None).is_ok());
+    }
+
+    #[test]
+    fn test_parse() {
+        let db = GameDb::parse(
+            r#"
+            [game."ABCDEF"]
+            title = "Test"
+            speed = 1000
+            "#,
+        )
+        .unwrap();
+        let entry = db.get("abcdef").unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Test"));
+        assert_eq!(entry.settings.speed, Some(1000));
+        assert!(db.get("012345").is_none());
+    }
+}
@@ -0,0 +1,411 @@
+use crate::headless::Headless;
+use pich8::cpu::Register;
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+/// A minimal GDB remote serial protocol server, so ROMs can be debugged with gdb, LLDB and the IDEs built on them.
+///
+/// Registers are numbered V0 - VF, I, PC, DT and ST. GDB has no CHIP-8 architecture, so the
+/// registers are described with a target description and sent in little-endian byte order,
+/// the default of most architectures GDB falls back to.
+pub struct GdbStub {
+    headless: Headless,
+    breakpoints: BTreeSet<u16>,
+    /// Set after the client asked to stop acknowledging packets
+    no_ack: bool,
+}
+
+/// What to do after a packet was handled.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Reply(String),
+    /// Run until a breakpoint is hit or the client interrupts
+    Continue,
+    /// Close the connection after replying
+    Detach(String),
+}
+
+impl GdbStub {
+    const SIGTRAP: &'static str = "S05";
+    const SIGILL: &'static str = "S04";
+    /// Instructions run between checks for an interrupt from the client
+    const INTERRUPT_CHECK_INTERVAL: u32 = 1000;
+    const TARGET_XML: &'static str = concat!(
+        r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target version="1.0">"#,
+        r#"<feature name="org.pich8.chip8">"#,
+        r#"<reg name="v0" bitsize="8"/><reg name="v1" bitsize="8"/><reg name="v2" bitsize="8"/>"#,
+        r#"<reg name="v3" bitsize="8"/><reg name="v4" bitsize="8"/><reg name="v5" bitsize="8"/>"#,
+        r#"<reg name="v6" bitsize="8"/><reg name="v7" bitsize="8"/><reg name="v8" bitsize="8"/>"#,
+        r#"<reg name="v9" bitsize="8"/><reg name="va" bitsize="8"/><reg name="vb" bitsize="8"/>"#,
+        r#"<reg name="vc" bitsize="8"/><reg name="vd" bitsize="8"/><reg name="ve" bitsize="8"/>"#,
+        r#"<reg name="vf" bitsize="8"/><reg name="i" bitsize="16" type="data_ptr"/>"#,
+        r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#,
+        r#"<reg name="dt" bitsize="8"/><reg name="st" bitsize="8"/>"#,
+        r#"</feature></target>"#
+    );
+
+    pub fn new(headless: Headless) -> Self {
+        Self {
+            headless,
+            breakpoints: BTreeSet::new(),
+            no_ack: false,
+        }
+    }
+
+    /// Waits for a client on the port of localhost and serves it until it detaches.
+    pub fn serve(&mut self, port: u16) -> Result<(), String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Cannot listen on port {}: {}", port, e))?;
+        println!(
+            "Waiting for GDB on port {}, connect with 'target remote localhost:{}'",
+            port, port
+        );
+        let (stream, address) = listener.accept().map_err(|e| e.to_string())?;
+        println!("GDB connected from {}", address);
+        self.handle_connection(stream).map_err(|e| e.to_string())
+    }
+
+    fn handle_connection(&mut self, stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        loop {
+            let packet = match Self::read_packet(&mut reader)? {
+                Some(Packet::Data(packet)) => packet,
+                // An interrupt while stopped, the client still expects a stop reply
+                Some(Packet::Interrupt) => {
+                    self.send(&mut writer, Self::SIGTRAP)?;
+                    continue;
+                }
+                None => return Ok(()),
+            };
+            if !self.no_ack {
+                writer.write_all(b"+")?;
+            }
+            match self.handle_packet(&packet) {
+                Action::Reply(reply) => self.send(&mut writer, &reply)?,
+                Action::Continue => {
+                    let reply = self.run(|| Self::interrupted(&writer))?;
+                    self.send(&mut writer, reply)?;
+                }
+                Action::Detach(reply) => {
+                    self.send(&mut writer, &reply)?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Checks without blocking whether the client sent an interrupt.
+    fn interrupted(stream: &TcpStream) -> std::io::Result<bool> {
+        stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = match stream.peek(&mut byte) {
+            Ok(1) if byte[0] == 0x03 => {
+                (&*stream).read_exact(&mut byte)?;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        };
+        stream.set_nonblocking(false)?;
+        result
+    }
+
+    fn read_packet(reader: &mut impl Read) -> std::io::Result<Option<Packet>> {
+        let mut byte = [0];
+        loop {
+            if reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            match byte[0] {
+                b'$' => break,
+                0x03 => return Ok(Some(Packet::Interrupt)),
+                // Acknowledgements, and noise before the packet
+                _ => (),
+            }
+        }
+        let mut data = Vec::new();
+        loop {
+            if reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        // The checksum isn't verified, TCP already guarantees the data arrives intact
+        let mut checksum = [0; 2];
+        reader.read_exact(&mut checksum)?;
+        Ok(Some(Packet::Data(
+            String::from_utf8_lossy(&data).into_owned(),
+        )))
+    }
+
+    fn send(&self, writer: &mut impl Write, data: &str) -> std::io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(writer, "${}#{:02x}", data, checksum)?;
+        writer.flush()
+    }
+
+    fn handle_packet(&mut self, packet: &str) -> Action {
+        let reply = |reply: &str| Action::Reply(reply.to_string());
+        let (command, args) = packet.split_at(packet.len().min(1));
+        match command {
+            "?" => reply(Self::SIGTRAP),
+            "g" => Action::Reply((0..20).map(|idx| self.read_register(idx)).collect()),
+            "G" => {
+                let mut args = args;
+                for idx in 0..20 {
+                    let len = Self::register_size(idx) * 2;
+                    match args.get(..len).and_then(decode_le) {
+                        Some(value) => self.write_register(idx, value),
+                        None => return reply("E01"),
+                    }
+                    args = &args[len..];
+                }
+                reply("OK")
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(idx) if idx < 20 => Action::Reply(self.read_register(idx)),
+                _ => reply("E01"),
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(idx, value)| {
+                    let idx = usize::from_str_radix(idx, 16)
+                        .ok()
+                        .filter(|&idx| idx < 20)?;
+                    Some((idx, decode_le(value)?))
+                });
+                match parsed {
+                    Some((idx, value)) => {
+                        self.write_register(idx, value);
+                        reply("OK")
+                    }
+                    None => reply("E01"),
+                }
+            }
+            "m" => match parse_range(args) {
+                Some((address, len)) => {
+                    let cpu = self.headless.cpu();
+                    Action::Reply((0..len).fold(String::new(), |mut data, offset| {
+                        let _ = write!(data, "{:02x}", cpu.read_mem(address.wrapping_add(offset)));
+                        data
+                    }))
+                }
+                None => reply("E01"),
+            },
+            "M" => {
+                let parsed = args
+                    .split_once(':')
+                    .and_then(|(range, data)| Some((parse_range(range)?, decode_bytes(data)?)));
+                match parsed {
+                    Some(((address, len), data)) if data.len() == len as usize => {
+                        for (offset, value) in data.into_iter().enumerate() {
+                            self.headless
+                                .cpu_mut()
+                                .write_mem(address.wrapping_add(offset as u16), value);
+                        }
+                        reply("OK")
+                    }
+                    _ => reply("E01"),
+                }
+            }
+            "Z" | "z" => match args.strip_prefix("0,").and_then(parse_range) {
+                Some((address, _)) => {
+                    if command == "Z" {
+                        self.breakpoints.insert(address);
+                    } else {
+                        self.breakpoints.remove(&address);
+                    }
+                    reply("OK")
+                }
+                // Only software breakpoints are supported
+                None => reply(""),
+            },
+            "s" => reply(match self.headless.step(&[false; 16]) {
+                Ok(_) => Self::SIGTRAP,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    Self::SIGILL
+                }
+            }),
+            "c" => Action::Continue,
+            "H" => reply("OK"),
+            "k" => Action::Detach(String::new()),
+            "D" => Action::Detach("OK".to_string()),
+            _ => self.handle_query(packet),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Action {
+        let reply = |reply: &str| Action::Reply(reply.to_string());
+        if packet.starts_with("qSupported") {
+            reply("PacketSize=4000;qXfer:features:read+;QStartNoAckMode+")
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            reply("OK")
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, len) = match range.split_once(',').and_then(|(offset, len)| {
+                Some((
+                    usize::from_str_radix(offset, 16).ok()?,
+                    usize::from_str_radix(len, 16).ok()?,
+                ))
+            }) {
+                Some(range) => range,
+                None => return reply("E01"),
+            };
+            let xml = Self::TARGET_XML;
+            let start = offset.min(xml.len());
+            let end = (start + len).min(xml.len());
+            let more = if end < xml.len() { "m" } else { "l" };
+            Action::Reply(format!("{}{}", more, &xml[start..end]))
+        } else if packet == "qAttached" {
+            reply("1")
+        } else if packet == "qC" {
+            reply("QC1")
+        } else if packet == "qfThreadInfo" {
+            reply("m1")
+        } else if packet == "qsThreadInfo" {
+            reply("l")
+        } else {
+            // An empty reply tells the client the packet isn't supported
+            reply("")
+        }
+    }
+
+    /// Runs until a breakpoint is hit or `interrupted` returns true, and returns the stop reply.
+    fn run(
+        &mut self,
+        mut interrupted: impl FnMut() -> std::io::Result<bool>,
+    ) -> std::io::Result<&'static str> {
+        loop {
+            for _ in 0..Self::INTERRUPT_CHECK_INTERVAL {
+                if let Err(e) = self.headless.step(&[false; 16]) {
+                    eprintln!("{}", e);
+                    return Ok(Self::SIGILL);
+                }
+                if self.breakpoints.contains(&self.headless.cpu().PC()) {
+                    return Ok(Self::SIGTRAP);
+                }
+            }
+            if interrupted()? {
+                return Ok(Self::SIGTRAP);
+            }
+        }
+    }
+
+    fn register(idx: usize) -> Register {
+        match idx {
+            16 => Register::I,
+            17 => Register::PC,
+            18 => Register::DT,
+            19 => Register::ST,
+            idx => Register::V(idx),
+        }
+    }
+
+    fn register_size(idx: usize) -> usize {
+        match Self::register(idx) {
+            Register::I | Register::PC => 2,
+            _ => 1,
+        }
+    }
+
+    fn read_register(&self, idx: usize) -> String {
+        let value = self.headless.cpu().register(Self::register(idx));
+        value.to_le_bytes()[..Self::register_size(idx)]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn write_register(&mut self, idx: usize, value: u16) {
+        self.headless
+            .cpu_mut()
+            .set_register(Self::register(idx), value);
+    }
+}
+
+enum Packet {
+    Data(String),
+    Interrupt,
+}
+
+fn decode_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+/// Decodes a register value of one or two bytes in little-endian byte order.
+fn decode_le(hex: &str) -> Option<u16> {
+    match decode_bytes(hex)?.as_slice() {
+        [low] => Some(*low as u16),
+        [low, high] => Some(u16::from_le_bytes([*low, *high])),
+        _ => None,
+    }
+}
+
+/// Parses an `address,length` pair in hex.
+fn parse_range(range: &str) -> Option<(u16, u16)> {
+    let (address, len) = range.split_once(',')?;
+    Some((
+        u16::from_str_radix(address, 16).ok()?,
+        u16::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod gdb_stub_test {
+    use super::*;
+    use crate::config::Settings;
+
+    #[test]
+    fn test_packets() {
+        let rom = std::fs::read("core/data/bootrom/pich8-logo.ch8").unwrap();
+        let headless = Headless::new(&rom, &Settings::default()).unwrap();
+        let mut stub = GdbStub::new(headless);
+        let reply = |reply: &str| Action::Reply(reply.to_string());
+
+        assert_eq!(stub.handle_packet("?"), reply("S05"));
+        assert_eq!(stub.handle_packet("p11"), reply("0002"));
+        assert_eq!(stub.handle_packet("m200,2"), reply("a224"));
+        assert_eq!(stub.handle_packet("M3a0,2:beef"), reply("OK"));
+        assert_eq!(stub.handle_packet("m3a0,2"), reply("beef"));
+        assert_eq!(stub.handle_packet("P3=1f"), reply("OK"));
+        assert_eq!(stub.handle_packet("p3"), reply("1f"));
+        assert_eq!(stub.handle_packet("p14"), reply("E01"));
+
+        assert_eq!(stub.handle_packet("s"), reply("S05"));
+        assert_eq!(stub.handle_packet("p11"), reply("0202"));
+        assert_eq!(stub.handle_packet("Z0,206,2"), reply("OK"));
+        assert_eq!(stub.handle_packet("c"), Action::Continue);
+        assert_eq!(stub.run(|| Ok(false)).unwrap(), "S05");
+        assert_eq!(stub.handle_packet("p11"), reply("0602"));
+        assert_eq!(stub.handle_packet("z0,206,2"), reply("OK"));
+
+        let registers = match stub.handle_packet("g") {
+            Action::Reply(registers) => registers,
+            action => panic!("{:?}", action),
+        };
+        assert_eq!(registers.len(), (16 + 2 + 2 + 1 + 1) * 2);
+        assert_eq!(stub.handle_packet(&format!("G{}", registers)), reply("OK"));
+
+        match stub.handle_packet("qXfer:features:read:target.xml:0,ffff") {
+            Action::Reply(xml) => assert!(xml.starts_with("l<?xml")),
+            action => panic!("{:?}", action),
+        }
+        assert_eq!(stub.handle_packet("vMustReplyEmpty"), reply(""));
+        assert_eq!(stub.handle_packet("D"), Action::Detach("OK".to_string()));
+    }
+}
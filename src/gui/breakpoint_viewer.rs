@@ -0,0 +1,100 @@
+use imgui::{Condition, Ui, Window};
+use pich8::breakpoints::BreakpointList;
+use pich8::symbols::Symbols;
+
+/// Lists the breakpoints and adds breakpoints with conditions like `V4 == 0x1F`,
+/// optionally only checked at an address, which may also be given as a label of the symbol file.
+pub struct BreakpointViewer {
+    pub open: bool,
+    address: String,
+    condition: String,
+    error: Option<String>,
+}
+
+impl BreakpointViewer {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            address: String::new(),
+            condition: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, breakpoints: &mut BreakpointList, symbols: &Symbols) {
+        let mut open = self.open;
+        Window::new("Conditional Breakpoints")
+            .opened(&mut open)
+            .position([260.0, 130.0], Condition::FirstUseEver)
+            .size([420.0, 280.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let width = ui.push_item_width(80.0);
+                ui.input_text("Address", &mut self.address)
+                    .hint("any")
+                    .build();
+                width.pop(ui);
+                ui.same_line();
+                let width = ui.push_item_width(150.0);
+                let entered = ui
+                    .input_text("Condition", &mut self.condition)
+                    .hint("V4 == 0x1F")
+                    .enter_returns_true(true)
+                    .build();
+                width.pop(ui);
+                ui.same_line();
+                if ui.button("Add") || entered {
+                    self.add(breakpoints, symbols);
+                }
+                ui.text_disabled("Compare with == != < <= > >= or use e.g. 'mem[0x3a0] changed'");
+                ui.text("Break on");
+                ui.same_line();
+                ui.checkbox("Draw (DXYN)", &mut breakpoints.events.draw);
+                ui.same_line();
+                ui.checkbox("Sound", &mut breakpoints.events.sound);
+                ui.same_line();
+                ui.checkbox("Key Wait (FX0A)", &mut breakpoints.events.key_wait);
+                if let Some(error) = &self.error {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+                }
+                ui.separator();
+
+                let mut remove = None;
+                for (idx, bp) in breakpoints.iter().enumerate() {
+                    if ui.small_button(format!("x##{}", idx)) {
+                        remove = Some(idx);
+                    }
+                    ui.same_line();
+                    let address = bp
+                        .address
+                        .map_or_else(|| "any".to_string(), |address| symbols.describe(address));
+                    match bp.condition {
+                        Some(_) => ui.text(format!("{}  if {}", address, bp.description)),
+                        None => ui.text(address),
+                    }
+                }
+                if let Some(idx) = remove {
+                    breakpoints.remove(idx);
+                }
+            });
+        self.open = open;
+    }
+
+    fn add(&mut self, breakpoints: &mut BreakpointList, symbols: &Symbols) {
+        let address = match self.address.trim() {
+            "" => Ok(None),
+            address => symbols
+                .address(address)
+                .or_else(|| u16::from_str_radix(address, 16).ok())
+                .map(Some)
+                .ok_or_else(|| format!("Invalid address '{}'", address)),
+        };
+        match address.and_then(|address| breakpoints.add(address, &self.condition)) {
+            Ok(()) => {
+                self.address.clear();
+                self.condition.clear();
+                self.error = None;
+            }
+            Err(msg) => self.error = Some(msg),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use imgui::{Condition, Ui, Window};
+use pich8::cpu::CPU;
+use pich8::symbols::Symbols;
+
+/// Lists the active subroutine calls, innermost first, with the labels of a symbol file.
+pub struct CallStackViewer {
+    pub open: bool,
+}
+
+impl CallStackViewer {
+    pub fn new() -> Self {
+        Self { open: false }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU, symbols: &Symbols) {
+        let mut open = self.open;
+        Window::new("Call Stack")
+            .opened(&mut open)
+            .position([220.0, 90.0], Condition::FirstUseEver)
+            .size([300.0, 250.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let sp = cpu.sp();
+                if sp == 0 {
+                    ui.text_disabled("No subroutine called");
+                    return;
+                }
+                ui.columns(3, "call stack", true);
+                ui.text("Subroutine");
+                ui.next_column();
+                ui.text("Called from");
+                ui.next_column();
+                ui.text("Returns to");
+                ui.next_column();
+                ui.separator();
+                // The stack holds the addresses of the CALL instructions
+                for &call in cpu.stack()[..sp].iter().rev() {
+                    let opcode = (cpu.read_mem(call) as u16) << 8
+                        | cpu.read_mem(call.wrapping_add(1)) as u16;
+                    if opcode & 0xF000 == 0x2000 {
+                        ui.text(symbols.describe(opcode & 0x0FFF));
+                    } else {
+                        // The code was changed after the call
+                        ui.text_disabled("????");
+                    }
+                    ui.next_column();
+                    ui.text(format!("{:04X}", call));
+                    ui.next_column();
+                    ui.text(symbols.describe(call.wrapping_add(2)));
+                    ui.next_column();
+                }
+                ui.columns(1, "", false);
+            });
+        self.open = open;
+    }
+}
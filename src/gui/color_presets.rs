@@ -1,6 +1,8 @@
 use super::ColorSettings;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ColorPreset {
     Default,
     OctoClassic,
@@ -11,6 +13,21 @@ pub enum ColorPreset {
     OctoCga1,
 }
 
+impl ColorPreset {
+    /// Returns the colors of this preset, indexed by `Color`.
+    pub fn values(self) -> [[f32; 3]; 4] {
+        match self {
+            ColorPreset::Default => ColorPresetHandler::COLOR_PRESET_DEFAULT,
+            ColorPreset::OctoClassic => ColorPresetHandler::COLOR_PRESET_OCTO_CLASSIC,
+            ColorPreset::OctoLcd => ColorPresetHandler::COLOR_PRESET_OCTO_LCD,
+            ColorPreset::OctoHotdog => ColorPresetHandler::COLOR_PRESET_OCTO_HOTDOG,
+            ColorPreset::OctoGray => ColorPresetHandler::COLOR_PRESET_OCTO_GRAY,
+            ColorPreset::OctoCga0 => ColorPresetHandler::COLOR_PRESET_OCTO_CGA0,
+            ColorPreset::OctoCga1 => ColorPresetHandler::COLOR_PRESET_OCTO_CGA1,
+        }
+    }
+}
+
 pub struct ColorPresetHandler<'a> {
     settings: &'a mut ColorSettings,
 }
@@ -78,14 +95,6 @@ impl<'a> ColorPresetHandler<'a> {
     }
 
     fn get_preset(&self, preset: ColorPreset) -> [[f32; 3]; 4] {
-        match preset {
-            ColorPreset::Default => Self::COLOR_PRESET_DEFAULT,
-            ColorPreset::OctoClassic => Self::COLOR_PRESET_OCTO_CLASSIC,
-            ColorPreset::OctoLcd => Self::COLOR_PRESET_OCTO_LCD,
-            ColorPreset::OctoHotdog => Self::COLOR_PRESET_OCTO_HOTDOG,
-            ColorPreset::OctoGray => Self::COLOR_PRESET_OCTO_GRAY,
-            ColorPreset::OctoCga0 => Self::COLOR_PRESET_OCTO_CGA0,
-            ColorPreset::OctoCga1 => Self::COLOR_PRESET_OCTO_CGA1,
-        }
+        preset.values()
     }
 }
@@ -28,11 +28,11 @@ impl ColorSettings {
         &mut self.colors[color as usize]
     }
 
-    pub fn iter(&self) -> Iter<[f32; 3]> {
+    pub fn iter(&self) -> Iter<'_, [f32; 3]> {
         self.colors.iter()
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<[f32; 3]> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, [f32; 3]> {
         self.colors.iter_mut()
     }
 }
@@ -0,0 +1,123 @@
+use imgui::{ChildWindow, Condition, ListClipper, Selectable, StyleColor, Ui, Window};
+use pich8::breakpoints::BreakpointList;
+use pich8::cpu::CPU;
+use pich8::disassembler;
+use pich8::symbols::Symbols;
+
+/// Memory disassembled as code, following the PC. Clicking a line toggles a breakpoint.
+/// Labels of a symbol file are shown at their address and after the instructions using them.
+pub struct DisassemblyViewer {
+    pub open: bool,
+    follow_pc: bool,
+    last_pc: Option<u16>,
+}
+
+impl DisassemblyViewer {
+    /// Every line is a 2 byte word of the 64 KB address space, aligned to the PC.
+    const NUM_ROWS: usize = 0x8000;
+    const COLOR_PC: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+    const COLOR_BREAKPOINT: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            follow_pc: true,
+            last_pc: None,
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        ui: &Ui,
+        cpu: &CPU,
+        breakpoints: &mut BreakpointList,
+        symbols: &Symbols,
+    ) {
+        let mut open = self.open;
+        Window::new("Disassembly")
+            .opened(&mut open)
+            .position([180.0, 70.0], Condition::FirstUseEver)
+            .size([300.0, 400.0], Condition::FirstUseEver)
+            .build(ui, || {
+                ui.checkbox("Follow PC", &mut self.follow_pc);
+                ui.same_line();
+                if ui.small_button("Clear Breakpoints") {
+                    breakpoints.clear();
+                }
+                ui.separator();
+
+                let pc = cpu.PC();
+                let alignment = pc & 1;
+                ChildWindow::new("code").build(ui, || {
+                    let row_height = ui.text_line_height_with_spacing();
+                    if self.follow_pc && self.last_pc != Some(pc) {
+                        // Keep a few lines before the PC visible
+                        let row = (pc / 2).saturating_sub(4);
+                        ui.set_scroll_y(row as f32 * row_height);
+                    }
+                    self.last_pc = Some(pc);
+                    let mut clipper = ListClipper::new(Self::NUM_ROWS as i32)
+                        .items_height(row_height)
+                        .begin(ui);
+                    while clipper.step() {
+                        for row in clipper.display_start()..clipper.display_end() {
+                            let address = (row as u16 * 2) | alignment;
+                            Self::draw_line(ui, cpu, breakpoints, symbols, address);
+                        }
+                    }
+                });
+            });
+        self.open = open;
+    }
+
+    fn draw_line(
+        ui: &Ui,
+        cpu: &CPU,
+        breakpoints: &mut BreakpointList,
+        symbols: &Symbols,
+        address: u16,
+    ) {
+        let word = |address: u16| {
+            (cpu.read_mem(address) as u16) << 8 | cpu.read_mem(address.wrapping_add(1)) as u16
+        };
+        let opcode = word(address);
+        let ext = word(address.wrapping_add(2));
+        let breakpoint = breakpoints.has_address(address);
+        let color = if address == cpu.PC() {
+            Some(Self::COLOR_PC)
+        } else if breakpoint {
+            Some(Self::COLOR_BREAKPOINT)
+        } else {
+            None
+        };
+        let style = color.map(|color| ui.push_style_color(StyleColor::Text, color));
+        let mut label = format!(
+            "{} {:04X}  {:04X}  ",
+            if breakpoint { '*' } else { ' ' },
+            address,
+            opcode
+        );
+        if let Some(name) = symbols.label(address) {
+            label.push_str(&format!("{}: ", name));
+        }
+        label.push_str(&disassembler::disassemble(opcode, ext));
+        if let Some(name) = Self::target(opcode, ext).and_then(|target| symbols.label(target)) {
+            label.push_str(&format!(" ({})", name));
+        }
+        if Selectable::new(&label).build(ui) {
+            breakpoints.toggle(address);
+        }
+        if let Some(style) = style {
+            style.pop();
+        }
+    }
+
+    /// Returns the address an instruction jumps to, calls or points I to.
+    fn target(opcode: u16, ext: u16) -> Option<u16> {
+        match opcode & 0xF000 {
+            0x1000 | 0x2000 | 0xA000 | 0xB000 => Some(opcode & 0x0FFF),
+            0xF000 if opcode == 0xF000 => Some(ext),
+            _ => None,
+        }
+    }
+}
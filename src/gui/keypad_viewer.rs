@@ -0,0 +1,56 @@
+use crate::config::KeyMap;
+use imgui::{Condition, StyleColor, Ui, Window};
+use pich8::cpu::CPU;
+
+/// Shows the CHIP-8 keypad as seen by the CPU, with the keyboard keys mapped to it
+/// and the register FX0A stores the next key press in.
+pub struct KeypadViewer {
+    pub open: bool,
+}
+
+impl KeypadViewer {
+    /// The keys in the layout of the COSMAC VIP keypad.
+    pub const LAYOUT: [[usize; 4]; 4] = [
+        [0x1, 0x2, 0x3, 0xC],
+        [0x4, 0x5, 0x6, 0xD],
+        [0x7, 0x8, 0x9, 0xE],
+        [0xA, 0x0, 0xB, 0xF],
+    ];
+    const BUTTON_SIZE: [f32; 2] = [48.0, 36.0];
+    const COLOR_PRESSED: [f32; 4] = [0.2, 0.7, 0.3, 1.0];
+
+    pub fn new() -> Self {
+        Self { open: false }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU, keymap: &KeyMap) {
+        let mut open = self.open;
+        Window::new("Keypad")
+            .opened(&mut open)
+            .position([300.0, 120.0], Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let keys = cpu.keys();
+                for row in &Self::LAYOUT {
+                    for (idx, &key) in row.iter().enumerate() {
+                        if idx > 0 {
+                            ui.same_line();
+                        }
+                        let style = keys[key]
+                            .then(|| ui.push_style_color(StyleColor::Button, Self::COLOR_PRESSED));
+                        let label = format!("{:X}\n{}", key, keymap.key_name(key));
+                        ui.button_with_size(&label, Self::BUTTON_SIZE);
+                        if let Some(style) = style {
+                            style.pop();
+                        }
+                    }
+                }
+                ui.separator();
+                match cpu.key_wait() {
+                    Some(register) => ui.text(format!("FX0A waiting for a key (V{:X})", register)),
+                    None => ui.text_disabled("Not waiting for a key"),
+                }
+            });
+        self.open = open;
+    }
+}
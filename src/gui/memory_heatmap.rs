@@ -0,0 +1,167 @@
+use glium::{
+    texture::{ClientFormat, RawImage2d, Texture2d},
+    uniforms::{MagnifySamplerFilter, SamplerBehavior},
+    Display,
+};
+use imgui::{Condition, Image, Slider, TextureId, Textures, Ui, Window};
+use imgui_glium_renderer::Texture;
+use std::{borrow::Cow, rc::Rc};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Range {
+    /// The 4 KB of CHIP-8 and S-CHIP
+    Small,
+    /// The 64 KB of XO-CHIP
+    Full,
+}
+
+impl Range {
+    /// Returns the number of addresses and the addresses per row.
+    fn size(self) -> (usize, usize) {
+        match self {
+            Range::Small => (0x1000, 64),
+            Range::Full => (0x10000, 256),
+        }
+    }
+}
+
+/// Memory colored by how it's accessed, writes red, reads green and executed code blue,
+/// fading out when the access stops.
+///
+/// Accesses are derived from the executed opcodes and I, sprites drawn with DXY0 are
+/// assumed to be 16x16 on a single plane.
+pub struct MemoryHeatmap {
+    pub open: bool,
+    range: Range,
+    zoom: u32,
+    /// Write, read and execute intensity per address
+    heat: Vec<[u8; 3]>,
+    texture: Option<TextureId>,
+}
+
+impl MemoryHeatmap {
+    const WRITE: usize = 0;
+    const READ: usize = 1;
+    const EXECUTE: usize = 2;
+    /// Intensity lost per frame, an access fades out in about a second
+    const DECAY: u8 = 4;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            range: Range::Small,
+            zoom: 4,
+            heat: vec![[0; 3]; 0x10000],
+            texture: None,
+        }
+    }
+
+    pub fn record(&mut self, address: u16, opcode: u16, i: u16) {
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let len = if opcode == 0xF000 { 4 } else { 2 };
+        self.mark(address, len, Self::EXECUTE);
+        match (opcode >> 12, opcode & 0x00FF) {
+            (0xD, _) => {
+                let n = opcode & 0x000F;
+                self.mark(i, if n == 0 { 32 } else { n }, Self::READ);
+            }
+            (0x5, _) if opcode & 0x000E == 2 => {
+                let access = if opcode & 1 == 0 {
+                    Self::WRITE
+                } else {
+                    Self::READ
+                };
+                self.mark(i, x.abs_diff(y) + 1, access);
+            }
+            (0xF, 0x02) => self.mark(i, 16, Self::READ),
+            (0xF, 0x33) => self.mark(i, 3, Self::WRITE),
+            (0xF, 0x55) => self.mark(i, x + 1, Self::WRITE),
+            (0xF, 0x65) => self.mark(i, x + 1, Self::READ),
+            _ => (),
+        }
+    }
+
+    fn mark(&mut self, start: u16, len: u16, access: usize) {
+        for offset in 0..len {
+            self.heat[start.wrapping_add(offset) as usize][access] = u8::MAX;
+        }
+    }
+
+    /// Fades out the accesses and uploads the map, has to be called before the frame is drawn.
+    pub fn update(&mut self, display: &Display, textures: &mut Textures<Texture>) {
+        if let Some(texture_id) = self.texture.take() {
+            textures.remove(texture_id);
+        }
+        if !self.open {
+            return;
+        }
+        let (size, width) = self.range.size();
+        let mut pixels = Vec::with_capacity(size * 3);
+        for heat in &mut self.heat[..size] {
+            for value in heat.iter_mut() {
+                *value = value.saturating_sub(Self::DECAY);
+            }
+            pixels.extend_from_slice(&heat[..]);
+        }
+        let image = RawImage2d {
+            data: Cow::Owned(pixels),
+            width: width as u32,
+            height: (size / width) as u32,
+            format: ClientFormat::U8U8U8,
+        };
+        if let Ok(texture) = Texture2d::new(display, image) {
+            self.texture = Some(textures.insert(Texture {
+                texture: Rc::new(texture),
+                sampler: SamplerBehavior {
+                    magnify_filter: MagnifySamplerFilter::Nearest,
+                    ..Default::default()
+                },
+            }));
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui) {
+        let mut open = self.open;
+        Window::new("Memory Heatmap")
+            .opened(&mut open)
+            .position([360.0, 230.0], Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .build(ui, || {
+                ui.radio_button("4 KB", &mut self.range, Range::Small);
+                ui.same_line();
+                ui.radio_button("64 KB", &mut self.range, Range::Full);
+                ui.same_line();
+                let width = ui.push_item_width(80.0);
+                Slider::new("Zoom", 1, 8).build(ui, &mut self.zoom);
+                width.pop(ui);
+                ui.text_colored([1.0, 0.3, 0.3, 1.0], "Write");
+                ui.same_line();
+                ui.text_colored([0.3, 1.0, 0.3, 1.0], "Read");
+                ui.same_line();
+                ui.text_colored([0.4, 0.5, 1.0, 1.0], "Execute");
+
+                let texture_id = match self.texture {
+                    Some(texture_id) => texture_id,
+                    None => return,
+                };
+                let (size, width) = self.range.size();
+                let zoom = self.zoom as f32;
+                let image_size = [width as f32 * zoom, (size / width) as f32 * zoom];
+                Image::new(texture_id, image_size)
+                    .border_col([0.5; 4])
+                    .build(ui);
+                if ui.is_item_hovered() {
+                    let [mouse_x, mouse_y] = ui.io().mouse_pos;
+                    let [min_x, min_y] = ui.item_rect_min();
+                    let x = ((mouse_x - min_x) / zoom) as usize;
+                    let y = ((mouse_y - min_y) / zoom) as usize;
+                    let address = (y * width + x).min(size - 1);
+                    ui.text(format!("Address: {:04X}", address));
+                } else {
+                    ui.text("");
+                }
+            });
+        self.open = open;
+    }
+}
@@ -0,0 +1,149 @@
+use imgui::{ChildWindow, Condition, ListClipper, Selectable, Ui, Window};
+use pich8::cpu::CPU;
+
+#[derive(Clone, Copy)]
+enum Filter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+/// Finds the addresses of e.g. the lives or the score of a game by searching for a value
+/// and narrowing the results down by how they changed since the last search.
+pub struct MemorySearch {
+    pub open: bool,
+    value: String,
+    /// The remaining addresses with their values at the last search
+    candidates: Option<Vec<(u16, u8)>>,
+    error: Option<String>,
+}
+
+impl MemorySearch {
+    /// CHIP-8 programs start at 0x200, below is the interpreter and the font.
+    const START: usize = 0x200;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            value: String::new(),
+            candidates: None,
+            error: None,
+        }
+    }
+
+    /// Draws the window, returns the address of a clicked result.
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU) -> Option<u16> {
+        let mut clicked = None;
+        let mut open = self.open;
+        Window::new("Memory Search")
+            .opened(&mut open)
+            .position([280.0, 150.0], Condition::FirstUseEver)
+            .size([330.0, 320.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let width = ui.push_item_width(60.0);
+                ui.input_text("Value", &mut self.value).hint("0x03").build();
+                width.pop(ui);
+                ui.same_line();
+                if ui.button("New Search") {
+                    self.candidates = Some(
+                        (Self::START..=u16::MAX as usize)
+                            .map(|address| (address as u16, cpu.read_mem(address as u16)))
+                            .collect(),
+                    );
+                    if !self.value.trim().is_empty() {
+                        self.narrow_by_value(cpu);
+                    }
+                }
+
+                if self.candidates.is_some() {
+                    if ui.button("Equal to Value") {
+                        self.narrow_by_value(cpu);
+                    }
+                    ui.same_line();
+                    if ui.button("Changed") {
+                        self.narrow(cpu, Filter::Changed);
+                    }
+                    ui.same_line();
+                    if ui.button("Unchanged") {
+                        self.narrow(cpu, Filter::Unchanged);
+                    }
+                    if ui.button("Increased") {
+                        self.narrow(cpu, Filter::Increased);
+                    }
+                    ui.same_line();
+                    if ui.button("Decreased") {
+                        self.narrow(cpu, Filter::Decreased);
+                    }
+                }
+                if let Some(error) = &self.error {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+                }
+                ui.separator();
+
+                let candidates = match &self.candidates {
+                    Some(candidates) => candidates,
+                    None => {
+                        ui.text_disabled("Search for a value, e.g. the number of lives,");
+                        ui.text_disabled("then narrow the results down as it changes");
+                        return;
+                    }
+                };
+                ui.text(format!("{} addresses", candidates.len()));
+                ChildWindow::new("results").build(ui, || {
+                    let mut clipper = ListClipper::new(candidates.len() as i32)
+                        .items_height(ui.text_line_height_with_spacing())
+                        .begin(ui);
+                    while clipper.step() {
+                        for &(address, last) in candidates
+                            [clipper.display_start() as usize..clipper.display_end() as usize]
+                            .iter()
+                        {
+                            let label = format!(
+                                "{:04X}: {:02X} (was {:02X})",
+                                address,
+                                cpu.read_mem(address),
+                                last
+                            );
+                            if Selectable::new(&label).build(ui) {
+                                clicked = Some(address);
+                            }
+                        }
+                    }
+                });
+            });
+        self.open = open;
+        clicked
+    }
+
+    fn narrow_by_value(&mut self, cpu: &CPU) {
+        let value = self.value.trim();
+        let parsed = match value.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        match parsed {
+            Ok(value) => self.narrow(cpu, Filter::Equal(value)),
+            Err(_) => self.error = Some(format!("Invalid value '{}'", value)),
+        }
+    }
+
+    fn narrow(&mut self, cpu: &CPU, filter: Filter) {
+        self.error = None;
+        if let Some(candidates) = &mut self.candidates {
+            candidates.retain_mut(|(address, last)| {
+                let value = cpu.read_mem(*address);
+                let keep = match filter {
+                    Filter::Equal(expected) => value == expected,
+                    Filter::Changed => value != *last,
+                    Filter::Unchanged => value == *last,
+                    Filter::Increased => value > *last,
+                    Filter::Decreased => value < *last,
+                };
+                *last = value;
+                keep
+            });
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use imgui::{ChildWindow, Condition, ListClipper, Selectable, StyleColor, Ui, Window};
+use pich8::cpu::CPU;
+
+/// Hex view of the whole address space, bytes can be edited while paused.
+pub struct MemoryViewer {
+    pub open: bool,
+    selected: Option<u16>,
+    goto: String,
+    value: String,
+    scroll_to: Option<u16>,
+}
+
+impl MemoryViewer {
+    const BYTES_PER_ROW: usize = 16;
+    const NUM_ROWS: usize = (u16::MAX as usize + 1) / Self::BYTES_PER_ROW;
+    const COLOR_PC: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+    const COLOR_I: [f32; 4] = [0.4, 0.8, 1.0, 1.0];
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            selected: None,
+            goto: String::new(),
+            value: String::new(),
+            scroll_to: None,
+        }
+    }
+
+    /// Draws the window, returns the address and new value of an edited byte.
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU, editable: bool) -> Option<(u16, u8)> {
+        let mut edit = None;
+        let mut open = self.open;
+        Window::new("Memory")
+            .opened(&mut open)
+            .position([150.0, 60.0], Condition::FirstUseEver)
+            .size([520.0, 340.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let width = ui.push_item_width(40.0);
+                if ui
+                    .input_text("Go to", &mut self.goto)
+                    .chars_hexadecimal(true)
+                    .chars_uppercase(true)
+                    .enter_returns_true(true)
+                    .build()
+                {
+                    if let Ok(address) = u16::from_str_radix(&self.goto, 16) {
+                        self.select(address, cpu);
+                    }
+                }
+                ui.same_line();
+                if ui.small_button("PC") {
+                    self.select(cpu.PC(), cpu);
+                }
+                ui.same_line();
+                if ui.small_button("I") {
+                    self.select(cpu.I(), cpu);
+                }
+                if let Some(address) = self.selected {
+                    ui.same_line();
+                    ui.text(format!("   {:04X}:", address));
+                    ui.same_line();
+                    if ui
+                        .input_text("##value", &mut self.value)
+                        .read_only(!editable)
+                        .chars_hexadecimal(true)
+                        .chars_uppercase(true)
+                        .enter_returns_true(true)
+                        .build()
+                    {
+                        if let Ok(value) = u8::from_str_radix(&self.value, 16) {
+                            edit = Some((address, value));
+                        }
+                    }
+                    if !editable && ui.is_item_hovered() {
+                        ui.tooltip_text("Pause to edit memory");
+                    }
+                }
+                width.pop(ui);
+                ui.separator();
+
+                ChildWindow::new("hex").build(ui, || {
+                    let row_height = ui.text_line_height_with_spacing();
+                    if let Some(address) = self.scroll_to.take() {
+                        let row = address as usize / Self::BYTES_PER_ROW;
+                        ui.set_scroll_y(row as f32 * row_height);
+                    }
+                    let mut clipper = ListClipper::new(Self::NUM_ROWS as i32)
+                        .items_height(row_height)
+                        .begin(ui);
+                    while clipper.step() {
+                        for row in clipper.display_start()..clipper.display_end() {
+                            self.draw_row(ui, cpu, row as usize * Self::BYTES_PER_ROW);
+                        }
+                    }
+                });
+            });
+        self.open = open;
+        edit
+    }
+
+    fn draw_row(&mut self, ui: &Ui, cpu: &CPU, start: usize) {
+        ui.text(format!("{:04X}:", start));
+        for address in (start..start + Self::BYTES_PER_ROW).map(|a| a as u16) {
+            ui.same_line();
+            let value = cpu.read_mem(address);
+            // The PC points to a 2 byte opcode, I to the start of e.g. a sprite
+            let color = if address.wrapping_sub(cpu.PC()) < 2 {
+                Some(Self::COLOR_PC)
+            } else if address == cpu.I() {
+                Some(Self::COLOR_I)
+            } else {
+                None
+            };
+            let style = color.map(|color| ui.push_style_color(StyleColor::Text, color));
+            let label = format!("{:02X}##{}", value, address);
+            let size = ui.calc_text_size("00");
+            if Selectable::new(&label)
+                .selected(self.selected == Some(address))
+                .size(size)
+                .build(ui)
+            {
+                self.selected = Some(address);
+                self.value = format!("{:02X}", value);
+            }
+            if let Some(style) = style {
+                style.pop();
+            }
+        }
+    }
+
+    /// Opens the window and scrolls to the address.
+    pub fn show(&mut self, address: u16, cpu: &CPU) {
+        self.open = true;
+        self.select(address, cpu);
+    }
+
+    fn select(&mut self, address: u16, cpu: &CPU) {
+        self.selected = Some(address);
+        self.scroll_to = Some(address);
+        self.value = format!("{:02X}", cpu.read_mem(address));
+    }
+}
@@ -1,23 +1,96 @@
-use crate::cpu::CPU;
-use color_presets::{ColorPreset, ColorPresetHandler};
+use crate::cheats::Cheat;
+use crate::chip8_archive::ProgramInfo;
+use crate::config::{DebugLayout, KeyMap};
+use crate::error::Pich8Error;
+use breakpoint_viewer::BreakpointViewer;
+use call_stack_viewer::CallStackViewer;
+pub use color_presets::ColorPreset;
+use color_presets::ColorPresetHandler;
 pub use color_settings::Color;
 use color_settings::ColorSettings;
-use glium::{glutin::event::Event, Display, Surface};
+use disassembly_viewer::DisassemblyViewer;
+use glium::{
+    glutin::event::Event,
+    texture::{ClientFormat, RawImage2d, Texture2d},
+    uniforms::{MagnifySamplerFilter, SamplerBehavior},
+    Display, Surface,
+};
 use imgui::{
-    ColorEdit, Condition, Context, FontId, FontSource, MenuItem, Slider,
-    StyleColor, Ui, Window,
+    CollapsingHeader, ColorEdit, Condition, Context, Drag, FontId, FontSource, Image, MenuItem,
+    Selectable, Slider, StyleColor, TextureId, Ui, Window,
 };
-use imgui_glium_renderer::Renderer;
+use imgui_glium_renderer::{Renderer, Texture};
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
-use quirks_presets::{QuirksPreset, QuirksPresetHandler};
+use keypad_viewer::KeypadViewer;
+use log::{info, warn};
+use memory_heatmap::MemoryHeatmap;
+use memory_search::MemorySearch;
+use memory_viewer::MemoryViewer;
+use opcode_histogram::OpcodeHistogram;
+use pich8::breakpoints::BreakpointList;
+use pich8::cpu::{Register, CPU};
+use pich8::state::Thumbnail;
+use pich8::symbols::Symbols;
+use profiler_viewer::ProfilerViewer;
+pub use quirks_presets::QuirksPreset;
+use quirks_presets::QuirksPresetHandler;
 pub use quirks_settings::Quirk;
 use quirks_settings::QuirksSettings;
-use std::time::Duration;
+#[cfg(feature = "scripting")]
+pub use script_console::ScriptCommand;
+#[cfg(feature = "scripting")]
+use script_console::ScriptConsole;
+use sprite_viewer::SpriteViewer;
+use std::{
+    borrow::Cow,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use trace_viewer::TraceViewer;
+use vram_viewer::VramViewer;
+use watch_viewer::WatchViewer;
 
+mod breakpoint_viewer;
+mod call_stack_viewer;
 mod color_presets;
 mod color_settings;
+mod disassembly_viewer;
+mod keypad_viewer;
+mod memory_heatmap;
+mod memory_search;
+mod memory_viewer;
+mod opcode_histogram;
+mod profiler_viewer;
 mod quirks_presets;
 mod quirks_settings;
+#[cfg(feature = "scripting")]
+mod script_console;
+mod sprite_viewer;
+mod trace_viewer;
+mod vram_viewer;
+mod watch_viewer;
+
+/// Emulation speed details shown in the menu bar.
+pub struct SpeedInfo {
+    pub cycles_per_frame: u32,
+    pub frame: u64,
+    pub multiplier: f64,
+}
+
+/// What's known about the loaded ROM, shown in View > ROM Info.
+pub struct RomDetails {
+    pub file_name: Option<String>,
+    pub size: usize,
+    pub hash: String,
+    pub platform: String,
+    /// The title in the game database
+    pub title: Option<String>,
+    /// Where the applied settings come from besides the global config
+    pub settings_sources: Vec<&'static str>,
+    /// The metadata from the chip8Archive
+    pub info: Option<ProgramInfo>,
+}
 
 pub struct GUI {
     imgui: Context,
@@ -34,17 +107,67 @@ pub struct GUI {
 
     // Flags
     pub flag_open: bool,
+    pub flag_open_with_patch: bool,
+    pub open_recent: Option<usize>,
+    recent_files: Vec<String>,
+    cheats: Vec<Cheat>,
+    pub cheat_toggled: Option<(usize, bool)>,
 
     #[cfg(feature = "rom-download")]
     pub flag_open_rom_url: bool,
+    #[cfg(feature = "rom-download")]
+    flag_archive_browser: bool,
+    #[cfg(feature = "rom-download")]
+    pub flag_archive_refresh: bool,
+    #[cfg(feature = "rom-download")]
+    archive_programs: Vec<(String, ProgramInfo)>,
+    #[cfg(feature = "rom-download")]
+    archive_filter: String,
+    #[cfg(feature = "rom-download")]
+    pub archive_choice: Option<String>,
 
     pub flag_save_state: bool,
+    pub flag_export_octo: bool,
+    pub flag_import_octo_state: bool,
+    pub flag_export_octo_state: bool,
+    pub flag_quick_save: bool,
+    pub flag_quick_load: bool,
+    pub state_slot: usize,
+    slot_thumbnails: Vec<Option<TextureId>>,
     pub flag_reset: bool,
     pub flag_exit: bool,
 
     pub flag_fullscreen: bool,
     pub flag_display_fps: bool,
+    pub flag_display_speed: bool,
     pub flag_debug: bool,
+    pub register_edit: Option<(Register, u16)>,
+    memory_viewer: MemoryViewer,
+    memory_search: MemorySearch,
+    memory_heatmap: MemoryHeatmap,
+    vram_viewer: VramViewer,
+    sprite_viewer: SpriteViewer,
+    keypad_viewer: KeypadViewer,
+    keymap: KeyMap,
+    flag_settings: bool,
+    pub key_binding: Option<usize>,
+    pub flag_import_keys: bool,
+    pub flag_export_keys: bool,
+    pub settings_save: Option<bool>,
+    disassembly_viewer: DisassemblyViewer,
+    breakpoint_viewer: BreakpointViewer,
+    breakpoints: BreakpointList,
+    symbols: Symbols,
+    call_stack_viewer: CallStackViewer,
+    watch_viewer: WatchViewer,
+    opcode_histogram: OpcodeHistogram,
+    trace_viewer: TraceViewer,
+    profiler_viewer: ProfilerViewer,
+    #[cfg(feature = "scripting")]
+    script_console: ScriptConsole,
+    #[cfg(feature = "scripting")]
+    pub script_command: Option<ScriptCommand>,
+    pub memory_edit: Option<(u16, u8)>,
 
     color_settings: ColorSettings,
 
@@ -57,11 +180,33 @@ pub struct GUI {
     quirks_settings: QuirksSettings,
 
     flag_about: bool,
+    flag_rom_info: bool,
+    pub flag_screenshot: bool,
+    pub flag_record: bool,
+    pub recording: bool,
+    rom_details: Option<RomDetails>,
     flag_error: bool,
     error_text: String,
+    notice_text: String,
+    notice_time: Option<Instant>,
     pub flag_downloading: bool,
+    pub download_progress: Option<(u64, Option<u64>)>,
+    pub flag_resume_prompt: bool,
+    pub resume_choice: Option<bool>,
+    pub exit_prompt_text: String,
+    pub exit_choice: Option<bool>,
+    pub zip_entries: Vec<String>,
+    pub zip_choice: Option<usize>,
+    pub compare_quirks: Option<QuirksPreset>,
+    pub flag_compare_changed: bool,
+    pub stuck_prompt_text: String,
+    pub stuck_choice: Option<bool>,
     pub flag_step: bool,
     pub flag_step_timers: bool,
+    pub flag_step_back: bool,
+    pub flag_step_over: bool,
+    pub flag_step_out: bool,
+    pub can_step_back: bool,
 
     flag_breakpoint_pc: bool,
     breakpoint_pc: String,
@@ -81,8 +226,11 @@ impl GUI {
     const MENU_HEIGHT_CLEARANCE: u32 = 1;
     const WIDTH_TEXTBOX_REGISTER: f32 = 32.0;
     const COLOR_TEXT_DISABLED: [f32; 4] = [1.0, 1.0, 1.0, 0.5];
+    const NOTICE_DURATION: Duration = Duration::from_secs(2);
+    pub const NUM_STATE_SLOTS: usize = 10;
+    const THUMBNAIL_SCALE: f32 = 3.0;
 
-    pub fn new(display: &Display) -> Self {
+    pub fn new(display: &Display) -> Result<Self, Pich8Error> {
         let mut imgui = Context::create();
         imgui.set_ini_filename(None);
 
@@ -107,9 +255,9 @@ impl GUI {
 
         // Set default breakpoint values
         let mut breakpoint_pc = String::with_capacity(4);
-        breakpoint_pc.push_str("0");
+        breakpoint_pc.push('0');
         let mut breakpoint_i = String::with_capacity(4);
-        breakpoint_i.push_str("0");
+        breakpoint_i.push('0');
         let mut breakpoint_opcode = String::with_capacity(4);
         breakpoint_opcode.push_str("****");
 
@@ -122,7 +270,8 @@ impl GUI {
         QuirksPresetHandler::new(&mut quirks_settings).set_preset(QuirksPreset::Default);
 
         // Create renderer and platform
-        let renderer = Renderer::init(&mut imgui, display).expect("Failed to initialize renderer");
+        let renderer =
+            Renderer::init(&mut imgui, display).map_err(|e| Pich8Error::Gui(e.to_string()))?;
         let mut platform = WinitPlatform::init(&mut imgui);
         {
             let gl_win = display.gl_window();
@@ -130,7 +279,7 @@ impl GUI {
             platform.attach_window(imgui.io_mut(), window, HiDpiMode::Rounded);
         }
 
-        Self {
+        Ok(Self {
             imgui,
             renderer,
             platform,
@@ -141,11 +290,33 @@ impl GUI {
             is_open: false,
 
             flag_open: false,
+            flag_open_with_patch: false,
+            open_recent: None,
+            recent_files: Vec::new(),
+            cheats: Vec::new(),
+            cheat_toggled: None,
 
             #[cfg(feature = "rom-download")]
             flag_open_rom_url: false,
+            #[cfg(feature = "rom-download")]
+            flag_archive_browser: false,
+            #[cfg(feature = "rom-download")]
+            flag_archive_refresh: false,
+            #[cfg(feature = "rom-download")]
+            archive_programs: Vec::new(),
+            #[cfg(feature = "rom-download")]
+            archive_filter: String::new(),
+            #[cfg(feature = "rom-download")]
+            archive_choice: None,
 
             flag_save_state: false,
+            flag_export_octo: false,
+            flag_import_octo_state: false,
+            flag_export_octo_state: false,
+            flag_quick_save: false,
+            flag_quick_load: false,
+            state_slot: 0,
+            slot_thumbnails: Vec::new(),
             flag_reset: false,
             flag_exit: false,
 
@@ -153,7 +324,35 @@ impl GUI {
             color_settings,
 
             flag_display_fps: false,
+            flag_display_speed: false,
             flag_debug: false,
+            register_edit: None,
+            memory_viewer: MemoryViewer::new(),
+            memory_search: MemorySearch::new(),
+            memory_heatmap: MemoryHeatmap::new(),
+            vram_viewer: VramViewer::new(),
+            sprite_viewer: SpriteViewer::new(),
+            keypad_viewer: KeypadViewer::new(),
+            keymap: KeyMap::default(),
+            flag_settings: false,
+            key_binding: None,
+            flag_import_keys: false,
+            flag_export_keys: false,
+            settings_save: None,
+            disassembly_viewer: DisassemblyViewer::new(),
+            breakpoint_viewer: BreakpointViewer::new(),
+            breakpoints: BreakpointList::default(),
+            symbols: Symbols::default(),
+            call_stack_viewer: CallStackViewer::new(),
+            watch_viewer: WatchViewer::new(),
+            opcode_histogram: OpcodeHistogram::new(),
+            trace_viewer: TraceViewer::new(),
+            profiler_viewer: ProfilerViewer::new(),
+            #[cfg(feature = "scripting")]
+            script_console: ScriptConsole::new(),
+            #[cfg(feature = "scripting")]
+            script_command: None,
+            memory_edit: None,
 
             flag_pause: false,
 
@@ -166,11 +365,33 @@ impl GUI {
             quirks_settings,
 
             flag_about: false,
+            flag_rom_info: false,
+            flag_screenshot: false,
+            flag_record: false,
+            recording: false,
+            rom_details: None,
             flag_error: false,
             error_text: String::new(),
+            notice_text: String::new(),
+            notice_time: None,
             flag_downloading: false,
+            download_progress: None,
+            flag_resume_prompt: false,
+            resume_choice: None,
+            exit_prompt_text: String::new(),
+            exit_choice: None,
+            zip_entries: Vec::new(),
+            zip_choice: None,
+            compare_quirks: None,
+            flag_compare_changed: false,
+            stuck_prompt_text: String::new(),
+            stuck_choice: None,
             flag_step: false,
             flag_step_timers: false,
+            flag_step_back: false,
+            flag_step_over: false,
+            flag_step_out: false,
+            can_step_back: false,
 
             flag_breakpoint_pc: false,
             breakpoint_pc,
@@ -179,14 +400,11 @@ impl GUI {
             flag_breakpoint_opcode: false,
             breakpoint_opcode,
 
-            about_name: env!("CARGO_PKG_NAME").to_string(),
+            about_name: env!("CARGO_CRATE_NAME").to_string(),
             about_version: env!("CARGO_PKG_VERSION").to_string(),
             about_description: env!("CARGO_PKG_DESCRIPTION").to_string(),
-            about_license: format!(
-                "Released under the {} license",
-                env!("CARGO_PKG_LICENSE").to_string()
-            ),
-        }
+            about_license: format!("Released under the {} license", env!("CARGO_PKG_LICENSE")),
+        })
     }
 
     pub fn is_open(&self) -> bool {
@@ -198,12 +416,167 @@ impl GUI {
     pub fn quirks_settings(&self) -> &QuirksSettings {
         &self.quirks_settings
     }
+    pub fn quirks_settings_mut(&mut self) -> &mut QuirksSettings {
+        &mut self.quirks_settings
+    }
+    pub fn set_recent_files(&mut self, files: &[PathBuf]) {
+        self.recent_files = files.iter().map(|f| f.display().to_string()).collect();
+    }
+    pub fn set_cheats(&mut self, cheats: &[Cheat]) {
+        self.cheats = cheats.to_vec();
+    }
+    /// Replaces the thumbnails shown in the state slot menu.
+    pub fn set_slot_thumbnails(&mut self, display: &Display, thumbnails: &[Option<Thumbnail>]) {
+        for texture_id in self.slot_thumbnails.drain(..).flatten() {
+            self.renderer.textures().remove(texture_id);
+        }
+        for thumbnail in thumbnails {
+            let texture_id = thumbnail.as_ref().and_then(|thumbnail| {
+                let image = RawImage2d {
+                    data: Cow::Borrowed(&thumbnail.pixels[..]),
+                    width: thumbnail.width as u32,
+                    height: thumbnail.height as u32,
+                    format: ClientFormat::U8U8U8,
+                };
+                let texture = Texture2d::new(display, image).ok()?;
+                Some(self.renderer.textures().insert(Texture {
+                    texture: Rc::new(texture),
+                    sampler: SamplerBehavior {
+                        magnify_filter: MagnifySamplerFilter::Nearest,
+                        ..Default::default()
+                    },
+                }))
+            });
+            self.slot_thumbnails.push(texture_id);
+        }
+    }
+
+    /// Sets the programs listed in the chip8Archive browser, sorted by title.
+    #[cfg(feature = "rom-download")]
+    pub fn set_archive_programs(&mut self, mut programs: Vec<(String, ProgramInfo)>) {
+        programs.sort_by_cached_key(|(_, info)| info.title.to_lowercase());
+        self.archive_programs = programs;
+    }
+
+    pub fn set_keymap(&mut self, keymap: KeyMap) {
+        self.keymap = keymap;
+    }
+
+    pub fn set_rom_details(&mut self, rom_details: Option<RomDetails>) {
+        if rom_details.is_none() {
+            self.flag_rom_info = false;
+        }
+        self.rom_details = rom_details;
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn set_rom_file_name(&mut self, file_name: Option<String>) {
+        if let Some(details) = &mut self.rom_details {
+            details.file_name = file_name;
+        }
+    }
+
+    pub fn set_color_preset(&mut self, preset: ColorPreset) {
+        ColorPresetHandler::new(&mut self.color_settings).set_preset(preset);
+        self.color_settings.changed = true;
+    }
+    pub fn set_quirks_preset(&mut self, preset: QuirksPreset) {
+        QuirksPresetHandler::new(&mut self.quirks_settings).set_preset(preset);
+    }
     pub fn flag_breakpoint_pc(&self) -> bool {
         self.flag_breakpoint_pc
     }
     pub fn breakpoint_pc(&self) -> &str {
         &self.breakpoint_pc
     }
+    /// Returns the breakpoints set in the disassembly view and the conditional breakpoints window.
+    pub fn breakpoints_mut(&mut self) -> &mut BreakpointList {
+        &mut self.breakpoints
+    }
+
+    /// Returns the open debug windows and the positions and sizes of all windows.
+    pub fn debug_layout(&mut self) -> DebugLayout {
+        let mut positions = String::new();
+        self.imgui.save_ini_settings(&mut positions);
+        let windows = self
+            .debug_windows()
+            .iter()
+            .filter(|(_, open)| **open)
+            .map(|(name, _)| name.to_string())
+            .collect();
+        DebugLayout {
+            enabled: self.flag_debug,
+            windows,
+            positions,
+        }
+    }
+
+    /// Restores the debug windows saved with `debug_layout`, called before the first frame.
+    pub fn set_debug_layout(&mut self, layout: &DebugLayout) {
+        self.imgui.load_ini_settings(&layout.positions);
+        self.flag_debug = layout.enabled;
+        for (name, open) in self.debug_windows() {
+            *open = layout.windows.iter().any(|window| window == name);
+        }
+    }
+
+    /// The windows of the Debug menu with the names they're saved as in the config.
+    fn debug_windows(&mut self) -> [(&'static str, &mut bool); 13] {
+        [
+            ("disassembly", &mut self.disassembly_viewer.open),
+            ("breakpoints", &mut self.breakpoint_viewer.open),
+            ("trace", &mut self.trace_viewer.open),
+            ("call_stack", &mut self.call_stack_viewer.open),
+            ("watches", &mut self.watch_viewer.open),
+            ("memory", &mut self.memory_viewer.open),
+            ("memory_search", &mut self.memory_search.open),
+            ("memory_heatmap", &mut self.memory_heatmap.open),
+            ("vram", &mut self.vram_viewer.open),
+            ("sprites", &mut self.sprite_viewer.open),
+            ("keypad", &mut self.keypad_viewer.open),
+            ("profiler", &mut self.profiler_viewer.open),
+            ("opcode_histogram", &mut self.opcode_histogram.open),
+        ]
+    }
+
+    /// Shows lines printed by a script, or its errors, in the script console.
+    #[cfg(feature = "scripting")]
+    pub fn script_output(&mut self, lines: Vec<String>) {
+        for line in lines {
+            self.script_console.push(line);
+        }
+    }
+
+    /// Returns whether a window showing executed instructions is open.
+    pub fn records_instructions(&self) -> bool {
+        self.opcode_histogram.open
+            || self.trace_viewer.open
+            || self.profiler_viewer.open
+            || self.memory_heatmap.open
+    }
+
+    /// Passes an executed instruction to the windows analyzing the program flow.
+    pub fn record_instruction(&mut self, address: u16, opcode: u16, i: u16) {
+        if self.opcode_histogram.open {
+            self.opcode_histogram.record(opcode);
+        }
+        if self.trace_viewer.open {
+            self.trace_viewer.record(address, opcode, i);
+        }
+        if self.profiler_viewer.open {
+            self.profiler_viewer.record(opcode);
+        }
+        if self.memory_heatmap.open {
+            self.memory_heatmap.record(address, opcode, i);
+        }
+    }
     pub fn flag_breakpoint_i(&self) -> bool {
         self.flag_breakpoint_i
     }
@@ -220,7 +593,7 @@ impl GUI {
     pub fn handle_event<T>(&mut self, display: &Display, event: &Event<T>) {
         let gl_window = display.gl_window();
         self.platform
-            .handle_event(self.imgui.io_mut(), gl_window.window(), &event);
+            .handle_event(self.imgui.io_mut(), gl_window.window(), event);
     }
 
     pub fn prepare_frame(&mut self, display: &Display) -> Result<(), String> {
@@ -236,6 +609,7 @@ impl GUI {
         display: &Display,
         target: &mut S,
         fps: f64,
+        speed: &SpeedInfo,
         cpu: &CPU,
     ) -> Result<(), String> {
         self.is_open = false;
@@ -251,6 +625,11 @@ impl GUI {
         let window_width = display.gl_window().window().inner_size().width as f32;
         let window_height = display.gl_window().window().inner_size().height as f32;
 
+        self.vram_viewer
+            .update(display, self.renderer.textures(), cpu.vmem());
+        self.memory_heatmap
+            .update(display, self.renderer.textures());
+
         let ui = self.imgui.frame();
         let custom_font = ui.push_font(self.custom_font);
         if let Some(menu_bar) = ui.begin_main_menu_bar() {
@@ -259,19 +638,88 @@ impl GUI {
                 MenuItem::new("Open ROM or State...")
                     .shortcut("Ctrl + O")
                     .build_with_ref(&ui, &mut self.flag_open);
+                MenuItem::new("Open ROM with Patch...")
+                    .build_with_ref(&ui, &mut self.flag_open_with_patch);
+                if let Some(recent_menu) =
+                    ui.begin_menu_with_enabled("Open Recent", !self.recent_files.is_empty())
+                {
+                    for (idx, file) in self.recent_files.iter().enumerate() {
+                        if MenuItem::new(file).build(&ui) {
+                            self.open_recent = Some(idx);
+                        }
+                    }
+                    recent_menu.end();
+                }
 
                 #[cfg(feature = "rom-download")]
                 MenuItem::new("Open ROM from URL...")
                     .shortcut("Ctrl + Shift + O")
                     .build_with_ref(&ui, &mut self.flag_open_rom_url);
+                #[cfg(feature = "rom-download")]
+                if MenuItem::new("Browse chip8Archive...").build(&ui) {
+                    self.flag_archive_browser = true;
+                    self.flag_archive_refresh = self.archive_programs.is_empty();
+                }
 
                 MenuItem::new("Save State...")
                     .shortcut("Ctrl + S")
                     .build_with_ref(&ui, &mut self.flag_save_state);
+                MenuItem::new("Export as Octo Source...")
+                    .build_with_ref(&ui, &mut self.flag_export_octo);
+                MenuItem::new("Import Octo State or Flags...")
+                    .build_with_ref(&ui, &mut self.flag_import_octo_state);
+                MenuItem::new("Export Octo State...")
+                    .build_with_ref(&ui, &mut self.flag_export_octo_state);
                 ui.separator();
-                MenuItem::new("Reset")
-                    .shortcut("F5")
-                    .build_with_ref(&ui, &mut self.flag_reset);
+                MenuItem::new("Quick Save")
+                    .shortcut("F2")
+                    .build_with_ref(&ui, &mut self.flag_quick_save);
+                MenuItem::new("Quick Load")
+                    .shortcut("F3")
+                    .build_with_ref(&ui, &mut self.flag_quick_load);
+                if let Some(slot_menu) = ui.begin_menu("State Slot") {
+                    for slot in 0..Self::NUM_STATE_SLOTS {
+                        let thumbnail = self.slot_thumbnails.get(slot).copied().flatten();
+                        let label = if thumbnail.is_some() {
+                            format!("Slot {}", slot)
+                        } else {
+                            format!("Slot {} (empty)", slot)
+                        };
+                        if MenuItem::new(&label)
+                            .shortcut(&format!("Ctrl + {}", slot))
+                            .selected(self.state_slot == slot)
+                            .build(&ui)
+                        {
+                            self.state_slot = slot;
+                        }
+                        if let Some(texture_id) = thumbnail {
+                            if ui.is_item_hovered() {
+                                ui.tooltip(|| {
+                                    Image::new(
+                                        texture_id,
+                                        [
+                                            Thumbnail::WIDTH as f32 * Self::THUMBNAIL_SCALE,
+                                            Thumbnail::HEIGHT as f32 * Self::THUMBNAIL_SCALE,
+                                        ],
+                                    )
+                                    .build(&ui);
+                                });
+                            }
+                        }
+                    }
+                    slot_menu.end();
+                }
+                ui.separator();
+                MenuItem::new("Save Screenshot")
+                    .shortcut("F12")
+                    .build_with_ref(&ui, &mut self.flag_screenshot);
+                if MenuItem::new("Record Input")
+                    .shortcut("Shift + F12")
+                    .selected(self.recording)
+                    .build(&ui)
+                {
+                    self.flag_record = true;
+                }
                 ui.separator();
                 MenuItem::new("Exit")
                     .shortcut("Esc")
@@ -283,6 +731,9 @@ impl GUI {
                 MenuItem::new("Fullscreen")
                     .shortcut("F11")
                     .build_with_ref(&ui, &mut self.flag_fullscreen);
+                MenuItem::new("ROM Info")
+                    .enabled(self.rom_details.is_some())
+                    .build_with_ref(&ui, &mut self.flag_rom_info);
                 ui.separator();
                 if let Some(menu) = ui.begin_menu("Colors") {
                     if ColorEdit::new(
@@ -388,20 +839,19 @@ impl GUI {
                 MenuItem::new("Display FPS")
                     .shortcut("F1")
                     .build_with_ref(&ui, &mut self.flag_display_fps);
-                MenuItem::new("Debug")
-                    .shortcut("F7")
-                    .build_with_ref(&ui, &mut self.flag_debug);
-                if self.flag_debug {
-                    MenuItem::new("Reset Debug Window Layout")
-                        .build_with_ref(&ui, &mut reset_debug_layout);
-                }
+                MenuItem::new("Display Speed")
+                    .shortcut("Shift + F1")
+                    .build_with_ref(&ui, &mut self.flag_display_speed);
                 menu.end();
             }
-            if let Some(menu) = ui.begin_menu("Settings") {
+            if let Some(menu) = ui.begin_menu("Emulation") {
                 self.is_open = true;
                 MenuItem::new("Pause")
-                    .shortcut("P")
+                    .shortcut("P / Space")
                     .build_with_ref(&ui, &mut self.flag_pause);
+                MenuItem::new("Reset")
+                    .shortcut("F5")
+                    .build_with_ref(&ui, &mut self.flag_reset);
                 ui.separator();
                 if let Some(cpu_speed_menu) = ui.begin_menu("CPU Speed") {
                     Self::cpu_speed_menu_item(
@@ -455,23 +905,19 @@ impl GUI {
                 }
                 if let Some(quirks_menu) = ui.begin_menu("Quirks") {
                     MenuItem::new("Load/Store")
-                        .build_with_ref(&ui, &mut self.quirks_settings.get_mut(Quirk::LoadStore));
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::LoadStore));
                     MenuItem::new("Shift")
-                        .build_with_ref(&ui, &mut self.quirks_settings.get_mut(Quirk::Shift));
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::Shift));
                     MenuItem::new("Draw")
-                        .build_with_ref(&ui, &mut self.quirks_settings.get_mut(Quirk::Draw));
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::Draw));
                     MenuItem::new("Jump0")
-                        .build_with_ref(&ui, &mut self.quirks_settings.get_mut(Quirk::Jump));
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::Jump));
                     MenuItem::new("VF Order")
-                        .build_with_ref(&ui, &mut self.quirks_settings.get_mut(Quirk::VfOrder));
-                    MenuItem::new("Partial Wrapping - Horizontal").build_with_ref(
-                        &ui,
-                        &mut self.quirks_settings.get_mut(Quirk::PartialWrapH),
-                    );
-                    MenuItem::new("Partial Wrapping - Vertical").build_with_ref(
-                        &ui,
-                        &mut self.quirks_settings.get_mut(Quirk::PartialWrapV),
-                    );
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::VfOrder));
+                    MenuItem::new("Partial Wrapping - Horizontal")
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::PartialWrapH));
+                    MenuItem::new("Partial Wrapping - Vertical")
+                        .build_with_ref(&ui, self.quirks_settings.get_mut(Quirk::PartialWrapV));
                     ui.separator();
 
                     let mut preset_handler = QuirksPresetHandler::new(&mut self.quirks_settings);
@@ -487,9 +933,50 @@ impl GUI {
                         "Octo Preset",
                         QuirksPreset::Octo,
                     );
+                    ui.separator();
+
+                    if let Some(compare_menu) = ui.begin_menu("Compare Side by Side") {
+                        let items = [
+                            ("Off", None),
+                            ("With Default Preset", Some(QuirksPreset::Default)),
+                            ("With Octo Preset", Some(QuirksPreset::Octo)),
+                        ];
+                        for (name, preset) in items {
+                            if MenuItem::new(name)
+                                .selected(self.compare_quirks == preset)
+                                .build(&ui)
+                            {
+                                self.compare_quirks = preset;
+                                self.flag_compare_changed = true;
+                            }
+                        }
+                        compare_menu.end();
+                    }
 
                     quirks_menu.end();
                 }
+                if let Some(cheats_menu) =
+                    ui.begin_menu_with_enabled("Cheats", !self.cheats.is_empty())
+                {
+                    for (idx, cheat) in self.cheats.iter_mut().enumerate() {
+                        if MenuItem::new(&cheat.name).build_with_ref(&ui, &mut cheat.enabled) {
+                            self.cheat_toggled = Some((idx, cheat.enabled));
+                        }
+                        if ui.is_item_hovered() {
+                            ui.tooltip(|| {
+                                for &(address, value) in &cheat.writes {
+                                    ui.text(format!(
+                                        "{:03X}: {:02X} (currently {:02X})",
+                                        address,
+                                        value,
+                                        cpu.read_mem(address)
+                                    ));
+                                }
+                            });
+                        }
+                    }
+                    cheats_menu.end();
+                }
                 ui.separator();
 
                 let mut vol = (self.volume * 100.0) as u8;
@@ -501,6 +988,37 @@ impl GUI {
                 MenuItem::new("Mute Audio")
                     .shortcut("M")
                     .build_with_ref(&ui, &mut self.flag_mute);
+                ui.separator();
+                MenuItem::new("Settings...").build_with_ref(&ui, &mut self.flag_settings);
+                menu.end();
+            }
+            if let Some(menu) = ui.begin_menu("Debug") {
+                self.is_open = true;
+                MenuItem::new("Debug Windows")
+                    .shortcut("F7")
+                    .build_with_ref(&ui, &mut self.flag_debug);
+                MenuItem::new("Reset Debug Window Layout")
+                    .enabled(self.flag_debug)
+                    .build_with_ref(&ui, &mut reset_debug_layout);
+                ui.separator();
+                MenuItem::new("Disassembly").build_with_ref(&ui, &mut self.disassembly_viewer.open);
+                MenuItem::new("Conditional Breakpoints")
+                    .build_with_ref(&ui, &mut self.breakpoint_viewer.open);
+                MenuItem::new("Trace").build_with_ref(&ui, &mut self.trace_viewer.open);
+                MenuItem::new("Call Stack").build_with_ref(&ui, &mut self.call_stack_viewer.open);
+                MenuItem::new("Watches").build_with_ref(&ui, &mut self.watch_viewer.open);
+                MenuItem::new("Memory Viewer").build_with_ref(&ui, &mut self.memory_viewer.open);
+                MenuItem::new("Memory Search").build_with_ref(&ui, &mut self.memory_search.open);
+                MenuItem::new("Memory Heatmap").build_with_ref(&ui, &mut self.memory_heatmap.open);
+                MenuItem::new("VRAM Viewer").build_with_ref(&ui, &mut self.vram_viewer.open);
+                MenuItem::new("Sprite Viewer").build_with_ref(&ui, &mut self.sprite_viewer.open);
+                MenuItem::new("Subroutine Profiler")
+                    .build_with_ref(&ui, &mut self.profiler_viewer.open);
+                MenuItem::new("Opcode Histogram")
+                    .build_with_ref(&ui, &mut self.opcode_histogram.open);
+                MenuItem::new("Keypad").build_with_ref(&ui, &mut self.keypad_viewer.open);
+                #[cfg(feature = "scripting")]
+                MenuItem::new("Script Console").build_with_ref(&ui, &mut self.script_console.open);
                 menu.end();
             }
             if let Some(menu) = ui.begin_menu("Help") {
@@ -509,15 +1027,79 @@ impl GUI {
                 menu.end();
             }
 
+            let mut stats = Vec::new();
+            if self.flag_display_speed {
+                stats.push(format!(
+                    "{} c/f   frame {}   {:.2}x",
+                    speed.cycles_per_frame, speed.frame, speed.multiplier
+                ));
+            }
             if self.flag_display_fps {
-                let fps = format!("{:.0} fps", fps);
-                let text_width = ui.calc_text_size_with_opts(&fps, false, 0.0);
-                ui.same_line_with_pos(window_width - (text_width[0] * 1.25));
-                ui.text_colored([0.75, 0.75, 0.75, 1.0], fps);
+                stats.push(format!("{:.0} fps", fps));
+            }
+            if !stats.is_empty() {
+                let text = stats.join("   ");
+                let text_width = ui.calc_text_size_with_opts(&text, false, 0.0);
+                ui.same_line_with_pos(window_width - text_width[0] - 20.0);
+                ui.text_colored([0.75, 0.75, 0.75, 1.0], text);
+            }
+            if self.flag_pause && !self.flag_debug {
+                let text = "PAUSED";
+                let text_size = ui.calc_text_size_with_opts(text, false, 0.0);
+                let badge_size = [text_size[0] + 30.0, text_size[1] + 16.0];
+                let badge_pos = [
+                    window_width / 2.0 - badge_size[0] / 2.0,
+                    self.last_menu_height as f32 + 10.0,
+                ];
+                Window::new("Paused")
+                    .position(badge_pos, Condition::Always)
+                    .size(badge_size, Condition::Always)
+                    .resizable(false)
+                    .collapsible(false)
+                    .movable(false)
+                    .title_bar(false)
+                    .mouse_inputs(false)
+                    .focus_on_appearing(false)
+                    .build(&ui, || {
+                        ui.set_cursor_pos([
+                            badge_size[0] / 2.0 - text_size[0] / 2.0,
+                            badge_size[1] / 2.0 - text_size[1] / 2.0,
+                        ]);
+                        ui.text(text);
+                    });
+            }
+            if let Some(notice_time) = self.notice_time {
+                if notice_time.elapsed() < Self::NOTICE_DURATION {
+                    let text_size = ui.calc_text_size_with_opts(&self.notice_text, false, 0.0);
+                    let notice_size = [text_size[0] + 20.0, text_size[1] + 16.0];
+                    let notice_pos = [10.0, window_height - notice_size[1] - 10.0];
+                    let notice_text = &self.notice_text;
+                    Window::new("Notice")
+                        .position(notice_pos, Condition::Always)
+                        .size(notice_size, Condition::Always)
+                        .resizable(false)
+                        .collapsible(false)
+                        .movable(false)
+                        .title_bar(false)
+                        .mouse_inputs(false)
+                        .focus_on_appearing(false)
+                        .build(&ui, || {
+                            ui.text(notice_text);
+                        });
+                } else {
+                    self.notice_time = None;
+                }
             }
             if self.flag_downloading {
                 self.is_open = true;
-                let text = "Downloading...";
+                let text = match self.download_progress {
+                    Some((received, Some(total))) => {
+                        format!("Downloading... {} / {} bytes", received, total)
+                    }
+                    Some((received, None)) => format!("Downloading... {} bytes", received),
+                    None => "Downloading...".to_string(),
+                };
+                let text = text.as_str();
                 let text_size = ui.calc_text_size_with_opts(text, false, 250.0);
                 let dl_win_size = [text_size[0] + 50.0, text_size[1] + 40.0];
                 let dl_win_pos = [
@@ -536,7 +1118,7 @@ impl GUI {
                             dl_win_size[0] / 2.0 - text_size[0] / 2.0,
                             dl_win_size[1] / 2.0 - text_size[1] / 2.0,
                         ]);
-                        ui.text_wrapped(&text);
+                        ui.text_wrapped(text);
                     });
             }
             if self.flag_about {
@@ -583,6 +1165,296 @@ impl GUI {
                         Self::centered_text(&ui, about_license, about_win_size[0]);
                     });
             }
+            if self.flag_settings {
+                let cpu_speed = &mut self.cpu_speed;
+                let volume = &mut self.volume;
+                let flag_mute = &mut self.flag_mute;
+                let quirks_settings = &mut self.quirks_settings;
+                let color_settings = &mut self.color_settings;
+                let keymap = &self.keymap;
+                let key_binding = &mut self.key_binding;
+                let flag_import_keys = &mut self.flag_import_keys;
+                let flag_export_keys = &mut self.flag_export_keys;
+                let settings_save = &mut self.settings_save;
+                Window::new("Settings")
+                    .opened(&mut self.flag_settings)
+                    .size([420.0, 480.0], Condition::FirstUseEver)
+                    .position(
+                        [window_width / 2.0 - 210.0, window_height / 2.0 - 240.0],
+                        Condition::FirstUseEver,
+                    )
+                    .build(&ui, || {
+                        if CollapsingHeader::new("Emulation")
+                            .default_open(true)
+                            .build(&ui)
+                        {
+                            Drag::new("CPU Speed")
+                                .range(1, 100_000)
+                                .speed(10.0)
+                                .display_format("%d Hz")
+                                .build(&ui, cpu_speed);
+                            for (name, quirk) in [
+                                ("Load/Store", Quirk::LoadStore),
+                                ("Shift", Quirk::Shift),
+                                ("Draw", Quirk::Draw),
+                                ("Jump0", Quirk::Jump),
+                                ("VF Order", Quirk::VfOrder),
+                                ("Partial Wrapping - Horizontal", Quirk::PartialWrapH),
+                                ("Partial Wrapping - Vertical", Quirk::PartialWrapV),
+                            ] {
+                                ui.checkbox(name, quirks_settings.get_mut(quirk));
+                            }
+                        }
+                        if CollapsingHeader::new("Colors").build(&ui) {
+                            for (name, color) in [
+                                ("Background", Color::Background),
+                                ("Plane 1", Color::Plane1),
+                                ("Plane 2", Color::Plane2),
+                                ("Both Planes", Color::PlaneBoth),
+                            ] {
+                                if ColorEdit::new(name, color_settings.get_mut(color)).build(&ui) {
+                                    color_settings.changed = true;
+                                }
+                            }
+                        }
+                        if CollapsingHeader::new("Audio").build(&ui) {
+                            let mut vol = (*volume * 100.0).round() as u8;
+                            Slider::new("Volume", 0, 100)
+                                .display_format("%d %%")
+                                .build(&ui, &mut vol);
+                            *volume = vol as f32 / 100.0;
+                            ui.checkbox("Mute", flag_mute);
+                        }
+                        if CollapsingHeader::new("Keys").build(&ui) {
+                            ui.text_disabled("Click a key, then press the key to map to it");
+                            for row in KeypadViewer::LAYOUT {
+                                for (idx, &key) in row.iter().enumerate() {
+                                    if idx > 0 {
+                                        ui.same_line();
+                                    }
+                                    let label = if *key_binding == Some(key) {
+                                        format!("{:X}: ...##key{}", key, key)
+                                    } else {
+                                        format!("{:X}: {}##key{}", key, keymap.key_name(key), key)
+                                    };
+                                    if ui.button_with_size(&label, [90.0, 0.0]) {
+                                        *key_binding = Some(key);
+                                    }
+                                }
+                            }
+                            if ui.button("Import Profile...") {
+                                *flag_import_keys = true;
+                            }
+                            ui.same_line();
+                            if ui.button("Export Profile...") {
+                                *flag_export_keys = true;
+                            }
+                        }
+                        ui.separator();
+                        ui.text_disabled("Changes are applied right away");
+                        if ui.button("Save") {
+                            *settings_save = Some(false);
+                        }
+                        ui.same_line();
+                        if ui.button("Save for This Game") {
+                            *settings_save = Some(true);
+                        }
+                    });
+                if !self.flag_settings {
+                    self.key_binding = None;
+                }
+            }
+            if self.flag_rom_info {
+                if let Some(details) = &self.rom_details {
+                    let custom_font_big = self.custom_font_big;
+                    let quirks_settings = &self.quirks_settings;
+                    let quirks_profile = [QuirksPreset::Default, QuirksPreset::Octo]
+                        .iter()
+                        .find(|preset| quirks_settings.iter().eq(preset.values().iter()))
+                        .map_or("Custom", |preset| match preset {
+                            QuirksPreset::Default => "Default",
+                            QuirksPreset::Octo => "Octo",
+                        });
+                    Window::new("ROM Info")
+                        .opened(&mut self.flag_rom_info)
+                        .size([400.0, 300.0], Condition::FirstUseEver)
+                        .position(
+                            [window_width / 2.0 - 200.0, window_height / 2.0 - 150.0],
+                            Condition::FirstUseEver,
+                        )
+                        .build(&ui, || {
+                            let info = details.info.as_ref();
+                            let title = info
+                                .map(|info| info.title.as_str())
+                                .or(details.title.as_deref())
+                                .or(details.file_name.as_deref())
+                                .unwrap_or("Unknown ROM");
+                            let cfont_big = ui.push_font(custom_font_big);
+                            ui.text_wrapped(title);
+                            cfont_big.pop();
+                            if let Some(info) = info.filter(|info| !info.authors.is_empty()) {
+                                ui.text_wrapped(format!("by {}", info.authors.join(", ")));
+                            }
+                            ui.separator();
+                            if let Some(file_name) = &details.file_name {
+                                ui.text(format!("File: {}", file_name));
+                            }
+                            ui.text(format!("Size: {} bytes", details.size));
+                            ui.text(format!("SHA-1: {}", details.hash));
+                            match info.and_then(|info| info.platform.as_ref()) {
+                                Some(platform) => ui.text(format!("Platform: {}", platform)),
+                                None => {
+                                    ui.text(format!("Platform: {} (detected)", details.platform))
+                                }
+                            }
+                            ui.text(format!("Quirks: {}", quirks_profile));
+                            if !details.settings_sources.is_empty() {
+                                ui.text_wrapped(format!(
+                                    "Settings from: {}",
+                                    details.settings_sources.join(", ")
+                                ));
+                            }
+                            if let Some(info) = info {
+                                if let Some(event) = &info.event {
+                                    ui.text(format!("Event: {}", event));
+                                }
+                                if let Some(release) = &info.release {
+                                    ui.text(format!("Released: {}", release));
+                                }
+                                if let Some(desc) = &info.desc {
+                                    ui.spacing();
+                                    ui.text_wrapped(desc);
+                                }
+                            }
+                        });
+                }
+            }
+            #[cfg(feature = "rom-download")]
+            if self.flag_archive_browser {
+                let archive_programs = &self.archive_programs;
+                let archive_filter = &mut self.archive_filter;
+                let mut choice = None;
+                Window::new("chip8Archive")
+                    .opened(&mut self.flag_archive_browser)
+                    .size([450.0, 350.0], Condition::FirstUseEver)
+                    .position(
+                        [window_width / 2.0 - 225.0, window_height / 2.0 - 175.0],
+                        Condition::FirstUseEver,
+                    )
+                    .build(&ui, || {
+                        if archive_programs.is_empty() {
+                            ui.text("Loading the list of programs...");
+                            return;
+                        }
+                        ui.input_text("Filter", archive_filter).build();
+                        ui.separator();
+                        let filter = archive_filter.to_lowercase();
+                        imgui::ChildWindow::new("programs").build(&ui, || {
+                            for (key, info) in archive_programs {
+                                if !info.title.to_lowercase().contains(&filter)
+                                    && !info
+                                        .authors
+                                        .iter()
+                                        .any(|author| author.to_lowercase().contains(&filter))
+                                {
+                                    continue;
+                                }
+                                let label = match &info.platform {
+                                    Some(platform) => format!("{} ({})", info.title, platform),
+                                    None => info.title.clone(),
+                                };
+                                if Selectable::new(&label).build(&ui) {
+                                    choice = Some(key.clone());
+                                }
+                                if ui.is_item_hovered() {
+                                    ui.tooltip(|| {
+                                        if !info.authors.is_empty() {
+                                            ui.text(format!("by {}", info.authors.join(", ")));
+                                        }
+                                        if let Some(desc) = &info.desc {
+                                            ui.text(desc);
+                                        }
+                                        ui.text("Click to download and play");
+                                    });
+                                }
+                            }
+                        });
+                    });
+                if choice.is_some() {
+                    self.archive_choice = choice;
+                    self.flag_archive_browser = false;
+                }
+            }
+            if !self.zip_entries.is_empty() {
+                self.is_open = true;
+                let mut choice = None;
+                let mut cancel = false;
+                let zip_entries = &self.zip_entries;
+                Window::new("Open from Archive")
+                    .size([300.0, 200.0], Condition::FirstUseEver)
+                    .position(
+                        [window_width / 2.0 - 150.0, window_height / 2.0 - 100.0],
+                        Condition::FirstUseEver,
+                    )
+                    .collapsible(false)
+                    .build(&ui, || {
+                        ui.text("The archive contains several ROMs:");
+                        ui.separator();
+                        for (idx, name) in zip_entries.iter().enumerate() {
+                            if Selectable::new(name).build(&ui) {
+                                choice = Some(idx);
+                            }
+                        }
+                        ui.separator();
+                        cancel = ui.button("Cancel");
+                    });
+                if choice.is_some() || cancel {
+                    self.zip_entries.clear();
+                    self.zip_choice = choice;
+                }
+            }
+            if self.flag_resume_prompt {
+                self.is_open = true;
+                let choice = Self::prompt(
+                    &ui,
+                    [window_width, window_height],
+                    "Resume",
+                    "Do you want to resume where you left off?",
+                    ["Resume", "Start Over"],
+                );
+                if choice.is_some() {
+                    self.flag_resume_prompt = false;
+                    self.resume_choice = choice;
+                }
+            }
+            if !self.stuck_prompt_text.is_empty() {
+                self.is_open = true;
+                let choice = Self::prompt(
+                    &ui,
+                    [window_width, window_height],
+                    "Stuck",
+                    &self.stuck_prompt_text,
+                    ["Reset", "Pause"],
+                );
+                if choice.is_some() {
+                    self.stuck_prompt_text.clear();
+                    self.stuck_choice = choice;
+                }
+            }
+            if !self.exit_prompt_text.is_empty() {
+                self.is_open = true;
+                let choice = Self::prompt(
+                    &ui,
+                    [window_width, window_height],
+                    "Quit",
+                    &self.exit_prompt_text,
+                    ["Quit", "Cancel"],
+                );
+                if choice.is_some() {
+                    self.exit_prompt_text.clear();
+                    self.exit_choice = choice;
+                }
+            }
             if self.flag_error {
                 self.is_open = true;
                 let text_size = ui.calc_text_size_with_opts(&self.error_text, false, 250.0);
@@ -604,7 +1476,7 @@ impl GUI {
                             error_win_size[0] / 2.0 - text_size[0] / 2.0,
                             ui.cursor_pos()[1],
                         ]);
-                        ui.text_wrapped(&error_text);
+                        ui.text_wrapped(error_text);
                     });
             }
 
@@ -620,6 +1492,8 @@ impl GUI {
 
                 let size = [130.0, 265.0];
                 let pos = [10.0, 40.0];
+                let editable = self.flag_pause;
+                let mut register_edit = None;
                 Window::new("Registers")
                     .position(pos, pos_condition)
                     .size(size, Condition::Always)
@@ -627,29 +1501,34 @@ impl GUI {
                     .build(&ui, || {
                         ui.columns(2, "registers", true);
 
-                        Self::register_col_u16(&ui, "PC", cpu.PC());
-                        Self::register_col_u16(&ui, "I ", cpu.I());
-                        Self::register_col_u8_greyed(&ui, "DT", cpu.DT(), cpu.DT() == 0);
-                        Self::register_col_u8_greyed(&ui, "ST", cpu.ST(), cpu.ST() == 0);
+                        let registers = [
+                            ("PC", Register::PC, cpu.PC(), 4, false),
+                            ("I ", Register::I, cpu.I(), 4, false),
+                            ("DT", Register::DT, cpu.DT() as u16, 2, cpu.DT() == 0),
+                            ("ST", Register::ST, cpu.ST() as u16, 2, cpu.ST() == 0),
+                        ];
+                        for (name, register, value, digits, greyed) in registers {
+                            if let Some(value) =
+                                Self::register_col(&ui, name, value, digits, greyed, editable)
+                            {
+                                register_edit = Some((register, value));
+                            }
+                        }
                         ui.separator();
                         let v = cpu.V();
-                        Self::register_col_u8(&ui, "V0", v[0]);
-                        Self::register_col_u8(&ui, "V8", v[8]);
-                        Self::register_col_u8(&ui, "V1", v[1]);
-                        Self::register_col_u8(&ui, "V9", v[9]);
-                        Self::register_col_u8(&ui, "V2", v[2]);
-                        Self::register_col_u8(&ui, "VA", v[10]);
-                        Self::register_col_u8(&ui, "V3", v[3]);
-                        Self::register_col_u8(&ui, "VB", v[11]);
-                        Self::register_col_u8(&ui, "V4", v[4]);
-                        Self::register_col_u8(&ui, "VC", v[12]);
-                        Self::register_col_u8(&ui, "V5", v[5]);
-                        Self::register_col_u8(&ui, "VD", v[13]);
-                        Self::register_col_u8(&ui, "V6", v[6]);
-                        Self::register_col_u8(&ui, "VE", v[14]);
-                        Self::register_col_u8(&ui, "V7", v[7]);
-                        Self::register_col_u8(&ui, "VF", v[15]);
+                        // Two columns, V0 - V7 on the left and V8 - VF on the right
+                        for idx in (0..8).flat_map(|row| [row, row + 8]) {
+                            let name = format!("V{:X}", idx);
+                            if let Some(value) =
+                                Self::register_col(&ui, &name, v[idx] as u16, 2, false, editable)
+                            {
+                                register_edit = Some((Register::V(idx), value));
+                            }
+                        }
                     });
+                if register_edit.is_some() {
+                    self.register_edit = register_edit;
+                }
 
                 let size = [130.0, 245.0];
                 let pos = [window_width - size[0] - 10.0, 40.0];
@@ -698,26 +1577,14 @@ impl GUI {
                     .resizable(false)
                     .build(&ui, || {
                         // Break on PC value
-                        Self::breakpoint_input(
-                            &ui,
-                            "PC",
-                            flag_breakpoint_pc,
-                            breakpoint_pc,
-                            true,
-                        );
+                        Self::breakpoint_input(&ui, "PC", flag_breakpoint_pc, breakpoint_pc, true);
 
                         ui.same_line();
                         ui.dummy([30.0, 0.0]);
 
                         // Break on I value
                         ui.same_line();
-                        Self::breakpoint_input(
-                            &ui,
-                            "I ",
-                            flag_breakpoint_i,
-                            breakpoint_i,
-                            true,
-                        );
+                        Self::breakpoint_input(&ui, "I ", flag_breakpoint_i, breakpoint_i, true);
 
                         // Break on opcode
                         if Self::breakpoint_input(
@@ -766,14 +1633,18 @@ impl GUI {
                         style.pop();
                     });
 
-                let size = [347.0, 37.0];
+                let size = [683.0, 37.0];
                 let pos = [
                     window_width / 2.0 - size[0] / 2.0,
                     self.last_menu_height as f32 + 10.0,
                 ];
-                let mut pause = &mut self.flag_pause;
+                let pause = &mut self.flag_pause;
                 let step = &mut self.flag_step;
                 let step_timers = &mut self.flag_step_timers;
+                let step_back = &mut self.flag_step_back;
+                let step_over = &mut self.flag_step_over;
+                let step_out = &mut self.flag_step_out;
+                let can_step_back = self.can_step_back;
                 Window::new("Debug")
                     .position(pos, Condition::Always)
                     .size(size, Condition::Always)
@@ -781,25 +1652,130 @@ impl GUI {
                     .title_bar(false)
                     .build(&ui, || {
                         let button_size = [105.0, 20.0];
-                        Self::toggle_button(&ui, "Pause (P)", button_size, &mut pause);
+                        Self::toggle_button(&ui, "Pause (P)", button_size, pause);
                         ui.same_line();
                         if Self::button_disabled(&ui, "Step (F8)", button_size, !*pause) {
                             *step = true;
                         }
                         ui.same_line();
+                        if Self::button_disabled(&ui, "Step Over (F10)", button_size, !*pause) {
+                            *step_over = true;
+                        }
+                        ui.same_line();
+                        if Self::button_disabled(&ui, "Step Out (S+F10)", button_size, !*pause) {
+                            *step_out = true;
+                        }
+                        ui.same_line();
+                        if Self::button_disabled(&ui, "Step Timers (F9)", button_size, !*pause) {
+                            *step_timers = true;
+                        }
+                        ui.same_line();
                         if Self::button_disabled(
                             &ui,
-                            "Step Timers (F9)",
+                            "Step Back (S+F8)",
                             button_size,
-                            !*pause,
+                            !*pause || !can_step_back,
                         ) {
-                            *step_timers = true;
+                            *step_back = true;
                         }
                     });
 
                 font.pop();
             }
 
+            if self.disassembly_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.disassembly_viewer
+                    .draw(&ui, cpu, &mut self.breakpoints, &self.symbols);
+                font.pop();
+            }
+
+            if self.breakpoint_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.breakpoint_viewer
+                    .draw(&ui, &mut self.breakpoints, &self.symbols);
+                font.pop();
+            }
+
+            if self.call_stack_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.call_stack_viewer.draw(&ui, cpu, &self.symbols);
+                font.pop();
+            }
+
+            if self.trace_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.trace_viewer.draw(&ui, cpu, &self.symbols);
+                font.pop();
+            }
+
+            if self.watch_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.watch_viewer.draw(&ui, cpu);
+                font.pop();
+            }
+
+            if self.opcode_histogram.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.opcode_histogram.draw(&ui);
+                font.pop();
+            }
+
+            if self.profiler_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.profiler_viewer.draw(&ui, &self.symbols);
+                font.pop();
+            }
+
+            #[cfg(feature = "scripting")]
+            if self.script_console.open {
+                let font = ui.push_font(self.custom_font_small);
+                if let Some(command) = self.script_console.draw(&ui) {
+                    self.script_command = Some(command);
+                }
+                font.pop();
+            }
+
+            if self.memory_search.open {
+                let font = ui.push_font(self.custom_font_small);
+                if let Some(address) = self.memory_search.draw(&ui, cpu) {
+                    self.memory_viewer.show(address, cpu);
+                }
+                font.pop();
+            }
+
+            if self.memory_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                if let Some(edit) = self.memory_viewer.draw(&ui, cpu, self.flag_pause) {
+                    self.memory_edit = Some(edit);
+                }
+                font.pop();
+            }
+
+            if self.memory_heatmap.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.memory_heatmap.draw(&ui);
+                font.pop();
+            }
+
+            if self.vram_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.vram_viewer.draw(&ui, cpu.vmem());
+                font.pop();
+            }
+
+            if self.sprite_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.sprite_viewer.draw(&ui, cpu);
+                font.pop();
+            }
+
+            if self.keypad_viewer.open {
+                let font = ui.push_font(self.custom_font_small);
+                self.keypad_viewer.draw(&ui, cpu, &self.keymap);
+                font.pop();
+            }
+
             // Store menu bar height with a bit of clearance
             self.last_menu_height = ui.window_size()[1] as u32 + Self::MENU_HEIGHT_CLEARANCE;
 
@@ -819,17 +1795,45 @@ impl GUI {
         Ok(())
     }
 
-    fn register_col_u16(ui: &Ui, name: &str, value: u16) {
+    /// Shows a register as hex value, which can be edited while paused and is returned after pressing enter.
+    fn register_col(
+        ui: &Ui,
+        name: &str,
+        value: u16,
+        digits: usize,
+        greyed: bool,
+        editable: bool,
+    ) -> Option<u16> {
+        let style = if greyed {
+            Some(ui.push_style_color(StyleColor::Text, Self::COLOR_TEXT_DISABLED))
+        } else {
+            None
+        };
         ui.align_text_to_frame_padding();
         ui.text(name);
         ui.same_line();
-        let mut inp = format!("{:04X}", value);
+        let mut inp = format!("{:01$X}", value, digits);
         let width = ui.push_item_width(Self::WIDTH_TEXTBOX_REGISTER);
-        ui.input_text(format!("##{}", name), &mut inp)
-            .read_only(true)
+        let entered = ui
+            .input_text(format!("##{}", name), &mut inp)
+            .read_only(!editable)
+            .chars_hexadecimal(true)
+            .chars_uppercase(true)
+            .enter_returns_true(true)
             .build();
-        width.pop(&ui);
+        if editable && ui.is_item_hovered() {
+            ui.tooltip_text(
+                "Enter applies the value, Step Back undoes the edits since the last step",
+            );
+        }
+        width.pop(ui);
         ui.next_column();
+        if let Some(style) = style {
+            style.pop();
+        }
+        u16::from_str_radix(&inp, 16)
+            .ok()
+            .filter(|&value| entered && (value as u32) < 1 << (4 * digits))
     }
 
     fn register_col_u8(ui: &Ui, name: &str, value: u8) {
@@ -841,7 +1845,7 @@ impl GUI {
         ui.input_text(format!("##{}", name), &mut inp)
             .read_only(true)
             .build();
-        width.pop(&ui);
+        width.pop(ui);
         ui.next_column();
     }
 
@@ -858,27 +1862,7 @@ impl GUI {
         ui.input_text(format!("##{}", name), &mut inp)
             .read_only(true)
             .build();
-        width.pop(&ui);
-        ui.next_column();
-        if let Some(style) = style {
-            style.pop();
-        }
-    }
-
-    fn register_col_u8_greyed(ui: &Ui, name: &str, value: u8, greyed: bool) {
-        let mut style = None;
-        if greyed {
-            style = Some(ui.push_style_color(StyleColor::Text, Self::COLOR_TEXT_DISABLED));
-        }
-        ui.align_text_to_frame_padding();
-        ui.text(name);
-        ui.same_line();
-        let mut inp = format!("{:02X}", value);
-        let width = ui.push_item_width(Self::WIDTH_TEXTBOX_REGISTER);
-        ui.input_text(format!("##{}", name), &mut inp)
-            .read_only(true)
-            .build();
-        width.pop(&ui);
+        width.pop(ui);
         ui.next_column();
         if let Some(style) = style {
             style.pop();
@@ -894,7 +1878,7 @@ impl GUI {
         ui.input_text(format!("##{}", name), &mut inp)
             .read_only(true)
             .build();
-        width.pop(&ui);
+        width.pop(ui);
         ui.same_line();
         ui.text(description);
     }
@@ -913,7 +1897,7 @@ impl GUI {
             .chars_hexadecimal(hex_chars)
             .chars_uppercase(true)
             .build();
-        width.pop(&ui);
+        width.pop(ui);
         ui.is_item_deactivated_after_edit()
     }
 
@@ -984,10 +1968,50 @@ impl GUI {
         }
     }
 
+    /// Shows a centered window with a question and two buttons, returns `Some(true)` if the first one was clicked.
+    fn prompt(
+        ui: &Ui,
+        window_size: [f32; 2],
+        title: &str,
+        text: &str,
+        buttons: [&str; 2],
+    ) -> Option<bool> {
+        let text_size = ui.calc_text_size_with_opts(text, false, 0.0);
+        let button_size = [80.0, 20.0];
+        let prompt_win_size = [text_size[0] + 50.0, text_size[1] + button_size[1] + 60.0];
+        let prompt_win_pos = [
+            window_size[0] / 2.0 - prompt_win_size[0] / 2.0,
+            window_size[1] / 2.0 - prompt_win_size[1] / 2.0,
+        ];
+        let mut choice = None;
+        Window::new(title)
+            .position(prompt_win_pos, Condition::Always)
+            .size(prompt_win_size, Condition::Always)
+            .resizable(false)
+            .collapsible(false)
+            .movable(false)
+            .build(ui, || {
+                Self::centered_text(ui, text, prompt_win_size[0]);
+                ui.spacing();
+                ui.set_cursor_pos([
+                    prompt_win_size[0] / 2.0 - button_size[0] - 5.0,
+                    ui.cursor_pos()[1],
+                ]);
+                if ui.button_with_size(buttons[0], button_size) {
+                    choice = Some(true);
+                }
+                ui.same_line();
+                if ui.button_with_size(buttons[1], button_size) {
+                    choice = Some(false);
+                }
+            });
+        choice
+    }
+
     fn centered_text(ui: &Ui, text: &str, window_width: f32) {
         let text_width = ui.calc_text_size_with_opts(text, false, 0.0)[0];
         ui.set_cursor_pos([window_width / 2.0 - text_width / 2.0, ui.cursor_pos()[1]]);
-        ui.text_wrapped(&text);
+        ui.text_wrapped(text);
     }
 
     pub fn menu_height(&self) -> u32 {
@@ -1009,7 +2033,7 @@ impl GUI {
         preset: ColorPreset,
     ) -> bool {
         let active = &mut preset_handler.is_active(preset);
-        MenuItem::new(name).build_with_ref(&ui, active);
+        MenuItem::new(name).build_with_ref(ui, active);
         if *active {
             preset_handler.set_preset(preset);
             true
@@ -1025,14 +2049,22 @@ impl GUI {
         preset: QuirksPreset,
     ) {
         let active = &mut preset_handler.is_active(preset);
-        MenuItem::new(name).build_with_ref(&ui, active);
+        MenuItem::new(name).build_with_ref(ui, active);
         if *active {
             preset_handler.set_preset(preset);
         }
     }
 
     pub fn display_error(&mut self, message: &str) {
+        warn!("{}", message);
         self.flag_error = true;
         self.error_text = String::from(message);
     }
+
+    /// Briefly displays a message without interrupting the emulation.
+    pub fn display_notice(&mut self, message: &str) {
+        info!("{}", message);
+        self.notice_text = String::from(message);
+        self.notice_time = Some(Instant::now());
+    }
 }
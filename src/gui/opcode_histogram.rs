@@ -0,0 +1,77 @@
+use imgui::{Condition, ProgressBar, Ui, Window};
+use pich8::disassembler::OpcodeClass;
+use std::time::Instant;
+
+/// Instructions executed per second by opcode class, e.g. how much of the time goes into drawing.
+pub struct OpcodeHistogram {
+    pub open: bool,
+    /// Counts of the running second
+    counts: [u32; OpcodeClass::ALL.len()],
+    /// Counts of the last complete second
+    rates: [u32; OpcodeClass::ALL.len()],
+    /// Total instructions per second of the last minute, oldest first
+    totals: Vec<f32>,
+    second_start: Instant,
+}
+
+impl OpcodeHistogram {
+    const HISTORY_LEN: usize = 60;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            counts: [0; OpcodeClass::ALL.len()],
+            rates: [0; OpcodeClass::ALL.len()],
+            totals: Vec::with_capacity(Self::HISTORY_LEN),
+            second_start: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, opcode: u16) {
+        self.counts[OpcodeClass::of(opcode) as usize] += 1;
+    }
+
+    pub fn draw(&mut self, ui: &Ui) {
+        if self.second_start.elapsed().as_secs() >= 1 {
+            self.rates = self.counts;
+            self.counts = [0; OpcodeClass::ALL.len()];
+            self.second_start = Instant::now();
+            if self.totals.len() == Self::HISTORY_LEN {
+                self.totals.remove(0);
+            }
+            self.totals.push(self.rates.iter().sum::<u32>() as f32);
+        }
+
+        let mut open = self.open;
+        Window::new("Opcode Histogram")
+            .opened(&mut open)
+            .position([300.0, 170.0], Condition::FirstUseEver)
+            .size([320.0, 300.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let total: u32 = self.rates.iter().sum();
+                ui.text(format!("{} instructions/s", total));
+                ui.separator();
+                for class in OpcodeClass::ALL {
+                    let rate = self.rates[class as usize];
+                    ui.text(class.name());
+                    ui.same_line_with_pos(90.0);
+                    let fraction = if total > 0 {
+                        rate as f32 / total as f32
+                    } else {
+                        0.0
+                    };
+                    ProgressBar::new(fraction)
+                        .overlay_text(format!("{}/s", rate))
+                        .size([-1.0, 0.0])
+                        .build(ui);
+                }
+                ui.separator();
+                ui.plot_lines("##totals", &self.totals)
+                    .overlay_text("Instructions/s, last minute")
+                    .scale_min(0.0)
+                    .graph_size([ui.content_region_avail()[0], 60.0])
+                    .build();
+            });
+        self.open = open;
+    }
+}
@@ -0,0 +1,78 @@
+use imgui::{ChildWindow, Condition, Ui, Window};
+use pich8::profiler::Profiler;
+use pich8::symbols::Symbols;
+
+/// The time spent per subroutine with call counts, while the window is open.
+pub struct ProfilerViewer {
+    pub open: bool,
+    profiler: Profiler,
+}
+
+impl ProfilerViewer {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            profiler: Profiler::default(),
+        }
+    }
+
+    pub fn record(&mut self, opcode: u16) {
+        self.profiler.record(opcode);
+    }
+
+    pub fn draw(&mut self, ui: &Ui, symbols: &Symbols) {
+        let mut open = self.open;
+        Window::new("Subroutine Profiler")
+            .opened(&mut open)
+            .position([340.0, 210.0], Condition::FirstUseEver)
+            .size([420.0, 300.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let instructions = self.profiler.instructions();
+                ui.text(format!("{} instructions", instructions));
+                ui.same_line();
+                if ui.small_button("Reset") {
+                    self.profiler.reset();
+                }
+                ui.separator();
+                if instructions == 0 {
+                    ui.text_disabled("Nothing executed yet");
+                    return;
+                }
+                let percent = |count: u64| 100.0 * count as f64 / instructions as f64;
+
+                let profiler = &self.profiler;
+                ChildWindow::new("routines").build(ui, || {
+                    ui.columns(5, "routines", true);
+                    for title in ["Subroutine", "Calls", "Own", "Total", "Per Call"] {
+                        ui.text(title);
+                        ui.next_column();
+                    }
+                    ui.separator();
+                    ui.text("(main)");
+                    ui.next_column();
+                    ui.next_column();
+                    ui.text(format!("{:.1}%", percent(profiler.main())));
+                    ui.next_column();
+                    ui.next_column();
+                    ui.next_column();
+                    for (address, stats) in profiler.routines() {
+                        ui.text(symbols.describe(address));
+                        ui.next_column();
+                        ui.text(stats.calls.to_string());
+                        ui.next_column();
+                        ui.text(format!("{:.1}%", percent(stats.own)));
+                        ui.next_column();
+                        ui.text(format!("{:.1}%", percent(stats.total)));
+                        ui.next_column();
+                        ui.text(format!(
+                            "{:.1}",
+                            stats.total as f64 / stats.calls.max(1) as f64
+                        ));
+                        ui.next_column();
+                    }
+                    ui.columns(1, "", false);
+                });
+            });
+        self.open = open;
+    }
+}
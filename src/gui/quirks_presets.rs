@@ -1,11 +1,23 @@
 use super::QuirksSettings;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum QuirksPreset {
     Default,
     Octo,
 }
 
+impl QuirksPreset {
+    /// Returns the quirk values of this preset, indexed by `Quirk`.
+    pub fn values(self) -> [bool; QuirksSettings::NUM_QUIRKS] {
+        match self {
+            QuirksPreset::Default => QuirksPresetHandler::QUIRKS_PRESET_DEFAULT,
+            QuirksPreset::Octo => QuirksPresetHandler::QUIRKS_PRESET_OCTO,
+        }
+    }
+}
+
 pub struct QuirksPresetHandler<'a> {
     settings: &'a mut QuirksSettings,
 }
@@ -38,9 +50,6 @@ impl<'a> QuirksPresetHandler<'a> {
     }
 
     fn get_preset(&self, preset: QuirksPreset) -> [bool; QuirksSettings::NUM_QUIRKS] {
-        match preset {
-            QuirksPreset::Default => Self::QUIRKS_PRESET_DEFAULT,
-            QuirksPreset::Octo => Self::QUIRKS_PRESET_OCTO,
-        }
+        preset.values()
     }
 }
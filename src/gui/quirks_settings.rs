@@ -31,11 +31,11 @@ impl QuirksSettings {
         &mut self.quirks[quirk as usize]
     }
 
-    pub fn iter(&self) -> Iter<bool> {
+    pub fn iter(&self) -> Iter<'_, bool> {
         self.quirks.iter()
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<bool> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, bool> {
         self.quirks.iter_mut()
     }
 }
@@ -0,0 +1,86 @@
+use imgui::{ChildWindow, Condition, FocusedWidget, Ui, Window};
+use std::path::PathBuf;
+
+/// What the user asked the script engine to do.
+pub enum ScriptCommand {
+    Eval(String),
+    Load(PathBuf),
+}
+
+/// Loads Rhai scripts, evaluates single lines and shows what scripts print.
+pub struct ScriptConsole {
+    pub open: bool,
+    path: String,
+    input: String,
+    lines: Vec<String>,
+    scroll_to_end: bool,
+}
+
+impl ScriptConsole {
+    const MAX_LINES: usize = 500;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            path: String::new(),
+            input: String::new(),
+            lines: Vec::new(),
+            scroll_to_end: false,
+        }
+    }
+
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() == Self::MAX_LINES {
+            self.lines.remove(0);
+        }
+        self.lines.push(line);
+        self.scroll_to_end = true;
+    }
+
+    pub fn draw(&mut self, ui: &Ui) -> Option<ScriptCommand> {
+        let mut command = None;
+        let mut open = self.open;
+        Window::new("Script Console")
+            .opened(&mut open)
+            .position([380.0, 250.0], Condition::FirstUseEver)
+            .size([420.0, 300.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let width = ui.push_item_width(300.0);
+                ui.input_text("##path", &mut self.path)
+                    .hint("trainer.rhai")
+                    .build();
+                width.pop(ui);
+                ui.same_line();
+                if ui.button("Load Script") && !self.path.trim().is_empty() {
+                    command = Some(ScriptCommand::Load(PathBuf::from(self.path.trim())));
+                }
+                ui.separator();
+
+                let footer = ui.frame_height_with_spacing();
+                ChildWindow::new("output")
+                    .size([0.0, -footer])
+                    .build(ui, || {
+                        for line in &self.lines {
+                            ui.text_wrapped(line);
+                        }
+                        if self.scroll_to_end {
+                            ui.set_scroll_here_y_with_ratio(1.0);
+                            self.scroll_to_end = false;
+                        }
+                    });
+                let width = ui.push_item_width(-1.0);
+                let entered = ui
+                    .input_text("##input", &mut self.input)
+                    .hint("poke(0x3e8, 3)")
+                    .enter_returns_true(true)
+                    .build();
+                width.pop(ui);
+                if entered && !self.input.trim().is_empty() {
+                    command = Some(ScriptCommand::Eval(std::mem::take(&mut self.input)));
+                    ui.set_keyboard_focus_here_with_offset(FocusedWidget::Previous);
+                }
+            });
+        self.open = open;
+        command
+    }
+}
@@ -0,0 +1,88 @@
+use imgui::{Condition, Slider, Ui, Window};
+use pich8::cpu::CPU;
+
+/// Previews the bytes at I as a sprite, both as 8 pixels wide rows (DXYN) and as
+/// 16 pixels wide rows (the 16x16 sprites of DXY0).
+pub struct SpriteViewer {
+    pub open: bool,
+    height: u32,
+    auto_height: bool,
+}
+
+impl SpriteViewer {
+    const PIXEL_SIZE: f32 = 8.0;
+    const COLOR_ON: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const COLOR_OFF: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            height: 5,
+            auto_height: true,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU) {
+        let mut open = self.open;
+        Window::new("Sprite at I")
+            .opened(&mut open)
+            .position([250.0, 100.0], Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .build(ui, || {
+                // Use the height of the next draw call, DXY0 draws 16 rows
+                let next_opcode = cpu.next_opcode();
+                if self.auto_height && next_opcode & 0xF000 == 0xD000 {
+                    self.height = match next_opcode & 0x000F {
+                        0 => 16,
+                        n => n as u32,
+                    };
+                }
+                ui.checkbox("Height of next DXYN", &mut self.auto_height);
+                let width = ui.push_item_width(100.0);
+                if Slider::new("Rows", 1, 16).build(ui, &mut self.height) {
+                    self.auto_height = false;
+                }
+                width.pop(ui);
+                ui.separator();
+
+                let rows = self.height as usize;
+                ui.text(format!("8 wide, {} bytes at {:04X}", rows, cpu.I()));
+                self.draw_sprite(ui, cpu, 1, rows);
+                ui.text(format!("16 wide, {} bytes at {:04X}", rows * 2, cpu.I()));
+                self.draw_sprite(ui, cpu, 2, rows);
+            });
+        self.open = open;
+    }
+
+    fn draw_sprite(&self, ui: &Ui, cpu: &CPU, bytes_per_row: usize, rows: usize) {
+        let [left, top] = ui.cursor_screen_pos();
+        let draw_list = ui.get_window_draw_list();
+        for row in 0..rows {
+            for byte in 0..bytes_per_row {
+                let address = cpu.I().wrapping_add((row * bytes_per_row + byte) as u16);
+                let value = cpu.read_mem(address);
+                for bit in 0..8 {
+                    let x = left + (byte * 8 + bit) as f32 * Self::PIXEL_SIZE;
+                    let y = top + row as f32 * Self::PIXEL_SIZE;
+                    let color = if value & (0x80 >> bit) != 0 {
+                        Self::COLOR_ON
+                    } else {
+                        Self::COLOR_OFF
+                    };
+                    draw_list
+                        .add_rect(
+                            [x, y],
+                            [x + Self::PIXEL_SIZE - 1.0, y + Self::PIXEL_SIZE - 1.0],
+                            color,
+                        )
+                        .filled(true)
+                        .build();
+                }
+            }
+        }
+        ui.dummy([
+            (bytes_per_row * 8) as f32 * Self::PIXEL_SIZE,
+            rows as f32 * Self::PIXEL_SIZE,
+        ]);
+    }
+}
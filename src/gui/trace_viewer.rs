@@ -0,0 +1,171 @@
+use imgui::{ChildWindow, Condition, ListClipper, Ui, Window};
+use pich8::cpu::CPU;
+use pich8::disassembler;
+use pich8::symbols::Symbols;
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, PartialEq)]
+enum TraceFilter {
+    All,
+    JumpsAndCalls,
+    MemoryWrites,
+}
+
+impl TraceFilter {
+    fn matches(self, opcode: u16) -> bool {
+        match self {
+            TraceFilter::All => true,
+            // JP, CALL, RET and JP V0
+            TraceFilter::JumpsAndCalls => {
+                matches!(opcode & 0xF000, 0x1000 | 0x2000 | 0xB000) || opcode == 0x00EE
+            }
+            // BCD, register stores and XO-CHIP register range stores
+            TraceFilter::MemoryWrites => {
+                matches!(opcode & 0xF0FF, 0xF033 | 0xF055) || opcode & 0xF00F == 0x5002
+            }
+        }
+    }
+}
+
+struct TraceEntry {
+    address: u16,
+    opcode: u16,
+    /// I before the instruction, the address memory writes go to
+    i: u16,
+}
+
+/// The last executed instructions matching a filter, a lighter alternative to tracing into a file.
+pub struct TraceViewer {
+    pub open: bool,
+    filter: TraceFilter,
+    range_start: String,
+    range_end: String,
+    range: Option<(u16, u16)>,
+    entries: VecDeque<TraceEntry>,
+    paused: bool,
+    auto_scroll: bool,
+}
+
+impl TraceViewer {
+    const MAX_ENTRIES: usize = 2000;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            filter: TraceFilter::All,
+            range_start: String::new(),
+            range_end: String::new(),
+            range: None,
+            entries: VecDeque::with_capacity(Self::MAX_ENTRIES),
+            paused: false,
+            auto_scroll: true,
+        }
+    }
+
+    pub fn record(&mut self, address: u16, opcode: u16, i: u16) {
+        if self.paused
+            || !self.filter.matches(opcode)
+            || self
+                .range
+                .is_some_and(|(start, end)| address < start || address > end)
+        {
+            return;
+        }
+        if self.entries.len() == Self::MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TraceEntry { address, opcode, i });
+    }
+
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU, symbols: &Symbols) {
+        let mut open = self.open;
+        Window::new("Trace")
+            .opened(&mut open)
+            .position([320.0, 190.0], Condition::FirstUseEver)
+            .size([360.0, 400.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let filter = self.filter;
+                ui.radio_button("All", &mut self.filter, TraceFilter::All);
+                ui.same_line();
+                ui.radio_button("Jumps/Calls", &mut self.filter, TraceFilter::JumpsAndCalls);
+                ui.same_line();
+                ui.radio_button("Memory Writes", &mut self.filter, TraceFilter::MemoryWrites);
+                if self.filter != filter {
+                    self.entries.clear();
+                }
+
+                let width = ui.push_item_width(50.0);
+                let mut changed = ui
+                    .input_text("##start", &mut self.range_start)
+                    .chars_hexadecimal(true)
+                    .chars_uppercase(true)
+                    .hint("0000")
+                    .build();
+                ui.same_line();
+                changed |= ui
+                    .input_text("PC Range##end", &mut self.range_end)
+                    .chars_hexadecimal(true)
+                    .chars_uppercase(true)
+                    .hint("FFFF")
+                    .build();
+                width.pop(ui);
+                if changed {
+                    self.update_range();
+                }
+
+                ui.checkbox("Pause", &mut self.paused);
+                ui.same_line();
+                ui.checkbox("Auto-scroll", &mut self.auto_scroll);
+                ui.same_line();
+                if ui.small_button("Clear") {
+                    self.entries.clear();
+                }
+                ui.separator();
+
+                let entries = &self.entries;
+                let auto_scroll = self.auto_scroll && !self.paused;
+                ChildWindow::new("trace").build(ui, || {
+                    let mut clipper = ListClipper::new(entries.len() as i32)
+                        .items_height(ui.text_line_height_with_spacing())
+                        .begin(ui);
+                    while clipper.step() {
+                        for idx in clipper.display_start()..clipper.display_end() {
+                            let entry = &entries[idx as usize];
+                            let ext = (cpu.read_mem(entry.address.wrapping_add(2)) as u16) << 8
+                                | cpu.read_mem(entry.address.wrapping_add(3)) as u16;
+                            let mut line = format!(
+                                "{:04X}  {:04X}  I={:04X}  ",
+                                entry.address, entry.opcode, entry.i
+                            );
+                            if let Some(label) = symbols.label(entry.address) {
+                                line.push_str(&format!("{}: ", label));
+                            }
+                            line.push_str(&disassembler::disassemble(entry.opcode, ext));
+                            ui.text(line);
+                        }
+                    }
+                    if auto_scroll {
+                        ui.set_scroll_here_y_with_ratio(1.0);
+                    }
+                });
+            });
+        self.open = open;
+    }
+
+    /// Parses the PC range, an empty bound is the start or the end of the memory.
+    fn update_range(&mut self) {
+        let parse = |value: &str, default| match value.trim() {
+            "" => Some(default),
+            value => u16::from_str_radix(value, 16).ok(),
+        };
+        self.range = match (
+            parse(&self.range_start, 0),
+            parse(&self.range_end, u16::MAX),
+        ) {
+            (Some(0), Some(u16::MAX)) => None,
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => None,
+        };
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,137 @@
+use crate::display::pixel_color;
+use glium::{
+    texture::{ClientFormat, RawImage2d, Texture2d},
+    uniforms::{MagnifySamplerFilter, SamplerBehavior},
+    Display,
+};
+use imgui::{Condition, Image, Slider, TextureId, Textures, Ui, Window};
+use imgui_glium_renderer::Texture;
+use pich8::video_memory::{Plane, VideoMemory, VideoMode};
+use std::{borrow::Cow, rc::Rc};
+
+/// Shows the raw frame buffer without the display's colors, scaling and filters,
+/// either a single plane or both planes in fixed colors.
+pub struct VramViewer {
+    pub open: bool,
+    plane: Plane,
+    zoom: u32,
+    texture: Option<TextureId>,
+}
+
+impl VramViewer {
+    const COLORS: [[u8; 3]; 4] = [[0, 0, 0], [255, 255, 255], [255, 64, 64], [255, 224, 64]];
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            plane: Plane::Both,
+            zoom: 3,
+            texture: None,
+        }
+    }
+
+    /// Uploads the current video memory, has to be called before the frame is drawn.
+    pub fn update(
+        &mut self,
+        display: &Display,
+        textures: &mut Textures<Texture>,
+        vmem: &VideoMemory,
+    ) {
+        if let Some(texture_id) = self.texture.take() {
+            textures.remove(texture_id);
+        }
+        if !self.open {
+            return;
+        }
+        let (width, height) = (vmem.render_width(), vmem.render_height());
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for idx in 0..width * height {
+            let color = match self.plane {
+                Plane::Both => pixel_color(vmem, idx, &Self::COLORS),
+                plane if vmem.get_index_plane(plane, idx) => Self::COLORS[1],
+                _ => Self::COLORS[0],
+            };
+            pixels.extend_from_slice(&color);
+        }
+        let image = RawImage2d {
+            data: Cow::Owned(pixels),
+            width: width as u32,
+            height: height as u32,
+            format: ClientFormat::U8U8U8,
+        };
+        if let Ok(texture) = Texture2d::new(display, image) {
+            self.texture = Some(textures.insert(Texture {
+                texture: Rc::new(texture),
+                sampler: SamplerBehavior {
+                    magnify_filter: MagnifySamplerFilter::Nearest,
+                    ..Default::default()
+                },
+            }));
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, vmem: &VideoMemory) {
+        let mut open = self.open;
+        Window::new("VRAM")
+            .opened(&mut open)
+            .position([200.0, 80.0], Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .build(ui, || {
+                ui.radio_button("Plane 1", &mut self.plane, Plane::First);
+                ui.same_line();
+                ui.radio_button("Plane 2", &mut self.plane, Plane::Second);
+                ui.same_line();
+                ui.radio_button("Both", &mut self.plane, Plane::Both);
+                ui.same_line();
+                let width = ui.push_item_width(80.0);
+                Slider::new("Zoom", 1, 8).build(ui, &mut self.zoom);
+                width.pop(ui);
+                ui.text(format!(
+                    "{:?} mode, {}x{}",
+                    vmem.video_mode,
+                    vmem.width(),
+                    vmem.height()
+                ));
+
+                let texture_id = match self.texture {
+                    Some(texture_id) => texture_id,
+                    None => return,
+                };
+                let zoom = self.zoom as f32;
+                let size = [
+                    vmem.render_width() as f32 * zoom,
+                    vmem.render_height() as f32 * zoom,
+                ];
+                Image::new(texture_id, size).border_col([0.5; 4]).build(ui);
+                let hovered = if ui.is_item_hovered() {
+                    let [mouse_x, mouse_y] = ui.io().mouse_pos;
+                    let [min_x, min_y] = ui.item_rect_min();
+                    let x = ((mouse_x - min_x) / zoom) as usize;
+                    let y = ((mouse_y - min_y) / zoom) as usize;
+                    (x < vmem.render_width() && y < vmem.render_height()).then_some((x, y))
+                } else {
+                    None
+                };
+                match hovered {
+                    Some((x, y)) => {
+                        let idx = vmem.to_index(x, y);
+                        // Coordinates as used by DXYN, in low res every pixel is stored as 2x2
+                        let (x, y) = if vmem.video_mode == VideoMode::Default {
+                            (x / 2, y / 2)
+                        } else {
+                            (x, y)
+                        };
+                        ui.text(format!(
+                            "x: {:3} y: {:3}   plane 1: {}   plane 2: {}",
+                            x,
+                            y,
+                            vmem.get_index_plane(Plane::First, idx) as u8,
+                            vmem.get_index_plane(Plane::Second, idx) as u8
+                        ));
+                    }
+                    None => ui.text(""),
+                }
+            });
+        self.open = open;
+    }
+}
@@ -0,0 +1,101 @@
+use imgui::{Condition, StyleColor, Ui, Window};
+use pich8::cpu::CPU;
+use pich8::expression::Watch;
+
+struct WatchEntry {
+    expression: String,
+    watch: Watch,
+    value: String,
+    /// Frames the value is still highlighted after it changed
+    highlight: u32,
+}
+
+/// Values like `V3`, `I` or `mem[0x3a0..0x3b0]` evaluated every frame, changes are highlighted.
+pub struct WatchViewer {
+    pub open: bool,
+    watches: Vec<WatchEntry>,
+    input: String,
+    error: Option<String>,
+}
+
+impl WatchViewer {
+    const HIGHLIGHT_FRAMES: u32 = 30;
+    const COLOR_CHANGED: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            watches: Vec::new(),
+            input: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, cpu: &CPU) {
+        let mut open = self.open;
+        Window::new("Watches")
+            .opened(&mut open)
+            .position([240.0, 110.0], Condition::FirstUseEver)
+            .size([360.0, 250.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let width = ui.push_item_width(200.0);
+                let entered = ui
+                    .input_text("##watch", &mut self.input)
+                    .hint("V3, I, DT, mem[0x3a0..0x3b0]")
+                    .enter_returns_true(true)
+                    .build();
+                width.pop(ui);
+                ui.same_line();
+                if (ui.button("Add") || entered) && !self.input.trim().is_empty() {
+                    self.add(cpu);
+                }
+                if let Some(error) = &self.error {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+                }
+                ui.separator();
+
+                let mut remove = None;
+                for (idx, entry) in self.watches.iter_mut().enumerate() {
+                    let value = entry.watch.evaluate(cpu);
+                    if value != entry.value {
+                        entry.value = value;
+                        entry.highlight = Self::HIGHLIGHT_FRAMES;
+                    } else {
+                        entry.highlight = entry.highlight.saturating_sub(1);
+                    }
+                    if ui.small_button(format!("x##{}", idx)) {
+                        remove = Some(idx);
+                    }
+                    ui.same_line();
+                    ui.text(&entry.expression);
+                    ui.same_line_with_pos(160.0);
+                    let style = (entry.highlight > 0)
+                        .then(|| ui.push_style_color(StyleColor::Text, Self::COLOR_CHANGED));
+                    ui.text_wrapped(&entry.value);
+                    if let Some(style) = style {
+                        style.pop();
+                    }
+                }
+                if let Some(idx) = remove {
+                    self.watches.remove(idx);
+                }
+            });
+        self.open = open;
+    }
+
+    fn add(&mut self, cpu: &CPU) {
+        match self.input.parse::<Watch>() {
+            Ok(watch) => {
+                self.watches.push(WatchEntry {
+                    expression: self.input.trim().to_string(),
+                    value: watch.evaluate(cpu),
+                    watch,
+                    highlight: 0,
+                });
+                self.input.clear();
+                self.error = None;
+            }
+            Err(msg) => self.error = Some(msg),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use crate::cheats::Cheats;
+use crate::cli::Cli;
+use crate::config::{Config, KeyMap, Settings};
+use crate::display::{self, DisplayOutput, Frame};
+use crate::fbdev::{EvdevKeyboard, Framebuffer};
+use crate::game_db::GameDb;
+use crate::gui::ColorPreset;
+use crate::input::InputSource;
+use crate::metrics::Metrics;
+use crate::render_thread::RenderThread;
+#[cfg(feature = "scripting")]
+use crate::scripting::Script;
+use crate::stream::StreamServer;
+use log::warn;
+use pich8::assembler;
+use pich8::cpu::{self, CPU};
+use pich8::lockstep::ReferenceTrace;
+use pich8::patch;
+use pich8::replay::Recording;
+use pich8::rom::{self, FileType};
+use pich8::runner::Runner;
+use pich8::state::{self, StateFile};
+use std::{
+    fs,
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Runs the CPU without window and sound as fast as possible, e.g. for benchmarks.
+/// A frame consists of the instructions executed within 1/60 s at the configured speed, followed by a timer update.
+pub struct Headless {
+    cpu: CPU,
+    runner: Runner,
+    colors: [[u8; 3]; 4],
+    keymap: KeyMap,
+    cheats: Cheats,
+    metrics: Option<Arc<Metrics>>,
+    #[cfg(feature = "scripting")]
+    script: Option<Script>,
+}
+
+impl Headless {
+    pub fn new(rom: &[u8], settings: &Settings) -> Result<Self, String> {
+        rom::validate(rom)?;
+        let mut cpu = CPU::new();
+        cpu.load_rom(rom)?;
+        Ok(Self::with_cpu(cpu, settings))
+    }
+
+    fn with_cpu(mut cpu: CPU, settings: &Settings) -> Self {
+        if let Some(quirks) = settings.quirks {
+            quirks.quirks().apply(&mut cpu);
+        }
+        let speed = settings
+            .speed
+            .unwrap_or(Config::default().settings.speed.unwrap_or(720));
+        let colors = settings
+            .palette
+            .map_or(ColorPreset::Default.values(), |palette| palette.colors())
+            .map(|color| color.map(|c| (c * 255.0) as u8));
+        Self {
+            cpu,
+            runner: Runner::new(speed),
+            colors,
+            keymap: settings.keys.unwrap_or_default(),
+            cheats: Cheats::default(),
+            metrics: None,
+            #[cfg(feature = "scripting")]
+            script: None,
+        }
+    }
+
+    fn read_state(path: &Path) -> Result<CPU, String> {
+        let file = fs::read(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        CPU::from_state(StateFile::parse(&file)?.cpu_state)
+    }
+
+    /// Reads a ROM, taking the first one of archives and assembling Octo source.
+    pub fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+        let mut rom = rom::read(path)?;
+        if FileType::detect(&rom) == FileType::Zip {
+            let name = rom::zip_roms(&rom)?
+                .into_iter()
+                .next()
+                .ok_or("The archive doesn't contain any ROMs!")?;
+            rom = rom::read_from_zip(&rom, &name)?;
+        }
+        if FileType::detect(&rom) == FileType::OctoSource {
+            rom = assembler::assemble(&String::from_utf8_lossy(&rom))
+                .map_err(|msg| format!("Cannot assemble {}: {}", path.display(), msg))?
+                .rom;
+        }
+        Ok(rom)
+    }
+
+    /// Loads the ROM given on the command line with the settings the GUI would use for it.
+    pub fn from_cli(cli: &Cli) -> Result<Self, String> {
+        let path = cli.files.first().ok_or("No ROM given!")?;
+        let mut rom = Self::read_rom(path)?;
+        if let Some(patch) = &cli.patch {
+            rom = patch::apply_file(&rom, patch)?;
+        }
+
+        let config = match cli.config.clone().or_else(Config::default_path) {
+            Some(path) if path.exists() => Config::load(&path).unwrap_or_else(|msg| {
+                warn!("{}", msg);
+                Config::default()
+            }),
+            _ => Config::default(),
+        };
+        let game_db = GameDb::load(Config::dir().as_deref()).unwrap_or_else(|msg| {
+            warn!("{}", msg);
+            GameDb::default()
+        });
+        if FileType::detect(&rom) == FileType::State {
+            let settings = config.settings_for(None, None).merged(&cli.settings());
+            let cpu = CPU::from_state(StateFile::parse(&rom)?.cpu_state)?;
+            return Ok(Self::with_cpu(cpu, &settings));
+        }
+        let rom_hash = rom::sha1_hex(&rom);
+        let known = game_db.get(&rom_hash).map(|entry| &entry.settings);
+        let settings = config
+            .settings_for(Some(&rom_hash), known)
+            .merged(&cli.settings());
+
+        let mut headless = match &cli.state {
+            Some(path) => {
+                rom::validate(&rom)?;
+                Self::with_cpu(Self::read_state(path)?, &settings)
+            }
+            None => Self::new(&rom, &settings)?,
+        };
+        if !cli.cheats.is_empty() {
+            let rom_path = Some(path.as_path()).filter(|path| *path != Path::new(rom::STDIN_PATH));
+            let cheats_path =
+                Cheats::find(rom_path, &rom_hash).ok_or("There are no cheats for this ROM!")?;
+            headless.cheats = Cheats::load(&cheats_path)?;
+            for name in &cli.cheats {
+                if !headless.cheats.enable(name) {
+                    return Err(format!("There's no cheat named '{}'!", name));
+                }
+            }
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(path) = &cli.script {
+            let mut script = Script::new();
+            let result = script.load(path, &mut headless.cpu);
+            for line in script.take_output() {
+                println!("{}", line);
+            }
+            result?;
+            headless.script = Some(script);
+        }
+        Ok(headless)
+    }
+
+    /// Like `Runner::run_frame`.
+    pub fn run_frame(&mut self, keys: &[bool; 16]) -> Result<(), cpu::Error> {
+        while !self.step(keys)? {}
+        Ok(())
+    }
+
+    /// Like `Runner::step`, applying the cheats after each frame.
+    pub fn step(&mut self, keys: &[bool; 16]) -> Result<bool, cpu::Error> {
+        let frame_done = self.runner.step(&mut self.cpu, keys)?;
+        if frame_done {
+            self.cheats.apply(&mut self.cpu);
+        }
+        Ok(frame_done)
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.runner.frame()
+    }
+
+    /// Returns the current screen, where bit 0 of a pixel is the first plane and bit 1 the second plane.
+    pub fn framebuffer(&self) -> Frame {
+        let mut frame = Frame::default();
+        frame.copy_from(self.cpu.vmem());
+        frame
+    }
+
+    /// Returns the SHA-1 hash of the current screen content.
+    pub fn framebuffer_hash(&self) -> String {
+        rom::sha1_hex(&self.framebuffer().pixels)
+    }
+
+    /// Plays back the recorded input with a fixed random seed and returns the hash of the final screen.
+    pub fn replay(&mut self, recording: &Recording, seed: u64) -> Result<String, String> {
+        self.cpu.seed_rng(seed);
+        while self.frame() < recording.end() {
+            let keys = recording.keys(self.frame());
+            self.run_frame(&keys)
+                .map_err(|e| format!("Error in frame {}: {}", self.frame(), e))?;
+        }
+        Ok(self.framebuffer_hash())
+    }
+
+    /// Runs the given number of frames with a fixed random seed, without input unless a script presses keys.
+    pub fn run_for(&mut self, frames: u64, seed: u64) -> Result<(), String> {
+        self.cpu.seed_rng(seed);
+        #[allow(unused_mut)]
+        let mut keys = [false; 16];
+        for _ in 0..frames {
+            self.run_frame(&keys)
+                .map_err(|e| format!("Error in frame {}: {}", self.frame(), e))?;
+            #[cfg(feature = "scripting")]
+            self.run_script_frame(&mut keys)?;
+        }
+        Ok(())
+    }
+
+    /// Serves the metrics of the real time modes over HTTP on the address.
+    pub fn serve_metrics(&mut self, address: SocketAddr) -> Result<(), String> {
+        self.metrics = Some(Metrics::serve(address)?);
+        Ok(())
+    }
+
+    /// Runs in real time without input, streaming the screen to viewers connecting to the address.
+    pub fn stream(&mut self, address: SocketAddr, seed: u64) -> Result<(), String> {
+        let mut server = StreamServer::start(address)?;
+        server.set_metrics(self.metrics.clone());
+        self.run_realtime(seed, None, server)
+    }
+
+    /// Runs in real time on the framebuffer device, with the keys of the evdev keyboard if given.
+    pub fn run_on_framebuffer(
+        &mut self,
+        device: &Path,
+        keyboard: Option<&Path>,
+        seed: u64,
+    ) -> Result<(), String> {
+        let framebuffer = Framebuffer::open(device)?;
+        let mut keyboard = match keyboard {
+            Some(path) => Some(EvdevKeyboard::open(path, self.keymap)?),
+            None => None,
+        };
+        self.run_realtime(
+            seed,
+            keyboard
+                .as_mut()
+                .map(|keyboard| keyboard as &mut dyn InputSource),
+            framebuffer,
+        )
+    }
+
+    /// Runs frames at the original speed, presenting the screen on the output after each of them.
+    /// The output is moved to a render thread, so a slow output doesn't delay the next frames but
+    /// drops the screens it has no time for.
+    fn run_realtime<D: DisplayOutput + Send + 'static>(
+        &mut self,
+        seed: u64,
+        mut input: Option<&mut dyn InputSource>,
+        mut output: D,
+    ) -> Result<(), String> {
+        output.set_colors(self.colors);
+        let mut render = RenderThread::spawn("present", move |frame| output.present(frame))?;
+        self.cpu.seed_rng(seed);
+        let frame_time = Duration::from_secs(1) / Runner::FRAMES_PER_SECOND;
+        let mut next_frame = Instant::now();
+        // The start and first instruction of the second the instructions per second are measured in
+        let mut second = (Instant::now(), self.runner.instructions());
+        loop {
+            let keys = match &mut input {
+                Some(input) => input.poll(self.frame()).keys,
+                None => [false; 16],
+            };
+            self.run_frame(&keys)
+                .map_err(|e| format!("Error in frame {}: {}", self.frame(), e))?;
+            render.frame_mut()?.copy_from(self.cpu.vmem());
+            let not_presented = render.submit()?;
+            next_frame += frame_time;
+            let dropped = match next_frame.checked_duration_since(Instant::now()) {
+                Some(wait) => {
+                    thread::sleep(wait);
+                    0
+                }
+                // Don't try to catch up after falling behind
+                None => {
+                    let behind = Instant::now() - next_frame;
+                    next_frame = Instant::now();
+                    (behind.as_secs_f64() / frame_time.as_secs_f64()) as u64
+                }
+            };
+            if let Some(metrics) = &self.metrics {
+                metrics.add_frame(dropped + not_presented as u64);
+                let elapsed = second.0.elapsed();
+                if elapsed >= Duration::from_secs(1) {
+                    let instructions = self.runner.instructions() - second.1;
+                    metrics.set_instructions_per_second(
+                        (instructions as f64 / elapsed.as_secs_f64()) as u64,
+                    );
+                    second = (Instant::now(), self.runner.instructions());
+                }
+            }
+        }
+    }
+
+    /// Calls the frame hook of the script, printing its output and applying the keys it pressed.
+    #[cfg(feature = "scripting")]
+    fn run_script_frame(&mut self, keys: &mut [bool; 16]) -> Result<(), String> {
+        let script = match &mut self.script {
+            Some(script) => script,
+            None => return Ok(()),
+        };
+        let result = script.on_frame(&mut self.cpu);
+        for line in script.take_output() {
+            println!("{}", line);
+        }
+        for (key, pressed) in script.take_keys() {
+            keys[key] = pressed;
+        }
+        let frame = self.runner.frame();
+        result.map_err(|e| format!("Script error in frame {}: {}", frame, e))
+    }
+
+    /// Saves the current screen as image in the configured colors.
+    pub fn save_screenshot(&self, path: &Path) -> Result<(), String> {
+        let mut image = image::RgbImage::new(0, 0);
+        display::copy_screenshot(self.cpu.vmem(), &self.colors, &mut image);
+        image
+            .save(path)
+            .map_err(|e| format!("Cannot save {}: {}", path.display(), e))
+    }
+
+    /// Saves the current CPU state as p8s file.
+    pub fn save_state(&self, path: &Path) -> Result<(), String> {
+        let cpu_state = self.cpu.save_state()?;
+        let state_file = StateFile {
+            thumbnail: None,
+            cpu_state: &cpu_state,
+        };
+        fs::write(path, state_file.to_bytes())
+            .map_err(|e| format!("Cannot save {}: {}", path.display(), e))
+    }
+
+    /// Runs along the reference trace and prints how many instructions matched.
+    pub fn lockstep(&mut self, trace: &ReferenceTrace, seed: u64) -> Result<(), String> {
+        self.cpu.seed_rng(seed);
+        let speed = self.runner.speed();
+        let matched = trace.run(&mut self.cpu, speed)?;
+        println!("All {} instructions of the trace matched", matched);
+        Ok(())
+    }
+
+    /// Checks that saving and restoring the state keeps the emulation identical for the frames.
+    pub fn check_state(&mut self, frames: u64, seed: u64) -> Result<(), String> {
+        let speed = self.runner.speed();
+        state::check_round_trip(&mut self.cpu, frames, speed, seed)?;
+        println!("The state round trip is identical for {} frames", frames);
+        Ok(())
+    }
+
+    /// Runs the given number of frames and prints performance statistics.
+    pub fn bench(&mut self, frames: u32) -> Result<(), String> {
+        let keys = [false; 16];
+        let mut frame_times = Vec::with_capacity(frames as usize);
+        let first_instruction = self.runner.instructions();
+        let start = Instant::now();
+        for _ in 0..frames {
+            let frame_start = Instant::now();
+            self.run_frame(&keys)
+                .map_err(|e| format!("Error in frame {}: {}", self.frame(), e))?;
+            frame_times.push(frame_start.elapsed());
+        }
+        let total = start.elapsed();
+
+        frame_times.sort_unstable();
+        let percentile = |p: usize| {
+            frame_times
+                .get((frame_times.len() * p / 100).min(frame_times.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+        let instructions = self.runner.instructions() - first_instruction;
+        let micros = |d: Duration| d.as_secs_f64() * 1_000_000.0;
+
+        println!(
+            "{} frames, {} instructions in {:.3} s",
+            frames,
+            instructions,
+            total.as_secs_f64()
+        );
+        println!(
+            "{:.0} instructions/s ({:.1}x realtime)",
+            instructions as f64 / total.as_secs_f64(),
+            frames as f64 / Runner::FRAMES_PER_SECOND as f64 / total.as_secs_f64()
+        );
+        println!(
+            "frame time (us): min {:.1}, avg {:.1}, median {:.1}, p99 {:.1}, max {:.1}",
+            micros(percentile(0)),
+            micros(total) / frames.max(1) as f64,
+            micros(percentile(50)),
+            micros(percentile(99)),
+            micros(frame_times.last().copied().unwrap_or_default())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use pich8::replay::Recording;
+
+/// An event controlling the emulator rather than the emulated machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    TogglePause,
+    Reset,
+    Step,
+    QuickSave,
+    QuickLoad,
+}
+
+/// The input of a frame.
+#[derive(Debug, Default, PartialEq)]
+pub struct Input {
+    pub keys: [bool; 16],
+    /// Control events since the last poll
+    pub controls: Vec<Control>,
+}
+
+/// Provides the keypad and control input, so the emulator doesn't depend on where it comes from,
+/// e.g. the keyboard or a recording.
+pub trait InputSource {
+    fn poll(&mut self, frame: u64) -> Input;
+
+    /// Whether the source has no more input from the given frame on, e.g. the end of a recording.
+    fn finished(&self, _frame: u64) -> bool {
+        false
+    }
+}
+
+/// The keys pressed on the keyboard, by a remote client or a script.
+#[derive(Default)]
+pub struct Keypad {
+    keys: [bool; 16],
+    controls: Vec<Control>,
+}
+
+impl Keypad {
+    pub fn set(&mut self, key: usize, pressed: bool) {
+        self.keys[key] = pressed;
+    }
+
+    pub fn push(&mut self, control: Control) {
+        self.controls.push(control);
+    }
+}
+
+impl InputSource for Keypad {
+    fn poll(&mut self, _frame: u64) -> Input {
+        Input {
+            keys: self.keys,
+            controls: std::mem::take(&mut self.controls),
+        }
+    }
+}
+
+impl InputSource for Recording {
+    fn poll(&mut self, frame: u64) -> Input {
+        Input {
+            keys: self.keys(frame),
+            controls: Vec::new(),
+        }
+    }
+
+    fn finished(&self, frame: u64) -> bool {
+        frame >= self.end()
+    }
+}
+
+#[cfg(test)]
+mod input_test {
+    use super::*;
+
+    #[test]
+    fn test_keypad() {
+        let mut keypad = Keypad::default();
+        keypad.set(4, true);
+        keypad.push(Control::TogglePause);
+        let mut keys = [false; 16];
+        keys[4] = true;
+        assert_eq!(
+            keypad.poll(0),
+            Input {
+                keys,
+                controls: vec![Control::TogglePause]
+            }
+        );
+        // Control events are only polled once, held keys every frame
+        assert_eq!(keypad.poll(1).controls, vec![]);
+        assert_eq!(keypad.poll(2).keys, keys);
+    }
+}
@@ -1,34 +1,243 @@
 #![cfg_attr(not(any(test, debug_assertions)), windows_subsystem = "windows")]
+#![allow(clippy::upper_case_acronyms)]
 
-mod cpu;
+mod capture;
+mod cheats;
+mod chip8_archive;
+mod cli;
+mod commands;
+mod config;
+mod crash;
+mod debug_repl;
 mod dialog_handler;
 mod display;
 mod emulator;
+mod error;
+mod fbdev;
 mod fps_counter;
+mod frame_export;
+mod frame_limiter;
+mod game_db;
+mod gdb_stub;
 mod gui;
+mod headless;
+mod input;
+mod metrics;
+mod netplay;
+mod playlist;
+#[cfg(feature = "plugins")]
+mod plugin;
+mod recent;
+#[cfg(feature = "remote")]
+mod remote;
+mod render_thread;
 mod sound;
-mod video_memory;
+mod step_history;
+mod stream;
+mod verify;
 
+#[cfg(feature = "discord")]
+mod discord;
 #[cfg(feature = "rom-download")]
 mod rom_downloader;
+#[cfg(feature = "scripting")]
+mod scripting;
 
-use emulator::Emulator;
-use getopts::Options;
-use std::env;
-
-const OPT_VSYNC: &str = "vsync";
+use clap::Parser;
+use cli::Cli;
+use config::Config;
+use debug_repl::DebugRepl;
+use emulator::{Emulator, EmulatorBuilder};
+use error::Pich8Error;
+use gdb_stub::GdbStub;
+use gui::ColorPreset;
+use headless::Headless;
+use log::LevelFilter;
+use pich8::lockstep::ReferenceTrace;
+use pich8::replay::Recording;
+use pich8::rom;
+use pich8::symbols::Symbols;
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process,
+};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut opts = Options::new();
-    opts.optflag("", OPT_VSYNC, "Turn on vsync");
+    let cli = Cli::parse();
+    init_logger(cli.verbose);
+    if cli.portable || Config::portable_marker_exists() {
+        if let Err(msg) = Config::set_portable() {
+            log::warn!("{}", msg);
+        }
+    }
+    // Older versions stored everything in the config directory
+    Config::migrate_data(&["states", "recordings", "crashes"]);
+    let result = if cli.headless() {
+        run_headless(&cli).map_err(Pich8Error::Headless)
+    } else {
+        run(&cli)
+    };
+    if let Err(e) = result {
+        report_error(&e, !cli.headless());
+        process::exit(1);
+    }
+}
 
-    let mut vsync = false;
-    if let Ok(matches) = opts.parse(args) {
-        vsync = matches.opt_present(OPT_VSYNC);
+fn run_headless(cli: &Cli) -> Result<(), String> {
+    if let Some(address) = &cli.view {
+        let colors = cli
+            .palette
+            .unwrap_or(ColorPreset::Default)
+            .values()
+            .map(|color| color.map(|c| (c * 255.0) as u8));
+        return stream::view(address, &colors);
+    }
+    if let Some(command) = &cli.command {
+        return commands::run(command);
+    }
+    let mut headless = Headless::from_cli(cli)?;
+    if let Some(address) = cli.metrics {
+        headless.serve_metrics(address)?;
+    }
+    if cli.debug_repl {
+        let symbols = match cli.files.first().and_then(|path| Symbols::find(path)) {
+            Some(path) => Symbols::load(&path)?,
+            None => Symbols::default(),
+        };
+        let stdin = std::io::stdin();
+        DebugRepl::new(headless, symbols).run(stdin.lock(), std::io::stdout())?;
+    } else if let Some(port) = cli.gdb {
+        GdbStub::new(headless).serve(port)?;
+    } else if let Some(address) = cli.stream {
+        headless.stream(address, cli.seed)?;
+    } else if let Some(device) = &cli.fbdev {
+        headless.run_on_framebuffer(device, cli.evdev.as_deref(), cli.seed)?;
+    } else if let Some(frames) = cli.bench {
+        headless.bench(frames)?;
+    } else if let Some(path) = &cli.replay {
+        let recording = Recording::load(path)?;
+        let hash = headless.replay(&recording, cli.seed)?;
+        println!("{}", hash);
+        if let Some(expected) = &cli.expect {
+            if !hash.eq_ignore_ascii_case(expected) {
+                return Err(format!(
+                    "Replay mismatch: expected {}, got {}",
+                    expected, hash
+                ));
+            }
+        }
+    } else if let Some(path) = &cli.lockstep {
+        headless.lockstep(&ReferenceTrace::load(path)?, cli.seed)?;
+    } else if let Some(frames) = cli.run_for {
+        headless.run_for(frames, cli.seed)?;
+        if let Some(path) = &cli.screenshot {
+            headless.save_screenshot(path)?;
+        }
+        if let Some(path) = &cli.save_state {
+            headless.save_state(path)?;
+        }
+        if let Some(frames) = cli.check_state {
+            headless.check_state(frames, cli.seed)?;
+        }
+    }
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<(), Pich8Error> {
+    // Fail before opening the window if a given file doesn't exist
+    for file in cli
+        .files
+        .iter()
+        .chain(&cli.state)
+        .chain(&cli.patch)
+        .chain(&cli.play)
+        .filter(|file| file.as_path() != Path::new(rom::STDIN_PATH))
+    {
+        fs::metadata(file).map_err(|source| Pich8Error::File {
+            path: file.clone(),
+            source,
+        })?;
     }
 
     let event_loop = glium::glutin::event_loop::EventLoop::new();
-    let mut emu = Emulator::new(&event_loop, vsync).expect("Failed to create emulator");
-    event_loop.run(move |event, _, ctrl_flow| emu.handle_event(event, ctrl_flow));
+    let mut emu = EmulatorBuilder::from_cli(cli).build(&event_loop)?;
+    match (cli.files.as_slice(), &cli.patch) {
+        ([], _) => {
+            if let Some(n) = cli.recent {
+                emu.load_recent(n);
+            }
+        }
+        ([file], Some(patch)) => emu.load_patched(file, patch),
+        ([file], None) if file.as_path() == Path::new(rom::STDIN_PATH) => {
+            let data = rom::read(file).map_err(Pich8Error::Stdin)?;
+            emu.load_data(&data, None);
+        }
+        ([file], None) if !file.is_dir() => emu.load_file(file),
+        (files, _) => emu.start_playlist(files).map_err(Pich8Error::Playlist)?,
+    }
+    if let Some(state) = &cli.state {
+        emu.resume_state(state);
+    }
+    if let Some(recording) = &cli.play {
+        emu.play_recording(recording, cli.seed);
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &cli.script {
+        emu.load_script(script);
+    }
+    crash::install_hook();
+    event_loop.run(move |event, _, ctrl_flow| {
+        match panic::catch_unwind(AssertUnwindSafe(|| emu.handle_event(event, ctrl_flow))) {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                report_error(&e, true);
+                process::exit(1);
+            }
+            Err(_) => {
+                report_crash(&emu);
+                process::exit(101);
+            }
+        }
+    });
+}
+
+fn report_crash(emu: &Emulator) {
+    let message = match emu.write_crash_report() {
+        Ok(path) => format!(
+            "pich8 crashed, a report was written to {}. Please attach it when reporting the issue.",
+            path.display()
+        ),
+        Err(msg) => format!("pich8 crashed and the report couldn't be written: {}", msg),
+    };
+    log::error!("{}", message);
+    tinyfiledialogs::message_box_ok("pich8", &message, tinyfiledialogs::MessageBoxIcon::Error);
+}
+
+fn init_logger(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    // Only our own messages get more verbose, dependencies stay at warnings
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Warn)
+        .filter_module(env!("CARGO_CRATE_NAME"), level)
+        .parse_default_env()
+        .init();
+}
+
+/// Reports a fatal error on stderr and optionally in a message box, as release builds on Windows have no console.
+fn report_error(error: &Pich8Error, dialog: bool) {
+    log::error!("{}", error);
+    if dialog {
+        tinyfiledialogs::message_box_ok(
+            "pich8",
+            &error.to_string(),
+            tinyfiledialogs::MessageBoxIcon::Error,
+        );
+    }
 }
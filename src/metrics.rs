@@ -0,0 +1,162 @@
+use log::{info, warn};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Runtime metrics of a headless instance, served over HTTP in the Prometheus text format,
+/// so long-running setups can be monitored.
+#[derive(Default)]
+pub struct Metrics {
+    frames: AtomicU64,
+    instructions_per_second: AtomicU64,
+    dropped_frames: AtomicU64,
+    clients: AtomicUsize,
+}
+
+impl Metrics {
+    /// A client not sending its request for this long is disconnected
+    const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Listens on the address, requests are answered by a background thread.
+    pub fn serve(address: SocketAddr) -> Result<Arc<Self>, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        info!("Serving metrics on http://{}/metrics", address);
+        let metrics = Arc::new(Self::default());
+        let serving = Arc::clone(&metrics);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.set_read_timeout(Some(Self::READ_TIMEOUT));
+                let result = stream
+                    .try_clone()
+                    .and_then(|reader| serving.respond(BufReader::new(reader), &stream));
+                if let Err(e) = result {
+                    warn!("Cannot answer metrics request: {}", e);
+                }
+            }
+        });
+        Ok(metrics)
+    }
+
+    /// Counts an emulated frame, and the frames skipped because the emulation fell behind or the
+    /// output was too slow to present them.
+    pub fn add_frame(&self, dropped: u64) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.dropped_frames.fetch_add(dropped, Ordering::Relaxed);
+    }
+
+    pub fn set_instructions_per_second(&self, instructions: u64) {
+        self.instructions_per_second
+            .store(instructions, Ordering::Relaxed);
+    }
+
+    pub fn set_clients(&self, clients: usize) {
+        self.clients.store(clients, Ordering::Relaxed);
+    }
+
+    fn render(&self) -> String {
+        let metrics = [
+            (
+                "pich8_frames_total",
+                "counter",
+                "Frames emulated",
+                self.frames.load(Ordering::Relaxed),
+            ),
+            (
+                "pich8_instructions_per_second",
+                "gauge",
+                "Instructions executed within the last second",
+                self.instructions_per_second.load(Ordering::Relaxed),
+            ),
+            (
+                "pich8_dropped_frames_total",
+                "counter",
+                "Frames skipped because the emulation fell behind",
+                self.dropped_frames.load(Ordering::Relaxed),
+            ),
+            (
+                "pich8_clients",
+                "gauge",
+                "Connected viewers",
+                self.clients.load(Ordering::Relaxed) as u64,
+            ),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = write!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n",
+                name = name,
+                help = help,
+                kind = kind,
+                value = value
+            );
+        }
+        text
+    }
+
+    /// Reads an HTTP request and answers `GET /metrics` (or `/`) with the metrics.
+    fn respond(&self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        // Skip the headers up to the empty line
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+            header.clear();
+        }
+        let mut parts = request.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics" | "/")) => ("200 OK", self.render()),
+            (Some("GET"), Some(_)) => ("404 Not Found", String::from("Not found\n")),
+            _ => (
+                "405 Method Not Allowed",
+                String::from("Method not allowed\n"),
+            ),
+        };
+        write!(
+            writer,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+}
+
+#[cfg(test)]
+mod metrics_test {
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let metrics = Metrics::default();
+        metrics.add_frame(0);
+        metrics.add_frame(2);
+        metrics.set_instructions_per_second(720);
+        metrics.set_clients(1);
+
+        let mut response = Vec::new();
+        let request = "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        metrics.respond(request.as_bytes(), &mut response).unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\npich8_frames_total 2\n"));
+        assert!(response.contains("\npich8_instructions_per_second 720\n"));
+        assert!(response.contains("\npich8_dropped_frames_total 2\n"));
+        assert!(response.ends_with("\npich8_clients 1\n"));
+
+        let mut response = Vec::new();
+        metrics
+            .respond("GET /other HTTP/1.1\r\n\r\n".as_bytes(), &mut response)
+            .unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,296 @@
+use log::info;
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+const HELLO: u8 = 0;
+const INPUT: u8 = 1;
+
+enum Event {
+    Connected(TcpStream),
+    Hello { rom_hash: String, speed: u32 },
+    Input { frame: u32, keys: u16 },
+    Closed(String),
+}
+
+/// Shares the keypad with another instance over TCP, so two players can play on one emulated machine.
+///
+/// Both instances restart the ROM when connecting and run in lockstep: a frame only runs once the
+/// keys of both players are known, and pressed keys take effect `INPUT_DELAY` frames later
+/// to hide the network latency.
+pub struct Netplay {
+    events: Receiver<Event>,
+    stream: Option<TcpStream>,
+    /// ROM hash and speed of this instance, set when the session starts
+    local_hello: Option<(String, u32)>,
+    peer_hello: Option<(String, u32)>,
+    /// Next frame to run
+    frame: u32,
+    local: VecDeque<u16>,
+    remote: VecDeque<u16>,
+}
+
+impl Netplay {
+    /// Number of frames between pressing a key and the key taking effect
+    const INPUT_DELAY: usize = 3;
+
+    fn with_events(events: Receiver<Event>) -> Self {
+        Self {
+            events,
+            stream: None,
+            local_hello: None,
+            peer_hello: None,
+            frame: 0,
+            local: VecDeque::new(),
+            remote: VecDeque::new(),
+        }
+    }
+
+    /// Waits in the background for a peer connecting to the port. Unlike the other servers, this
+    /// listens on all interfaces, since the peer is on another machine.
+    pub fn host(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Cannot listen on port {}: {}", port, e))?;
+        info!("Waiting for netplay peer on port {}", port);
+        let (tx, rx) = channel();
+        thread::spawn(move || match listener.accept() {
+            Ok((stream, _)) => Self::receive(stream, tx),
+            Err(e) => drop(tx.send(Event::Closed(e.to_string()))),
+        });
+        Ok(Self::with_events(rx))
+    }
+
+    /// Connects to a hosting peer in the background.
+    pub fn connect(address: String) -> Self {
+        let (tx, rx) = channel();
+        thread::spawn(move || match TcpStream::connect(&address) {
+            Ok(stream) => Self::receive(stream, tx),
+            Err(e) => drop(tx.send(Event::Closed(format!(
+                "Cannot connect to {}: {}",
+                address, e
+            )))),
+        });
+        Self::with_events(rx)
+    }
+
+    fn receive(mut stream: TcpStream, events: Sender<Event>) {
+        let _ = stream.set_nodelay(true);
+        let connected = stream
+            .try_clone()
+            .map(|writer| events.send(Event::Connected(writer)));
+        if connected.is_err() {
+            return;
+        }
+        loop {
+            let event =
+                Self::read_event(&mut stream).unwrap_or_else(|e| Event::Closed(e.to_string()));
+            let closed = matches!(event, Event::Closed(_));
+            if events.send(event).is_err() || closed {
+                return;
+            }
+        }
+    }
+
+    fn read_event(stream: &mut impl Read) -> std::io::Result<Event> {
+        let mut tag = [0; 1];
+        stream.read_exact(&mut tag)?;
+        match tag[0] {
+            HELLO => {
+                let mut header = [0; 5];
+                stream.read_exact(&mut header)?;
+                let mut rom_hash = vec![0; header[4] as usize];
+                stream.read_exact(&mut rom_hash)?;
+                Ok(Event::Hello {
+                    rom_hash: String::from_utf8_lossy(&rom_hash).into_owned(),
+                    speed: u32::from_be_bytes([header[0], header[1], header[2], header[3]]),
+                })
+            }
+            INPUT => {
+                let mut input = [0; 6];
+                stream.read_exact(&mut input)?;
+                Ok(Event::Input {
+                    frame: u32::from_be_bytes([input[0], input[1], input[2], input[3]]),
+                    keys: u16::from_be_bytes([input[4], input[5]]),
+                })
+            }
+            tag => Ok(Event::Closed(format!("Invalid netplay message {}", tag))),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), String> {
+        match &mut self.stream {
+            Some(stream) => stream
+                .write_all(message)
+                .map_err(|e| format!("Netplay connection lost: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Handles the received messages, returns true once the peer connected,
+    /// which is when the ROM should be restarted and `start` be called.
+    pub fn poll(&mut self) -> Result<bool, String> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Connected(stream) => {
+                    self.stream = Some(stream);
+                    return Ok(true);
+                }
+                Event::Hello { rom_hash, speed } => {
+                    self.peer_hello = Some((rom_hash, speed));
+                    self.check_hello()?;
+                }
+                Event::Input { frame, keys } => {
+                    let expected = self.frame as usize + self.remote.len();
+                    if frame as usize != expected {
+                        return Err(format!(
+                            "Netplay out of sync: expected frame {}, got {}",
+                            expected, frame
+                        ));
+                    }
+                    self.remote.push_back(keys);
+                }
+                Event::Closed(msg) => return Err(format!("Netplay connection lost: {}", msg)),
+            }
+        }
+        Ok(false)
+    }
+
+    fn check_hello(&self) -> Result<(), String> {
+        match (&self.local_hello, &self.peer_hello) {
+            (Some((local_hash, _)), Some((peer_hash, _))) if local_hash != peer_hash => {
+                Err("Netplay peer runs a different ROM!".to_string())
+            }
+            (Some((_, local_speed)), Some((_, peer_speed))) if local_speed != peer_speed => {
+                Err(format!(
+                    "Netplay peer runs at {} Hz instead of {} Hz!",
+                    peer_speed, local_speed
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts the session from frame 0 with the restarted ROM.
+    pub fn start(&mut self, rom_hash: &str, speed: u32) -> Result<(), String> {
+        let mut hello = vec![HELLO];
+        hello.extend_from_slice(&speed.to_be_bytes());
+        hello.push(rom_hash.len() as u8);
+        hello.extend_from_slice(rom_hash.as_bytes());
+        self.send(&hello)?;
+        self.local_hello = Some((rom_hash.to_string(), speed));
+        self.check_hello()?;
+        // Both players press nothing in the frames before the first keys take effect
+        self.frame = 0;
+        self.local = vec![0; Self::INPUT_DELAY].into();
+        self.remote = vec![0; Self::INPUT_DELAY].into();
+        Ok(())
+    }
+
+    /// Returns the keys of both players for the next frame, or `None` if the frame must wait
+    /// for the peer. `keys` are this player's keys, sent to take effect after the input delay.
+    pub fn frame(&mut self, keys: &[bool; 16]) -> Result<Option<[bool; 16]>, String> {
+        if self.local_hello.is_none() || self.peer_hello.is_none() {
+            return Ok(None);
+        }
+        if self.local.len() <= Self::INPUT_DELAY {
+            let mask = keys
+                .iter()
+                .enumerate()
+                .fold(0, |mask, (idx, &pressed)| mask | (pressed as u16) << idx);
+            let frame = self.frame + self.local.len() as u32;
+            let mut input = vec![INPUT];
+            input.extend_from_slice(&frame.to_be_bytes());
+            input.extend_from_slice(&mask.to_be_bytes());
+            self.send(&input)?;
+            self.local.push_back(mask);
+        }
+        if self.remote.is_empty() {
+            return Ok(None);
+        }
+        let mask = self.local.pop_front().unwrap() | self.remote.pop_front().unwrap();
+        self.frame += 1;
+        let mut merged = [false; 16];
+        for (idx, key) in merged.iter_mut().enumerate() {
+            *key = mask >> idx & 1 == 1;
+        }
+        Ok(Some(merged))
+    }
+}
+
+#[cfg(test)]
+mod netplay_test {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_for_frame(netplay: &mut Netplay, keys: &[bool; 16]) -> [bool; 16] {
+        let start = Instant::now();
+        loop {
+            netplay.poll().unwrap();
+            if let Some(keys) = netplay.frame(keys).unwrap() {
+                return keys;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Peer input missing"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn connect() -> (Netplay, Netplay) {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let mut host = Netplay::host(port).unwrap();
+        let mut guest = Netplay::connect(format!("127.0.0.1:{}", port));
+        for netplay in [&mut host, &mut guest] {
+            while !netplay.poll().unwrap() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        (host, guest)
+    }
+
+    #[test]
+    fn test_lockstep() {
+        let (mut host, mut guest) = connect();
+        host.start("abc", 720).unwrap();
+        guest.start("abc", 720).unwrap();
+
+        let mut host_keys = [false; 16];
+        host_keys[1] = true;
+        let mut guest_keys = [false; 16];
+        guest_keys[0xC] = true;
+        // The keys take effect after the input delay
+        for _ in 0..Netplay::INPUT_DELAY {
+            assert_eq!(wait_for_frame(&mut host, &host_keys), [false; 16]);
+            assert_eq!(wait_for_frame(&mut guest, &guest_keys), [false; 16]);
+        }
+        let mut merged = [false; 16];
+        merged[1] = true;
+        merged[0xC] = true;
+        assert_eq!(wait_for_frame(&mut host, &host_keys), merged);
+        assert_eq!(wait_for_frame(&mut guest, &guest_keys), merged);
+    }
+
+    #[test]
+    fn test_different_rom() {
+        let (mut host, mut guest) = connect();
+        host.start("abc", 720).unwrap();
+        guest.start("def", 720).unwrap();
+        let start = Instant::now();
+        loop {
+            if let Err(msg) = host.poll() {
+                assert_eq!(msg, "Netplay peer runs a different ROM!");
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
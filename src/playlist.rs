@@ -0,0 +1,97 @@
+use pich8::rom;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// A list of ROMs which are cycled through, e.g. for unattended demos.
+pub struct Playlist {
+    files: Vec<PathBuf>,
+    current: usize,
+    interval: Option<Duration>,
+    started: Instant,
+}
+
+impl Playlist {
+    /// Creates a playlist from the given files, directories are replaced by the ROMs they contain.
+    pub fn new(paths: &[PathBuf], interval: Option<Duration>) -> Result<Self, String> {
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                files.extend(Self::roms_in_dir(path)?);
+            } else {
+                files.push(path.clone());
+            }
+        }
+        if files.is_empty() {
+            return Err("The playlist doesn't contain any ROMs!".to_string());
+        }
+        Ok(Self {
+            files,
+            current: 0,
+            interval,
+            started: Instant::now(),
+        })
+    }
+
+    fn roms_in_dir(dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let entries =
+            fs::read_dir(dir).map_err(|e| format!("Cannot open {}: {}", dir.display(), e))?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| rom::has_rom_extension(path))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn current(&self) -> &Path {
+        &self.files[self.current]
+    }
+
+    /// Moves to the next (or previous) file, wrapping around at the end.
+    pub fn advance(&mut self, forward: bool) -> &Path {
+        self.current = if forward {
+            (self.current + 1) % self.files.len()
+        } else {
+            (self.current + self.files.len() - 1) % self.files.len()
+        };
+        self.restart_timer();
+        self.current()
+    }
+
+    pub fn restart_timer(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Returns whether it's time to switch to the next file.
+    pub fn is_due(&self) -> bool {
+        self.interval
+            .is_some_and(|interval| self.started.elapsed() >= interval)
+    }
+}
+
+#[cfg(test)]
+mod playlist_test {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let files = vec![PathBuf::from("a.ch8"), PathBuf::from("b.ch8")];
+        let mut playlist = Playlist::new(&files, None).unwrap();
+        assert_eq!(playlist.current(), Path::new("a.ch8"));
+        assert_eq!(playlist.advance(true), Path::new("b.ch8"));
+        assert_eq!(playlist.advance(true), Path::new("a.ch8"));
+        assert_eq!(playlist.advance(false), Path::new("b.ch8"));
+        assert!(!playlist.is_due());
+
+        let playlist = Playlist::new(&files, Some(Duration::ZERO)).unwrap();
+        assert!(playlist.is_due());
+        assert!(Playlist::new(&[], None).is_err());
+    }
+}
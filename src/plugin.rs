@@ -0,0 +1,171 @@
+//! Outputs loaded from shared libraries, so LED matrices, flip-dot displays or MIDI beepers can be
+//! driven without changing pich8. A plugin exports these C functions, of which the ones marked
+//! optional are only called if present:
+//!
+//! ```c
+//! uint32_t pich8_plugin_abi_version(void);             // returns 1
+//! void *pich8_plugin_open(const char *args);           // returns NULL on failure
+//! void pich8_plugin_close(void *plugin);
+//! // Optional: the screen as RGB pixels whenever it changed
+//! void pich8_plugin_present(void *plugin, const uint8_t *rgb, uint32_t width, uint32_t height);
+//! // Optional: called for every frame the sound timer is active, with the 16 byte XO-CHIP
+//! // pattern or NULL for the default beep
+//! void pich8_plugin_sound(void *plugin, const uint8_t *pattern);
+//! void pich8_plugin_pause(void *plugin, int paused);     // Optional
+//! void pich8_plugin_set_volume(void *plugin, float volume); // Optional, 0 - 1
+//! ```
+//!
+//! All functions are called from the emulation thread.
+use crate::display::{DisplayOutput, Frame};
+use crate::sound::SoundOutput;
+use libloading::Library;
+use log::info;
+use pich8::video_memory::VideoMemory;
+use std::{
+    ffi::{c_void, CString},
+    os::raw::{c_char, c_float, c_int},
+    path::Path,
+};
+
+type OpenFn = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type CloseFn = unsafe extern "C" fn(*mut c_void);
+type PresentFn = unsafe extern "C" fn(*mut c_void, *const u8, u32, u32);
+type SoundFn = unsafe extern "C" fn(*mut c_void, *const u8);
+type PauseFn = unsafe extern "C" fn(*mut c_void, c_int);
+type SetVolumeFn = unsafe extern "C" fn(*mut c_void, c_float);
+
+pub struct Plugin {
+    handle: *mut c_void,
+    close: CloseFn,
+    present: Option<PresentFn>,
+    sound: Option<SoundFn>,
+    pause: Option<PauseFn>,
+    set_volume: Option<SetVolumeFn>,
+    colors: [[u8; 3]; 4],
+    // Declared last, so it's only unloaded after the functions above aren't used anymore
+    _library: Library,
+}
+
+impl Plugin {
+    const ABI_VERSION: u32 = 1;
+
+    /// Loads a plugin given as `PATH` or `PATH=ARGS`, the arguments are passed to the plugin.
+    pub fn load(spec: &str) -> Result<Self, String> {
+        let (path, args) = spec.split_once('=').unwrap_or((spec, ""));
+        let path = Path::new(path);
+        let error = |msg: String| format!("Cannot load plugin {}: {}", path.display(), msg);
+        let args = CString::new(args).map_err(|e| error(e.to_string()))?;
+        // Safety: the library is trusted like the emulator itself, and the symbols have the types
+        // documented above
+        unsafe {
+            let library = Library::new(path).map_err(|e| error(e.to_string()))?;
+            let abi_version = library
+                .get::<unsafe extern "C" fn() -> u32>(b"pich8_plugin_abi_version")
+                .map_err(|e| error(e.to_string()))?();
+            if abi_version != Self::ABI_VERSION {
+                return Err(error(format!("unsupported ABI version {}", abi_version)));
+            }
+            let open = *library
+                .get::<OpenFn>(b"pich8_plugin_open")
+                .map_err(|e| error(e.to_string()))?;
+            let close = *library
+                .get::<CloseFn>(b"pich8_plugin_close")
+                .map_err(|e| error(e.to_string()))?;
+            let present = library.get::<PresentFn>(b"pich8_plugin_present").ok();
+            let sound = library.get::<SoundFn>(b"pich8_plugin_sound").ok();
+            let pause = library.get::<PauseFn>(b"pich8_plugin_pause").ok();
+            let set_volume = library.get::<SetVolumeFn>(b"pich8_plugin_set_volume").ok();
+            let (present, sound, pause, set_volume) = (
+                present.map(|f| *f),
+                sound.map(|f| *f),
+                pause.map(|f| *f),
+                set_volume.map(|f| *f),
+            );
+            let handle = open(args.as_ptr());
+            if handle.is_null() {
+                return Err(error("opening failed".to_string()));
+            }
+            info!("Loaded plugin {}", path.display());
+            Ok(Self {
+                handle,
+                close,
+                present,
+                sound,
+                pause,
+                set_volume,
+                colors: [[0; 3]; 4],
+                _library: library,
+            })
+        }
+    }
+
+    /// Passes the screen to the plugin, if it's a display.
+    pub fn present_vmem(&self, vmem: &VideoMemory) {
+        if let Some(present) = self.present {
+            let image = self.screenshot(vmem);
+            unsafe { present(self.handle, image.as_ptr(), image.width(), image.height()) };
+        }
+    }
+}
+
+impl DisplayOutput for Plugin {
+    fn colors(&self) -> [[u8; 3]; 4] {
+        self.colors
+    }
+
+    fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+        self.colors = colors;
+    }
+
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        if let Some(present) = self.present {
+            let colors = self.colors;
+            let image: Vec<u8> = frame
+                .pixels
+                .iter()
+                .flat_map(|&value| colors[value as usize & 3])
+                .collect();
+            let (width, height) = (frame.width as u32, frame.height as u32);
+            unsafe { present(self.handle, image.as_ptr(), width, height) };
+        }
+        Ok(())
+    }
+}
+
+impl SoundOutput for Plugin {
+    fn beep(&self) {
+        if let Some(sound) = self.sound {
+            unsafe { sound(self.handle, std::ptr::null()) };
+        }
+    }
+
+    fn play_buffer(&self, buf: [u8; 16]) {
+        if let Some(sound) = self.sound {
+            unsafe { sound(self.handle, buf.as_ptr()) };
+        }
+    }
+
+    fn pause(&self) {
+        if let Some(pause) = self.pause {
+            unsafe { pause(self.handle, 1) };
+        }
+    }
+
+    fn resume(&self) {
+        if let Some(pause) = self.pause {
+            unsafe { pause(self.handle, 0) };
+        }
+    }
+
+    fn set_volume(&self, volume: f32) {
+        if let Some(set_volume) = self.set_volume {
+            unsafe { set_volume(self.handle, volume) };
+        }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        unsafe { (self.close)(self.handle) };
+    }
+}
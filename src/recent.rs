@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Keeps track of the most recently opened files and persists them between sessions.
+#[derive(Default, Serialize, Deserialize)]
+pub struct RecentFiles {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    const FILE_NAME: &'static str = "recent.toml";
+    const MAX_ENTRIES: usize = 10;
+
+    /// Loads the list from the given directory, a missing or broken file results in an empty list.
+    pub fn load(dir: Option<&Path>) -> Self {
+        let path = dir.map(|dir| dir.join(Self::FILE_NAME));
+        let mut recent: Self = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+        recent.path = path;
+        recent
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Moves the file to the top of the list and saves it.
+    pub fn add(&mut self, file: &Path) {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        self.files.retain(|f| *f != file);
+        self.files.insert(0, file);
+        self.files.truncate(Self::MAX_ENTRIES);
+        self.save();
+    }
+
+    fn save(&self) {
+        // Failing to persist the list isn't worth bothering the user
+        if let Some(path) = &self.path {
+            if let Ok(content) = toml::to_string(self) {
+                if let Some(dir) = path.parent() {
+                    let _ = fs::create_dir_all(dir);
+                }
+                let _ = fs::write(path, content);
+            }
+        }
+    }
+}
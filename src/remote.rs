@@ -0,0 +1,438 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+use tungstenite::Message;
+
+#[cfg(unix)]
+use serde_json::Value;
+#[cfg(unix)]
+use std::{
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+};
+
+/// A command sent by a remote client as JSON text message, e.g. `{"cmd": "press", "key": 5}`.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum RemoteCommand {
+    Load { path: PathBuf },
+    Pause,
+    Resume,
+    TogglePause,
+    Step,
+    State,
+    Press { key: usize },
+    Release { key: usize },
+    Screenshot,
+}
+
+/// The registers and timers sent for the `state` command.
+#[derive(Serialize)]
+pub struct RemoteState {
+    pub paused: bool,
+    pub rom: Option<String>,
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub dt: u8,
+    pub st: u8,
+    pub sp: usize,
+    pub stack: [u16; 16],
+    pub keys: [bool; 16],
+}
+
+pub enum RemoteResponse {
+    Ok,
+    Error(String),
+    State(RemoteState),
+    /// The screen as PNG, sent as binary message
+    Png(Vec<u8>),
+}
+
+/// A command waiting for the emulator, which replies through `reply`.
+pub struct RemoteRequest {
+    pub command: RemoteCommand,
+    reply: Sender<RemoteResponse>,
+}
+
+impl RemoteRequest {
+    pub fn reply(self, response: RemoteResponse) {
+        // The client may have disconnected in the meantime
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Lets external tools drive the emulator, e.g. test harnesses, dashboards, editor plugins or build scripts.
+///
+/// Over WebSocket, every text message is a command, answered with `{"ok": true}`,
+/// `{"ok": false, "error": "..."}` or `{"ok": true, "state": {...}}`, except `screenshot`,
+/// which is answered with a binary PNG.
+/// Over the unix socket, every line is a JSON-RPC 2.0 request whose method is the command,
+/// answered with a line holding the response.
+/// The named pipe takes plain commands like `pause` or `load rom.ch8` line by line, e.g. from
+/// window manager key bindings, and isn't answered.
+/// The emulator handles the commands of all clients between frames.
+pub struct RemoteServer {
+    requests: Receiver<RemoteRequest>,
+    sender: Sender<RemoteRequest>,
+}
+
+impl RemoteServer {
+    /// Error code of JSON-RPC requests the emulator couldn't execute
+    #[cfg(unix)]
+    const RPC_COMMAND_FAILED: i64 = -32000;
+
+    pub fn new() -> Self {
+        let (sender, requests) = channel();
+        Self { requests, sender }
+    }
+
+    /// Listens for WebSocket clients on the port of localhost, connections are served by background threads.
+    pub fn listen(&self, port: u16) -> Result<(), String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Cannot listen on port {}: {}", port, e))?;
+        info!("Remote control listening on ws://127.0.0.1:{}", port);
+        let tx = self.sender.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    if let Err(e) = Self::serve(stream, tx) {
+                        warn!("Remote connection closed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Listens for JSON-RPC clients on the unix socket, replacing a socket left over by a previous instance.
+    #[cfg(unix)]
+    pub fn listen_rpc(&self, path: &Path) -> Result<(), String> {
+        if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            let _ = std::fs::remove_file(path);
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("Cannot listen on {}: {}", path.display(), e))?;
+        info!("JSON-RPC listening on {}", path.display());
+        let tx = self.sender.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    if let Err(e) = Self::serve_rpc(stream, tx) {
+                        warn!("JSON-RPC connection closed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Reads commands from the named pipe, which is created unless it exists.
+    #[cfg(unix)]
+    pub fn listen_pipe(&self, path: &Path) -> Result<(), String> {
+        let error = |msg: String| format!("Cannot open {}: {}", path.display(), msg);
+        match fs::metadata(path) {
+            Ok(meta) if !meta.file_type().is_fifo() => {
+                return Err(error("not a named pipe".to_string()))
+            }
+            Ok(_) => (),
+            Err(_) => {
+                let c_path =
+                    CString::new(path.as_os_str().as_bytes()).map_err(|e| error(e.to_string()))?;
+                // Safety: the path is a valid C string
+                if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                    return Err(error(std::io::Error::last_os_error().to_string()));
+                }
+            }
+        }
+        // Also opened for writing, so reading doesn't end when a writer closes the pipe
+        let pipe = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| error(e.to_string()))?;
+        info!("Reading commands from {}", path.display());
+        let tx = self.sender.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let result =
+                    Self::parse_pipe(&line).and_then(|(command, screenshot)| match Self::request(
+                        command, &tx,
+                    )? {
+                        RemoteResponse::Error(msg) => Err(msg),
+                        RemoteResponse::Png(png) => {
+                            let path = screenshot.unwrap_or_default();
+                            fs::write(&path, png)
+                                .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+                        }
+                        _ => Ok(()),
+                    });
+                if let Err(msg) = result {
+                    warn!("Pipe command '{}' failed: {}", line.trim(), msg);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Parses a command of the named pipe, e.g. `press 5`, and for `screenshot PATH` the path to
+    /// save the screen to.
+    #[cfg(unix)]
+    fn parse_pipe(line: &str) -> Result<(RemoteCommand, Option<PathBuf>), String> {
+        let line = line.trim();
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (line, None),
+        };
+        let key = |argument: Option<&str>| {
+            argument
+                .and_then(|key| usize::from_str_radix(key, 16).ok())
+                .ok_or_else(|| format!("Invalid key in '{}'", line))
+        };
+        let command = match (name, argument) {
+            ("load", Some(path)) => RemoteCommand::Load { path: path.into() },
+            ("pause", None) => RemoteCommand::Pause,
+            ("resume", None) => RemoteCommand::Resume,
+            ("toggle_pause", None) => RemoteCommand::TogglePause,
+            ("step", None) => RemoteCommand::Step,
+            ("press", _) => RemoteCommand::Press {
+                key: key(argument)?,
+            },
+            ("release", _) => RemoteCommand::Release {
+                key: key(argument)?,
+            },
+            ("screenshot", Some(path)) => {
+                return Ok((RemoteCommand::Screenshot, Some(path.into())))
+            }
+            _ => return Err(format!("Unknown command '{}'", line)),
+        };
+        Ok((command, None))
+    }
+
+    /// Passes the command to the emulator and waits for its response.
+    fn request(
+        command: RemoteCommand,
+        requests: &Sender<RemoteRequest>,
+    ) -> Result<RemoteResponse, String> {
+        let (reply, response) = channel();
+        requests
+            .send(RemoteRequest { command, reply })
+            .map_err(|_| "The emulator exited")?;
+        Ok(response.recv().map_err(|_| "The emulator exited")?)
+    }
+
+    #[cfg(unix)]
+    fn serve_rpc(stream: UnixStream, requests: Sender<RemoteRequest>) -> Result<(), String> {
+        let mut writer = stream.try_clone().map_err(|e| e.to_string())?;
+        for line in BufReader::new(stream).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match Self::parse_rpc(&line) {
+                Ok((id, command)) => {
+                    let response = Self::rpc_response(Self::request(command, &requests)?);
+                    // Notifications without id aren't answered
+                    id.map(|id| Self::rpc_message(id, response))
+                }
+                Err((id, error)) => Some(Self::rpc_message(id, Err(error))),
+            };
+            if let Some(response) = response {
+                writeln!(writer, "{}", response).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the id and command of a JSON-RPC request, e.g.
+    /// `{"jsonrpc": "2.0", "id": 1, "method": "press", "params": {"key": 5}}`,
+    /// or the id and error to respond with.
+    #[cfg(unix)]
+    fn parse_rpc(line: &str) -> Result<(Option<Value>, RemoteCommand), (Value, Value)> {
+        let error = |code: i64, message: String| json!({ "code": code, "message": message });
+        let request: Value = serde_json::from_str(line)
+            .map_err(|e| (Value::Null, error(-32700, format!("Parse error: {}", e))))?;
+        let id = request.get("id").cloned();
+        let invalid = |message: &str| {
+            (
+                id.clone().unwrap_or(Value::Null),
+                error(-32600, message.to_string()),
+            )
+        };
+        if request.get("jsonrpc") != Some(&json!("2.0")) {
+            return Err(invalid("Invalid request: jsonrpc must be \"2.0\""));
+        }
+        let method = match request.get("method") {
+            Some(Value::String(method)) => method,
+            _ => return Err(invalid("Invalid request: method is missing")),
+        };
+        let mut command = match request.get("params") {
+            Some(Value::Object(params)) => params.clone(),
+            None => serde_json::Map::new(),
+            Some(_) => return Err(invalid("Invalid request: params must be an object")),
+        };
+        command.insert("cmd".to_string(), Value::String(method.clone()));
+        let command = serde_json::from_value(Value::Object(command)).map_err(|e| {
+            let (code, message) = if e.to_string().starts_with("unknown variant") {
+                (-32601, format!("Method not found: {}", method))
+            } else {
+                (-32602, format!("Invalid params: {}", e))
+            };
+            (id.clone().unwrap_or(Value::Null), error(code, message))
+        })?;
+        Ok((id, command))
+    }
+
+    #[cfg(unix)]
+    fn rpc_response(response: RemoteResponse) -> Result<Value, Value> {
+        use base64::Engine;
+        match response {
+            RemoteResponse::Ok => Ok(Value::Bool(true)),
+            RemoteResponse::Error(msg) => {
+                Err(json!({ "code": Self::RPC_COMMAND_FAILED, "message": msg }))
+            }
+            RemoteResponse::State(state) => serde_json::to_value(state)
+                .map_err(|e| json!({ "code": Self::RPC_COMMAND_FAILED, "message": e.to_string() })),
+            // The screen as base64 encoded PNG
+            RemoteResponse::Png(png) => Ok(Value::String(
+                base64::engine::general_purpose::STANDARD.encode(png),
+            )),
+        }
+    }
+
+    #[cfg(unix)]
+    fn rpc_message(id: Value, response: Result<Value, Value>) -> Value {
+        match response {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        }
+    }
+
+    fn serve(stream: TcpStream, requests: Sender<RemoteRequest>) -> Result<(), String> {
+        let mut socket = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+        loop {
+            let text = match socket.read().map_err(|e| e.to_string())? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Ok(()),
+                // Pings are answered by tungstenite
+                _ => continue,
+            };
+            let response = match serde_json::from_str(&text) {
+                Ok(command) => Self::request(command, &requests)?,
+                Err(e) => RemoteResponse::Error(format!("Invalid command: {}", e)),
+            };
+            let message = match response {
+                RemoteResponse::Ok => Message::text(r#"{"ok":true}"#),
+                RemoteResponse::Error(msg) => {
+                    Message::text(json!({ "ok": false, "error": msg }).to_string())
+                }
+                RemoteResponse::State(state) => {
+                    Message::text(json!({ "ok": true, "state": state }).to_string())
+                }
+                RemoteResponse::Png(png) => Message::binary(png),
+            };
+            socket.send(message).map_err(|e| e.to_string())?;
+        }
+    }
+
+    /// Returns the next command waiting for the emulator, if any.
+    pub fn poll(&self) -> Option<RemoteRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod remote_test {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        let parse = |json: &str| serde_json::from_str::<RemoteCommand>(json);
+        assert_eq!(parse(r#"{"cmd": "pause"}"#).unwrap(), RemoteCommand::Pause);
+        assert_eq!(
+            parse(r#"{"cmd": "press", "key": 5}"#).unwrap(),
+            RemoteCommand::Press { key: 5 }
+        );
+        assert_eq!(
+            parse(r#"{"cmd": "load", "path": "roms/pong.ch8"}"#).unwrap(),
+            RemoteCommand::Load {
+                path: PathBuf::from("roms/pong.ch8")
+            }
+        );
+        assert!(parse(r#"{"cmd": "press"}"#).is_err());
+        assert!(parse(r#"{"cmd": "explode"}"#).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_rpc() {
+        let (id, command) = RemoteServer::parse_rpc(
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "press", "params": {"key": 5}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            (id, command),
+            (Some(json!(7)), RemoteCommand::Press { key: 5 })
+        );
+        let (id, command) =
+            RemoteServer::parse_rpc(r#"{"jsonrpc": "2.0", "method": "pause"}"#).unwrap();
+        assert_eq!((id, command), (None, RemoteCommand::Pause));
+
+        let code = |line: &str| RemoteServer::parse_rpc(line).unwrap_err().1["code"].clone();
+        assert_eq!(code("{"), json!(-32700));
+        assert_eq!(code(r#"{"id": 1, "method": "pause"}"#), json!(-32600));
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "explode"}"#),
+            json!(-32601)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "press"}"#),
+            json!(-32602)
+        );
+        assert_eq!(
+            RemoteServer::rpc_message(json!(1), Ok(json!(true))),
+            json!({"jsonrpc": "2.0", "id": 1, "result": true})
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_pipe() {
+        assert_eq!(
+            RemoteServer::parse_pipe("toggle_pause\n"),
+            Ok((RemoteCommand::TogglePause, None))
+        );
+        assert_eq!(
+            RemoteServer::parse_pipe("press f"),
+            Ok((RemoteCommand::Press { key: 15 }, None))
+        );
+        assert_eq!(
+            RemoteServer::parse_pipe("screenshot /tmp/my screen.png"),
+            Ok((
+                RemoteCommand::Screenshot,
+                Some(PathBuf::from("/tmp/my screen.png"))
+            ))
+        );
+        assert!(RemoteServer::parse_pipe("press").is_err());
+        assert!(RemoteServer::parse_pipe("pause now").is_err());
+        assert!(RemoteServer::parse_pipe("screenshot").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    mem,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+};
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+}
+
+struct State<T> {
+    middle: T,
+    /// Whether the middle buffer holds a frame the consumer hasn't taken yet
+    fresh: bool,
+    closed: bool,
+}
+
+/// The producing half of a triple buffer, see `triple_buffer`.
+pub struct FrameSender<T> {
+    shared: Arc<Shared<T>>,
+    back: T,
+}
+
+/// The consuming half of a triple buffer, see `triple_buffer`.
+pub struct FrameReceiver<T> {
+    shared: Arc<Shared<T>>,
+    front: T,
+}
+
+/// Creates a triple buffer, with which the producer never waits for the consumer: it fills the
+/// back buffer and swaps it with the middle one, from which the consumer takes the latest frame
+/// in exchange for its front buffer. Frames the consumer is too slow for are dropped, and the
+/// buffers are reused, so frames can be written without allocating.
+pub fn triple_buffer<T: Default>() -> (FrameSender<T>, FrameReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            middle: T::default(),
+            fresh: false,
+            closed: false,
+        }),
+        ready: Condvar::new(),
+    });
+    (
+        FrameSender {
+            shared: Arc::clone(&shared),
+            back: T::default(),
+        },
+        FrameReceiver {
+            shared,
+            front: T::default(),
+        },
+    )
+}
+
+impl<T> FrameSender<T> {
+    /// The buffer to write the next frame into, which holds an older frame.
+    pub fn back_mut(&mut self) -> &mut T {
+        &mut self.back
+    }
+
+    /// Passes the back buffer to the consumer and returns whether this dropped the previous frame.
+    pub fn publish(&mut self) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        mem::swap(&mut state.middle, &mut self.back);
+        let dropped = mem::replace(&mut state.fresh, true);
+        self.shared.ready.notify_one();
+        dropped
+    }
+}
+
+impl<T> Drop for FrameSender<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.ready.notify_one();
+    }
+}
+
+impl<T> FrameReceiver<T> {
+    /// Waits for the next frame, or returns `None` once the sender is gone.
+    pub fn recv(&mut self) -> Option<&T> {
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.fresh {
+            if state.closed {
+                return None;
+            }
+            state = self.shared.ready.wait(state).unwrap();
+        }
+        mem::swap(&mut state.middle, &mut self.front);
+        state.fresh = false;
+        Some(&self.front)
+    }
+}
+
+/// Presents frames on a separate thread fed by a triple buffer, so a slow output like a display
+/// driver or a network connection can't stall the emulation timing.
+/// Used by the real time outputs of the headless mode and the frame export. The window still
+/// presents on the thread of its event loop, which its GL context is bound to.
+pub struct RenderThread<T> {
+    sender: Option<FrameSender<T>>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+    /// Why the thread stopped, after which frames aren't accepted anymore
+    error: Option<String>,
+}
+
+impl<T: Default + Send + 'static> RenderThread<T> {
+    pub fn spawn(
+        name: &str,
+        mut present: impl FnMut(&T) -> Result<(), String> + Send + 'static,
+    ) -> Result<Self, String> {
+        let (sender, mut receiver) = triple_buffer();
+        let thread = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                while let Some(frame) = receiver.recv() {
+                    present(frame)?;
+                }
+                Ok(())
+            })
+            .map_err(|e| format!("Cannot start the {} thread: {}", name, e))?;
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            error: None,
+        })
+    }
+}
+
+impl<T> RenderThread<T> {
+    /// The buffer to write the next frame into, which holds an older frame, or the error the
+    /// thread stopped with.
+    pub fn frame_mut(&mut self) -> Result<&mut T, String> {
+        self.sender().map(FrameSender::back_mut)
+    }
+
+    /// Passes the frame to the thread and returns whether the previous one was dropped, or the
+    /// error the thread stopped with.
+    pub fn submit(&mut self) -> Result<bool, String> {
+        self.sender().map(FrameSender::publish)
+    }
+
+    fn sender(&mut self) -> Result<&mut FrameSender<T>, String> {
+        if self.thread.as_ref().is_some_and(JoinHandle::is_finished) {
+            let error = self.join();
+            self.error = Some(error.unwrap_or_else(|| "The render thread stopped".to_string()));
+        }
+        match (&mut self.sender, &self.error) {
+            (Some(sender), None) => Ok(sender),
+            (_, Some(error)) => Err(error.clone()),
+            (None, None) => Err("The render thread stopped".to_string()),
+        }
+    }
+
+    /// Waits for the thread to present the last frame and stop, and returns its error.
+    fn join(&mut self) -> Option<String> {
+        self.sender = None;
+        match self.thread.take()?.join() {
+            Ok(result) => result.err(),
+            Err(_) => Some("The render thread panicked".to_string()),
+        }
+    }
+}
+
+impl<T> Drop for RenderThread<T> {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod render_thread_test {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_triple_buffer() {
+        let (mut sender, mut receiver) = triple_buffer::<Vec<u8>>();
+        *sender.back_mut() = vec![1];
+        assert!(!sender.publish());
+        *sender.back_mut() = vec![2];
+        assert!(sender.publish());
+        assert_eq!(receiver.recv(), Some(&vec![2]));
+        // The buffer of the dropped frame is reused
+        assert_eq!(sender.back_mut(), &vec![1]);
+        drop(sender);
+        assert_eq!(receiver.recv(), None);
+    }
+
+    #[test]
+    fn test_render_thread() {
+        let (tx, rx) = channel();
+        let mut render = RenderThread::spawn("test", move |&frame: &u32| {
+            tx.send(frame).unwrap();
+            match frame {
+                3 => Err("failed".to_string()),
+                _ => Ok(()),
+            }
+        })
+        .unwrap();
+        for frame in 1..=3 {
+            *render.frame_mut().unwrap() = frame;
+            render.submit().unwrap();
+            assert_eq!(rx.recv(), Ok(frame));
+        }
+        while !render.thread.as_ref().unwrap().is_finished() {
+            thread::yield_now();
+        }
+        assert_eq!(render.frame_mut(), Err("failed".to_string()));
+        assert_eq!(render.submit(), Err("failed".to_string()));
+    }
+}
@@ -1,9 +1,11 @@
-use std::sync::mpsc::Receiver;
+use reqwest::{blocking::Client, redirect};
+use std::{io::Read, sync::mpsc::Receiver, time::Duration};
 use url::Url;
 
 pub enum DownloadResult {
     Success(Vec<u8>),
     Fail(String),
+    Progress { received: u64, total: Option<u64> },
     None,
 }
 
@@ -13,6 +15,9 @@ pub struct RomDownloader {
 }
 
 impl RomDownloader {
+    const MAX_REDIRECTS: usize = 5;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
     pub fn new() -> Self {
         Self {
             is_active: false,
@@ -24,38 +29,113 @@ impl RomDownloader {
         self.is_active
     }
 
-    pub fn download(&mut self, url: Url) {
+    /// Parses the URL entered by the user.
+    /// Links to files on the GitHub website are turned into links to the raw file.
+    pub fn parse_url(input: &str) -> Result<Url, String> {
+        let mut url = Url::parse(input.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(format!(
+                "Invalid URL: only http and https are supported, not {}",
+                url.scheme()
+            ));
+        }
+        if url.host_str() == Some("github.com") {
+            let segments: Vec<&str> = url.path_segments().map_or(Vec::new(), |s| s.collect());
+            if let [user, repo, "blob", rest @ ..] = segments.as_slice() {
+                let path = format!("/{}/{}/{}", user, repo, rest.join("/"));
+                url = Url::parse("https://raw.githubusercontent.com").expect("Valid URL");
+                url.set_path(&path);
+            }
+        }
+        Ok(url)
+    }
+
+    /// Downloads at most `max_size` bytes, reporting the progress while doing so.
+    pub fn download(&mut self, url: Url, max_size: u64) {
         self.is_active = true;
 
         let (tx, rx) = std::sync::mpsc::channel();
         self.chan_rx = Some(rx);
 
         std::thread::spawn(move || {
-            let result = match reqwest::blocking::get(url) {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        match resp.bytes() {
-                            Ok(data) => DownloadResult::Success(data.to_vec()),
-                            Err(e) => DownloadResult::Fail(format!("Download failed: {}", e)),
-                        }
-                    } else {
-                        DownloadResult::Fail("Download failed!".to_string())
-                    }
-                }
-                Err(e) => DownloadResult::Fail(format!("Download failed: {}", e)),
+            let result = Self::fetch(url, max_size, |received, total| {
+                let _ = tx.send(DownloadResult::Progress { received, total });
+            });
+            let result = match result {
+                Ok(data) => DownloadResult::Success(data),
+                Err(msg) => DownloadResult::Fail(format!("Download failed: {}", msg)),
             };
 
             tx.send(result).expect("Communication failed");
         });
     }
 
+    fn fetch(
+        url: Url,
+        max_size: u64,
+        progress: impl Fn(u64, Option<u64>),
+    ) -> Result<Vec<u8>, String> {
+        // Follow redirects, but never from https to plain http
+        let policy = redirect::Policy::custom(|attempt| {
+            let downgrade = attempt
+                .previous()
+                .last()
+                .is_some_and(|prev| prev.scheme() == "https")
+                && attempt.url().scheme() != "https";
+            if downgrade {
+                attempt.error("redirected from https to an insecure URL")
+            } else if attempt.previous().len() > Self::MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = Client::builder()
+            .redirect(policy)
+            .timeout(Self::TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut resp = client.get(url).send().map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("the server responded with {}", resp.status()));
+        }
+        let total = resp.content_length();
+        if total.is_some_and(|total| total > max_size) {
+            return Err(format!(
+                "the file is too big ({} bytes)",
+                total.unwrap_or_default()
+            ));
+        }
+
+        let mut data = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let len = resp.read(&mut buf).map_err(|e| e.to_string())?;
+            if len == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..len]);
+            if data.len() as u64 > max_size {
+                return Err(format!("the file is bigger than {} bytes", max_size));
+            }
+            progress(data.len() as u64, total);
+        }
+        Ok(data)
+    }
+
     pub fn check_result(&mut self) -> DownloadResult {
         let mut result = DownloadResult::None;
         if self.chan_rx.is_some() {
             if let Some(chan) = self.chan_rx.as_ref() {
-                if let Ok(download_result) = chan.try_recv() {
-                    self.is_active = false;
+                // Only the latest progress is of interest
+                while let Ok(download_result) = chan.try_recv() {
+                    let done = !matches!(download_result, DownloadResult::Progress { .. });
                     result = download_result;
+                    if done {
+                        self.is_active = false;
+                        break;
+                    }
                 }
             }
         }
@@ -63,3 +143,26 @@ impl RomDownloader {
         result
     }
 }
+
+#[cfg(test)]
+mod rom_downloader_test {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            RomDownloader::parse_url("https://github.com/user/repo/blob/main/roms/game.ch8")
+                .unwrap()
+                .as_str(),
+            "https://raw.githubusercontent.com/user/repo/main/roms/game.ch8"
+        );
+        assert_eq!(
+            RomDownloader::parse_url(" https://example.com/game.ch8 ")
+                .unwrap()
+                .as_str(),
+            "https://example.com/game.ch8"
+        );
+        assert!(RomDownloader::parse_url("ftp://example.com/game.ch8").is_err());
+        assert!(RomDownloader::parse_url("game.ch8").is_err());
+    }
+}
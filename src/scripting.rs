@@ -0,0 +1,259 @@
+use pich8::cpu::{Register, CPU};
+use pich8::expression::Operand;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+/// A change a script made to the machine, applied to the CPU after the script returned.
+enum Edit {
+    Register(Register, u16),
+    Memory(u16, u8),
+}
+
+/// The machine as seen by a script while it runs.
+#[derive(Default)]
+struct Machine {
+    mem: Vec<u8>,
+    /// V0 - VF, PC, I, DT and ST
+    registers: [u16; 20],
+    edits: Vec<Edit>,
+    keys: Vec<(usize, bool)>,
+    output: Vec<String>,
+}
+
+impl Machine {
+    fn index(register: Register) -> usize {
+        match register {
+            Register::V(idx) => idx & 0xF,
+            Register::PC => 16,
+            Register::I => 17,
+            Register::DT => 18,
+            Register::ST => 19,
+        }
+    }
+
+    fn load(&mut self, cpu: &CPU) {
+        self.mem.clear();
+        self.mem
+            .extend((0..=u16::MAX).map(|address| cpu.read_mem(address)));
+        for idx in 0..16 {
+            self.registers[idx] = cpu.register(Register::V(idx));
+        }
+        for register in [Register::PC, Register::I, Register::DT, Register::ST] {
+            self.registers[Self::index(register)] = cpu.register(register);
+        }
+    }
+
+    fn apply(&mut self, cpu: &mut CPU) {
+        for edit in self.edits.drain(..) {
+            match edit {
+                Edit::Register(register, value) => cpu.set_register(register, value),
+                Edit::Memory(address, value) => cpu.write_mem(address, value),
+            }
+        }
+    }
+
+    fn register(name: &str) -> Result<Register, Box<EvalAltResult>> {
+        match name.parse::<Operand>() {
+            Ok(Operand::Register(register)) => Ok(register),
+            _ => Err(format!("Unknown register '{}'", name).into()),
+        }
+    }
+}
+
+/// A Rhai script automating the emulator, e.g. for tests or trainers.
+///
+/// Scripts can read and write memory and registers with `peek(address)`, `poke(address, value)`,
+/// `reg("V3")` and `set_reg("V3", value)`, and press and release keys with `press(key)` and
+/// `release(key)`. A function `on_frame()` is called after every frame, `this` is a map kept
+/// between the calls, e.g. to count frames with
+/// `this.frames = (this.frames ?? 0) + 1`.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Dynamic,
+    machine: Rc<RefCell<Machine>>,
+}
+
+impl Script {
+    const HOOK_FRAME: &'static str = "on_frame";
+    /// Limits a script call so an endless loop doesn't freeze the emulator
+    const MAX_OPERATIONS: u64 = 1_000_000;
+
+    pub fn new() -> Self {
+        let machine = Rc::new(RefCell::new(Machine::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(Self::MAX_OPERATIONS);
+
+        let m = machine.clone();
+        engine.on_print(move |text| m.borrow_mut().output.push(text.to_string()));
+        let m = machine.clone();
+        engine.on_debug(move |text, _, _| m.borrow_mut().output.push(text.to_string()));
+        let m = machine.clone();
+        engine.register_fn("peek", move |address: i64| {
+            m.borrow().mem[address as u16 as usize] as i64
+        });
+        let m = machine.clone();
+        engine.register_fn("poke", move |address: i64, value: i64| {
+            let mut machine = m.borrow_mut();
+            machine.mem[address as u16 as usize] = value as u8;
+            machine
+                .edits
+                .push(Edit::Memory(address as u16, value as u8));
+        });
+        let m = machine.clone();
+        engine.register_fn(
+            "reg",
+            move |name: &str| -> Result<i64, Box<EvalAltResult>> {
+                let register = Machine::register(name)?;
+                Ok(m.borrow().registers[Machine::index(register)] as i64)
+            },
+        );
+        let m = machine.clone();
+        engine.register_fn(
+            "set_reg",
+            move |name: &str, value: i64| -> Result<(), Box<EvalAltResult>> {
+                let register = Machine::register(name)?;
+                let mut machine = m.borrow_mut();
+                machine.registers[Machine::index(register)] = value as u16;
+                machine.edits.push(Edit::Register(register, value as u16));
+                Ok(())
+            },
+        );
+        let m = machine.clone();
+        engine.register_fn("press", move |key: i64| {
+            m.borrow_mut().keys.push((key as usize & 0xF, true))
+        });
+        let m = machine.clone();
+        engine.register_fn("release", move |key: i64| {
+            m.borrow_mut().keys.push((key as usize & 0xF, false))
+        });
+
+        Self {
+            engine,
+            ast: AST::empty(),
+            scope: Scope::new(),
+            state: Map::new().into(),
+            machine,
+        }
+    }
+
+    /// Loads a script file and runs its top level statements.
+    pub fn load(&mut self, path: &Path, cpu: &mut CPU) -> Result<(), String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.ast = ast;
+        self.scope = Scope::new();
+        self.state = Map::new().into();
+        let ast = &self.ast;
+        let engine = &self.engine;
+        let scope = &mut self.scope;
+        Self::run(&self.machine, cpu, || engine.run_ast_with_scope(scope, ast))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Calls `on_frame()` if the script defines it.
+    pub fn on_frame(&mut self, cpu: &mut CPU) -> Result<(), String> {
+        if !self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == Self::HOOK_FRAME && f.params.is_empty())
+        {
+            return Ok(());
+        }
+        let (engine, ast, scope, state) =
+            (&self.engine, &self.ast, &mut self.scope, &mut self.state);
+        Self::run(&self.machine, cpu, || {
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+            engine.call_fn_with_options::<Dynamic>(options, scope, ast, Self::HOOK_FRAME, ())
+        })
+        .map(|_| ())
+    }
+
+    /// Evaluates a line of the console, which can call the functions of the loaded script.
+    pub fn eval(&mut self, line: &str, cpu: &mut CPU) -> Result<String, String> {
+        let ast = self.engine.compile(line).map_err(|e| e.to_string())?;
+        let ast = self.ast.clone_functions_only().merge(&ast);
+        let engine = &self.engine;
+        let scope = &mut self.scope;
+        let value = Self::run(&self.machine, cpu, || {
+            engine.eval_ast_with_scope::<Dynamic>(scope, &ast)
+        })?;
+        Ok(if value.is_unit() {
+            String::new()
+        } else {
+            value.to_string()
+        })
+    }
+
+    /// Returns the lines printed since the last call.
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.machine.borrow_mut().output)
+    }
+
+    /// Returns the keys pressed (true) and released (false) since the last call.
+    pub fn take_keys(&mut self) -> Vec<(usize, bool)> {
+        std::mem::take(&mut self.machine.borrow_mut().keys)
+    }
+
+    /// Runs a script with the machine loaded from the CPU, changes are applied even if it fails.
+    fn run<T>(
+        machine: &RefCell<Machine>,
+        cpu: &mut CPU,
+        f: impl FnOnce() -> Result<T, Box<EvalAltResult>>,
+    ) -> Result<T, String> {
+        machine.borrow_mut().load(cpu);
+        let result = f();
+        machine.borrow_mut().apply(cpu);
+        result.map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod scripting_test {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let mut cpu = CPU::new();
+        cpu.write_mem(0x3A0, 3);
+        let mut script = Script::new();
+        assert_eq!(script.eval("peek(0x3A0) + 1", &mut cpu).unwrap(), "4");
+        script
+            .eval("poke(0x3A0, 9); set_reg(\"v3\", 0x1F)", &mut cpu)
+            .unwrap();
+        assert_eq!(cpu.read_mem(0x3A0), 9);
+        assert_eq!(cpu.register(Register::V(3)), 0x1F);
+        assert_eq!(script.eval("reg(\"V3\")", &mut cpu).unwrap(), "31");
+        assert!(script.eval("reg(\"X\")", &mut cpu).is_err());
+
+        script
+            .eval("press(5); print(\"pressed\")", &mut cpu)
+            .unwrap();
+        assert_eq!(script.take_keys(), vec![(5, true)]);
+        assert_eq!(script.take_output(), vec!["pressed".to_string()]);
+    }
+
+    #[test]
+    fn test_frame_hook() {
+        let path = std::env::temp_dir().join("pich8_test_frame_hook.rhai");
+        fs::write(
+            &path,
+            "poke(0x3A0, 0);\nfn on_frame() { this.frames = (this.frames ?? 0) + 1; poke(0x3A0, this.frames); }",
+        )
+        .unwrap();
+        let mut cpu = CPU::new();
+        cpu.write_mem(0x3A0, 7);
+        let mut script = Script::new();
+        script.load(&path, &mut cpu).unwrap();
+        assert_eq!(cpu.read_mem(0x3A0), 0);
+        script.on_frame(&mut cpu).unwrap();
+        script.on_frame(&mut cpu).unwrap();
+        assert_eq!(cpu.read_mem(0x3A0), 2);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::Pich8Error;
+use log::{error, info};
 use rodio::{
     buffer::SamplesBuffer,
     queue::queue,
@@ -11,6 +13,23 @@ pub enum Command {
     PlayBeep,
     PlayBuffer([u8; 16]),
     SetVolume(f32),
+    Pause,
+    Resume,
+}
+
+/// Plays the sound of the emulated machine, one frame at a time.
+pub trait SoundOutput {
+    /// Plays the default tone for a frame.
+    fn beep(&self);
+
+    /// Plays the XO-CHIP audio pattern for a frame.
+    fn play_buffer(&self, buf: [u8; 16]);
+
+    fn pause(&self);
+
+    fn resume(&self);
+
+    fn set_volume(&self, volume: f32);
 }
 
 pub struct AudioPlayer {
@@ -22,63 +41,99 @@ impl AudioPlayer {
     const BUF_FREQ: u32 = 4000;
     const VOLUME: f32 = 0.05;
 
-    pub fn new() -> Result<Self, String> {
+    pub fn new() -> Result<Self, Pich8Error> {
         let (tx, rx) = channel();
+        let (tx_init, rx_init) = channel();
 
         std::thread::spawn(move || {
             let (queue, output_queue) = queue(true);
             let sample_rate = output_queue.sample_rate();
             let beep_duration = Duration::from_secs_f32(1.5 / 60.0);
-            if let Ok((_stream, stream_handle)) = OutputStream::try_default() {
-                if let Ok(sink) = Sink::try_new(&stream_handle) {
-                    sink.append(output_queue);
-
-                    loop {
-                        if let Ok(cmd) = rx.recv() {
-                            match cmd {
-                                Command::PlayBeep => queue.append(
-                                    SineWave::new(Self::BEEP_FREQ).take_duration(beep_duration),
-                                ),
-                                Command::PlayBuffer(buf) => {
-                                    let reps = sample_rate / Self::BUF_FREQ;
-                                    let mut samples =
-                                        Vec::with_capacity(buf.len() * 8 * reps as usize);
-                                    for byte in &buf {
-                                        for idx_bit in 0..8 {
-                                            let bit = byte >> (7 - idx_bit) & 0b1 == 0b1;
-                                            let val = if bit { Self::VOLUME } else { 0.0 };
-                                            for _ in 0..reps {
-                                                samples.push(val);
-                                            }
-                                        }
+            let output = OutputStream::try_default()
+                .map_err(|e| e.to_string())
+                .and_then(|(stream, stream_handle)| {
+                    let sink = Sink::try_new(&stream_handle).map_err(|e| e.to_string())?;
+                    Ok((stream, sink))
+                });
+            // The stream has to be kept alive as long as the sink is used
+            let (_stream, sink) = match output {
+                Ok(output) => {
+                    info!("Opened audio output");
+                    let _ = tx_init.send(Ok(()));
+                    output
+                }
+                Err(msg) => {
+                    error!("Failed to open audio output: {}", msg);
+                    let _ = tx_init.send(Err(msg));
+                    return;
+                }
+            };
+            sink.append(output_queue);
+
+            loop {
+                if let Ok(cmd) = rx.recv() {
+                    match cmd {
+                        Command::PlayBeep => queue
+                            .append(SineWave::new(Self::BEEP_FREQ).take_duration(beep_duration)),
+                        Command::PlayBuffer(buf) => {
+                            let reps = sample_rate / Self::BUF_FREQ;
+                            let mut samples = Vec::with_capacity(buf.len() * 8 * reps as usize);
+                            for byte in &buf {
+                                for idx_bit in 0..8 {
+                                    let bit = byte >> (7 - idx_bit) & 0b1 == 0b1;
+                                    let val = if bit { Self::VOLUME } else { 0.0 };
+                                    for _ in 0..reps {
+                                        samples.push(val);
                                     }
-                                    let sample_buffer = SamplesBuffer::new(1, sample_rate, samples);
-                                    queue.append(
-                                        sample_buffer
-                                            .take_duration(Duration::from_secs_f32(1.0 / 60.0)),
-                                    );
                                 }
-                                Command::SetVolume(vol) => sink.set_volume(vol),
                             }
+                            let sample_buffer = SamplesBuffer::new(1, sample_rate, samples);
+                            queue.append(
+                                sample_buffer.take_duration(Duration::from_secs_f32(1.0 / 60.0)),
+                            );
                         }
+                        Command::SetVolume(vol) => sink.set_volume(vol),
+                        Command::Pause => sink.pause(),
+                        Command::Resume => sink.play(),
                     }
                 }
             }
         });
 
-        Ok(Self { tx_play: tx })
+        // Wait for the output device to be opened
+        match rx_init.recv() {
+            Ok(Ok(())) => Ok(Self { tx_play: tx }),
+            Ok(Err(msg)) => Err(Pich8Error::Audio(msg)),
+            Err(e) => Err(Pich8Error::Audio(e.to_string())),
+        }
+    }
+
+    /// A player without audio device, which ignores all commands.
+    pub fn silent() -> Self {
+        let (tx, _) = channel();
+        Self { tx_play: tx }
     }
+}
 
-    pub fn beep(&self) {
+impl SoundOutput for AudioPlayer {
+    fn beep(&self) {
         // Ignore if something went wrong
         let _ = self.tx_play.send(Command::PlayBeep);
     }
 
-    pub fn play_buffer(&self, buf: [u8; 16]) {
+    fn play_buffer(&self, buf: [u8; 16]) {
         let _ = self.tx_play.send(Command::PlayBuffer(buf));
     }
 
-    pub fn set_volume(&self, volume: f32) {
+    fn pause(&self) {
+        let _ = self.tx_play.send(Command::Pause);
+    }
+
+    fn resume(&self) {
+        let _ = self.tx_play.send(Command::Resume);
+    }
+
+    fn set_volume(&self, volume: f32) {
         // The default volume range is extremely loud, I found 0 - 10 to be a good range
         let _ = self.tx_play.send(Command::SetVolume(volume / 10.0));
     }
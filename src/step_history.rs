@@ -0,0 +1,197 @@
+use pich8::cpu::CPU;
+use std::collections::VecDeque;
+
+struct Step {
+    /// The CPU state without the memory
+    state: Vec<u8>,
+    /// The addresses changed since the previous step with their values before
+    mem_undo: Vec<(u16, u8)>,
+}
+
+impl Step {
+    fn size(&self) -> usize {
+        self.state.len() + self.mem_undo.len() * std::mem::size_of::<(u16, u8)>()
+    }
+}
+
+/// The CPU states before the last steps, so they can be undone with Step Back.
+///
+/// Instead of the whole memory, each step keeps the bytes changed since the step before, so
+/// a step costs the registers and the screen plus what the program wrote.
+pub struct StepHistory {
+    steps: VecDeque<Step>,
+    /// The memory as of the last step, which the changes of the next one are found against
+    mem: Vec<u8>,
+    size: usize,
+    /// Whether the last step was pushed for an edit, which the following edits share
+    editing: bool,
+}
+
+impl StepHistory {
+    /// The number of steps kept, 5 seconds of frames
+    const MAX_STEPS: usize = 300;
+    /// The bytes the steps may take up, which only limits programs writing a lot of memory
+    const MAX_SIZE: usize = 8 << 20;
+
+    pub fn new() -> Self {
+        Self {
+            steps: VecDeque::new(),
+            mem: Vec::new(),
+            size: 0,
+            editing: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.mem.clear();
+        self.size = 0;
+        self.editing = false;
+    }
+
+    /// Remembers the CPU state before a step.
+    pub fn push(&mut self, cpu: &mut CPU) -> Result<(), String> {
+        self.editing = false;
+        let state = cpu.save_state_without_mem()?;
+        let mem = cpu.mem();
+        let mem_undo = if self.mem.len() == mem.len() {
+            let changes = mem.iter().zip(&mut self.mem).enumerate();
+            changes
+                .filter(|(_, (new, old))| new != old)
+                .map(|(address, (&new, old))| (address as u16, std::mem::replace(old, new)))
+                .collect()
+        } else {
+            self.mem = mem.to_vec();
+            Vec::new()
+        };
+        let step = Step { state, mem_undo };
+        self.size += step.size();
+        self.steps.push_back(step);
+        while self.steps.len() > Self::MAX_STEPS || self.size > Self::MAX_SIZE {
+            match self.steps.pop_front() {
+                Some(step) => self.size -= step.size(),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Remembers the CPU state before an edit in the debugger, unless it was already remembered
+    /// for an edit since the last step, so all edits in between are undone together.
+    pub fn push_edit(&mut self, cpu: &mut CPU) -> Result<(), String> {
+        if !self.editing {
+            self.push(cpu)?;
+            self.editing = true;
+        }
+        Ok(())
+    }
+
+    /// Returns the CPU state before the last step, or `None` if there are no more steps.
+    pub fn pop(&mut self) -> Option<Result<CPU, String>> {
+        let step = self.steps.pop_back()?;
+        self.editing = false;
+        self.size -= step.size();
+        let cpu = CPU::from_state_with_mem(&step.state, &self.mem);
+        for &(address, value) in &step.mem_undo {
+            self.mem[address as usize] = value;
+        }
+        Some(cpu)
+    }
+}
+
+#[cfg(test)]
+mod step_history_test {
+    use super::*;
+    use pich8::cpu::Breakpoint;
+
+    #[test]
+    fn test_step_history() {
+        let mut cpu = CPU::new();
+        // LD V0, 1; LD I, 300; LD [I], V0; JP 206
+        cpu.load_rom(&[0x60, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06])
+            .unwrap();
+        let mut history = StepHistory::new();
+        for _ in 0..4 {
+            history.push(&mut cpu).unwrap();
+            cpu.tick(&[false; 16]).unwrap();
+        }
+        assert_eq!(cpu.read_mem(0x300), 1);
+        // Only the step storing V0 changed the memory
+        assert_eq!(history.steps[3].mem_undo, [(0x300, 0)]);
+        assert!(history
+            .steps
+            .iter()
+            .take(3)
+            .all(|step| step.mem_undo.is_empty()));
+
+        for pc in [0x206, 0x204, 0x202] {
+            cpu = history.pop().unwrap().unwrap();
+            assert_eq!(cpu.PC(), pc);
+            assert_eq!(cpu.read_mem(0x300), (pc == 0x206) as u8);
+        }
+        // Stepping again after stepping back
+        cpu.tick(&[false; 16]).unwrap();
+        history.push(&mut cpu).unwrap();
+        cpu.tick(&[false; 16]).unwrap();
+        cpu = history.pop().unwrap().unwrap();
+        assert_eq!(cpu.PC(), 0x204);
+        cpu = history.pop().unwrap().unwrap();
+        assert_eq!((cpu.PC(), cpu.V()[0]), (0x200, 0));
+        assert!(history.pop().is_none());
+        assert_eq!(history.size, 0);
+    }
+
+    #[test]
+    fn test_step_back_after_breakpoint() {
+        let mut cpu = CPU::new();
+        // LD V0, 1; ADD V0, 1; ADD V0, 1; JP 200
+        cpu.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x00])
+            .unwrap();
+        let mut history = StepHistory::new();
+        // Frames of 3 instructions remembered before they run, like in the debugger
+        let run_frame = |cpu: &mut CPU, history: &mut StepHistory, breakpoint: u16| {
+            history.push(cpu).unwrap();
+            for _ in 0..3 {
+                cpu.tick(&[false; 16]).unwrap();
+                if cpu.check_breakpoint(Breakpoint::PC(breakpoint)) {
+                    return true;
+                }
+            }
+            false
+        };
+        assert!(!run_frame(&mut cpu, &mut history, 0x300));
+        assert_eq!(cpu.PC(), 0x206);
+        // Stops after JP and LD, which the first Step Back undoes
+        assert!(run_frame(&mut cpu, &mut history, 0x202));
+        assert_eq!((cpu.PC(), cpu.V()[0]), (0x202, 1));
+        cpu = history.pop().unwrap().unwrap();
+        assert_eq!((cpu.PC(), cpu.V()[0]), (0x206, 3));
+    }
+
+    #[test]
+    fn test_edits() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x01, 0x12, 0x02]).unwrap();
+        let mut history = StepHistory::new();
+        for value in 1..=3 {
+            history.push_edit(&mut cpu).unwrap();
+            cpu.write_mem(0x300, value);
+        }
+        assert_eq!(history.steps.len(), 1);
+        history.push(&mut cpu).unwrap();
+        cpu.tick(&[false; 16]).unwrap();
+        history.push_edit(&mut cpu).unwrap();
+        cpu.write_mem(0x300, 4);
+        assert_eq!(history.steps.len(), 3);
+
+        cpu = history.pop().unwrap().unwrap();
+        assert_eq!((cpu.PC(), cpu.read_mem(0x300)), (0x202, 3));
+        history.pop().unwrap().unwrap();
+        cpu = history.pop().unwrap().unwrap();
+        assert_eq!(cpu.read_mem(0x300), 0);
+    }
+}
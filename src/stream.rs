@@ -0,0 +1,215 @@
+use crate::display::{DisplayOutput, Frame};
+use crate::metrics::Metrics;
+use log::{info, warn};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+impl Frame {
+    /// Encodes the frame as width and height (u16), the length of the data (u32), all big-endian,
+    /// followed by the data as runs of a count and a pixel value (u8 each).
+    pub fn encode(&self) -> Vec<u8> {
+        let mut runs = Vec::new();
+        let mut pixels = self.pixels.iter().peekable();
+        while let Some(&value) = pixels.next() {
+            let mut count = 1u8;
+            while count < u8::MAX && pixels.next_if_eq(&&value).is_some() {
+                count += 1;
+            }
+            runs.extend_from_slice(&[count, value]);
+        }
+        let mut data = Vec::with_capacity(8 + runs.len());
+        data.extend_from_slice(&(self.width as u16).to_be_bytes());
+        data.extend_from_slice(&(self.height as u16).to_be_bytes());
+        data.extend_from_slice(&(runs.len() as u32).to_be_bytes());
+        data.extend_from_slice(&runs);
+        data
+    }
+
+    pub fn decode(reader: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        let width = u16::from_be_bytes([header[0], header[1]]) as usize;
+        let height = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let mut runs = vec![0; len];
+        reader.read_exact(&mut runs)?;
+        let mut pixels = Vec::with_capacity(width * height);
+        for run in runs.chunks_exact(2) {
+            pixels.extend(std::iter::repeat_n(run[1], run[0] as usize));
+        }
+        if pixels.len() != width * height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Frame size doesn't match its pixels",
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Viewers {
+    streams: Vec<TcpStream>,
+    /// The last sent frame, which new viewers get first
+    last_frame: Vec<u8>,
+}
+
+/// Streams the screen over TCP, so a headless instance can be watched from another machine.
+/// Frames are only sent when the screen changed.
+pub struct StreamServer {
+    viewers: Arc<Mutex<Viewers>>,
+    /// Only kept for screenshots, the viewers choose their own colors
+    colors: [[u8; 3]; 4],
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl StreamServer {
+    /// A viewer not reading for this long is disconnected instead of holding up the others
+    const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Listens on the address, viewers are accepted by a background thread.
+    pub fn start(address: SocketAddr) -> Result<Self, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        info!("Streaming the screen on {}", address);
+        let viewers = Arc::new(Mutex::new(Viewers::default()));
+        let accepting = Arc::clone(&viewers);
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.set_nodelay(true);
+                let _ = stream.set_write_timeout(Some(Self::WRITE_TIMEOUT));
+                let mut viewers = accepting.lock().unwrap();
+                if stream.write_all(&viewers.last_frame).is_ok() {
+                    viewers.streams.push(stream);
+                }
+            }
+        });
+        Ok(Self {
+            viewers,
+            colors: [[0; 3]; 4],
+            metrics: None,
+        })
+    }
+
+    /// Reports the number of viewers to the metrics with each presented frame.
+    pub fn set_metrics(&mut self, metrics: Option<Arc<Metrics>>) {
+        self.metrics = metrics;
+    }
+
+    pub fn viewers(&self) -> usize {
+        self.viewers.lock().unwrap().streams.len()
+    }
+
+    pub fn send(&self, frame: &Frame) {
+        let data = frame.encode();
+        let mut viewers = self.viewers.lock().unwrap();
+        if viewers.last_frame == data {
+            return;
+        }
+        viewers
+            .streams
+            .retain_mut(|stream| match stream.write_all(&data) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Disconnected viewer: {}", e);
+                    false
+                }
+            });
+        viewers.last_frame = data;
+    }
+}
+
+impl DisplayOutput for StreamServer {
+    fn colors(&self) -> [[u8; 3]; 4] {
+        self.colors
+    }
+
+    fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+        self.colors = colors;
+    }
+
+    fn present(&mut self, frame: &Frame) -> Result<(), String> {
+        self.send(frame);
+        if let Some(metrics) = &self.metrics {
+            metrics.set_clients(self.viewers());
+        }
+        Ok(())
+    }
+}
+
+/// Shows the screen streamed by another instance in the terminal, two pixels per character.
+pub fn view(address: &str, colors: &[[u8; 3]; 4]) -> Result<(), String> {
+    let mut stream =
+        TcpStream::connect(address).map_err(|e| format!("Cannot connect to {}: {}", address, e))?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut size = None;
+    loop {
+        let frame = match Frame::decode(&mut stream) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(format!("Cannot receive frame: {}", e)),
+        };
+        // Clear the screen when the resolution changed
+        let clear = size != Some((frame.width, frame.height));
+        size = Some((frame.width, frame.height));
+        render(&frame, colors, clear, &mut out).map_err(|e| e.to_string())?;
+    }
+}
+
+fn render(
+    frame: &Frame,
+    colors: &[[u8; 3]; 4],
+    clear: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let color = |x: usize, y: usize| {
+        let pixel = frame.pixels.get(y * frame.width + x).copied().unwrap_or(0);
+        colors[pixel as usize & 3]
+    };
+    let mut text = String::from(if clear { "\x1b[H\x1b[2J" } else { "\x1b[H" });
+    for y in (0..frame.height).step_by(2) {
+        for x in 0..frame.width {
+            let ([fr, fg, fb], [br, bg, bb]) = (color(x, y), color(x, y + 1));
+            text.push_str(&format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                fr, fg, fb, br, bg, bb
+            ));
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    out.write_all(text.as_bytes())?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod stream_test {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let mut pixels = vec![0; 64 * 32];
+        pixels[100..400].iter_mut().for_each(|pixel| *pixel = 3);
+        pixels[2047] = 1;
+        let frame = Frame {
+            width: 64,
+            height: 32,
+            pixels,
+        };
+        let data = frame.encode();
+        // Long runs are split at 255 pixels
+        assert_eq!(&data[8..12], &[100, 0, 255, 3]);
+        assert!(data.len() < 40);
+        assert_eq!(Frame::decode(&mut data.as_slice()).unwrap(), frame);
+        assert!(Frame::decode(&mut &data[..data.len() - 2]).is_err());
+    }
+}
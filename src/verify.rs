@@ -0,0 +1,208 @@
+use crate::config::{QuirksConfig, Settings};
+use crate::gui::QuirksPreset;
+use crate::headless::Headless;
+use clap::ValueEnum;
+use pich8::rom;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// The test ROMs `pich8 verify` runs and the screens they have to end with, see `data/verify.toml`.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(rename = "test")]
+    tests: Vec<TestRom>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TestRom {
+    name: String,
+    file: String,
+    frames: u64,
+    /// Bytes written to memory before running, as address and value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    poke: Vec<(u16, u8)>,
+    /// The SHA-1 hash of the ROM the screens were recorded with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha1: Option<String>,
+    /// The framebuffer hash per quirk preset
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    expected: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Outcome {
+    Pass,
+    Fail,
+    /// No screen was recorded for the preset yet
+    Unknown,
+    Recorded,
+    /// The ROM differs from the one the screens were recorded with
+    OtherRom,
+    Missing,
+    Error(String),
+}
+
+impl Outcome {
+    fn name(&self) -> &str {
+        match self {
+            Outcome::Pass => "pass",
+            Outcome::Fail => "FAIL",
+            Outcome::Unknown => "-",
+            Outcome::Recorded => "recorded",
+            Outcome::OtherRom => "other ROM",
+            Outcome::Missing => "missing",
+            Outcome::Error(_) => "ERROR",
+        }
+    }
+}
+
+impl Manifest {
+    const BUILTIN: &'static str = include_str!("../data/verify.toml");
+    const FILE_NAME: &'static str = "pich8-verify.toml";
+
+    /// Loads the recorded manifest of the directory, or the bundled one if there's none.
+    fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(Self::FILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => Self::BUILTIN.to_string(),
+        };
+        toml::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    fn save(&self, dir: &Path) -> Result<(), String> {
+        let path = dir.join(Self::FILE_NAME);
+        let content =
+            toml::to_string(self).map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+impl TestRom {
+    /// Runs the ROM with the preset's quirks and returns the hash of the final screen.
+    fn run(&self, rom: &[u8], preset: QuirksPreset) -> Result<String, String> {
+        let settings = Settings {
+            quirks: Some(QuirksConfig::Preset(preset)),
+            ..Settings::default()
+        };
+        let mut headless = Headless::new(rom, &settings)?;
+        for &(address, value) in &self.poke {
+            headless.cpu_mut().write_mem(address, value);
+        }
+        headless.run_for(self.frames, 0)?;
+        Ok(headless.framebuffer_hash())
+    }
+
+    /// Runs the ROM under every preset, recording the screens instead of comparing them if asked.
+    fn verify(&mut self, dir: &Path, record: bool) -> Vec<Outcome> {
+        let presets = QuirksPreset::value_variants();
+        let rom = match fs::read(dir.join(&self.file)) {
+            Ok(rom) => rom,
+            Err(_) => return presets.iter().map(|_| Outcome::Missing).collect(),
+        };
+        let sha1 = rom::sha1_hex(&rom);
+        if record {
+            self.sha1 = Some(sha1.clone());
+        } else if self.sha1.as_ref().is_some_and(|recorded| *recorded != sha1) {
+            return presets.iter().map(|_| Outcome::OtherRom).collect();
+        }
+        presets
+            .iter()
+            .map(|&preset| {
+                let name = preset_name(preset);
+                let hash = match self.run(&rom, preset) {
+                    Ok(hash) => hash,
+                    Err(msg) => return Outcome::Error(msg),
+                };
+                if record {
+                    self.expected.insert(name, hash);
+                    return Outcome::Recorded;
+                }
+                match self.expected.get(&name) {
+                    Some(expected) if *expected == hash => Outcome::Pass,
+                    Some(_) => Outcome::Fail,
+                    None => Outcome::Unknown,
+                }
+            })
+            .collect()
+    }
+}
+
+fn preset_name(preset: QuirksPreset) -> String {
+    preset
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+/// Runs the test ROMs in the directory under every quirk preset and prints a pass/fail matrix,
+/// failing if any screen differs from the recorded one.
+pub fn run(dir: &Path, record: bool) -> Result<(), String> {
+    let mut manifest = Manifest::load(dir)?;
+    let presets = QuirksPreset::value_variants();
+    print!("{:<16}", "Test");
+    for &preset in presets {
+        print!("  {:<9}", preset_name(preset));
+    }
+    println!();
+    let mut failed = 0;
+    let mut errors = Vec::new();
+    for test in &mut manifest.tests {
+        print!("{:<16}", test.name);
+        for outcome in test.verify(dir, record) {
+            print!("  {:<9}", outcome.name());
+            match outcome {
+                Outcome::Fail => failed += 1,
+                Outcome::Error(msg) => errors.push(format!("{}: {}", test.name, msg)),
+                _ => (),
+            }
+        }
+        println!();
+    }
+    for msg in &errors {
+        println!("{}", msg);
+    }
+    if record {
+        manifest.save(dir)?;
+        println!(
+            "Recorded the screens to {}",
+            dir.join(Manifest::FILE_NAME).display()
+        );
+    }
+    match failed + errors.len() {
+        0 => Ok(()),
+        count => Err(format!("{} of the test runs failed!", count)),
+    }
+}
+
+#[cfg(test)]
+mod verify_test {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let dir = std::env::temp_dir().join("pich8_verify_test");
+        fs::create_dir_all(&dir).unwrap();
+        // Draws the font sprite of the value poked to 0x300
+        let rom = [0xA3, 0x00, 0xF0, 0x65, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x08];
+        fs::write(dir.join("digit.ch8"), rom).unwrap();
+        let mut test = TestRom {
+            name: "digit".to_string(),
+            file: "digit.ch8".to_string(),
+            frames: 2,
+            poke: vec![(0x300, 7)],
+            sha1: None,
+            expected: BTreeMap::new(),
+        };
+        let presets = QuirksPreset::value_variants().len();
+        assert_eq!(test.verify(&dir, false), vec![Outcome::Unknown; presets]);
+        assert_eq!(test.verify(&dir, true), vec![Outcome::Recorded; presets]);
+        assert_eq!(test.verify(&dir, false), vec![Outcome::Pass; presets]);
+        test.poke = vec![(0x300, 8)];
+        assert_eq!(test.verify(&dir, false), vec![Outcome::Fail; presets]);
+        test.file = "other.ch8".to_string();
+        assert_eq!(test.verify(&dir, false), vec![Outcome::Missing; presets]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(toml::from_str::<Manifest>(Manifest::BUILTIN).is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+//! The browser frontend of pich8, built with `wasm-bindgen --target web` and used by `index.html`.
+//!
+//! The page drives the emulator from `requestAnimationFrame`, which calls `frame` with its
+//! timestamp, so the emulation never blocks the browser's event loop.
+use pich8::cpu::CPU;
+use pich8::rom;
+use pich8::runner::Runner;
+use pich8::video_memory::{Plane, VideoMemory};
+use wasm_bindgen::{prelude::*, Clamped, JsCast};
+use web_sys::{
+    AudioContext, CanvasRenderingContext2d, GainNode, HtmlCanvasElement, ImageData, OscillatorNode,
+    OscillatorType,
+};
+
+/// The keyboard codes of the CHIP-8 keys 0 - F, the same physical keys as in the desktop version
+const KEY_CODES: [&str; 16] = [
+    "KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE", "KeyA", "KeyS", "KeyD", "KeyZ",
+    "KeyC", "Digit4", "KeyR", "KeyF", "KeyV",
+];
+/// Background, first plane, second plane and both planes
+const COLORS: [[u8; 3]; 4] = [[0, 0, 0], [255, 255, 255], [85, 85, 85], [170, 170, 170]];
+
+fn key_index(code: &str) -> Option<usize> {
+    KEY_CODES.iter().position(|&key| key == code)
+}
+
+/// Converts the screen to RGBA pixels for the canvas.
+fn render(vmem: &VideoMemory, pixels: &mut Vec<u8>) {
+    pixels.clear();
+    for idx in 0..vmem.render_width() * vmem.render_height() {
+        let color = match (
+            vmem.get_index_plane(Plane::First, idx),
+            vmem.get_index_plane(Plane::Second, idx),
+        ) {
+            (true, true) => COLORS[3],
+            (true, false) => COLORS[1],
+            (false, true) => COLORS[2],
+            (false, false) => COLORS[0],
+        };
+        pixels.extend_from_slice(&color);
+        pixels.push(0xFF);
+    }
+}
+
+/// A square wave played while the sound timer is running.
+struct Beeper {
+    context: AudioContext,
+    gain: GainNode,
+    _oscillator: OscillatorNode,
+}
+
+impl Beeper {
+    const FREQUENCY: f32 = 440.0;
+    const VOLUME: f32 = 0.1;
+
+    fn new() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(OscillatorType::Square);
+        oscillator.frequency().set_value(Self::FREQUENCY);
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.0);
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+        Ok(Self {
+            context,
+            gain,
+            _oscillator: oscillator,
+        })
+    }
+
+    fn set_playing(&self, playing: bool) {
+        let volume = if playing { Self::VOLUME } else { 0.0 };
+        self.gain.gain().set_value(volume);
+    }
+}
+
+#[wasm_bindgen]
+pub struct WebEmulator {
+    cpu: CPU,
+    runner: Runner,
+    keys: [bool; 16],
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    pixels: Vec<u8>,
+    /// Browsers only allow audio after user input, so it's created on the first key press
+    beeper: Option<Beeper>,
+    /// The timestamp of the last animation frame in milliseconds
+    last_time: Option<f64>,
+}
+
+#[wasm_bindgen]
+impl WebEmulator {
+    /// Loads a ROM to run at the given number of instructions per second, drawing to the canvas.
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas: HtmlCanvasElement, rom: &[u8], speed: u32) -> Result<WebEmulator, JsValue> {
+        rom::validate(rom).map_err(|e| JsValue::from_str(&e))?;
+        let mut cpu = CPU::new();
+        cpu.load_rom(rom).map_err(|e| JsValue::from_str(&e))?;
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("The canvas doesn't support 2D drawing")?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        Ok(Self {
+            cpu,
+            runner: Runner::new(speed),
+            keys: [false; 16],
+            canvas,
+            context,
+            pixels: Vec::new(),
+            beeper: None,
+            last_time: None,
+        })
+    }
+
+    /// Runs the frames due since the last call and draws the screen, called with the timestamp
+    /// passed to the `requestAnimationFrame` callback.
+    pub fn frame(&mut self, timestamp: f64) -> Result<(), JsValue> {
+        let elapsed = self
+            .last_time
+            .map_or(0.0, |last| (timestamp - last) / 1000.0);
+        self.last_time = Some(timestamp);
+        let frames = self
+            .runner
+            .advance(&mut self.cpu, &self.keys, elapsed)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        if let Some(beeper) = &self.beeper {
+            beeper.set_playing(self.cpu.ST() > 0);
+        }
+        if frames > 0 || self.pixels.is_empty() {
+            self.draw()?;
+        }
+        Ok(())
+    }
+
+    /// Handles the `code` of a `keydown` event, returns whether it's a CHIP-8 key.
+    pub fn key_down(&mut self, code: &str) -> Result<bool, JsValue> {
+        match &self.beeper {
+            Some(beeper) => {
+                // Resuming is asynchronous, there's nothing to wait for
+                let _ = beeper.context.resume()?;
+            }
+            None => self.beeper = Some(Beeper::new()?),
+        }
+        Ok(self.set_key(code, true))
+    }
+
+    /// Handles the `code` of a `keyup` event, returns whether it's a CHIP-8 key.
+    pub fn key_up(&mut self, code: &str) -> bool {
+        self.set_key(code, false)
+    }
+
+    /// Stops the sound, e.g. before the emulator is replaced by another ROM.
+    pub fn stop(&mut self) {
+        if let Some(beeper) = self.beeper.take() {
+            let _ = beeper.context.close();
+        }
+    }
+
+    fn set_key(&mut self, code: &str, pressed: bool) -> bool {
+        match key_index(code) {
+            Some(key) => {
+                self.keys[key] = pressed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn draw(&mut self) -> Result<(), JsValue> {
+        let vmem = self.cpu.vmem();
+        let (width, height) = (vmem.render_width() as u32, vmem.render_height() as u32);
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        render(vmem, &mut self.pixels);
+        let image =
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(&self.pixels), width, height)?;
+        self.context.put_image_data(&image, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod web_test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(key_index("KeyX"), Some(0));
+        assert_eq!(key_index("KeyV"), Some(0xF));
+        assert_eq!(key_index("Space"), None);
+
+        let mut vmem = VideoMemory::new();
+        vmem.set_plane(Plane::First, 1, 0, true);
+        let mut pixels = Vec::new();
+        render(&vmem, &mut pixels);
+        assert_eq!(pixels.len(), 128 * 64 * 4);
+        assert_eq!(pixels[..4], [0, 0, 0, 0xFF]);
+        assert_eq!(pixels.chunks(4).filter(|pixel| pixel[0] == 0xFF).count(), 4);
+    }
+}